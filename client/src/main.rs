@@ -8,6 +8,10 @@ use visual_debug::VisualDebugPlugin;
 fn main() {
     client::init_tracing().expect("tracing initialized");
 
+    if engine::args::run_command() {
+        return;
+    }
+
     client::run(
         PluginManager::new()
             .with(&VoxelEngine)
@@ -1,17 +1,150 @@
+use std::io::{self, Write};
 use std::net::SocketAddr;
-use clap::Parser;
-use shipyard::AllStoragesView;
+use std::path::PathBuf;
+use std::time::Duration;
+use clap::{Parser, Subcommand};
+use shipyard::{AllStoragesView, Unique};
+use game::block::registry::BlockRegistry;
+use game::crafting::RecipeBook;
+use game::item::registry::ItemRegistry;
 use crate::environment::Environment;
+use crate::identity::PlayerIdentity;
+use crate::save::snapshot::{AutoSnapshotSettings, RetentionPolicy, SnapshotError, SnapshotKind, SnapshotStore};
 
 #[derive(Parser, Debug)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     #[arg(long, value_name = "SERVER_IP", help = "Run as a client connecting to the server at SERVER_IP")]
     client: Option<String>,
+    #[arg(long, value_name = "PATH", default_value = "worlds/world", help = "Directory of the world to load, or create if it doesn't exist")]
+    world: PathBuf,
+    #[arg(long, help = "Seed used if a new world is created, random if not specified")]
+    seed: Option<u32>,
+    #[arg(long, value_name = "PATH", default_value = "profile", help = "Directory storing this player's identity")]
+    profile: PathBuf,
+    #[arg(long, value_name = "MINUTES", default_value_t = 30, help = "Minutes between automatic snapshots of a hosted world, 0 to disable them")]
+    snapshot_interval: u64,
+    #[arg(long, value_name = "COUNT", default_value_t = RetentionPolicy::default().keep_automatic, help = "How many automatic snapshots to keep before removing the oldest")]
+    snapshot_keep: usize,
+    #[arg(long, value_name = "PATH", default_value = "blocks", help = "Directory of block definition files loaded after the base blocks, if it exists")]
+    blocks: PathBuf,
+    #[arg(long, value_name = "PATH", default_value = "items", help = "Directory of item definition files loaded after the base items, if it exists")]
+    items: PathBuf,
+    #[arg(long, value_name = "PATH", default_value = "recipes", help = "Directory of recipe files loaded after the base recipes, if it exists")]
+    recipes: PathBuf,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage snapshots of the world without starting the game
+    #[command(subcommand)]
+    Snapshot(SnapshotCommand),
+}
+
+#[derive(Subcommand, Debug)]
+enum SnapshotCommand {
+    /// Snapshot the world as it is on disk
+    Create,
+    /// List the world's snapshots, oldest first
+    List,
+    /// Replace the world with a snapshot, keeping a snapshot of what it replaced
+    Restore {
+        name: String,
+    },
+    /// Remove old automatic snapshots, keeping the newest ones
+    Prune,
+}
+
+#[derive(Unique, Debug, Clone)]
+pub struct WorldOptions {
+    pub path: PathBuf,
+    pub seed: Option<u32>,
+    pub auto_snapshots: AutoSnapshotSettings,
+}
+
+/// Runs the command given on the command line, if any, returning whether one was run.
+/// Commands work on the world directly, so the game shouldn't be started after one.
+pub fn run_command() -> bool {
+    let args = Args::parse();
+
+    let Some(Command::Snapshot(command)) = &args.command else {
+        return false;
+    };
+
+    if let Err(err) = run_snapshot_command(command, &args) {
+        tracing::error!("Snapshot command failed: {err}");
+    }
+
+    true
+}
+
+fn run_snapshot_command(command: &SnapshotCommand, args: &Args) -> Result<(), SnapshotError> {
+    let store = SnapshotStore::new(&args.world);
+
+    let mut stdout = io::stdout().lock();
+
+    match command {
+        SnapshotCommand::Create => {
+            let snapshot = store.create(SnapshotKind::Manual)?;
+
+            writeln!(stdout, "Created snapshot {} at {:?}", snapshot.name, snapshot.path)?;
+        }
+        SnapshotCommand::List => {
+            for snapshot in store.list()? {
+                writeln!(stdout, "{}\t{}\t{} bytes", snapshot.name, snapshot.created.format("%Y-%m-%d %H:%M:%S UTC"), snapshot.size)?;
+            }
+        }
+        SnapshotCommand::Restore { name } => {
+            let backup = store.restore(name)?;
+
+            writeln!(stdout, "Restored snapshot {name}, the replaced world was saved as {}", backup.name)?;
+        }
+        SnapshotCommand::Prune => {
+            for snapshot in store.prune(RetentionPolicy { keep_automatic: args.snapshot_keep })? {
+                writeln!(stdout, "Removed snapshot {}", snapshot.name)?;
+            }
+        }
+    }
+
+    Ok(())
 }
 
 pub fn parse_env(storages: AllStoragesView) {
     let args = Args::parse();
 
+    // blocks refer to the items they drop by name, so items are loaded first
+    let items = ItemRegistry::load(&args.items)
+        .unwrap_or_else(|err| panic!("failed to load item definitions: {err}"));
+
+    tracing::debug!("Loaded {} item definitions", items.len());
+
+    if items.install().is_err() {
+        tracing::warn!("Items were used before their definitions were loaded, only the base items are available");
+    }
+
+    let registry = BlockRegistry::load(&args.blocks)
+        .unwrap_or_else(|err| panic!("failed to load block definitions: {err}"));
+
+    tracing::debug!("Loaded {} block definitions", registry.len());
+
+    ItemRegistry::global()
+        .check_blocks(&registry)
+        .unwrap_or_else(|err| panic!("failed to load item definitions: {err}"));
+
+    if registry.install().is_err() {
+        tracing::warn!("Blocks were used before their definitions were loaded, only the base blocks are available");
+    }
+
+    let recipes = RecipeBook::load(&args.recipes)
+        .unwrap_or_else(|err| panic!("failed to load recipes: {err}"));
+
+    tracing::debug!("Loaded {} recipes", recipes.len());
+
+    if recipes.install().is_err() {
+        tracing::warn!("Recipes were used before they were loaded, only the base recipes are available");
+    }
+
     let env = match args.client {
         None => Environment::HostedGame,
         Some(addr) => Environment::MultiplayerClient(
@@ -24,4 +157,17 @@ pub fn parse_env(storages: AllStoragesView) {
     tracing::debug!("Set env to {env:?}");
 
     storages.add_unique(env);
+    let auto_snapshots = AutoSnapshotSettings {
+        interval: (args.snapshot_interval > 0).then(|| Duration::from_secs(args.snapshot_interval * 60)),
+        retention: RetentionPolicy { keep_automatic: args.snapshot_keep },
+    };
+
+    storages.add_unique(WorldOptions { path: args.world, seed: args.seed, auto_snapshots });
+
+    let identity = PlayerIdentity::load_or_create(&args.profile)
+        .unwrap_or_else(|err| panic!("failed to load player identity from {:?}: {err}", args.profile));
+
+    tracing::debug!("Playing as {identity}");
+
+    storages.add_unique(identity);
 }
\ No newline at end of file
@@ -0,0 +1,301 @@
+use std::collections::HashSet;
+use std::iter;
+use std::time::Duration;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use shipyard::{EntitiesViewMut, IntoIter, Unique, UniqueView, UniqueViewMut, View, ViewMut};
+use game::block::Block;
+use game::block::face_type::FaceType;
+use game::block::tick::{BlockTickHandlers, TickWorld, TICKS_PER_SECOND};
+use game::chunk::BLOCKS_PER_CHUNK;
+use game::chunk::pos::ChunkPos;
+use game::location::BlockLocation;
+use crate::chunks::chunk_manager::ChunkManager;
+use crate::events::{BlockChangeCause, BlockChanged, BlockUpdateEvent};
+use crate::save::world::WorldDirectory;
+
+/// How many blocks of each loaded chunk get a random tick every tick.
+pub const RANDOM_TICKS_PER_CHUNK: usize = 48;
+/// The most ticks caught up on in a single frame, so that one slow frame doesn't make the next one slower.
+const MAX_TICKS_PER_FRAME: u64 = 10;
+
+/// The tick of the world at `game_time`.
+pub fn tick_at(game_time: Duration) -> u64 {
+    (game_time.as_millis() * TICKS_PER_SECOND as u128 / 1000) as u64
+}
+
+/// Runs block ticks where the world is hosted, along with the handlers that decide what they do.
+#[derive(Unique, Debug)]
+pub struct BlockTicker {
+    pub handlers: BlockTickHandlers,
+    rng: StdRng,
+    /// The last tick that was run, or `None` before the first frame.
+    last_tick: Option<u64>,
+}
+
+impl Default for BlockTicker {
+    fn default() -> Self {
+        Self {
+            handlers: BlockTickHandlers::base(),
+            rng: StdRng::from_entropy(),
+            last_tick: None,
+        }
+    }
+}
+
+/// The loaded chunks as seen from a block tick, keeping track of the blocks it changes so they get sent to clients.
+struct ChunkTickWorld<'a> {
+    world: &'a mut ChunkManager,
+    tick: u64,
+    changes: &'a mut Vec<(BlockLocation, Block)>,
+}
+
+impl TickWorld for ChunkTickWorld<'_> {
+    fn block(&self, location: &BlockLocation) -> Option<Block> {
+        self.world.get_block_ref(location).copied()
+    }
+
+    fn set_block(&mut self, location: &BlockLocation, block: Block) {
+        if self.world.modify_block(location, block, BlockChangeCause::Tick).is_ok() {
+            self.changes.push((location.clone(), block));
+        }
+    }
+
+    fn schedule_tick(&mut self, location: &BlockLocation, delay: u32) {
+        self.world.schedule_tick(location, self.tick + delay as u64);
+    }
+}
+
+impl BlockTicker {
+    /// Runs the scheduled ticks due by `tick`, then the random ticks of every loaded chunk.
+    /// Returns every block that was changed.
+    pub fn run(&mut self, tick: u64, world: &mut ChunkManager) -> Vec<(BlockLocation, Block)> {
+        let mut changes = Vec::new();
+
+        for location in world.take_due_ticks(tick) {
+            let Some(handler) = world.get_block_ref(&location).and_then(|block| self.handlers.scheduled(*block)) else {
+                continue;
+            };
+
+            handler(&mut ChunkTickWorld { world, tick, changes: &mut changes }, &location, &mut self.rng);
+        }
+
+        let chunks = world.loaded_locations()
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for chunk_loc in chunks {
+            let Some(chunk) = world.get_chunk_ref(&chunk_loc) else {
+                continue;
+            };
+
+            // most chunks are all air or all stone, which nothing happens to
+            if chunk.data.uniform().is_some_and(|block| self.handlers.random(*block).is_none()) {
+                continue;
+            }
+
+            for _ in 0..RANDOM_TICKS_PER_CHUNK {
+                let pos = ChunkPos(self.rng.gen_range(0..BLOCKS_PER_CHUNK) as _);
+                let location = BlockLocation::from_chunk_parts(&chunk_loc, &pos);
+
+                let Some(handler) = world.get_block_ref(&location).and_then(|block| self.handlers.random(*block)) else {
+                    continue;
+                };
+
+                handler(&mut ChunkTickWorld { world, tick, changes: &mut changes }, &location, &mut self.rng);
+            }
+        }
+
+        changes
+    }
+
+    /// Runs the neighbor updates of every changed block, on tick `tick`. Each block is only updated once,
+    /// however many blocks changed around it. Returns every block that was changed in turn.
+    pub fn update_neighbors<'a>(&mut self, tick: u64, world: &mut ChunkManager, changed: impl IntoIterator<Item = &'a BlockChanged>) -> Vec<(BlockLocation, Block)> {
+        let mut changes = Vec::new();
+        let mut updated = HashSet::new();
+
+        for change in changed {
+            let around = FaceType::ALL.map(|ft| BlockLocation(change.location.0 + ft.as_vector()));
+
+            for location in iter::once(change.location.clone()).chain(around) {
+                if !updated.insert(location.clone()) {
+                    continue;
+                }
+
+                let Some(handler) = world.get_block_ref(&location).and_then(|block| self.handlers.neighbor(*block)) else {
+                    continue;
+                };
+
+                handler(&mut ChunkTickWorld { world, tick, changes: &mut changes }, &location, &mut self.rng);
+            }
+        }
+
+        changes
+    }
+}
+
+/// Block ticks follow the game time, so they only run where the world is hosted, and clients get sent whatever changed.
+/// Neighbor updates run for the blocks changed last frame, whether or not a tick has passed since.
+pub fn tick_blocks(
+    world_dir: UniqueView<WorldDirectory>,
+    mut ticker: UniqueViewMut<BlockTicker>,
+    mut world: UniqueViewMut<ChunkManager>,
+    v_block_changed: View<BlockChanged>,
+    mut entities: EntitiesViewMut,
+    mut vm_block_update_evt: ViewMut<BlockUpdateEvent>,
+) {
+    let now = tick_at(world_dir.level.game_time);
+    let last = *ticker.last_tick.get_or_insert(now);
+
+    let mut changes = ticker.update_neighbors(now, &mut world, v_block_changed.iter());
+
+    for tick in now.saturating_sub(MAX_TICKS_PER_FRAME).max(last) + 1..=now {
+        changes.extend(ticker.run(tick, &mut world));
+    }
+
+    for (location, block) in changes {
+        entities.add_entity(&mut vm_block_update_evt, BlockUpdateEvent(location, block));
+    }
+
+    ticker.last_tick = Some(now);
+}
+
+#[cfg(test)]
+mod tests {
+    use glm::IVec3;
+    use game::block::fluid::FluidState;
+    use game::chunk::data::ChunkData;
+    use game::chunk::location::ChunkLocation;
+    use super::*;
+
+    fn at(x: i32, y: i32, z: i32) -> BlockLocation {
+        BlockLocation(IVec3::new(x, y, z))
+    }
+
+    fn fluid_at(world: &ChunkManager, location: &BlockLocation) -> Option<FluidState> {
+        world.get_block_ref(location).and_then(|block| block.fluid())
+    }
+
+    /// A single chunk with a stone floor at y = 1, along with whatever else is given.
+    fn world_with(blocks: impl IntoIterator<Item = (BlockLocation, Block)>) -> ChunkManager {
+        let mut data = ChunkData::empty(ChunkLocation::default());
+
+        for x in 0..32 {
+            for z in 0..32 {
+                data.set(at(x, 1, z).as_chunk_parts().1, Block::STONE);
+            }
+        }
+
+        for (location, block) in blocks {
+            data.set(location.as_chunk_parts().1, block);
+        }
+
+        let mut world = ChunkManager::new(1, None);
+        world.insert_chunk(data);
+
+        world
+    }
+
+    /// Neighbor updates run every tick, as if every tick were its own frame.
+    fn run_ticks(ticker: &mut BlockTicker, world: &mut ChunkManager, ticks: std::ops::RangeInclusive<u64>) {
+        for tick in ticks {
+            let changed = world.take_block_changes();
+
+            ticker.update_neighbors(tick, world, &changed);
+            ticker.run(tick, world);
+        }
+    }
+
+    const PLAYER: BlockChangeCause = BlockChangeCause::Player(0);
+
+    fn seeded_ticker() -> BlockTicker {
+        BlockTicker {
+            rng: StdRng::seed_from_u64(0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_breaking_a_dam_floods() {
+        let flood = || {
+            let pool = (10..=12).flat_map(|x| (5..=7).map(move |z| (at(x, 2, z), Block::WATER)));
+            let dam = (5..=7).map(|z| (at(13, 2, z), Block::STONE));
+
+            let mut world = world_with(pool.chain(dam));
+            let mut ticker = seeded_ticker();
+
+            run_ticks(&mut ticker, &mut world, 1..=20);
+            assert_eq!(fluid_at(&world, &at(14, 2, 6)), None, "sources shouldn't flow until something changes around them");
+
+            world.modify_block(&at(13, 2, 6), Block::AIR, PLAYER).expect("chunk is loaded");
+            run_ticks(&mut ticker, &mut world, 21..=200);
+
+            world
+        };
+
+        let world = flood();
+
+        for x in 13..=19 {
+            assert_eq!(fluid_at(&world, &at(x, 2, 6)), Some(FluidState::Flowing((x - 12) as u8)));
+        }
+
+        assert_eq!(fluid_at(&world, &at(20, 2, 6)), None, "water should only flow so far from its source");
+        assert_eq!(fluid_at(&world, &at(14, 2, 5)), Some(FluidState::Flowing(3)));
+        assert_eq!(world.get_block_ref(&at(13, 2, 5)), Some(&Block::STONE));
+        assert_eq!(fluid_at(&world, &at(11, 2, 6)), Some(FluidState::Source));
+
+        let blocks = |world: &ChunkManager| world.get_chunk_ref(&ChunkLocation::default())
+            .expect("chunk is loaded")
+            .data
+            .blocks_ref()
+            .copied()
+            .collect::<Vec<_>>();
+
+        assert!(blocks(&world) == blocks(&flood()), "flooding should always turn out the same");
+    }
+
+    #[test]
+    fn test_water_falls_spreads_and_dries_up() {
+        let mut world = world_with([]);
+        let mut ticker = seeded_ticker();
+
+        world.modify_block(&at(10, 10, 10), Block::WATER, PLAYER).expect("chunk is loaded");
+        run_ticks(&mut ticker, &mut world, 1..=200);
+
+        for y in 2..10 {
+            assert_eq!(fluid_at(&world, &at(10, y, 10)), Some(FluidState::Falling));
+        }
+
+        assert_eq!(fluid_at(&world, &at(13, 2, 10)), Some(FluidState::Flowing(3)));
+        assert_eq!(fluid_at(&world, &at(10, 3, 13)), None, "falling water shouldn't spread sideways");
+
+        world.modify_block(&at(10, 10, 10), Block::AIR, PLAYER).expect("chunk is loaded");
+        run_ticks(&mut ticker, &mut world, 201..=1000);
+
+        let chunk = world.get_chunk_ref(&ChunkLocation::default()).expect("chunk is loaded");
+
+        assert!(chunk.data.blocks_ref().all(|block| block.fluid().is_none()), "water without a source should dry up");
+    }
+
+    #[test]
+    fn test_cutting_down_a_log_decays_its_leaves() {
+        let mut world = world_with([(at(10, 2, 10), Block::LOG), (at(10, 3, 10), Block::LOG), (at(10, 4, 10), Block::LEAF), (at(11, 4, 10), Block::LEAF)]);
+        let mut ticker = seeded_ticker();
+
+        world.modify_block(&at(10, 3, 10), Block::AIR, PLAYER).expect("chunk is loaded");
+
+        let changed = world.take_block_changes();
+
+        assert_eq!(changed.len(), 1);
+        assert_eq!((changed[0].old, changed[0].new, changed[0].cause), (Block::LOG, Block::AIR, PLAYER));
+
+        ticker.update_neighbors(0, &mut world, &changed);
+        run_ticks(&mut ticker, &mut world, 1..=100);
+
+        assert_eq!(world.get_block_ref(&at(10, 4, 10)), Some(&Block::AIR), "the leaf on the log should check its support once the log is gone");
+        assert_eq!(world.get_block_ref(&at(11, 4, 10)), Some(&Block::AIR), "the leaves next to it should follow");
+        assert_eq!(world.get_block_ref(&at(10, 2, 10)), Some(&Block::LOG));
+    }
+}
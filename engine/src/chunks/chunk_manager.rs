@@ -1,20 +1,24 @@
 use std::{fmt, mem};
 use std::time::Duration;
 use glm::IVec3;
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use shipyard::{EntitiesViewMut, IntoIter, Unique, UniqueView, UniqueViewMut, View, ViewMut};
 use wgpu::util::DeviceExt;
 use game::block::Block;
 use game::block::face_type::FaceType;
+use game::block_entity::BlockEntity;
+use game::chunk::data::{BlockMut, ReplacedBlock};
+use game::chunk::light::Light;
 use game::chunk::location::ChunkLocation;
 use game::location::BlockLocation;
 use crate::application::delta_time::LastDeltaTime;
 use crate::chunks::client_chunk::{BakeState, ClientChunk};
+use crate::chunks::lighting;
 use crate::components::{LocalPlayer, Transform};
 use crate::rendering::chunk_mesh::ChunkMeshContext;
 use crate::rendering::graphics_context::GraphicsContext;
 use crate::rendering::sized_buffer::SizedBuffer;
-use crate::events::{ChunkGenEvent, ChunkGenRequestEvent};
+use crate::events::{BlockChangeCause, BlockChanged, ChunkGenEvent, ChunkGenRequestEvent};
 use crate::render_distance::RenderDistance;
 use crate::save::WorldSaver;
 
@@ -28,6 +32,10 @@ pub struct ChunkManager {
     bakery: HashMap<ChunkLocation, SizedBuffer>,
 
     recently_requested_gen: HashMap<ChunkLocation, f32>,
+    /// Block entities changed since they were last sent to other players.
+    changed_block_entities: HashSet<BlockLocation>,
+    /// Every block changed since they were last emitted as [`BlockChanged`] events.
+    block_changes: Vec<BlockChanged>,
     max_bakes_per_frame: usize,
 }
 
@@ -38,6 +46,8 @@ impl ChunkManager {
         Self {
             loaded: HashMap::with_capacity(size),
             recently_requested_gen: HashMap::default(),
+            changed_block_entities: HashSet::new(),
+            block_changes: Vec::new(),
             bakery: HashMap::with_capacity(size),
             max_bakes_per_frame,
         }
@@ -84,7 +94,11 @@ impl ChunkManager {
                 self.loaded.get_mut(&neighbor_loc).map(ClientChunk::set_dirty);
             }
 
-            let _ = self.loaded.try_insert(data.location.clone(), ClientChunk { data, bake: BakeState::DontBake });
+            let location = data.location.clone();
+
+            if self.loaded.try_insert(location.clone(), ClientChunk::new(data)).is_ok() {
+                lighting::light_chunk(self, &location);
+            }
         }
 
         // 2. un-bake any chunks not in OUR render distance
@@ -117,7 +131,7 @@ impl ChunkManager {
             .take(self.max_bakes_per_frame)
         {
             // TODO change this iterator to a collected iterator iterating over location or a immutable iterator?
-            let mesher = ChunkMeshContext::from_manager(self, &chunk.data);
+            let mesher = ChunkMeshContext::from_manager(self, chunk);
 
             let faces = mesher.faces();
 
@@ -164,6 +178,8 @@ impl ChunkManager {
     pub fn unload_chunks<'a>(&mut self, players_info: impl IntoIterator<Item = (&'a Transform, &'a RenderDistance), IntoIter: Clone>, world_saver: &mut WorldSaver) {
         let players_info = players_info.into_iter();
 
+        let mut unloaded = Vec::new();
+
         for (loc, chunk_data) in self.loaded
             .extract_if(|loc, _| !players_info.clone().any(|(transform, rend)|
                 Self::in_render_distance_with(loc, &transform.get_loc(), rend)
@@ -176,10 +192,29 @@ impl ChunkManager {
             // then earlier when we checked if WE were loading this chunk, we should've gotten false and deleted it then
             // debug_assert!(_had_key.is_none(), "chunk should've been deleted earlier!");
             
-            world_saver.cache(loc, chunk_data.data);
+            // unmodified chunks can just be generated again
+            if chunk_data.data.is_modified() {
+                world_saver.cache(loc.clone(), chunk_data.data);
+            }
+
+            unloaded.push(loc);
+        }
+
+        // the chunks below were lit by the ones that just unloaded, which can't be seen through anymore
+        for loc in unloaded {
+            lighting::relight_top(self, &ChunkLocation(loc.0 + FaceType::Bottom.as_vector()));
         }
     }
 
+    /// Loads the chunk without ever baking it, for running the world headlessly.
+    #[cfg(test)]
+    pub(crate) fn insert_chunk(&mut self, data: game::chunk::data::ChunkData) {
+        let location = data.location.clone();
+
+        self.loaded.insert(location.clone(), ClientChunk::new(data));
+        lighting::light_chunk(self, &location);
+    }
+
     pub fn get_chunk_ref(&self, location: &ChunkLocation) -> Option<&ClientChunk> {
         self.loaded.get(location)
     }
@@ -191,32 +226,45 @@ impl ChunkManager {
     pub fn get_block_ref(&self, block_loc: &BlockLocation) -> Option<&Block> {
         let (loc, pos) = block_loc.as_chunk_parts();
 
-        self
+        Some(self
             .get_chunk_ref(&loc)?
             .data
-            .blocks_ref()
-            .get(pos.0 as usize)
+            .block_ref(pos))
+    }
+
+    /// `None` where the world isn't loaded.
+    pub fn light_at(&self, block_loc: &BlockLocation) -> Option<Light> {
+        let (loc, pos) = block_loc.as_chunk_parts();
+
+        Some(self
+            .get_chunk_ref(&loc)?
+            .light
+            .get(pos))
     }
 
-    pub fn get_block_mut(&mut self, block_loc: &BlockLocation) -> Option<&mut Block> {
+    pub fn get_block_mut(&mut self, block_loc: &BlockLocation) -> Option<BlockMut<'_>> {
         let (loc, pos) = block_loc.as_chunk_parts();
 
-        self
+        Some(self
             .get_chunk_mut(&loc)?
             .data
-            .blocks_mut()
-            .get_mut(pos.0 as usize)
+            .block_mut(pos))
     }
     
-    pub fn modify_block(&mut self, block_loc: &BlockLocation, new: Block) -> Result<Block, Block> {
-        let Some(block_mut) = self.get_block_mut(block_loc) else {
+    /// Replaces the block along with its block entity, creating one for the new block if it needs one.
+    /// Every change is recorded along with its `cause`, to be emitted as a [`BlockChanged`] event.
+    pub fn modify_block(&mut self, block_loc: &BlockLocation, new: Block, cause: BlockChangeCause) -> Result<ReplacedBlock, Block> {
+        let (loc, pos) = block_loc.as_chunk_parts();
+
+        let Some(chunk) = self.get_chunk_mut(&loc) else {
             return Err(new);
         };
         
-        if *block_mut != new {
-            let prev = mem::replace(block_mut, new);
+        if *chunk.data.block_ref(pos) != new {
+            let prev = chunk.data.replace(pos, new);
 
-            self.get_chunk_mut(&block_loc.into()).map(ClientChunk::set_dirty);
+            chunk.set_dirty();
+            chunk.data.mark_modified();
 
             // TODO: work with chunk parts instead?
             for ft in FaceType::ALL {
@@ -224,12 +272,20 @@ impl ChunkManager {
 
                 let (new_chunk_loc, new_chunk_pos) = original.as_chunk_parts();
 
-                if let Some(chunk) = self.get_chunk_mut(&new_chunk_loc) {
-                    if *chunk.data.block_ref(new_chunk_pos) != Block::Air {
-                        chunk.set_dirty();
-                    }
+                if let Some(chunk) = self.get_chunk_mut(&new_chunk_loc)
+                    && *chunk.data.block_ref(new_chunk_pos) != Block::AIR {
+                    chunk.set_dirty();
                 }
             }
+
+            lighting::relight_block(self, block_loc);
+
+            self.block_changes.push(BlockChanged {
+                location: block_loc.clone(),
+                old: prev.block,
+                new,
+                cause,
+            });
             
             Ok(prev)
         } else {
@@ -237,6 +293,145 @@ impl ChunkManager {
         }
     }
 
+    /// Places a block from world gen into a chunk that's already loaded, such as part of a structure reaching over from a
+    /// chunk generated after it. Only air is replaced, so nothing players built is overwritten.
+    pub fn place_generated(&mut self, block_loc: &BlockLocation, new: Block) -> Result<ReplacedBlock, Block> {
+        match self.get_block_ref(block_loc) {
+            Some(&Block::AIR) => self.modify_block(block_loc, new, BlockChangeCause::WorldGen),
+            _ => Err(new),
+        }
+    }
+
+    pub fn get_block_entity_ref(&self, block_loc: &BlockLocation) -> Option<&dyn BlockEntity> {
+        let (loc, pos) = block_loc.as_chunk_parts();
+
+        self.get_chunk_ref(&loc)?
+            .data
+            .block_entity_ref(pos)
+    }
+
+    pub fn get_block_entity_mut(&mut self, block_loc: &BlockLocation) -> Option<&mut dyn BlockEntity> {
+        let (loc, pos) = block_loc.as_chunk_parts();
+
+        self.get_chunk_mut(&loc)?
+            .data
+            .block_entity_mut(pos)
+    }
+
+    /// Replaces the block entity, such as with one received from another player.
+    /// Fails if the chunk isn't loaded or the block there doesn't have that kind of block entity.
+    pub fn set_block_entity(&mut self, block_loc: &BlockLocation, entity: Box<dyn BlockEntity>) -> Result<(), Box<dyn BlockEntity>> {
+        let (loc, pos) = block_loc.as_chunk_parts();
+
+        let Some(chunk) = self.get_chunk_mut(&loc) else {
+            return Err(entity);
+        };
+
+        chunk.data.set_block_entity(pos, entity)?;
+        chunk.data.mark_modified();
+
+        Ok(())
+    }
+
+    /// Ticks every block entity in the loaded chunks, keeping track of the ones that changed.
+    pub fn tick_block_entities(&mut self, delta: Duration) {
+        for (loc, chunk) in &mut self.loaded {
+            let mut modified = false;
+
+            for (pos, entity) in chunk.data.block_entities_mut() {
+                if entity.tick(delta) {
+                    modified = true;
+                    self.changed_block_entities.insert(BlockLocation::from_chunk_parts(loc, &pos));
+                }
+            }
+
+            if modified {
+                chunk.data.mark_modified();
+            }
+        }
+    }
+
+    /// For changes made through [`ChunkManager::get_block_entity_mut`], so the chunk gets saved
+    /// and the block entity gets sent to other players.
+    pub fn mark_block_entity_changed(&mut self, block_loc: &BlockLocation) {
+        self.mark_modified(&block_loc.into());
+        self.changed_block_entities.insert(block_loc.clone());
+    }
+
+    /// A copy of every block entity changed since this was last called, which is still loaded.
+    pub fn take_changed_block_entities(&mut self) -> Vec<(BlockLocation, Box<dyn BlockEntity>)> {
+        mem::take(&mut self.changed_block_entities)
+            .into_iter()
+            .filter_map(|loc| {
+                let entity = self.get_block_entity_ref(&loc)?.clone_boxed();
+
+                Some((loc, entity))
+            })
+            .collect()
+    }
+
+    /// Ticks the block on tick `due` of the world, returning whether its chunk was loaded.
+    pub fn schedule_tick(&mut self, block_loc: &BlockLocation, due: u64) -> bool {
+        let (loc, pos) = block_loc.as_chunk_parts();
+
+        let Some(chunk) = self.get_chunk_mut(&loc) else {
+            return false;
+        };
+
+        chunk.data.schedule_tick(pos, due);
+        chunk.data.mark_modified();
+
+        true
+    }
+
+    /// Removes the scheduled ticks in every loaded chunk that are due by tick `now` of the world.
+    /// They're ordered by where they are, so that running them always turns out the same.
+    pub fn take_due_ticks(&mut self, now: u64) -> Vec<BlockLocation> {
+        let mut due = Vec::new();
+
+        for (loc, chunk) in &mut self.loaded {
+            let positions = chunk.data.take_due_ticks(now);
+
+            if !positions.is_empty() {
+                chunk.data.mark_modified();
+                due.extend(positions.iter().map(|pos| BlockLocation::from_chunk_parts(loc, pos)));
+            }
+        }
+
+        due.sort_unstable_by_key(|location| (location.0.x, location.0.y, location.0.z));
+
+        due
+    }
+
+    pub fn take_block_changes(&mut self) -> Vec<BlockChanged> {
+        mem::take(&mut self.block_changes)
+    }
+
+    /// For changes made through [`ChunkManager::get_block_mut`] or [`ChunkManager::get_block_entity_mut`],
+    /// which don't know whether anything was actually changed.
+    pub fn mark_modified(&mut self, location: &ChunkLocation) {
+        if let Some(chunk) = self.get_chunk_mut(location) {
+            chunk.data.mark_modified();
+        }
+    }
+
+    /// Unloads every chunk regardless of render distance, such as when the game closes.
+    pub fn unload_all(&mut self, world_saver: &mut WorldSaver) {
+        self.bakery.clear();
+
+        for (loc, chunk) in self.loaded.drain().filter(|(_, chunk)| chunk.data.is_modified()) {
+            world_saver.cache(loc, chunk.data);
+        }
+    }
+
+    /// Saves a copy of every modified chunk while keeping them loaded, so what's on disk is up to date.
+    pub fn save_modified(&mut self, world_saver: &mut WorldSaver) {
+        for (loc, chunk) in self.loaded.iter_mut().filter(|(_, chunk)| chunk.data.is_modified()) {
+            world_saver.cache(loc.clone(), chunk.data.clone());
+            chunk.data.clear_modified();
+        }
+    }
+
     pub fn loaded_locations(&self) -> Vec<&ChunkLocation> {
         self.loaded.keys().collect()
     }
@@ -292,9 +487,132 @@ pub fn chunk_manager_update_and_request(
     chunk_mgr.unload_chunks(player_info_vec, &mut world_saver);
 }
 
+/// Replaces last frame's [`BlockChanged`] events with every block changed since, so they can be seen from any phase until the next frame's are emitted.
+pub fn emit_block_changes(mut chunk_mgr: UniqueViewMut<ChunkManager>, mut entities: EntitiesViewMut, mut vm_block_changed: ViewMut<BlockChanged>) {
+    vm_block_changed.drain();
+
+    let changes = chunk_mgr.take_block_changes();
+
+    if !changes.is_empty() {
+        entities.bulk_add_entity(&mut vm_block_changed, changes);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use glm::IVec3;
+    use game::block::loot::{BreakContext, Breaker};
+    use game::block_entity::CrateEntity;
+    use game::chunk::data::ChunkData;
+    use game::inventory::Inventory;
+    use game::item::ItemType;
+    use crate::save::{with_large_stack, ChunkLoadError, FakeSaver};
+    use super::*;
+
+    #[test]
+    fn test_only_modified_chunks_are_saved() {
+        with_large_stack(|| {
+            let mut chunk_mgr = ChunkManager::new(1, None);
+            let mut world_saver = WorldSaver::new(WorldSaver::DEFAULT_CACHE_TIME, FakeSaver);
+
+            let untouched = ChunkLocation(IVec3::new(0, 0, 0));
+            let modified = ChunkLocation(IVec3::new(1, 0, 0));
+
+            for loc in [&untouched, &modified] {
+                chunk_mgr.loaded.insert(loc.clone(), ClientChunk::new(ChunkData::empty(loc.clone())));
+            }
+
+            let block_loc = BlockLocation(IVec3::new(40, 3, 5));
+
+            assert_eq!(ChunkLocation::from(&block_loc), modified);
+            assert!(chunk_mgr.modify_block(&block_loc, Block::STONE, BlockChangeCause::Tick).is_ok());
+
+            // nobody is around, so everything unloads
+            chunk_mgr.unload_chunks([], &mut world_saver);
+
+            assert!(chunk_mgr.loaded.is_empty());
+            assert!(matches!(world_saver.try_get(&untouched), Err(ChunkLoadError::NotFound)));
+            assert!(world_saver.try_get(&modified).expect("should've been cached").data.is_modified());
+        });
+    }
+
+    #[test]
+    fn test_saved_chunks_are_not_saved_again_until_modified() {
+        with_large_stack(|| {
+            let mut chunk_mgr = ChunkManager::new(1, None);
+            let mut world_saver = WorldSaver::new(WorldSaver::DEFAULT_CACHE_TIME, FakeSaver);
+
+            let loc = ChunkLocation::default();
+            chunk_mgr.loaded.insert(loc.clone(), ClientChunk::new(ChunkData::empty(loc.clone())));
+
+            assert!(chunk_mgr.modify_block(&BlockLocation(IVec3::new(1, 2, 3)), Block::STONE, BlockChangeCause::Tick).is_ok());
+
+            chunk_mgr.save_modified(&mut world_saver);
+
+            // the saver keeps the copy, while the loaded chunk counts as saved
+            assert!(world_saver.try_get(&loc).expect("should've been cached").data.is_modified());
+            assert!(!chunk_mgr.loaded[&loc].data.is_modified());
+
+            chunk_mgr.save_modified(&mut world_saver);
+
+            assert!(matches!(world_saver.try_get(&loc), Err(ChunkLoadError::NotFound)));
+        });
+    }
+
+    #[test]
+    fn test_block_entities_follow_their_block() {
+        with_large_stack(|| {
+            let mut chunk_mgr = ChunkManager::new(1, None);
+
+            let loc = ChunkLocation::default();
+            chunk_mgr.loaded.insert(loc.clone(), ClientChunk::new(ChunkData::empty(loc)));
+
+            let block_loc = BlockLocation(IVec3::new(1, 2, 3));
+
+            assert!(chunk_mgr.modify_block(&block_loc, Block::CRATE, BlockChangeCause::Player(1)).is_ok());
+
+            let CrateEntity { inventory } = chunk_mgr.get_block_entity_mut(&block_loc)
+                .and_then(|entity| entity.downcast_mut())
+                .expect("placing a crate should create its block entity");
+
+            inventory.try_insert(ItemType::PLANKS.default_one());
+            chunk_mgr.mark_block_entity_changed(&block_loc);
+
+            let changed = chunk_mgr.take_changed_block_entities();
+
+            assert_eq!(changed.len(), 1);
+            assert_eq!(changed[0].0, block_loc);
+
+            let replaced = chunk_mgr.modify_block(&block_loc, Block::AIR, BlockChangeCause::Network).expect("chunk is loaded");
+
+            assert!(chunk_mgr.get_block_entity_ref(&block_loc).is_none());
+            assert_eq!(replaced.on_break(&mut BreakContext::new(Breaker::World, None, block_loc.clone(), 0)), vec![ItemType::CRATE.default_one(), ItemType::PLANKS.default_one()]);
+        });
+    }
+
+    #[test]
+    fn test_generated_blocks_only_replace_air() {
+        with_large_stack(|| {
+            let mut chunk_mgr = ChunkManager::new(1, None);
+
+            let loc = ChunkLocation::default();
+            chunk_mgr.loaded.insert(loc.clone(), ClientChunk::new(ChunkData::empty(loc)));
+
+            let (built, empty) = (BlockLocation(IVec3::new(1, 2, 3)), BlockLocation(IVec3::new(1, 3, 3)));
+
+            assert!(chunk_mgr.modify_block(&built, Block::PLANKS, BlockChangeCause::Player(1)).is_ok());
+            chunk_mgr.take_block_changes();
+
+            assert!(chunk_mgr.place_generated(&built, Block::STONE).is_err(), "it shouldn't overwrite what players built");
+            assert!(chunk_mgr.place_generated(&empty, Block::STONE).is_ok());
+            assert!(chunk_mgr.place_generated(&BlockLocation(IVec3::new(100, 0, 0)), Block::STONE).is_err(), "the chunk isn't loaded");
+
+            let changes = chunk_mgr.take_block_changes();
+
+            assert_eq!(changes.len(), 1);
+            assert_eq!((&changes[0].location, changes[0].new, changes[0].cause), (&empty, Block::STONE, BlockChangeCause::WorldGen));
+        });
+    }
 
     #[test]
     fn test_chunk_offset_into_chunk_vec() {
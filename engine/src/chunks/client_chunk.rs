@@ -1,11 +1,23 @@
 use game::chunk::data::ChunkData;
+use game::chunk::light::ChunkLight;
 
 pub struct ClientChunk {
     pub data: ChunkData,
     pub bake: BakeState,
+    /// Kept up to date by [`lighting`](crate::chunks::lighting) as chunks load and blocks change.
+    pub light: ChunkLight,
 }
 
 impl ClientChunk {
+    /// An unlit chunk, which is lit once it's loaded.
+    pub fn new(data: ChunkData) -> Self {
+        Self {
+            data,
+            bake: BakeState::DontBake,
+            light: ChunkLight::default(),
+        }
+    }
+
     pub fn set_dirty(&mut self) {
         if self.bake == BakeState::Baked {
             self.bake = BakeState::NeedsBaking;
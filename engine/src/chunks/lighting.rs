@@ -0,0 +1,401 @@
+use std::collections::VecDeque;
+use game::block::face_type::FaceType;
+use game::block::registry::MAX_LIGHT;
+use game::chunk::{BLOCKS_PER_CHUNK, CHUNK_SIZE};
+use game::chunk::light::{ChunkLight, Light, LightKind};
+use game::chunk::location::ChunkLocation;
+use game::chunk::pos::ChunkPos;
+use game::location::BlockLocation;
+use crate::chunks::chunk_manager::ChunkManager;
+
+// Light spreads out a level dimmer with every block it passes through, except for sky light going
+// straight down, which stays at full brightness until something stops it. Only transparent blocks
+// let light through. Where the chunk above isn't loaded, the sky is taken to reach down to the
+// terrain world gen put there, since it can't be seen whether anything else is in the way.
+
+fn offset(location: &BlockLocation, face: FaceType) -> BlockLocation {
+    BlockLocation(location.0 + face.as_vector())
+}
+
+fn light(world: &ChunkManager, location: &BlockLocation, kind: LightKind) -> Option<u8> {
+    world.light_at(location).map(|light| light.get(kind))
+}
+
+/// Sets the light, making sure anything showing it is meshed again, including faces in the chunks next to it.
+fn set_light(world: &mut ChunkManager, location: &BlockLocation, kind: LightKind, level: u8) {
+    let (loc, pos) = location.as_chunk_parts();
+
+    let Some(chunk) = world.get_chunk_mut(&loc) else {
+        return;
+    };
+
+    if !chunk.light.set(pos, kind, level) {
+        return;
+    }
+
+    chunk.set_dirty();
+
+    for ft in FaceType::ALL {
+        if pos.adjacent_to_face(ft).is_err()
+            && let Some(neighbor) = world.get_chunk_mut(&ChunkLocation(loc.0 + ft.as_vector()))
+        {
+            neighbor.set_dirty();
+        }
+    }
+}
+
+/// How much light the block gives off itself, whatever's around it.
+fn source(world: &ChunkManager, location: &BlockLocation, kind: LightKind) -> u8 {
+    let Some(block) = world.get_block_ref(location) else {
+        return 0;
+    };
+
+    match kind {
+        LightKind::Block => block.definition().light,
+        LightKind::Sky => {
+            let (loc, pos) = location.as_chunk_parts();
+            let open = pos.adjacent_to_face(FaceType::Top).is_err()
+                && world.get_chunk_ref(&ChunkLocation(loc.0 + FaceType::Top.as_vector::<i32>())).is_none()
+                && above_surface(world, location);
+
+            if open && block.is_transparent() { MAX_LIGHT } else { 0 }
+        }
+    }
+}
+
+/// Whether the block is above the terrain world gen put in its column, which chunks that didn't come from world gen always are.
+fn above_surface(world: &ChunkManager, location: &BlockLocation) -> bool {
+    let (loc, pos) = location.as_chunk_parts();
+
+    world.get_chunk_ref(&loc)
+        .and_then(|chunk| chunk.data.surface())
+        .is_none_or(|surface| location.0.y > surface.get(pos.x(), pos.z()))
+}
+
+fn spread_level(kind: LightKind, face: FaceType, level: u8) -> u8 {
+    match (kind, face) {
+        (LightKind::Sky, FaceType::Bottom) if level == MAX_LIGHT => MAX_LIGHT,
+        _ => level.saturating_sub(1),
+    }
+}
+
+/// Spreads the light of every block in the queue to the blocks around it, and on from those it brightens.
+fn spread(world: &mut ChunkManager, kind: LightKind, mut queue: VecDeque<BlockLocation>) {
+    while let Some(location) = queue.pop_front() {
+        let Some(level) = light(world, &location, kind) else {
+            continue;
+        };
+
+        for face in FaceType::ALL {
+            let neighbor = offset(&location, face);
+
+            if !world.get_block_ref(&neighbor).is_some_and(|block| block.is_transparent()) {
+                continue;
+            }
+
+            let spread = spread_level(kind, face, level);
+
+            if light(world, &neighbor, kind).is_some_and(|current| current < spread) {
+                set_light(world, &neighbor, kind, spread);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+}
+
+/// Takes away the light that came from the blocks in the queue, which were as bright as the level they're queued with.
+/// Returns the blocks whose light has to be spread again to fill in what was taken away.
+fn darken(world: &mut ChunkManager, kind: LightKind, mut queue: VecDeque<(BlockLocation, u8)>) -> VecDeque<BlockLocation> {
+    let mut relight = VecDeque::new();
+
+    while let Some((location, old)) = queue.pop_front() {
+        for face in FaceType::ALL {
+            let neighbor = offset(&location, face);
+
+            let Some(level) = light(world, &neighbor, kind).filter(|level| *level > 0) else {
+                continue;
+            };
+
+            // lit by the block being darkened, rather than by something else just as bright
+            if level < old || spread_level(kind, face, old) == level && level == MAX_LIGHT {
+                let own = source(world, &neighbor, kind);
+
+                set_light(world, &neighbor, kind, own);
+                queue.push_back((neighbor.clone(), level));
+
+                if own > 0 {
+                    relight.push_back(neighbor);
+                }
+            } else {
+                relight.push_back(neighbor);
+            }
+        }
+    }
+
+    relight
+}
+
+/// Lights a chunk that was just loaded, spreading light into it from the chunks around it and back out again.
+pub fn light_chunk(world: &mut ChunkManager, location: &ChunkLocation) {
+    let Some(chunk) = world.get_chunk_ref(location) else {
+        return;
+    };
+
+    let above = ChunkLocation(location.0 + FaceType::Top.as_vector());
+    let top = BlockLocation::from(&above).0.y - 1;
+
+    let sky_above = match world.get_chunk_ref(&above) {
+        Some(above) => above.light.uniform_light().is_some_and(|light| light.sky == MAX_LIGHT),
+        None => chunk.data.surface().is_none_or(|surface| surface.max() < top),
+    };
+
+    // open sky is common enough that it's worth not lighting every block of it one by one
+    let uniform = chunk.data.uniform()
+        .filter(|block| sky_above && block.is_transparent() && block.definition().light == 0)
+        .map(|_| ChunkLight::uniform(Light::SKY));
+
+    let mut queues = [VecDeque::new(), VecDeque::new()];
+
+    if let Some(light) = uniform {
+        world.get_chunk_mut(location).expect("was just checked").light = light;
+    } else {
+        for i in 0..BLOCKS_PER_CHUNK {
+            let block_loc = BlockLocation::from_chunk_parts(location, &ChunkPos(i as _));
+
+            for (kind, queue) in LightKind::ALL.into_iter().zip(&mut queues) {
+                let own = source(world, &block_loc, kind);
+
+                if own > 0 {
+                    set_light(world, &block_loc, kind, own);
+                    queue.push_back(block_loc.clone());
+                }
+            }
+        }
+    }
+
+    // light spreads both ways across the chunk's borders
+    for i in 0..BLOCKS_PER_CHUNK {
+        let pos = ChunkPos(i as _);
+
+        for ft in FaceType::ALL {
+            let Err(adj) = pos.adjacent_to_face(ft) else {
+                continue;
+            };
+
+            let neighbor_loc = ChunkLocation(location.0 + ft.as_vector());
+
+            if world.get_chunk_ref(&neighbor_loc).is_some() {
+                for queue in &mut queues {
+                    queue.push_back(BlockLocation::from_chunk_parts(location, &pos));
+                    queue.push_back(BlockLocation::from_chunk_parts(&neighbor_loc, &adj));
+                }
+            }
+        }
+    }
+
+    for (kind, queue) in LightKind::ALL.into_iter().zip(queues) {
+        spread(world, kind, queue);
+    }
+
+    // the chunk below was lit by what was taken to be above it before this chunk loaded
+    relight_top(world, &ChunkLocation(location.0 + FaceType::Bottom.as_vector()));
+}
+
+/// Works out again whether the sky reaches the top of a chunk once the chunk above it is loaded or unloaded,
+/// since sky light coming down from a loaded chunk can be stopped where the terrain wouldn't, and the other way around.
+pub fn relight_top(world: &mut ChunkManager, location: &ChunkLocation) {
+    if world.get_chunk_ref(location).is_none() {
+        return;
+    }
+
+    let mut darkened = VecDeque::new();
+    let mut lit = VecDeque::new();
+
+    for x in 0..CHUNK_SIZE.x {
+        for z in 0..CHUNK_SIZE.z {
+            let top = BlockLocation::from_chunk_parts(location, &ChunkPos::new_unchecked(x, CHUNK_SIZE.y - 1, z));
+
+            let Some(current) = light(world, &top, LightKind::Sky) else {
+                continue;
+            };
+
+            let open = match light(world, &offset(&top, FaceType::Top), LightKind::Sky) {
+                Some(above) => above == MAX_LIGHT && world.get_block_ref(&top).is_some_and(|block| block.is_transparent()),
+                None => source(world, &top, LightKind::Sky) == MAX_LIGHT,
+            };
+
+            if current == MAX_LIGHT && !open {
+                set_light(world, &top, LightKind::Sky, 0);
+                darkened.push_back((top, MAX_LIGHT));
+            } else if current < MAX_LIGHT && open {
+                set_light(world, &top, LightKind::Sky, MAX_LIGHT);
+                lit.push_back(top);
+            }
+        }
+    }
+
+    let mut relight = darken(world, LightKind::Sky, darkened);
+    relight.extend(lit);
+
+    spread(world, LightKind::Sky, relight);
+}
+
+/// Updates the light around a block that was just changed.
+pub fn relight_block(world: &mut ChunkManager, location: &BlockLocation) {
+    for kind in LightKind::ALL {
+        let Some(old) = light(world, location, kind) else {
+            return;
+        };
+
+        let own = source(world, location, kind);
+
+        set_light(world, location, kind, own);
+
+        let mut relight = darken(world, kind, VecDeque::from([(location.clone(), old)]));
+
+        if own > 0 {
+            relight.push_back(location.clone());
+        }
+
+        // whatever's around the block shines into it if it lets light through now
+        relight.extend(FaceType::ALL.map(|face| offset(location, face)));
+
+        spread(world, kind, relight);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glm::{IVec3, U16Vec3, Vec3};
+    use game::block::Block;
+    use game::chunk::data::ChunkData;
+    use game::chunk::heightmap::Heightmap;
+    use crate::components::Transform;
+    use crate::events::BlockChangeCause;
+    use crate::render_distance::RenderDistance;
+    use crate::save::{FakeSaver, WorldSaver};
+    use super::*;
+
+    fn at(x: i32, y: i32, z: i32) -> BlockLocation {
+        BlockLocation(IVec3::new(x, y, z))
+    }
+
+    fn sky_at(world: &ChunkManager, location: &BlockLocation) -> Option<u8> {
+        light(world, location, LightKind::Sky)
+    }
+
+    /// A chunk with a stone roof across it at `y`, or open to the sky if there isn't one.
+    fn chunk(location: IVec3, roof: Option<u8>) -> ChunkData {
+        let mut data = ChunkData::empty(ChunkLocation(location));
+
+        if let Some(y) = roof {
+            for x in 0..CHUNK_SIZE.x {
+                for z in 0..CHUNK_SIZE.z {
+                    data.set(ChunkPos::new_unchecked(x, y, z), Block::STONE);
+                }
+            }
+        }
+
+        data
+    }
+
+    /// Like [`chunk`], but with world gen's terrain at `surface` in every column.
+    fn generated(location: IVec3, roof: Option<u8>, surface: i32) -> ChunkData {
+        let mut data = chunk(location, roof);
+        data.set_surface(Heightmap::flat(surface));
+        data
+    }
+
+    /// Unloads every chunk that isn't in the same layer as the block.
+    fn unload_all_but_layer(world: &mut ChunkManager, location: &BlockLocation) {
+        let transform = Transform { position: Vec3::new(location.0.x as _, location.0.y as _, location.0.z as _), ..Default::default() };
+        let render_dist = RenderDistance(U16Vec3::new(1, 0, 1));
+
+        world.unload_chunks([(&transform, &render_dist)], &mut WorldSaver::new(WorldSaver::DEFAULT_CACHE_TIME, FakeSaver));
+    }
+
+    #[test]
+    fn test_sky_light_spreads_under_roofs_and_across_chunks() {
+        let mut world = ChunkManager::new(1, None);
+
+        world.insert_chunk(chunk(IVec3::new(1, 0, 0), Some(20)));
+
+        assert_eq!(sky_at(&world, &at(40, 21, 5)), Some(MAX_LIGHT));
+        assert_eq!(sky_at(&world, &at(40, 10, 5)), Some(0), "nothing should light under the roof yet");
+
+        world.insert_chunk(chunk(IVec3::new(0, 0, 0), None));
+
+        assert_eq!(sky_at(&world, &at(31, 10, 5)), Some(MAX_LIGHT));
+        assert_eq!(sky_at(&world, &at(32, 10, 5)), Some(MAX_LIGHT - 1), "sky light should spread in from the open chunk");
+        assert_eq!(sky_at(&world, &at(36, 10, 5)), Some(MAX_LIGHT - 5));
+        assert_eq!(sky_at(&world, &at(50, 10, 5)), Some(0));
+
+        world.modify_block(&at(50, 20, 5), Block::AIR, BlockChangeCause::Tick).expect("chunk is loaded");
+
+        assert_eq!(sky_at(&world, &at(50, 3, 5)), Some(MAX_LIGHT), "light should come through the hole straight down");
+        assert_eq!(sky_at(&world, &at(52, 3, 5)), Some(MAX_LIGHT - 2));
+
+        world.modify_block(&at(50, 20, 5), Block::STONE, BlockChangeCause::Tick).expect("chunk is loaded");
+
+        assert_eq!(sky_at(&world, &at(50, 3, 5)), Some(0), "filling the hole should take its light away again");
+        assert_eq!(sky_at(&world, &at(36, 10, 5)), Some(MAX_LIGHT - 5));
+    }
+
+    #[test]
+    fn test_loading_a_chunk_above_shades_the_one_below() {
+        let mut world = ChunkManager::new(1, None);
+
+        world.insert_chunk(chunk(IVec3::new(0, 0, 0), None));
+        assert_eq!(sky_at(&world, &at(5, 0, 5)), Some(MAX_LIGHT), "with nothing loaded above, the sky should be");
+
+        world.insert_chunk(chunk(IVec3::new(0, 1, 0), Some(0)));
+
+        assert_eq!(sky_at(&world, &at(5, 64, 5)), Some(0));
+        assert_eq!(sky_at(&world, &at(5, 0, 5)), Some(0), "the chunk below should be shaded by the floor of the one above");
+        assert_eq!(sky_at(&world, &at(5, 65, 5)), Some(MAX_LIGHT));
+    }
+
+    #[test]
+    fn test_sky_stays_out_two_chunks_below_a_solid_roof() {
+        let mut world = ChunkManager::new(1, None);
+
+        // the terrain's a solid roof across the bottom of the chunk two above
+        world.insert_chunk(generated(IVec3::new(0, 2, 0), Some(0), 128));
+        world.insert_chunk(generated(IVec3::new(0, 0, 0), None, 128));
+
+        assert_eq!(sky_at(&world, &at(5, 63, 5)), Some(0), "the sky shouldn't reach past the roof, even with the chunk between not loaded");
+        assert_eq!(sky_at(&world, &at(5, 0, 5)), Some(0));
+
+        world.insert_chunk(generated(IVec3::new(0, 1, 0), None, 128));
+
+        assert_eq!(sky_at(&world, &at(5, 100, 5)), Some(0));
+        assert_eq!(sky_at(&world, &at(5, 63, 5)), Some(0));
+
+        unload_all_but_layer(&mut world, &at(5, 10, 5));
+
+        assert!(world.get_chunk_ref(&ChunkLocation(IVec3::new(0, 1, 0))).is_none());
+        assert_eq!(sky_at(&world, &at(5, 63, 5)), Some(0), "unloading the chunks above shouldn't let the sky in");
+
+        world.modify_block(&at(5, 63, 5), Block::STONE, BlockChangeCause::Tick).expect("chunk is loaded");
+        world.modify_block(&at(5, 63, 5), Block::AIR, BlockChangeCause::Tick).expect("chunk is loaded");
+
+        assert_eq!(sky_at(&world, &at(5, 63, 5)), Some(0), "relighting the top of the chunk shouldn't let the sky in either");
+        assert_eq!(sky_at(&world, &at(5, 0, 5)), Some(0));
+    }
+
+    #[test]
+    fn test_unloading_a_chunk_above_relights_the_one_below() {
+        let mut world = ChunkManager::new(1, None);
+
+        // a floor that was built rather than generated, so only the loaded chunk knows about it
+        world.insert_chunk(generated(IVec3::new(0, 0, 0), None, -100));
+        world.insert_chunk(generated(IVec3::new(0, 1, 0), Some(0), -100));
+
+        assert_eq!(sky_at(&world, &at(5, 0, 5)), Some(0));
+
+        unload_all_but_layer(&mut world, &at(5, 10, 5));
+
+        assert_eq!(sky_at(&world, &at(5, 63, 5)), Some(MAX_LIGHT), "with only the terrain above, the sky should reach the chunk again");
+        assert_eq!(sky_at(&world, &at(5, 0, 5)), Some(MAX_LIGHT));
+    }
+}
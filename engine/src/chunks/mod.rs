@@ -1,3 +1,4 @@
 pub mod chunk_manager;
 pub mod client_chunk;
+pub mod lighting;
 pub mod raycast;
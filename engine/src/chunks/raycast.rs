@@ -1,5 +1,4 @@
 use glm::Vec3;
-use game::block::Block;
 use game::block::face_type::{Axis, FaceType};
 use game::location::{BlockLocation, WorldLocation};
 use crate::chunks::chunk_manager::ChunkManager;
@@ -53,7 +52,7 @@ impl ChunkManager {
         let mut face = None;
 
         while t < max_dist {
-            if *self.get_block_ref(&voxel)? != Block::Air {
+            if !self.get_block_ref(&voxel)?.is_replaceable() {
                 return Some(RaycastResult {
                     distance: t,
                     hit: RaycastHit::Block {
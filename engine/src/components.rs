@@ -112,7 +112,7 @@ pub struct IsOnGround(pub bool);
 #[derive(Clone, Unique, Debug, Default, Eq, PartialEq)]
 pub struct HeldBlock(pub usize); // inventory index, TODO: improve api
 
-#[derive(Copy, Clone, Component, Debug, Default, PartialEq)]
+#[derive(Copy, Clone, Component, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Health {
     pub curr: f32,
     pub max: f32,
@@ -128,7 +128,7 @@ impl Health {
     }
 }
 
-#[derive(Copy, Clone, Component, Debug, Default, PartialEq)]
+#[derive(Copy, Clone, Component, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Mana {
     pub curr: f32,
     pub max: f32,
@@ -0,0 +1,39 @@
+use shipyard::{Component, IntoIter, Unique, UniqueViewMut, View, ViewMut};
+use game::block::BlockInventory;
+use game::crafting::{RecipeBook, GRID_SIZE, GRID_WIDTH};
+use crate::components::LocalPlayer;
+use crate::inventory::PlayerInventory;
+
+/// Items a player has put in their crafting grid. The server keeps one for every player, so clients move items
+/// in and out of it with inventory actions, and crafting from it only happens on the server.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct CraftingGrid(pub BlockInventory<GRID_SIZE>);
+
+/// The recipe the local player asked to craft from their grid, into their inventory.
+/// Multiplayer clients send it to the server, which crafts it from its own copy of the grid.
+#[derive(Unique, Debug, Default)]
+pub struct PendingCraft(pub Option<String>);
+
+/// Crafts the recipe from the grid into the inventory, if it still matches the grid and there's room.
+pub(crate) fn craft_recipe(name: &str, grid: &mut CraftingGrid, inventory: &mut PlayerInventory) {
+    let Some(recipe) = RecipeBook::global().get(name) else {
+        tracing::warn!("Tried to craft unknown recipe {name}");
+        return;
+    };
+
+    if let Err(err) = recipe.craft(&mut grid.0, GRID_WIDTH, inventory) {
+        tracing::debug!("Couldn't craft {name}: {err}");
+    }
+}
+
+pub fn craft_pending(mut pending: UniqueViewMut<PendingCraft>, v_local_player: View<LocalPlayer>, mut vm_grid: ViewMut<CraftingGrid>, mut vm_inventory: ViewMut<PlayerInventory>) {
+    let Some(name) = pending.0.take() else {
+        return;
+    };
+
+    let (grid, inventory, _) = (&mut vm_grid, &mut vm_inventory, &v_local_player).iter()
+        .next()
+        .expect("LocalPlayer should exist");
+
+    craft_recipe(&name, grid, inventory);
+}
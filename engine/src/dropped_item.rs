@@ -0,0 +1,333 @@
+use std::num::NonZeroU8;
+use std::time::Duration;
+use glm::Vec3;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use shipyard::{AllStoragesViewMut, Component, EntitiesViewMut, IntoIter, IntoWithId, Unique, UniqueView, UniqueViewMut, View, ViewMut};
+use game::inventory::Inventory;
+use game::item::ItemStack;
+use game::location::BlockLocation;
+use crate::application::delta_time::LastDeltaTime;
+use crate::components::{Entity, GravityAffected, Hitbox, IsOnGround, LocalPlayer, Player, Transform, Velocity};
+use crate::events::DroppedItemUpdate;
+use crate::gamemode::Gamemode;
+use crate::inventory::{take_dropped, InventoryHand, PendingPlayerDrops, PlayerInventory};
+use crate::physics::movement::move_towards;
+
+/// How long an item lies in the world before it despawns.
+pub const DESPAWN_AFTER: Duration = Duration::from_secs(5 * 60);
+/// How long after being dropped an item can be picked up, so that players don't pick up what they just dropped.
+pub const PICKUP_DELAY: Duration = Duration::from_millis(500);
+/// How close a player has to be to an item to pick it up.
+pub const PICKUP_RADIUS: f32 = 1.5;
+/// How close items have to be to merge into one stack.
+pub const MERGE_RADIUS: f32 = 0.75;
+
+/// How big a dropped item's hitbox is along each side.
+pub const ITEM_SIZE: f32 = 0.25;
+/// How quickly items sliding along the ground come to a stop, in blocks per second squared.
+const GROUND_FRICTION: f32 = 8.0;
+/// How far an item has to move before clients are told where it is again.
+const RESEND_DISTANCE: f32 = 0.05;
+
+/// A stack to be spawned into the world as a [`DroppedItem`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemDrop {
+    pub stack: ItemStack,
+    pub position: Vec3,
+    pub velocity: Vec3,
+}
+
+impl ItemDrop {
+    pub fn at(stack: ItemStack, position: Vec3) -> Self {
+        Self {
+            stack,
+            position,
+            velocity: Vec3::zeros(),
+        }
+    }
+
+    /// Pops out of the middle of a block in a random direction, like drops from breaking it.
+    pub fn from_block(stack: ItemStack, location: &BlockLocation) -> Self {
+        let mut rng = rand::thread_rng();
+
+        Self {
+            stack,
+            position: location.0.cast() + Vec3::repeat(0.5),
+            velocity: Vec3::new(rng.gen_range(-1.0..=1.0), 3.0, rng.gen_range(-1.0..=1.0)),
+        }
+    }
+}
+
+/// Drops waiting to be spawned where the world is hosted. Players ask for theirs with [`PendingPlayerDrops`] instead,
+/// so that only what's really in their inventory gets dropped.
+///
+/// [`PendingPlayerDrops`]: crate::inventory::PendingPlayerDrops
+#[derive(Unique, Debug, Default)]
+pub struct PendingItemDrops(pub Vec<ItemDrop>);
+
+impl PendingItemDrops {
+    pub fn push(&mut self, drop: ItemDrop) {
+        self.0.push(drop);
+    }
+}
+
+/// An item lying in the world. Only the host simulates them, clients just get told where they are.
+// TODO: render these once there's a way to draw entities
+#[derive(Clone, Component, Debug)]
+pub struct DroppedItem {
+    pub stack: ItemStack,
+    pub age: Duration,
+    /// Set once it's been picked up or merged away, so it gets despawned.
+    removed: bool,
+    /// The stack and position clients were last told about.
+    sent: Option<(ItemStack, Vec3)>,
+}
+
+impl DroppedItem {
+    pub fn new(stack: ItemStack) -> Self {
+        Self {
+            stack,
+            age: Duration::ZERO,
+            removed: false,
+            sent: None,
+        }
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.removed
+    }
+
+    pub fn can_be_picked_up(&self) -> bool {
+        !self.removed && self.age >= PICKUP_DELAY
+    }
+
+    /// Merges as much of `other` into this stack as fits, returning what's left of it.
+    pub fn absorb(&mut self, other: &DroppedItem) -> Option<ItemStack> {
+        let residual = self.stack.try_combine(other.stack.clone());
+
+        // merging shouldn't make a fresh drop despawn any sooner
+        if residual.as_ref().is_none_or(|residual| residual.count != other.stack.count) {
+            self.age = self.age.min(other.age);
+        }
+
+        residual
+    }
+
+    /// Puts as much of the stack into `inventory` as fits, returning what was picked up.
+    pub fn pick_up(&mut self, inventory: &mut (impl Inventory + ?Sized)) -> Option<ItemStack> {
+        if !self.can_be_picked_up() {
+            return None;
+        }
+
+        match inventory.try_insert(self.stack.clone()) {
+            None => {
+                self.removed = true;
+
+                Some(self.stack.clone())
+            }
+            Some(residual) => {
+                let picked_up = NonZeroU8::new(self.stack.count.get() - residual.count.get())?;
+
+                self.stack = residual;
+
+                Some(self.stack.item.clone().with_count(picked_up))
+            }
+        }
+    }
+}
+
+/// The host's own drops are taken straight out of the local player's inventory, like the server does for clients.
+pub fn drop_local_player_items(
+    mut player_drops: UniqueViewMut<PendingPlayerDrops>,
+    v_local_player: View<LocalPlayer>,
+    v_transform: View<Transform>,
+    mut vm_hand: ViewMut<InventoryHand>,
+    mut vm_inventory: ViewMut<PlayerInventory>,
+    mut drops: UniqueViewMut<PendingItemDrops>,
+) {
+    let Some((_, transform, hand, inventory)) = (&v_local_player, &v_transform, &mut vm_hand, &mut vm_inventory).iter().next() else {
+        return;
+    };
+
+    for (slot, stack) in player_drops.0.drain(..) {
+        if let Some(stack) = take_dropped(hand, inventory, slot, &stack) {
+            drops.push(ItemDrop::at(stack, transform.position));
+        }
+    }
+}
+
+pub fn spawn_dropped_items(
+    mut pending: UniqueViewMut<PendingItemDrops>,
+    mut entities: EntitiesViewMut,
+    (mut vm_entity, mut vm_transform, mut vm_velocity, mut vm_hitbox): (ViewMut<Entity>, ViewMut<Transform>, ViewMut<Velocity>, ViewMut<Hitbox>),
+    (mut vm_gravity_affected, mut vm_is_on_ground, mut vm_dropped_item): (ViewMut<GravityAffected>, ViewMut<IsOnGround>, ViewMut<DroppedItem>),
+) {
+    for ItemDrop { stack, position, velocity } in pending.0.drain(..) {
+        entities.add_entity(
+            (&mut vm_entity, &mut vm_transform, &mut vm_velocity, &mut vm_hitbox, &mut vm_gravity_affected, &mut vm_is_on_ground, &mut vm_dropped_item),
+            (
+                Entity,
+                Transform {
+                    position,
+                    .. Default::default()
+                },
+                Velocity(velocity),
+                Hitbox(Vec3::repeat(ITEM_SIZE)),
+                GravityAffected,
+                IsOnGround::default(),
+                DroppedItem::new(stack),
+            ),
+        );
+    }
+}
+
+/// Ages items, slows down the ones sliding along the ground and merges the ones close enough to each other.
+pub fn tick_dropped_items(
+    delta_time: UniqueView<LastDeltaTime>,
+    v_transform: View<Transform>,
+    v_is_on_ground: View<IsOnGround>,
+    mut vm_velocity: ViewMut<Velocity>,
+    mut vm_dropped_item: ViewMut<DroppedItem>,
+) {
+    let dt_secs = delta_time.0.as_secs_f32();
+
+    for (item, velocity, is_on_ground) in (&mut vm_dropped_item, &mut vm_velocity, &v_is_on_ground).iter() {
+        item.age += delta_time.0;
+
+        if is_on_ground.0 {
+            let horizontal = move_towards(&velocity.0.xz(), &glm::Vec2::zeros(), GROUND_FRICTION * dt_secs);
+
+            velocity.0.x = horizontal.x;
+            velocity.0.z = horizontal.y;
+        }
+    }
+
+    let positions = (&v_transform, &vm_dropped_item)
+        .iter()
+        .with_id()
+        .map(|(id, (transform, _))| (id, transform.position))
+        .collect::<Vec<_>>();
+
+    for (i, &(id, position)) in positions.iter().enumerate() {
+        for &(other_id, other_position) in &positions[i + 1..] {
+            if glm::distance(&position, &other_position) > MERGE_RADIUS {
+                continue;
+            }
+
+            if vm_dropped_item[id].removed || vm_dropped_item[other_id].removed {
+                continue;
+            }
+
+            let other = vm_dropped_item[other_id].clone();
+
+            match vm_dropped_item[id].absorb(&other) {
+                Some(residual) => vm_dropped_item[other_id].stack = residual,
+                None => vm_dropped_item[other_id].removed = true,
+            }
+        }
+    }
+}
+
+/// Picks up items near players into the server's copy of their inventory, which other players then get sent.
+pub fn pick_up_dropped_items(
+    (v_player, v_transform, v_gamemode): (View<Player>, View<Transform>, View<Gamemode>),
+    mut vm_inventory: ViewMut<PlayerInventory>,
+    mut vm_dropped_item: ViewMut<DroppedItem>,
+) {
+    for (_, transform, gamemode, inventory) in (&v_player, &v_transform, &v_gamemode, &mut vm_inventory).iter() {
+        if *gamemode == Gamemode::Spectator {
+            continue;
+        }
+
+        for (item_transform, item) in (&v_transform, &mut vm_dropped_item).iter() {
+            if glm::distance(&transform.position, &item_transform.position) <= PICKUP_RADIUS {
+                item.pick_up(inventory);
+            }
+        }
+    }
+}
+
+pub fn despawn_dropped_items(mut storages: AllStoragesViewMut) {
+    let despawned = storages.run(|v_dropped_item: View<DroppedItem>| {
+        v_dropped_item
+            .iter()
+            .with_id()
+            .filter(|(_, item)| item.removed || item.age >= DESPAWN_AFTER)
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>()
+    });
+
+    for id in despawned {
+        storages.delete_entity(id);
+        storages.add_entity(DroppedItemUpdate(id.inner(), None));
+    }
+}
+
+/// Tells clients about items that have changed stack or moved since they were last told.
+pub fn queue_dropped_item_updates(mut entities: EntitiesViewMut, v_transform: View<Transform>, mut vm_dropped_item: ViewMut<DroppedItem>, mut vm_dropped_item_update_evt: ViewMut<DroppedItemUpdate>) {
+    for (id, (transform, item)) in (&v_transform, &mut vm_dropped_item).iter().with_id() {
+        let changed = item.sent.as_ref().is_none_or(|(stack, position)| {
+            *stack != item.stack || glm::distance(position, &transform.position) > RESEND_DISTANCE
+        });
+
+        if changed {
+            let state = (item.stack.clone(), transform.position);
+
+            entities.add_entity(&mut vm_dropped_item_update_evt, DroppedItemUpdate(id.inner(), Some(state.clone())));
+            item.sent = Some(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::item::ItemType;
+    use super::*;
+
+    fn dropped(stack: ItemStack) -> DroppedItem {
+        DroppedItem {
+            age: PICKUP_DELAY,
+            ..DroppedItem::new(stack)
+        }
+    }
+
+    fn count(n: u8) -> NonZeroU8 {
+        NonZeroU8::new(n).expect("shouldn't be zero")
+    }
+
+    #[test]
+    fn test_absorbs_matching_stacks() {
+        let mut item = dropped(ItemType::DIRT.default_item().with_count(count(60)));
+        let stone = dropped(ItemType::STONE.default_one());
+
+        assert_eq!(item.absorb(&stone), Some(stone.stack.clone()), "different items shouldn't merge");
+
+        let residual = item.absorb(&dropped(ItemType::DIRT.default_item().with_count(count(10))));
+
+        assert_eq!(item.stack.count, item.stack.max_count());
+        assert_eq!(residual.map(|stack| stack.count.get()), Some(70 - item.stack.max_count().get()));
+
+        let mut fresh = DroppedItem::new(ItemType::DIRT.default_one());
+
+        assert_eq!(fresh.absorb(&dropped(ItemType::DIRT.default_one())), None);
+        assert_eq!(fresh.age, Duration::ZERO, "merging shouldn't age the stack");
+    }
+
+    #[test]
+    fn test_picks_up_what_fits() {
+        let mut inventory = [None, Some(ItemType::STONE.default_one())];
+        let mut item = dropped(ItemType::DIRT.default_item().with_count(count(3)));
+
+        assert_eq!(DroppedItem::new(ItemType::DIRT.default_one()).pick_up(&mut inventory[..]), None, "fresh drops can't be picked up yet");
+        assert_eq!(item.pick_up(&mut inventory[..]), Some(ItemType::DIRT.default_item().with_count(count(3))));
+        assert!(item.is_removed());
+
+        let mut full = [Some(ItemType::DIRT.default_item().with_count(count(62)))];
+        let mut item = dropped(ItemType::DIRT.default_item().with_count(count(5)));
+
+        assert_eq!(item.pick_up(&mut full[..]).map(|stack| stack.count.get()), Some(full[0].as_ref().expect("should be full").max_count().get() - 62));
+        assert!(!item.is_removed());
+        assert_eq!(item.pick_up(&mut full[..]), None);
+    }
+}
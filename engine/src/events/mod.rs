@@ -1,16 +1,26 @@
 pub mod render_distance;
 pub mod event_bus;
 
+use glm::Vec3;
 use serde::{Deserialize, Serialize};
 use game::chunk::{data::ChunkData, location::ChunkLocation};
 use shipyard::Component;
-use game::block::Block;
+use game::block::{Block, BlockInventory};
+use game::block_entity::BlockEntity;
+use game::crafting::GRID_SIZE;
+use game::inventory::transaction::{InventoryAction, SlotRef};
+use game::item::ItemStack;
 use game::location::{BlockLocation, WorldLocation};
 use packet_derive::Packet;
 use packet::Packet;
 use crate::components::Transform;
+use crate::gamemode::Gamemode;
+use crate::identity::PlayerIdentity;
+use crate::inventory::{InventoryRef, PlayerInventory};
+use crate::networking::RegistryFingerprint;
 pub use crate::networking::types::PacketType;
 use crate::render_distance::RenderDistance;
+use crate::save::player::PlayerData;
 
 #[derive(Debug, Component, Packet, Serialize, Deserialize)]
 #[packet_type(PacketType::ChunkGenRequestEvent)]
@@ -25,6 +35,40 @@ pub struct ChunkGenEvent(pub ChunkData);
 #[packet_type(PacketType::BlockUpdateEvent)]
 pub struct BlockUpdateEvent(pub BlockLocation, pub Block);
 
+/// The inventory slot a client is holding, sent ahead of the blocks it changes so the server breaks them with the same tool.
+#[derive(Debug, Component, Packet, Serialize, Deserialize)]
+#[packet_type(PacketType::HeldSlotUpdate)]
+pub struct HeldSlotUpdate(pub usize);
+
+/// What made a block change.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BlockChangeCause {
+    /// A player, by their stable identity, whether they're playing here or sent the change to the server.
+    Player(u128),
+    /// A change sent by the server, or by a client that isn't known as a player yet.
+    Network,
+    /// Generation placing blocks in chunks that are already loaded.
+    WorldGen,
+    /// Block ticks, along with the neighbor updates that follow from a change.
+    Tick,
+}
+
+/// Every block changed through [`ChunkManager::modify_block`](crate::chunks::chunk_manager::ChunkManager::modify_block),
+/// for plugins that watch the world. Each frame's changes are emitted in late update, and last until the next frame's are.
+/// Chunks being generated or loaded aren't changes, but world gen placing blocks into loaded chunks is.
+#[derive(Clone, Debug, Component)]
+pub struct BlockChanged {
+    pub location: BlockLocation,
+    pub old: Block,
+    pub new: Block,
+    pub cause: BlockChangeCause,
+}
+
+/// Sent separately from [`BlockUpdateEvent`], since a block entity can change without its block changing.
+#[derive(Debug, Component, Packet, Serialize, Deserialize)]
+#[packet_type(PacketType::BlockEntityUpdateEvent)]
+pub struct BlockEntityUpdateEvent(pub BlockLocation, pub Box<dyn BlockEntity>);
+
 #[derive(Debug, Component, Packet, Serialize, Deserialize)]
 #[packet_type(PacketType::ClientInformationRequestEvent)]
 pub struct ClientInformationRequestEvent;
@@ -43,7 +87,7 @@ pub struct ClientSettingsUpdateEvent(pub RenderDistance);
 
 #[derive(Debug, Component, Packet, Serialize, Deserialize)]
 #[packet_type(PacketType::ConnectionRequest)]
-pub struct ConnectionRequest;
+pub struct ConnectionRequest(pub PlayerIdentity, pub RegistryFingerprint);
 
 #[derive(Debug, Component, Packet, Serialize, Deserialize)]
 #[packet_type(PacketType::ConnectionSuccess)]
@@ -53,6 +97,51 @@ pub struct ConnectionSuccess;
 #[packet_type(PacketType::ClientTransformUpdate)]
 pub struct ClientTransformUpdate(pub Transform);
 
+/// Everything the server keeps about a client's player, sent once they've connected.
+#[derive(Debug, Component, Packet, Serialize, Deserialize)]
+#[packet_type(PacketType::RestorePlayerData)]
+pub struct RestorePlayerData(pub PlayerData);
+
+/// Sent by clients to ask for a gamemode, and by the server with the gamemode it put them in.
+#[derive(Debug, Component, Packet, Serialize, Deserialize)]
+#[packet_type(PacketType::GamemodeUpdate)]
+pub struct GamemodeUpdate(pub Gamemode);
+
+/// An action a client already applied to its own inventories, for the server to apply to its copies of them.
+#[derive(Debug, Component, Packet, Serialize, Deserialize)]
+#[packet_type(PacketType::InventoryActionRequest)]
+pub struct InventoryActionRequest(pub Vec<InventoryRef>, pub InventoryAction);
+
+/// The server's copy of a client's inventory, hand and crafting grid, sent whenever the server changes them.
+#[derive(Debug, Component, Packet, Serialize, Deserialize)]
+#[packet_type(PacketType::PlayerInventoryUpdate)]
+pub struct PlayerInventoryUpdate(pub PlayerInventory, pub Option<ItemStack>, pub BlockInventory<GRID_SIZE>);
+
+/// Asks the server to craft a recipe from its copy of the client's crafting grid.
+#[derive(Debug, Component, Packet, Serialize, Deserialize)]
+#[packet_type(PacketType::CraftRequest)]
+pub struct CraftRequest(pub String);
+
+/// The block a client is breaking and its crack stage, or `None` once it stops breaking.
+#[derive(Debug, Component, Packet, Serialize, Deserialize)]
+#[packet_type(PacketType::BlockBreakStage)]
+pub struct BlockBreakStage(pub Option<(BlockLocation, u8)>);
+
+/// A stage the server accepted, sent to every other client along with the server's id for the player breaking.
+#[derive(Debug, Component, Packet, Serialize, Deserialize)]
+#[packet_type(PacketType::PlayerBreakStage)]
+pub struct PlayerBreakStage(pub u64, pub Option<(BlockLocation, u8)>);
+
+/// Asks the server to drop a stack from the client's hand or inventory, which it only does if its copy has that stack there.
+#[derive(Debug, Component, Packet, Serialize, Deserialize)]
+#[packet_type(PacketType::ItemDropRequest)]
+pub struct ItemDropRequest(pub SlotRef, pub ItemStack);
+
+/// The stack and position of a dropped item, by the id of its entity on the server, or `None` once it's despawned.
+#[derive(Debug, Component, Packet, Serialize, Deserialize)]
+#[packet_type(PacketType::DroppedItemUpdate)]
+pub struct DroppedItemUpdate(pub u64, pub Option<(ItemStack, Vec3)>);
+
 #[derive(Debug, Component, Packet, Serialize, Deserialize)]
 #[packet_type(PacketType::ClientChunkRequest)]
 pub struct ClientChunkRequest(pub ChunkLocation);
@@ -1,7 +1,10 @@
-use shipyard::{Component, IntoIter, View};
-use crate::components::LocalPlayer;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, EntityId, Get, IntoIter, View, ViewMut};
+use crate::components::{LocalPlayer, SpectatorSpeed, Velocity};
+use crate::looking_at_block::LookingAtBlock;
+use crate::save::player::PlayerDataViewMut;
 
-#[derive(Clone, Component, Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Component, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Gamemode {
     #[default]
     Survival,
@@ -24,4 +27,25 @@ pub fn local_player_is_gamemode_spectator(v_local_player: View<LocalPlayer>, v_g
         .expect("local player should have gamemode");
 
     *gamemode == Gamemode::Spectator
-}
\ No newline at end of file
+}
+
+/// Also resets what the local player was doing in their old gamemode.
+pub(crate) fn switch_local_gamemode(
+    id: EntityId,
+    gamemode: Gamemode,
+    player_data: &mut PlayerDataViewMut,
+    (vm_looking_at_block, vm_spec_speed, vm_velocity): &mut (ViewMut<LookingAtBlock>, ViewMut<SpectatorSpeed>, ViewMut<Velocity>),
+) {
+    let Ok((mut look_at, mut spec_speed, mut velocity)) = (&mut *vm_looking_at_block, &mut *vm_spec_speed, &mut *vm_velocity).get(id) else {
+        return;
+    };
+
+    if gamemode == Gamemode::Spectator {
+        spec_speed.curr_speed = SpectatorSpeed::default().curr_speed;
+        look_at.0 = None;
+    }
+
+    *velocity = Velocity::default();
+
+    player_data.set_gamemode(id, gamemode);
+}
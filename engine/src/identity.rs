@@ -0,0 +1,45 @@
+use std::{fmt, fs, io};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, Unique};
+
+const IDENTITY_FILE: &str = "player_id";
+
+/// Stable identity of a player across connections, unlike their socket address.
+/// As a unique, it's the identity of the local player.
+#[derive(Copy, Clone, Component, Unique, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct PlayerIdentity(pub u128);
+
+impl fmt::Display for PlayerIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl PlayerIdentity {
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+
+    /// Reads the identity stored in the profile directory, generating and storing a new one if there isn't one yet.
+    pub fn load_or_create(profile: &Path) -> io::Result<Self> {
+        let path = profile.join(IDENTITY_FILE);
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => u128::from_str_radix(contents.trim(), 16)
+                .map(Self)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let identity = Self::random();
+
+                fs::create_dir_all(profile)?;
+                fs::write(&path, identity.to_string())?;
+
+                tracing::debug!("Created new player identity {identity} at {path:?}");
+
+                Ok(identity)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
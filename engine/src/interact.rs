@@ -1,5 +1,4 @@
 use shipyard::{Unique, UniqueViewMut, UniqueView, View, IntoIter};
-use game::block::Block;
 use game::location::BlockLocation;
 use crate::chunks::chunk_manager::ChunkManager;
 use crate::chunks::raycast::RaycastHit;
@@ -27,7 +26,7 @@ pub fn focus_interactable_block(
         return;
     };
 
-    if matches!(world.get_block_ref(location), Some(Block::Crate { .. })) {
+    if world.get_block_ref(location).is_some_and(|block| block.block_entity_kind().is_some()) {
         interactable.0 = Some(location.clone());
     } else {
         interactable.0 = None;
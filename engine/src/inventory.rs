@@ -1,9 +1,15 @@
 use std::num::NonZeroUsize;
-use shipyard::Component;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, EntityId, Get, IntoIter, Unique, UniqueViewMut, View, ViewMut};
 use game::inventory::Inventory;
-use game::item::{Item, ItemStack};
+use game::inventory::transaction::{InventoryAction, SlotRef};
+use game::item::ItemStack;
+use game::location::BlockLocation;
+use crate::components::Transform;
+use crate::crafting::CraftingGrid;
+use crate::dropped_item::{ItemDrop, PendingItemDrops};
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PlayerInventory(Box<[Option<ItemStack>]>);
 
 impl Inventory for PlayerInventory {
@@ -24,4 +30,112 @@ impl PlayerInventory {
 
         Self(v.into_boxed_slice())
     }
-}
\ No newline at end of file
+
+    pub fn from_slots(slots: Vec<Option<ItemStack>>) -> Self {
+        Self(slots.into_boxed_slice())
+    }
+}
+
+/// The stack a player picked up in their inventory, which is kept by the server for every player so it can check their inventory actions.
+#[derive(Component, Debug, Clone, Default)]
+pub struct InventoryHand(pub Option<ItemStack>);
+
+/// An inventory that the server has its own copy of, so that clients can refer to it in the inventory actions they send.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum InventoryRef {
+    Player,
+    CraftingGrid,
+    /// One of the inventories of a block entity, by its index in [`BlockEntity::inventories_mut`](game::block_entity::BlockEntity::inventories_mut).
+    BlockEntity { location: BlockLocation, index: u8 },
+}
+
+/// Inventory actions the local player made, along with the inventories they were made between, in the order they were applied.
+/// Multiplayer clients send them to the server, while the host already applied them to the only copy there is.
+#[derive(Unique, Debug, Default)]
+pub struct PendingInventoryActions(pub Vec<(Vec<InventoryRef>, InventoryAction)>);
+
+impl PendingInventoryActions {
+    pub fn push(&mut self, inventories: &[InventoryRef], action: InventoryAction) {
+        self.0.push((inventories.to_vec(), action));
+    }
+}
+
+/// Stacks the local player asked to drop, from their hand or a slot of their inventory, in the order they were asked for.
+/// The host takes them out of the local player's inventory itself, while multiplayer clients send them to the server.
+#[derive(Unique, Debug, Default)]
+pub struct PendingPlayerDrops(pub Vec<(SlotRef, ItemStack)>);
+
+impl PendingPlayerDrops {
+    pub fn push(&mut self, slot: SlotRef, stack: ItemStack) {
+        self.0.push((slot, stack));
+    }
+}
+
+/// Takes the stack a player asked to drop out of their hand or inventory, but only if it's exactly what's there.
+pub fn take_dropped(hand: &mut InventoryHand, inventory: &mut PlayerInventory, slot: SlotRef, stack: &ItemStack) -> Option<ItemStack> {
+    let held = match slot {
+        SlotRef::Hand => &mut hand.0,
+        SlotRef::Slot { inventory: 0, index } => inventory.as_mut_slice().get_mut(index as usize)?,
+        SlotRef::Slot { .. } => return None,
+    };
+
+    if held.as_ref() != Some(stack) {
+        return None;
+    }
+
+    held.take()
+}
+
+/// Puts what's in the hand and crafting grid into the inventory, returning whatever doesn't fit.
+pub fn return_held(hand: &mut InventoryHand, grid: &mut CraftingGrid, inventory: &mut PlayerInventory) -> Vec<ItemStack> {
+    let held = hand.0.take().into_iter();
+    let crafting = grid.0.as_mut_slice().iter_mut().filter_map(Option::take);
+
+    held.chain(crafting)
+        .filter_map(|stack| inventory.try_insert(stack))
+        .collect()
+}
+
+/// Puts what a player is holding and has in their crafting grid back into their inventory, dropping whatever doesn't fit.
+pub fn return_hand(id: EntityId, mut vm_hand: ViewMut<InventoryHand>, mut vm_grid: ViewMut<CraftingGrid>, mut vm_inventory: ViewMut<PlayerInventory>, v_transform: View<Transform>, mut drops: UniqueViewMut<PendingItemDrops>) {
+    let Ok((mut hand, mut grid, mut inventory, transform)) = (&mut vm_hand, &mut vm_grid, &mut vm_inventory, &v_transform).get(id) else {
+        return;
+    };
+
+    for residual in return_held(&mut hand, &mut grid, &mut inventory) {
+        drops.push(ItemDrop::at(residual, transform.position));
+    }
+}
+
+/// Does what [`return_hand`] does for every player at once, so nothing they're holding is lost when the server stops.
+pub fn return_all_hands(mut vm_hand: ViewMut<InventoryHand>, mut vm_grid: ViewMut<CraftingGrid>, mut vm_inventory: ViewMut<PlayerInventory>, v_transform: View<Transform>, mut drops: UniqueViewMut<PendingItemDrops>) {
+    for (hand, grid, inventory, transform) in (&mut vm_hand, &mut vm_grid, &mut vm_inventory, &v_transform).iter() {
+        for residual in return_held(hand, grid, inventory) {
+            drops.push(ItemDrop::at(residual, transform.position));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::item::ItemType;
+    use super::*;
+
+    #[test]
+    fn test_only_drops_what_is_there() {
+        let mut hand = InventoryHand(Some(ItemType::DIRT.default_one()));
+        let mut inventory = PlayerInventory::new(4.try_into().expect("4 is nonzero"));
+
+        inventory.try_insert(ItemType::PLANKS.default_one());
+
+        assert_eq!(take_dropped(&mut hand, &mut inventory, SlotRef::at(0, 0), &ItemType::LOG.default_one()), None);
+        assert_eq!(take_dropped(&mut hand, &mut inventory, SlotRef::at(1, 0), &ItemType::PLANKS.default_one()), None);
+        assert_eq!(take_dropped(&mut hand, &mut inventory, SlotRef::at(0, 9), &ItemType::PLANKS.default_one()), None);
+
+        assert_eq!(take_dropped(&mut hand, &mut inventory, SlotRef::at(0, 0), &ItemType::PLANKS.default_one()), Some(ItemType::PLANKS.default_one()));
+        assert_eq!(take_dropped(&mut hand, &mut inventory, SlotRef::Hand, &ItemType::DIRT.default_one()), Some(ItemType::DIRT.default_one()));
+
+        assert_eq!(inventory.as_slice()[0], None);
+        assert_eq!(hand.0, None);
+    }
+}
@@ -8,7 +8,7 @@ pub mod input;
 pub mod camera;
 mod workloads;
 mod events;
-mod world_gen;
+pub mod world_gen;
 
 pub mod chunks;
 pub mod networking;
@@ -24,5 +24,10 @@ pub mod save;
 pub mod inventory;
 pub mod block_bar_focus;
 pub mod interact;
+pub mod identity;
+pub mod crafting;
+pub mod mining;
+pub mod dropped_item;
+pub mod block_tick;
 
 pub use workloads::VoxelEngine;
\ No newline at end of file
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use shipyard::{Component, EntitiesViewMut, IntoIter, Unique, View, ViewMut};
+use game::location::BlockLocation;
+use crate::components::LocalPlayer;
+use crate::events::{BlockBreakStage, PlayerBreakStage};
+
+/// How many stages of cracks breaking a block is shown in.
+pub const BREAK_STAGES: u8 = 10;
+
+/// How far the player is through breaking the block they're looking at.
+/// It starts over whenever they look at another block or stop breaking.
+#[derive(Component, Debug, Default)]
+pub struct BreakProgress {
+    location: Option<BlockLocation>,
+    elapsed: Duration,
+    total: Duration,
+    /// The stage the server was last told about.
+    sent: Option<(BlockLocation, u8)>,
+}
+
+impl BreakProgress {
+    /// Keeps breaking the block at `location`, which takes `total` to break, starting over if it's a different block.
+    /// Returns whether it's broken.
+    pub fn advance(&mut self, location: &BlockLocation, delta: Duration, total: Duration) -> bool {
+        if self.location.as_ref() != Some(location) {
+            self.location = Some(location.clone());
+            self.elapsed = Duration::ZERO;
+        }
+
+        self.elapsed += delta;
+        self.total = total;
+
+        self.elapsed >= total
+    }
+
+    pub fn reset(&mut self) {
+        self.location = None;
+        self.elapsed = Duration::ZERO;
+    }
+
+    /// How far through breaking the block it is, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        match self.location {
+            Some(_) if !self.total.is_zero() => (self.elapsed.as_secs_f32() / self.total.as_secs_f32()).min(1.0),
+            _ => 0.0,
+        }
+    }
+
+    /// The block being broken and which of the [`BREAK_STAGES`] its cracks are at.
+    pub fn stage(&self) -> Option<(BlockLocation, u8)> {
+        let location = self.location.clone()?;
+        let stage = (self.fraction() * BREAK_STAGES as f32) as u8;
+
+        Some((location, stage.min(BREAK_STAGES - 1)))
+    }
+}
+
+/// How much of a block's break time has to have passed on the server, since packets don't arrive as evenly as they're sent.
+const BREAK_TIME_LENIENCY: f32 = 0.8;
+
+/// The block a client started breaking and when, which the server keeps to check they didn't break it faster than they could.
+#[derive(Component, Debug)]
+pub struct BreakStarted(pub BlockLocation, pub Instant);
+
+impl BreakStarted {
+    /// Whether the block at `location` has been broken for long enough, given it takes `break_time` with the client's tool.
+    pub fn allows(started: Option<&BreakStarted>, location: &BlockLocation, break_time: Duration) -> bool {
+        break_time.is_zero() || started.is_some_and(|BreakStarted(started_at, since)| {
+            started_at == location && since.elapsed() >= break_time.mul_f32(BREAK_TIME_LENIENCY)
+        })
+    }
+}
+
+/// The crack stages of the blocks other players are breaking, by the server's id for each player, so they can be shown.
+/// Each player only breaks one block at a time, and the local player's own progress is in their [`BreakProgress`].
+// TODO: draw cracks over these once there are textures for them
+#[derive(Unique, Debug, Default)]
+pub struct BreakingBlocks(pub HashMap<u64, (BlockLocation, u8)>);
+
+impl BreakingBlocks {
+    pub fn apply(&mut self, PlayerBreakStage(player, stage): PlayerBreakStage) {
+        match stage {
+            Some(stage) => self.0.insert(player, stage),
+            None => self.0.remove(&player),
+        };
+    }
+}
+
+/// Tells the server whenever the local player's crack stage changes, or they stop breaking a block.
+pub fn queue_break_stage_updates(mut entities: EntitiesViewMut, v_local_player: View<LocalPlayer>, mut vm_break_progress: ViewMut<BreakProgress>, mut vm_break_stage_evt: ViewMut<BlockBreakStage>) {
+    let Some((_, progress)) = (&v_local_player, &mut vm_break_progress).iter().next() else {
+        return;
+    };
+
+    let stage = progress.stage();
+
+    if stage == progress.sent {
+        return;
+    }
+
+    entities.add_entity(&mut vm_break_stage_evt, BlockBreakStage(stage.clone()));
+
+    progress.sent = stage;
+}
+
+#[cfg(test)]
+mod tests {
+    use glm::IVec3;
+    use super::*;
+
+    #[test]
+    fn test_progress_starts_over_on_another_block() {
+        let mut progress = BreakProgress::default();
+        let (a, b) = (BlockLocation(IVec3::new(0, 0, 0)), BlockLocation(IVec3::new(1, 0, 0)));
+        let second = Duration::from_secs(1);
+
+        assert!(!progress.advance(&a, second, second * 2));
+        assert_eq!(progress.stage(), Some((a.clone(), BREAK_STAGES / 2)));
+
+        assert!(!progress.advance(&b, second, second * 2), "looking at another block should start over");
+        assert!(progress.advance(&b, second, second * 2));
+        assert_eq!(progress.stage(), Some((b, BREAK_STAGES - 1)));
+
+        progress.reset();
+
+        assert_eq!(progress.stage(), None);
+        assert!(progress.advance(&a, Duration::ZERO, Duration::ZERO), "blocks without hardness should break straight away");
+    }
+
+    #[test]
+    fn test_breaks_are_only_allowed_once_the_break_time_has_passed() {
+        let (a, b) = (BlockLocation(IVec3::new(0, 0, 0)), BlockLocation(IVec3::new(1, 0, 0)));
+        let second = Duration::from_secs(1);
+        let started = BreakStarted(a.clone(), Instant::now() - second);
+
+        assert!(BreakStarted::allows(Some(&started), &a, second));
+        assert!(!BreakStarted::allows(Some(&started), &a, second * 2));
+        assert!(!BreakStarted::allows(Some(&started), &b, second), "it should have to be the block they started breaking");
+        assert!(!BreakStarted::allows(None, &a, second));
+        assert!(BreakStarted::allows(None, &a, Duration::ZERO), "blocks without hardness should break straight away");
+    }
+}
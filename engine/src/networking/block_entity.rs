@@ -0,0 +1,64 @@
+use laminar::Packet;
+use shipyard::{EntitiesViewMut, IntoIter, UniqueView, UniqueViewMut, View, ViewMut};
+use networking::{PacketRegistry, RuntimePacket};
+use crate::application::delta_time::LastDeltaTime;
+use crate::chunks::chunk_manager::ChunkManager;
+use crate::events::BlockEntityUpdateEvent;
+use crate::events::event_bus::EventBus;
+use crate::networking::server_handler::ServerHandler;
+
+/// Block entities only tick where the world is hosted, and clients get sent whatever changed.
+pub fn tick_block_entities(delta_time: UniqueView<LastDeltaTime>, mut world: UniqueViewMut<ChunkManager>) {
+    world.tick_block_entities(delta_time.0);
+}
+
+/// Turns every block entity changed since the last frame, whether by ticking or by a player, into an update to send.
+pub fn queue_block_entity_updates(mut world: UniqueViewMut<ChunkManager>, mut entities: EntitiesViewMut, mut vm_block_entity_update_evt: ViewMut<BlockEntityUpdateEvent>) {
+    for (loc, entity) in world.take_changed_block_entities() {
+        entities.add_entity(&mut vm_block_entity_update_evt, BlockEntityUpdateEvent(loc, entity));
+    }
+}
+
+pub fn server_broadcast_block_entity_updates(server_handler: UniqueView<ServerHandler>, registry: UniqueView<PacketRegistry>, v_block_entity_update_evt: View<BlockEntityUpdateEvent>) {
+    let tx = &server_handler.tx;
+
+    let type_id = registry
+        .identifier_of()
+        .expect("should be registered");
+
+    for &addr in server_handler.clients.left_values() {
+        for evt in (&v_block_entity_update_evt).iter() {
+            let packet = Packet::reliable_unordered(
+                addr,
+                evt.serialize_uncompressed_with_id(type_id)
+                    .expect("packet serialization failed"),
+            );
+
+            if tx.try_send(packet).is_err() {
+                tracing::error!("Failed to send block entity update to client {addr:?}");
+            }
+        }
+    }
+}
+
+/// Clients can only change block entities through inventory actions, so whatever they send as a whole is thrown away.
+pub fn server_clear_block_entity_updates(mut vm_block_entity_update_evt_bus: ViewMut<EventBus<BlockEntityUpdateEvent>>, mut vm_block_entity_update_evt: ViewMut<BlockEntityUpdateEvent>) {
+    for (id, bus) in vm_block_entity_update_evt_bus.drain().with_id() {
+        if !bus.0.is_empty() {
+            tracing::debug!("Ignored block entity updates from client {id:?}");
+        }
+    }
+
+    vm_block_entity_update_evt.drain();
+}
+
+/// Local edits were sent as inventory actions, so only the server's copies are kept.
+pub fn client_apply_block_entity_updates(mut world: UniqueViewMut<ChunkManager>, mut vm_block_entity_update_evt: ViewMut<BlockEntityUpdateEvent>) {
+    world.take_changed_block_entities();
+
+    for BlockEntityUpdateEvent(loc, entity) in vm_block_entity_update_evt.drain() {
+        if world.set_block_entity(&loc, entity).is_err() {
+            tracing::error!("Block entity update at {loc:?} didn't match a loaded block");
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use laminar::Packet;
+use shipyard::{Get, UniqueView, UniqueViewMut, ViewMut};
+use networking::{PacketRegistry, RuntimePacket};
+use crate::crafting::{craft_recipe, CraftingGrid, PendingCraft};
+use crate::events::CraftRequest;
+use crate::events::event_bus::EventBus;
+use crate::inventory::PlayerInventory;
+use crate::networking::server_connection::ServerConnection;
+
+/// Only the server crafts, from its own copy of the grid, and the result comes back with the next inventory update.
+pub fn client_send_craft_requests(mut pending: UniqueViewMut<PendingCraft>, server_connection: UniqueView<ServerConnection>, registry: UniqueView<PacketRegistry>) {
+    let Some(name) = pending.0.take() else {
+        return;
+    };
+
+    let id = registry
+        .identifier_of()
+        .expect("should be registered");
+
+    // ordered along with inventory actions, so the server's grid has everything moved into it before crafting
+    let packet = Packet::reliable_ordered(
+        server_connection.server_addr,
+        CraftRequest(name)
+            .serialize_uncompressed_with_id(id)
+            .expect("packet serialization failed"),
+        None,
+    );
+
+    if let Err(err) = server_connection.tx.try_send(packet) {
+        tracing::error!("failed to send craft request to server: {err:?}");
+    }
+}
+
+pub fn server_apply_craft_requests(mut vm_craft_request_bus: ViewMut<EventBus<CraftRequest>>, mut vm_grid: ViewMut<CraftingGrid>, mut vm_inventory: ViewMut<PlayerInventory>) {
+    for (id, bus) in vm_craft_request_bus.drain().with_id() {
+        let Ok((mut grid, mut inventory)) = (&mut vm_grid, &mut vm_inventory).get(id) else {
+            tracing::debug!("Client sent craft requests before spawning");
+            continue;
+        };
+
+        for CraftRequest(name) in bus.0 {
+            craft_recipe(&name, &mut grid, &mut inventory);
+        }
+    }
+}
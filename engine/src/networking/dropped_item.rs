@@ -0,0 +1,173 @@
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use glm::Vec3;
+use laminar::Packet;
+use shipyard::{Delete, EntitiesViewMut, EntityId, Get, IntoIter, IntoWithId, Unique, UniqueOrDefaultViewMut, UniqueView, UniqueViewMut, View, ViewMut};
+use networking::{PacketRegistry, RuntimePacket};
+use crate::components::{Hitbox, LocalPlayer, Transform};
+use crate::dropped_item::{DroppedItem, ItemDrop, PendingItemDrops, ITEM_SIZE};
+use crate::events::{DroppedItemUpdate, ItemDropRequest};
+use crate::events::event_bus::EventBus;
+use crate::inventory::{take_dropped, InventoryHand, PendingPlayerDrops, PlayerInventory};
+use crate::networking::server_connection::ServerConnection;
+use crate::networking::server_handler::ServerHandler;
+
+/// The clients that have been sent every dropped item, so that from then on they're only sent what changes.
+#[derive(Unique, Debug, Default)]
+pub struct SyncedDroppedItems(HashSet<SocketAddr>);
+
+/// The local copies of the server's dropped items, by the id of their entity on the server.
+#[derive(Unique, Debug, Default)]
+pub struct ReplicatedDroppedItems(HashMap<u64, EntityId>);
+
+/// Items are only spawned by the server, so drops are sent to it instead. They're taken out of the local copy of the
+/// inventory straight away, and the server's next inventory update puts them back if it didn't agree.
+pub fn client_send_item_drops(
+    mut player_drops: UniqueViewMut<PendingPlayerDrops>,
+    v_local_player: View<LocalPlayer>,
+    mut vm_hand: ViewMut<InventoryHand>,
+    mut vm_inventory: ViewMut<PlayerInventory>,
+    server_connection: UniqueView<ServerConnection>,
+    registry: UniqueView<PacketRegistry>,
+) {
+    let Some((_, hand, inventory)) = (&v_local_player, &mut vm_hand, &mut vm_inventory).iter().next() else {
+        return;
+    };
+
+    let id = registry
+        .identifier_of()
+        .expect("should be registered");
+
+    for (slot, stack) in player_drops.0.drain(..) {
+        take_dropped(hand, inventory, slot, &stack);
+
+        // ordered along with inventory actions, since the stack is only there once the ones before it were applied
+        let packet = Packet::reliable_ordered(
+            server_connection.server_addr,
+            ItemDropRequest(slot, stack)
+                .serialize_uncompressed_with_id(id)
+                .expect("packet serialization failed"),
+            None,
+        );
+
+        if let Err(err) = server_connection.tx.try_send(packet) {
+            tracing::error!("failed to send item drop to server: {err:?}");
+        }
+    }
+}
+
+/// Drops are taken out of the server's copy of the client's inventory, and spawned where the server has the client.
+pub fn server_accept_item_drops(
+    mut vm_item_drop_req_bus: ViewMut<EventBus<ItemDropRequest>>,
+    v_transform: View<Transform>,
+    mut vm_hand: ViewMut<InventoryHand>,
+    mut vm_inventory: ViewMut<PlayerInventory>,
+    mut pending: UniqueViewMut<PendingItemDrops>,
+) {
+    for (id, bus) in vm_item_drop_req_bus.drain().with_id() {
+        let Ok((transform, mut hand, mut inventory)) = (&v_transform, &mut vm_hand, &mut vm_inventory).get(id) else {
+            tracing::debug!("Client dropped items before spawning");
+            continue;
+        };
+
+        for ItemDropRequest(slot, stack) in bus.0 {
+            match take_dropped(&mut hand, &mut inventory, slot, &stack) {
+                Some(stack) => pending.push(ItemDrop::at(stack, transform.position)),
+                None => tracing::debug!("Rejected drop of {stack:?} from {slot:?}, which client {id:?} doesn't have"),
+            }
+        }
+    }
+}
+
+/// Sends clients every dropped item the first time round, then only what changed.
+pub fn server_broadcast_dropped_items(
+    server_handler: UniqueView<ServerHandler>,
+    registry: UniqueView<PacketRegistry>,
+    mut synced: UniqueOrDefaultViewMut<SyncedDroppedItems>,
+    v_transform: View<Transform>,
+    v_dropped_item: View<DroppedItem>,
+    v_dropped_item_update_evt: View<DroppedItemUpdate>,
+) {
+    let tx = &server_handler.tx;
+
+    let type_id = registry
+        .identifier_of()
+        .expect("should be registered");
+
+    synced.0.retain(|addr| server_handler.clients.contains_left(addr));
+
+    for &addr in server_handler.clients.left_values() {
+        let updates = if synced.0.insert(addr) {
+            (&v_transform, &v_dropped_item)
+                .iter()
+                .with_id()
+                .filter(|(_, (_, item))| !item.is_removed())
+                .map(|(id, (transform, item))| DroppedItemUpdate(id.inner(), Some((item.stack.clone(), transform.position))))
+                .collect::<Vec<_>>()
+        } else {
+            v_dropped_item_update_evt
+                .iter()
+                .map(|DroppedItemUpdate(id, state)| DroppedItemUpdate(*id, state.clone()))
+                .collect()
+        };
+
+        for evt in updates {
+            let packet = Packet::reliable_unordered(
+                addr,
+                evt.serialize_uncompressed_with_id(type_id)
+                    .expect("packet serialization failed"),
+            );
+
+            if tx.try_send(packet).is_err() {
+                tracing::error!("Failed to send dropped item to client {addr:?}");
+            }
+        }
+    }
+}
+
+/// The host's updates were only needed for broadcasting.
+pub fn server_clear_dropped_item_updates(mut vm_dropped_item_update_evt: ViewMut<DroppedItemUpdate>) {
+    vm_dropped_item_update_evt.drain();
+}
+
+pub fn client_apply_dropped_item_updates(
+    mut entities: EntitiesViewMut,
+    mut replicated: UniqueOrDefaultViewMut<ReplicatedDroppedItems>,
+    mut vm_dropped_item_update_evt: ViewMut<DroppedItemUpdate>,
+    mut vm_transform: ViewMut<Transform>,
+    mut vm_hitbox: ViewMut<Hitbox>,
+    mut vm_dropped_item: ViewMut<DroppedItem>,
+) {
+    for DroppedItemUpdate(server_id, state) in vm_dropped_item_update_evt.drain() {
+        match (state, replicated.0.get(&server_id).copied()) {
+            (Some((stack, position)), Some(id)) => {
+                if let Ok((mut transform, mut item)) = (&mut vm_transform, &mut vm_dropped_item).get(id) {
+                    transform.position = position;
+                    item.stack = stack;
+                }
+            }
+            (Some((stack, position)), None) => {
+                let id = entities.add_entity(
+                    (&mut vm_transform, &mut vm_hitbox, &mut vm_dropped_item),
+                    (
+                        Transform {
+                            position,
+                            .. Default::default()
+                        },
+                        Hitbox(Vec3::repeat(ITEM_SIZE)),
+                        DroppedItem::new(stack),
+                    ),
+                );
+
+                replicated.0.insert(server_id, id);
+            }
+            (None, Some(id)) => {
+                replicated.0.remove(&server_id);
+
+                (&mut vm_transform, &mut vm_hitbox, &mut vm_dropped_item).delete(id);
+                entities.delete_unchecked(id);
+            }
+            (None, None) => {}
+        }
+    }
+}
@@ -0,0 +1,419 @@
+use laminar::Packet;
+use shipyard::{Component, EntitiesView, Get, IntoIter, UniqueView, UniqueViewMut, View, ViewMut};
+use game::inventory::Inventory;
+use game::inventory::transaction::{InventoryAction, Transaction, TransactionError};
+use game::item::ItemStack;
+use networking::{PacketRegistry, RuntimePacket};
+use game::location::BlockLocation;
+use crate::chunks::chunk_manager::ChunkManager;
+use crate::components::{LocalPlayer, Transform};
+use crate::crafting::CraftingGrid;
+use crate::events::{InventoryActionRequest, PlayerInventoryUpdate};
+use crate::events::event_bus::EventBus;
+use crate::inventory::{InventoryHand, InventoryRef, PendingInventoryActions, PlayerInventory};
+use crate::networking::MAX_REACH;
+use crate::networking::server_connection::ServerConnection;
+use crate::networking::server_handler::ServerHandler;
+
+#[derive(Debug, thiserror::Error)]
+pub enum InventoryActionError {
+    #[error("{0:?} was referred to more than once")]
+    Repeated(InventoryRef),
+    #[error("there's no block entity at {0:?}")]
+    NoBlockEntity(BlockLocation),
+    #[error("actions can only be between one block entity and the player")]
+    SeveralBlockEntities,
+    #[error("{0:?} is too far away")]
+    OutOfReach(BlockLocation),
+    #[error("{1:?} can't go in inventory {0} of the block entity")]
+    NotAccepted(usize, ItemStack),
+    #[error(transparent)]
+    Transaction(#[from] TransactionError),
+}
+
+/// The inventory, hand and crafting grid a client was last sent, so that they're only sent again once the server changes them.
+#[derive(Component, Debug)]
+pub struct SentInventory(PlayerInventory, Option<ItemStack>, CraftingGrid);
+
+/// The block entity the inventories refer to, if any, since a transaction can only borrow one of them from the world.
+fn block_entity_location(refs: &[InventoryRef]) -> Result<Option<&BlockLocation>, InventoryActionError> {
+    let mut locations = refs.iter().filter_map(|inventory_ref| match inventory_ref {
+        InventoryRef::BlockEntity { location, .. } => Some(location),
+        InventoryRef::Player | InventoryRef::CraftingGrid => None,
+    });
+
+    let location = locations.next();
+
+    if locations.any(|other| Some(other) != location) {
+        return Err(InventoryActionError::SeveralBlockEntities);
+    }
+
+    Ok(location)
+}
+
+/// Whatever was put into a slot by going from `before` to `after`, if anything.
+fn inserted<'a>(before: &Option<ItemStack>, after: &'a Option<ItemStack>) -> Option<&'a ItemStack> {
+    after.as_ref().filter(|after| before.as_ref().is_none_or(|before| before.item != after.item || before.count < after.count))
+}
+
+/// Applies a client's action to the server's copies of the inventories it names, in the order it names them.
+/// A block entity it changes is marked as changed, so it gets saved and sent to every client.
+/// Putting something into a block entity inventory that doesn't [accept](game::block_entity::BlockEntity::accepts) it
+/// undoes the whole action.
+pub fn apply_inventory_action(hand: &mut InventoryHand, inventory: &mut PlayerInventory, grid: &mut CraftingGrid, world: &mut ChunkManager, refs: &[InventoryRef], action: &InventoryAction) -> Result<(), InventoryActionError> {
+    let location = block_entity_location(refs)?;
+
+    let before = location
+        .and_then(|location| world.get_block_entity_mut(location))
+        .map(|entity| {
+            let slots = entity.inventories_mut().into_iter().map(|slots| slots.to_vec()).collect::<Vec<_>>();
+
+            (slots, hand.clone(), inventory.clone(), grid.clone())
+        });
+
+    {
+        let mut block_entity = match location {
+            Some(location) => world
+                .get_block_entity_mut(location)
+                .ok_or_else(|| InventoryActionError::NoBlockEntity(location.clone()))?
+                .inventories_mut()
+                .into_iter()
+                .map(Some)
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let mut player = Some(inventory.as_mut_slice());
+        let mut crafting = Some(grid.0.as_mut_slice());
+        let mut transaction = Transaction::new(&mut hand.0);
+
+        for inventory_ref in refs {
+            let slots = match inventory_ref {
+                InventoryRef::Player => player.take(),
+                InventoryRef::CraftingGrid => crafting.take(),
+                // an index that's out of range is left for the transaction to report, by giving it nothing to look in
+                InventoryRef::BlockEntity { index, .. } => match block_entity.get_mut(*index as usize) {
+                    Some(slots) => slots.take(),
+                    None => Some(&mut [][..]),
+                },
+            };
+
+            transaction = transaction.with(slots.ok_or_else(|| InventoryActionError::Repeated(inventory_ref.clone()))?);
+        }
+
+        transaction.apply(action)?;
+    }
+
+    if let Some(location) = location {
+        let (slots_before, hand_before, inventory_before, grid_before) = before.expect("the block entity was there for the transaction");
+        let entity = world.get_block_entity_mut(location).expect("the block entity was there for the transaction");
+
+        let slots_after = entity.inventories_mut().into_iter().map(|slots| slots.to_vec()).collect::<Vec<_>>();
+
+        let rejected = slots_before
+            .iter()
+            .zip(&slots_after)
+            .enumerate()
+            .flat_map(|(index, (before, after))| before.iter().zip(after).filter_map(move |(before, after)| Some((index, inserted(before, after)?))))
+            .find(|(index, stack)| !entity.accepts(*index, stack));
+
+        if let Some((index, stack)) = rejected {
+            let stack = stack.clone();
+
+            for (slots, before) in entity.inventories_mut().into_iter().zip(slots_before) {
+                slots.clone_from_slice(&before);
+            }
+
+            *hand = hand_before;
+            *inventory = inventory_before;
+            *grid = grid_before;
+
+            return Err(InventoryActionError::NotAccepted(index, stack));
+        }
+
+        world.mark_block_entity_changed(location);
+    }
+
+    Ok(())
+}
+
+pub fn client_send_inventory_actions(mut pending: UniqueViewMut<PendingInventoryActions>, server_connection: UniqueView<ServerConnection>, registry: UniqueView<PacketRegistry>) {
+    let id = registry
+        .identifier_of()
+        .expect("should be registered");
+
+    for (refs, action) in pending.0.drain(..) {
+        // each action is made against what the ones before it left behind
+        let packet = Packet::reliable_ordered(
+            server_connection.server_addr,
+            InventoryActionRequest(refs, action)
+                .serialize_uncompressed_with_id(id)
+                .expect("packet serialization failed"),
+            None,
+        );
+
+        if let Err(err) = server_connection.tx.try_send(packet) {
+            tracing::error!("failed to send inventory action to server: {err:?}");
+        }
+    }
+}
+
+/// The host applied its own actions straight away, so they were only kept in case they had to be sent.
+pub fn clear_pending_inventory_actions(mut pending: UniqueViewMut<PendingInventoryActions>) {
+    pending.0.clear();
+}
+
+/// Actions that don't apply to the server's copies are dropped, and the client finds out through the next inventory
+/// and block entity updates.
+pub fn server_apply_inventory_actions(mut world: UniqueViewMut<ChunkManager>, mut vm_inventory_action_req_bus: ViewMut<EventBus<InventoryActionRequest>>, v_transform: View<Transform>, mut vm_hand: ViewMut<InventoryHand>, mut vm_grid: ViewMut<CraftingGrid>, mut vm_inventory: ViewMut<PlayerInventory>) {
+    for (id, bus) in vm_inventory_action_req_bus.drain().with_id() {
+        let Ok((transform, mut hand, mut grid, mut inventory)) = (&v_transform, &mut vm_hand, &mut vm_grid, &mut vm_inventory).get(id) else {
+            tracing::debug!("Client sent inventory actions before spawning");
+            continue;
+        };
+
+        for InventoryActionRequest(refs, action) in bus.0 {
+            let result = match block_entity_location(&refs) {
+                Ok(Some(location)) if glm::distance(&location.0.cast::<f32>(), &transform.position) > MAX_REACH => Err(InventoryActionError::OutOfReach(location.clone())),
+                _ => apply_inventory_action(&mut hand, &mut inventory, &mut grid, &mut world, &refs, &action),
+            };
+
+            if let Err(err) = result {
+                tracing::debug!("Rejected {action:?} from client {id:?}: {err}");
+            }
+        }
+    }
+}
+
+/// Sends clients their inventory, hand and crafting grid whenever they differ from what they were last sent, however the server changed them.
+pub fn server_send_inventory_updates(
+    server_handler: UniqueView<ServerHandler>,
+    registry: UniqueView<PacketRegistry>,
+    entities: EntitiesView,
+    v_inventory: View<PlayerInventory>,
+    v_hand: View<InventoryHand>,
+    v_grid: View<CraftingGrid>,
+    mut vm_sent_inventory: ViewMut<SentInventory>,
+) {
+    let type_id = registry
+        .identifier_of()
+        .expect("should be registered");
+
+    for (&addr, &id) in &server_handler.clients {
+        let Ok((inventory, hand, grid)) = (&v_inventory, &v_hand, &v_grid).get(id) else {
+            continue;
+        };
+
+        let sent = vm_sent_inventory.get(id).ok();
+
+        if sent.is_some_and(|SentInventory(sent_inventory, sent_hand, sent_grid)| sent_inventory == inventory && *sent_hand == hand.0 && sent_grid == grid) {
+            continue;
+        }
+
+        let payload = PlayerInventoryUpdate(inventory.clone(), hand.0.clone(), grid.0.clone())
+            .serialize_uncompressed_with_id(type_id)
+            .expect("packet serialization failed");
+
+        // an older inventory arriving late would undo a newer one
+        if let Err(err) = server_handler.tx.try_send(Packet::reliable_ordered(addr, payload, None)) {
+            tracing::error!("failed to send inventory to client at {addr:?}: {err:?}");
+            continue;
+        }
+
+        entities.add_component(id, &mut vm_sent_inventory, SentInventory(inventory.clone(), hand.0.clone(), grid.clone()));
+    }
+}
+
+pub fn client_apply_inventory_updates(mut vm_inventory_update: ViewMut<PlayerInventoryUpdate>, v_local_player: View<LocalPlayer>, mut vm_inventory: ViewMut<PlayerInventory>, mut vm_hand: ViewMut<InventoryHand>, mut vm_grid: ViewMut<CraftingGrid>) {
+    let Some((_, inventory, hand, grid)) = (&v_local_player, &mut vm_inventory, &mut vm_hand, &mut vm_grid).iter().next() else {
+        return;
+    };
+
+    // only the latest one counts, since each has the whole inventory
+    if let Some(PlayerInventoryUpdate(update, held, crafting)) = vm_inventory_update.drain().last() {
+        *inventory = update;
+        hand.0 = held;
+        grid.0 = crafting;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glm::IVec3;
+    use game::block::Block;
+    use game::block_entity::CrateEntity;
+    use game::machine::SmelterEntity;
+    use game::chunk::data::ChunkData;
+    use game::chunk::location::ChunkLocation;
+    use game::inventory::transaction::SlotRef;
+    use game::item::ItemType;
+    use crate::crafting::craft_recipe;
+    use crate::events::BlockChangeCause;
+    use crate::save::with_large_stack;
+    use super::*;
+
+    #[test]
+    fn test_inventories_can_only_be_referred_to_once() {
+        let mut world = ChunkManager::new(1, None);
+        let mut hand = InventoryHand::default();
+        let mut grid = CraftingGrid::default();
+        let mut inventory = PlayerInventory::new(4.try_into().expect("4 is nonzero"));
+
+        inventory.try_insert(ItemType::DIRT.default_one());
+
+        let action = InventoryAction::QuickTransfer { from: SlotRef::at(0, 0), to: 1 };
+
+        assert!(matches!(
+            apply_inventory_action(&mut hand, &mut inventory, &mut grid, &mut world, &[InventoryRef::Player, InventoryRef::Player], &action),
+            Err(InventoryActionError::Repeated(InventoryRef::Player)),
+        ));
+
+        apply_inventory_action(&mut hand, &mut inventory, &mut grid, &mut world, &[InventoryRef::Player], &InventoryAction::Swap(SlotRef::Hand, SlotRef::at(0, 0)))
+            .expect("should pick up the dirt");
+
+        assert_eq!(hand.0, Some(ItemType::DIRT.default_one()));
+        assert_eq!(inventory.as_slice()[0], None);
+    }
+
+    #[test]
+    fn test_actions_move_items_into_block_entities() {
+        with_large_stack(|| {
+            let mut world = ChunkManager::new(1, None);
+            world.insert_chunk(ChunkData::empty(ChunkLocation::default()));
+
+            let location = BlockLocation(IVec3::new(1, 2, 3));
+            world.modify_block(&location, Block::CRATE, BlockChangeCause::Player(1)).expect("chunk is loaded");
+            world.take_changed_block_entities();
+
+            let mut hand = InventoryHand::default();
+            let mut grid = CraftingGrid::default();
+            let mut inventory = PlayerInventory::new(4.try_into().expect("4 is nonzero"));
+
+            inventory.try_insert(ItemType::PLANKS.default_one());
+
+            let refs = [InventoryRef::Player, InventoryRef::BlockEntity { location: location.clone(), index: 0 }];
+            let action = InventoryAction::QuickTransfer { from: SlotRef::at(0, 0), to: 1 };
+
+            apply_inventory_action(&mut hand, &mut inventory, &mut grid, &mut world, &refs, &action).expect("the crate has room");
+
+            assert_eq!(inventory.as_slice()[0], None);
+
+            let CrateEntity { inventory: crate_inventory } = world.get_block_entity_ref(&location)
+                .and_then(|entity| entity.downcast_ref())
+                .expect("the crate should still be there");
+
+            assert_eq!(crate_inventory.as_slice()[0], Some(ItemType::PLANKS.default_one()));
+            assert_eq!(world.take_changed_block_entities().len(), 1);
+
+            let elsewhere = InventoryRef::BlockEntity { location: BlockLocation(IVec3::new(3, 2, 1)), index: 0 };
+
+            assert!(matches!(
+                apply_inventory_action(&mut hand, &mut inventory, &mut grid, &mut world, &[elsewhere], &action),
+                Err(InventoryActionError::NoBlockEntity(_)),
+            ));
+        });
+    }
+
+    #[test]
+    fn test_actions_keep_progress_ticked_in_the_same_frame() {
+        with_large_stack(|| {
+            let mut world = ChunkManager::new(1, None);
+            world.insert_chunk(ChunkData::empty(ChunkLocation::default()));
+
+            let location = BlockLocation(IVec3::new(1, 2, 3));
+            world.modify_block(&location, Block::SMELTER, BlockChangeCause::Player(1)).expect("chunk is loaded");
+
+            let smelter: &mut SmelterEntity = world.get_block_entity_mut(&location)
+                .and_then(|entity| entity.downcast_mut())
+                .expect("placing a smelter should create its block entity");
+
+            smelter.input.try_insert(ItemType::HEMATITE_NUGGETS.default_one());
+            smelter.fuel.try_insert(ItemType::LOG.default_one());
+
+            let time = smelter.recipe().expect("hematite should smelt").time;
+
+            let mut hand = InventoryHand::default();
+            let mut grid = CraftingGrid::default();
+            let mut inventory = PlayerInventory::new(4.try_into().expect("4 is nonzero"));
+
+            inventory.try_insert(ItemType::HEMATITE_NUGGETS.default_one());
+
+            // the server ticks in early_update, and applies what the client sent once it's received in the same frame
+            world.tick_block_entities(time / 2);
+
+            let refs = [InventoryRef::Player, InventoryRef::BlockEntity { location: location.clone(), index: 0 }];
+            let action = InventoryAction::QuickTransfer { from: SlotRef::at(0, 0), to: 1 };
+
+            apply_inventory_action(&mut hand, &mut inventory, &mut grid, &mut world, &refs, &action).expect("the input slot has room");
+
+            let smelter: &SmelterEntity = world.get_block_entity_ref(&location)
+                .and_then(|entity| entity.downcast_ref())
+                .expect("the smelter should still be there");
+
+            assert_eq!(smelter.progress, time / 2, "the client's action shouldn't undo the tick");
+            assert_eq!(smelter.input.as_slice()[0], Some(ItemType::HEMATITE_NUGGETS.default_item().with_count(2.try_into().expect("2 is nonzero"))));
+            assert!(smelter.fuel.items().next().is_none(), "the log should still be burning");
+            assert_eq!(inventory.as_slice()[0], None);
+        });
+    }
+
+    #[test]
+    fn test_smelters_only_take_fuel_and_never_take_output() {
+        with_large_stack(|| {
+            let mut world = ChunkManager::new(1, None);
+            world.insert_chunk(ChunkData::empty(ChunkLocation::default()));
+
+            let location = BlockLocation(IVec3::new(1, 2, 3));
+            world.modify_block(&location, Block::SMELTER, BlockChangeCause::Player(1)).expect("chunk is loaded");
+
+            let mut hand = InventoryHand::default();
+            let mut grid = CraftingGrid::default();
+            let mut inventory = PlayerInventory::new(4.try_into().expect("4 is nonzero"));
+
+            inventory.try_insert(ItemType::HEMATITE_NUGGETS.default_one());
+            inventory.try_insert(ItemType::LOG.default_one());
+
+            let refs = |index| [InventoryRef::Player, InventoryRef::BlockEntity { location: location.clone(), index }];
+            let move_into = |slot| InventoryAction::QuickTransfer { from: SlotRef::at(0, slot), to: 1 };
+
+            assert!(matches!(
+                apply_inventory_action(&mut hand, &mut inventory, &mut grid, &mut world, &refs(2), &move_into(0)),
+                Err(InventoryActionError::NotAccepted(2, _)),
+            ));
+            assert!(matches!(
+                apply_inventory_action(&mut hand, &mut inventory, &mut grid, &mut world, &refs(1), &move_into(0)),
+                Err(InventoryActionError::NotAccepted(1, _)),
+            ));
+            assert_eq!(inventory.as_slice()[0], Some(ItemType::HEMATITE_NUGGETS.default_one()), "rejected actions should be undone");
+
+            apply_inventory_action(&mut hand, &mut inventory, &mut grid, &mut world, &refs(1), &move_into(1)).expect("logs are fuel");
+
+            let smelter: &SmelterEntity = world.get_block_entity_ref(&location)
+                .and_then(|entity| entity.downcast_ref())
+                .expect("the smelter should still be there");
+
+            assert_eq!(smelter.fuel.as_slice()[0], Some(ItemType::LOG.default_one()));
+            assert!(smelter.output.items().next().is_none());
+        });
+    }
+
+    #[test]
+    fn test_crafts_from_what_actions_moved_into_the_grid() {
+        let mut world = ChunkManager::new(1, None);
+        let mut hand = InventoryHand::default();
+        let mut grid = CraftingGrid::default();
+        let mut inventory = PlayerInventory::new(4.try_into().expect("4 is nonzero"));
+
+        inventory.try_insert(ItemType::LOG.default_one());
+
+        let action = InventoryAction::QuickTransfer { from: SlotRef::at(1, 0), to: 0 };
+
+        apply_inventory_action(&mut hand, &mut inventory, &mut grid, &mut world, &[InventoryRef::CraftingGrid, InventoryRef::Player], &action)
+            .expect("the grid is empty");
+
+        craft_recipe("protovox:planks", &mut grid, &mut inventory);
+
+        assert!(grid.0.items().next().is_none(), "the log should've been used up");
+        assert_eq!(inventory.as_slice()[0], Some(ItemType::PLANKS.default_item().with_count(4.try_into().expect("4 is nonzero"))));
+    }
+}
@@ -0,0 +1,127 @@
+use laminar::Packet;
+use std::time::Instant;
+use shipyard::{EntitiesViewMut, EntityId, Get, IntoIter, IntoWithId, Remove, UniqueView, UniqueViewMut, View, ViewMut};
+use game::block::Block;
+use networking::{PacketRegistry, RuntimePacket};
+use crate::chunks::chunk_manager::ChunkManager;
+use crate::components::{LocalPlayer, Transform};
+use crate::events::{BlockBreakStage, PlayerBreakStage};
+use crate::events::event_bus::EventBus;
+use crate::gamemode::Gamemode;
+use crate::mining::{BreakStarted, BreakingBlocks, BREAK_STAGES};
+use crate::networking::MAX_REACH;
+use crate::networking::server_connection::ServerConnection;
+use crate::networking::server_handler::ServerHandler;
+
+/// Sent on the same ordered stream as block updates, so the server has seen a break start before the block is broken.
+pub fn client_send_break_stages(server_connection: UniqueView<ServerConnection>, registry: UniqueView<PacketRegistry>, mut vm_break_stage_evt: ViewMut<BlockBreakStage>) {
+    let tx = &server_connection.tx;
+    let server_addr = server_connection.server_addr;
+
+    let id = registry
+        .identifier_of()
+        .expect("should be registered");
+
+    for evt in vm_break_stage_evt.drain() {
+        let packet = Packet::reliable_ordered(
+            server_addr,
+            evt.serialize_uncompressed_with_id(id)
+                .expect("packet serialization failed"),
+            None,
+        );
+
+        if tx.try_send(packet).is_err() {
+            tracing::error!("Failed to send {evt:?} to server");
+        }
+    }
+}
+
+/// Clients can only be breaking a solid, loaded block within reach, and not while spectating.
+/// Accepted stages are kept by player and queued for broadcasting, and the rest are dropped.
+/// The host's own stages are trusted, but aren't kept, since it draws its own cracks.
+pub fn server_accept_break_stages(
+    world: UniqueView<ChunkManager>,
+    mut breaking: UniqueViewMut<BreakingBlocks>,
+    (v_local_player, v_gamemode, v_transform): (View<LocalPlayer>, View<Gamemode>, View<Transform>),
+    mut vm_break_started: ViewMut<BreakStarted>,
+    mut vm_break_stage_evt_bus: ViewMut<EventBus<BlockBreakStage>>,
+    mut vm_break_stage_evt: ViewMut<BlockBreakStage>,
+    mut entities: EntitiesViewMut,
+    mut vm_player_break_stage_evt: ViewMut<PlayerBreakStage>,
+) {
+    if let Some((host, _)) = v_local_player.iter().with_id().next() {
+        for BlockBreakStage(stage) in vm_break_stage_evt.drain() {
+            entities.add_entity(&mut vm_player_break_stage_evt, PlayerBreakStage(host.inner(), stage));
+        }
+    }
+
+    for (id, bus) in vm_break_stage_evt_bus.drain().with_id() {
+        let spectating = v_gamemode.get(id).is_ok_and(|gamemode| *gamemode == Gamemode::Spectator);
+        let position = v_transform.get(id).ok().map(|transform| transform.position);
+
+        for BlockBreakStage(stage) in bus.0 {
+            let allowed = stage.as_ref().is_none_or(|(location, stage)| {
+                *stage < BREAK_STAGES
+                    && !spectating
+                    && world.get_block_ref(location).is_some_and(|block| *block != Block::AIR)
+                    && position.is_some_and(|position| glm::distance(&location.0.cast::<f32>(), &position) <= MAX_REACH)
+            });
+
+            if !allowed {
+                tracing::debug!("Rejected break stage {stage:?} from client {id:?}");
+                continue;
+            }
+
+            match &stage {
+                Some((location, _)) if vm_break_started.get(id).is_ok_and(|BreakStarted(started_at, _)| started_at == location) => {}
+                Some((location, _)) => entities.add_component(id, &mut vm_break_started, BreakStarted(location.clone(), Instant::now())),
+                None => {
+                    vm_break_started.remove(id);
+                }
+            }
+
+            breaking.apply(PlayerBreakStage(id.inner(), stage.clone()));
+            entities.add_entity(&mut vm_player_break_stage_evt, PlayerBreakStage(id.inner(), stage));
+        }
+    }
+}
+
+/// Each player's stages go to everyone but them.
+pub fn server_broadcast_break_stages(server_handler: UniqueView<ServerHandler>, registry: UniqueView<PacketRegistry>, v_player_break_stage_evt: View<PlayerBreakStage>) {
+    let tx = &server_handler.tx;
+
+    let type_id = registry
+        .identifier_of()
+        .expect("should be registered");
+
+    for (&addr, &id) in &server_handler.clients {
+        for evt in v_player_break_stage_evt.iter().filter(|evt| evt.0 != id.inner()) {
+            let packet = Packet::reliable_unordered(
+                addr,
+                evt.serialize_uncompressed_with_id(type_id)
+                    .expect("packet serialization failed"),
+            );
+
+            if tx.try_send(packet).is_err() {
+                tracing::error!("Failed to send break stage to client {addr:?}");
+            }
+        }
+    }
+}
+
+/// Clears the block a disconnecting client was breaking, and tells everyone else they stopped.
+pub fn server_stop_breaking(id: EntityId, mut breaking: UniqueViewMut<BreakingBlocks>, mut entities: EntitiesViewMut, mut vm_player_break_stage_evt: ViewMut<PlayerBreakStage>) {
+    if breaking.0.remove(&id.inner()).is_some() {
+        entities.add_entity(&mut vm_player_break_stage_evt, PlayerBreakStage(id.inner(), None));
+    }
+}
+
+pub fn server_clear_break_stages(mut vm_player_break_stage_evt: ViewMut<PlayerBreakStage>) {
+    vm_player_break_stage_evt.drain();
+}
+
+pub fn client_apply_break_stages(mut breaking: UniqueViewMut<BreakingBlocks>, mut vm_player_break_stage_evt: ViewMut<PlayerBreakStage>) {
+    for evt in vm_player_break_stage_evt.drain() {
+        breaking.apply(evt);
+    }
+}
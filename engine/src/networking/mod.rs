@@ -1,13 +1,16 @@
 use std::net::SocketAddr;
 use crossbeam::channel::Sender;
+use serde::{Deserialize, Serialize};
 use laminar::Packet;
 use shipyard::{AllStoragesView, EntitiesView, EntitiesViewMut, IntoIter, IntoWithId, UniqueView, View, ViewMut};
+use game::block::registry::BlockRegistry;
 use game::chunk::data::ChunkData;
+use game::item::registry::ItemRegistry;
 use networking::{PacketIdentifier, PacketRegistry, RuntimePacket};
 use crate::application::exit::ExitRequested;
 use crate::chunks::chunk_manager::ChunkManager;
-use crate::components::{LocalPlayer, Transform};
-use crate::events::{BlockUpdateEvent, ChunkGenEvent, ChunkGenRequestEvent, ClientChunkRequest, ClientSettingsRequestEvent, ClientTransformUpdate, ConnectionRequest, ConnectionSuccess, KickedByServer};
+use crate::components::{HeldBlock, LocalPlayer, Transform};
+use crate::events::{BlockUpdateEvent, HeldSlotUpdate, ChunkGenEvent, ChunkGenRequestEvent, ClientChunkRequest, ClientSettingsRequestEvent, ClientTransformUpdate, ConnectionRequest, ConnectionSuccess, KickedByServer};
 use crate::events::event_bus::EventBus;
 use crate::events::render_distance::RenderDistanceUpdateEvent;
 use crate::networking::server_connection::ServerConnection;
@@ -18,20 +21,67 @@ pub mod types;
 pub mod server_handler;
 pub mod keep_alive;
 pub mod server_connection;
+pub mod player_data;
+pub mod block_entity;
+pub mod crafting;
+pub mod mining;
+pub mod dropped_item;
+pub mod inventory;
+
+/// How far from a block a client can be to break, place or use it.
+pub(crate) const MAX_REACH: f32 = 8.0;
+
+/// Blocks and items are sent by id, so clients can only join a server that gives every name the same id.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RegistryFingerprint {
+    pub blocks: u64,
+    pub items: u64,
+}
+
+impl RegistryFingerprint {
+    pub fn local() -> Self {
+        Self {
+            blocks: BlockRegistry::global().fingerprint(),
+            items: ItemRegistry::global().fingerprint(),
+        }
+    }
+}
 
-pub fn client_send_block_updates(server_connection: UniqueView<ServerConnection>, registry: UniqueView<PacketRegistry>, v_block_update_evt: View<BlockUpdateEvent>) {
+/// The held slot goes first, on the same ordered stream, since the server rolls the loot of broken blocks with its tool.
+pub fn client_send_block_updates(server_connection: UniqueView<ServerConnection>, registry: UniqueView<PacketRegistry>, held: UniqueView<HeldBlock>, v_block_update_evt: View<BlockUpdateEvent>) {
     let tx = &server_connection.tx;
     let server_addr = server_connection.server_addr;
 
+    if v_block_update_evt.is_empty() {
+        return;
+    }
+
+    let held_id = registry
+        .identifier_of()
+        .expect("should be registered");
+
+    let held_packet = Packet::reliable_ordered(
+        server_addr,
+        HeldSlotUpdate(held.0)
+            .serialize_uncompressed_with_id(held_id)
+            .expect("packet serialization failed"),
+        None,
+    );
+
+    if let Err(err) = tx.try_send(held_packet) {
+        tracing::error!("failed to send held slot to server: {err:?}");
+    }
+
     let id = registry
         .identifier_of()
         .expect("should be registered");
     
     for evt in (&v_block_update_evt).iter() {
-        let packet = Packet::reliable_unordered(
+        let packet = Packet::reliable_ordered(
             server_addr,
             evt.serialize_uncompressed_with_id(id)
-                .expect("packet serialization failed")
+                .expect("packet serialization failed"),
+            None,
         );
         
         if tx.try_send(packet).is_err() {
@@ -0,0 +1,122 @@
+use laminar::Packet;
+use shipyard::{Get, IntoIter, IntoWithId, UniqueView, View, ViewMut};
+use networking::{PacketRegistry, RuntimePacket};
+use crate::components::{LocalPlayer, SpectatorSpeed, Velocity};
+use crate::events::{GamemodeUpdate, RestorePlayerData};
+use crate::gamemode::switch_local_gamemode;
+use crate::identity::PlayerIdentity;
+use crate::looking_at_block::LookingAtBlock;
+use crate::networking::server_connection::ServerConnection;
+use crate::networking::server_handler::ServerHandler;
+use crate::save::player::{PlayerData, PlayerDataStore, PlayerDataViewMut, RestorePlayerDataRequest};
+use crate::save::world::WorldDirectory;
+
+/// Clients get sent their saved data, or what new players start with, since the server's copy is the only one that counts.
+pub fn server_restore_player_data(mut vm_restore_req: ViewMut<RestorePlayerDataRequest>, v_identity: View<PlayerIdentity>, store: UniqueView<PlayerDataStore>, world_dir: UniqueView<WorldDirectory>, server_handler: UniqueView<ServerHandler>, registry: UniqueView<PacketRegistry>, mut player_data: PlayerDataViewMut) {
+    let type_id = registry
+        .identifier_of()
+        .expect("should be registered");
+
+    for (id, _) in vm_restore_req.drain().with_id() {
+        let Some(&addr) = server_handler.clients.get_by_right(&id) else {
+            tracing::debug!("Client has disconnected!");
+            continue;
+        };
+
+        let Ok(identity) = v_identity.get(id) else {
+            tracing::error!("Client at {addr:?} doesn't have a player identity");
+            continue;
+        };
+
+        let data = store.load(identity).unwrap_or_else(|| {
+            tracing::debug!("No saved player data for {identity}, starting fresh");
+
+            PlayerData::starting(world_dir.level.spawn)
+        });
+
+        let payload = RestorePlayerData(data.clone())
+            .serialize_uncompressed_with_id(type_id)
+            .expect("packet serialization failed");
+
+        if let Err(err) = server_handler.tx.try_send(Packet::reliable_unordered(addr, payload)) {
+            tracing::error!("failed to send player data to client at {addr:?}: {err:?}");
+        }
+
+        player_data.apply(id, data);
+    }
+}
+
+pub fn client_restore_player_data(mut vm_restore: ViewMut<RestorePlayerData>, v_local_player: View<LocalPlayer>, mut player_data: PlayerDataViewMut) {
+    let Some((local_player_id, _)) = v_local_player.iter().with_id().next() else {
+        return;
+    };
+
+    for RestorePlayerData(data) in vm_restore.drain() {
+        tracing::debug!("Restoring player data sent by the server");
+
+        player_data.apply(local_player_id, data);
+    }
+}
+
+/// Drains the local player's requests as they're sent, so that only the server's answers are left once packets are received.
+pub fn client_send_gamemode_requests(server_connection: UniqueView<ServerConnection>, registry: UniqueView<PacketRegistry>, mut vm_gamemode_update: ViewMut<GamemodeUpdate>) {
+    let id = registry
+        .identifier_of()
+        .expect("should be registered");
+
+    for evt in vm_gamemode_update.drain() {
+        let packet = Packet::reliable_unordered(
+            server_connection.server_addr,
+            evt.serialize_uncompressed_with_id(id)
+                .expect("packet serialization failed")
+        );
+
+        if let Err(err) = server_connection.tx.try_send(packet) {
+            tracing::error!("failed to send gamemode request to server: {err:?}");
+        }
+    }
+}
+
+/// Every player can switch gamemode for now, so requests are only checked against the player existing.
+pub fn server_apply_gamemode_requests(mut vm_gamemode_update: ViewMut<GamemodeUpdate>, server_handler: UniqueView<ServerHandler>, registry: UniqueView<PacketRegistry>, mut player_data: PlayerDataViewMut) {
+    let type_id = registry
+        .identifier_of()
+        .expect("should be registered");
+
+    for (id, GamemodeUpdate(gamemode)) in vm_gamemode_update.drain().with_id() {
+        let Some(&addr) = server_handler.clients.get_by_right(&id) else {
+            tracing::debug!("Client has disconnected!");
+            continue;
+        };
+
+        if !player_data.gamemode.contains(id) {
+            tracing::debug!("Client at {addr:?} asked for a gamemode before spawning");
+            continue;
+        }
+
+        player_data.set_gamemode(id, gamemode.clone());
+
+        let payload = GamemodeUpdate(gamemode)
+            .serialize_uncompressed_with_id(type_id)
+            .expect("packet serialization failed");
+
+        if let Err(err) = server_handler.tx.try_send(Packet::reliable_unordered(addr, payload)) {
+            tracing::error!("failed to send gamemode to client at {addr:?}: {err:?}");
+        }
+    }
+}
+
+pub fn client_apply_gamemode_updates(
+    mut vm_gamemode_update: ViewMut<GamemodeUpdate>,
+    v_local_player: View<LocalPlayer>,
+    mut player_data: PlayerDataViewMut,
+    mut reset: (ViewMut<LookingAtBlock>, ViewMut<SpectatorSpeed>, ViewMut<Velocity>),
+) {
+    let Some((local_player_id, _)) = v_local_player.iter().with_id().next() else {
+        return;
+    };
+
+    for GamemodeUpdate(gamemode) in vm_gamemode_update.drain() {
+        switch_local_gamemode(local_player_id, gamemode, &mut player_data, &mut reset);
+    }
+}
@@ -6,6 +6,8 @@ use laminar::{Packet, Socket, SocketEvent};
 use shipyard::{AllStoragesViewMut, Unique, UniqueView};
 use networking::{PacketIdentifier, PacketRegistry, RuntimePacket};
 use crate::events::ConnectionRequest;
+use crate::identity::PlayerIdentity;
+use crate::networking::RegistryFingerprint;
 
 #[derive(Unique)]
 pub struct ServerConnection {
@@ -15,7 +17,7 @@ pub struct ServerConnection {
 }
 
 impl ServerConnection {
-    pub fn bind(server_addr: impl Into<SocketAddr>, packet_id: PacketIdentifier<ConnectionRequest>) -> Self {
+    pub fn bind(server_addr: impl Into<SocketAddr>, packet_id: PacketIdentifier<ConnectionRequest>, identity: PlayerIdentity) -> Self {
         let config = laminar::Config {
             max_packet_size: 64 * 1024,
             max_fragments: 64,
@@ -38,7 +40,7 @@ impl ServerConnection {
 
         let connection_req = Packet::reliable_ordered(
             server_addr,
-            ConnectionRequest
+            ConnectionRequest(identity, RegistryFingerprint::local())
                 .serialize_uncompressed_with_id(packet_id)
                 .expect("packet serialization failed"),
             None, // TODO: configure stream ids
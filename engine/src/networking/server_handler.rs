@@ -4,9 +4,14 @@ use std::time::Duration;
 use bimap::BiHashMap;
 use crossbeam::channel::{Receiver, Sender};
 use laminar::{Socket, SocketEvent};
-use shipyard::{AllStoragesViewMut, EntityId, Unique, UniqueView, UniqueViewMut};
-use networking::PacketRegistry;
-use crate::events::{ClientInformationRequestEvent, ClientSettingsRequestEvent, ConnectionRequest};
+use shipyard::{AllStoragesViewMut, EntityId, IntoIter, Unique, UniqueView, UniqueViewMut, View};
+use networking::{PacketRegistry, RuntimePacket};
+use crate::events::{ClientInformationRequestEvent, ClientSettingsRequestEvent, ConnectionRequest, KickedByServer};
+use crate::identity::PlayerIdentity;
+use crate::inventory::return_hand;
+use crate::networking::RegistryFingerprint;
+use crate::networking::mining::server_stop_breaking;
+use crate::save::player::{save_player, RestorePlayerDataRequest};
 
 #[derive(Unique)]
 pub struct ServerHandler {
@@ -92,8 +97,30 @@ pub fn server_process_network_events(mut storages: AllStoragesViewMut) {
                                 continue;
                             }
 
+                            let Some(ConnectionRequest(identity, fingerprint)) = ConnectionRequest::deserialize::<false>(payload) else {
+                                tracing::warn!("Failed to deserialize ConnectionRequest from {addr:?}");
+                                continue;
+                            };
+
+                            if fingerprint != RegistryFingerprint::local() {
+                                tracing::warn!("Player {identity} tried to connect from {addr:?} with different block or item definitions.");
+                                kick(&storages, addr, "Your block and item definitions don't match the server's");
+                                continue;
+                            }
+
+                            let already_connected = storages
+                                .borrow::<View<PlayerIdentity>>()
+                                .is_ok_and(|v_identity| v_identity.iter().any(|other| *other == identity));
+
+                            if already_connected {
+                                tracing::warn!("Player {identity} tried to connect from {addr:?}, but is already connected; they need a different profile.");
+                                continue;
+                            }
+
                             let id = storages.add_entity((
-                                ConnectionRequest,
+                                ConnectionRequest(identity, fingerprint),
+                                identity,
+                                RestorePlayerDataRequest,
                                 ClientInformationRequestEvent,
                                 ClientSettingsRequestEvent,
                             ));
@@ -140,6 +167,9 @@ pub fn server_process_network_events(mut storages: AllStoragesViewMut) {
 
                     if let Some((_, id)) = server_handler.clients.remove_by_left(&addr) {
                         drop(server_handler);
+                        storages.run_with_data(return_hand, id);
+                        storages.run_with_data(server_stop_breaking, id);
+                        storages.run_with_data(save_player, id);
                         storages.delete_entity(id);
                     } else {
                         tracing::error!("Client disconnected at {addr:?}, but it never existed.");
@@ -151,4 +181,23 @@ pub fn server_process_network_events(mut storages: AllStoragesViewMut) {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Kicks an address that never joined, such as one that was turned away while connecting.
+fn kick(storages: &AllStoragesViewMut, addr: SocketAddr, reason: &str) {
+    let registry = storages
+        .borrow::<UniqueView<PacketRegistry>>()
+        .expect("registry to be initialized");
+
+    let payload = KickedByServer(reason.into())
+        .serialize_uncompressed_with_id(registry.identifier_of().expect("should be registered"))
+        .expect("packet serialization failed");
+
+    let server_handler = storages
+        .borrow::<UniqueView<ServerHandler>>()
+        .expect("ServerHandler re-borrowed");
+
+    if server_handler.tx.try_send(laminar::Packet::reliable_unordered(addr, payload)).is_err() {
+        tracing::error!("Failed to send kick packet to {addr:?}");
+    }
+}
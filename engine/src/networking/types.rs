@@ -13,6 +13,8 @@ pub enum PacketType {
     ChunkGenEvent,
 
     BlockUpdateEvent,
+    HeldSlotUpdate,
+    BlockEntityUpdateEvent,
 
     RenderDistanceRequestEvent,
     RenderDistanceUpdateEvent,
@@ -24,6 +26,19 @@ pub enum PacketType {
     ClientSettingsUpdateEvent,
 
     ClientTransformUpdate,
+    RestorePlayerData,
+    GamemodeUpdate,
+
+    InventoryActionRequest,
+    PlayerInventoryUpdate,
+
+    CraftRequest,
+
+    BlockBreakStage,
+    PlayerBreakStage,
+
+    ItemDropRequest,
+    DroppedItemUpdate,
 
     KickedByServer,
     
@@ -1,6 +1,5 @@
 use glm::Vec3;
 use shipyard::{IntoIter, UniqueView, View, ViewMut};
-use game::block::Block;
 use game::location::WorldLocation;
 use crate::application::delta_time::LastDeltaTime;
 use crate::chunks::chunk_manager::ChunkManager;
@@ -34,7 +33,7 @@ pub fn move_with_collision(
                         let world_loc = WorldLocation(Vec3::new(x as f32, y as f32, z as f32));
                         
                         if let Some(block) = world.get_block_ref(&world_loc.into()) {
-                            if *block != Block::Air {
+                            if block.is_solid() {
                                 return Some(true);
                             }
                         } else {
@@ -1,6 +1,7 @@
 use std::array;
 use shipyard::{AllStoragesView, IntoIter, Unique, UniqueView, UniqueViewMut, View};
 use game::block::face_type::FaceType;
+use game::block::registry::MAX_LIGHT;
 use game::chunk::pos::ChunkPos;
 use game::texture_ids::TextureId;
 use crate::chunks::raycast::RaycastHit;
@@ -36,7 +37,7 @@ pub fn update_block_outline_buffer(
 
     let chunk_pos = ChunkPos::from(location);
 
-    let faces: [_; 6] = array::from_fn(|ty| FaceData::new(chunk_pos, FaceType::ALL[ty], TextureId::Selection));
+    let faces: [_; 6] = array::from_fn(|ty| FaceData::new(chunk_pos, FaceType::ALL[ty], TextureId::Selection, MAX_LIGHT));
 
     outline_rend_state.buffer.size = 6;
 
@@ -1,22 +1,24 @@
 use std::array;
 use game::block::Block;
 use game::block::face_type::FaceType;
+use game::block::registry::MAX_LIGHT;
 use game::chunk::BLOCKS_PER_CHUNK;
-use game::chunk::data::{ChunkBlocks, ChunkData};
+use game::chunk::data::ChunkData;
 use game::chunk::location::ChunkLocation;
 use game::chunk::pos::ChunkPos;
 use crate::chunks::chunk_manager::ChunkManager;
+use crate::chunks::client_chunk::ClientChunk;
 use crate::rendering::face_data::FaceData;
 
 pub struct ChunkMeshContext<'a> {
-    pub sides: [Option<&'a ChunkBlocks>; 6],
-    pub center: &'a ChunkBlocks,
+    pub sides: [Option<&'a ClientChunk>; 6],
+    pub center: &'a ClientChunk,
 }
 
 
 impl<'a> ChunkMeshContext<'a> {
-    pub fn from_manager(chunk_mgr: &'a ChunkManager, center_chunk: &'a ChunkData) -> Self {
-        let center_loc = &center_chunk.location;
+    pub fn from_manager(chunk_mgr: &'a ChunkManager, center_chunk: &'a ClientChunk) -> Self {
+        let center_loc = &center_chunk.data.location;
 
         let sides = array::from_fn(|i| {
             let ft = FaceType::from_repr(i as _)
@@ -25,47 +27,57 @@ impl<'a> ChunkMeshContext<'a> {
             let new_loc = ChunkLocation(center_loc.0 + ft.as_vector());
 
             chunk_mgr.get_chunk_ref(&new_loc)
-                .map(|c| c.data.blocks_ref())
         });
 
         Self {
             sides,
-            center: &center_chunk.blocks_ref(),
+            center: center_chunk,
         }
     }
 
     pub fn faces(&self) -> Vec<FaceData> {
         let mut faces = Vec::new();
 
+        // empty chunks are common enough that it's worth skipping them without looking at every block
+        if self.center.data.uniform().is_some_and(|block| block.definition().textures.is_none()) {
+            return faces;
+        }
+
         for pos in 0..BLOCKS_PER_CHUNK {
             let pos = ChunkPos(pos as _);
 
-            let block = self.center.get(pos.0 as usize).expect("should be in range");
+            let block = self.center.data.block_ref(pos);
 
-            if *block == Block::Air {
+            if block.definition().textures.is_none() {
                 continue;
             }
 
             for ft in FaceType::ALL {
-                fn adj_is_air(blocks: &ChunkBlocks, adj: ChunkPos) -> bool {
-                    *blocks.get(adj.0 as usize).expect("in range") == Block::Air
+                // faces are hidden behind opaque blocks, and between blocks of the same kind like water, whatever their state
+                fn shows_face(chunk: &ChunkData, adj: ChunkPos, block: &Block) -> bool {
+                    let adj = chunk.block_ref(adj);
+
+                    adj.is_transparent() && adj.id() != block.id()
                 }
 
-                match pos.adjacent_to_face(ft) {
+                // faces are as lit as the block they face into
+                let light = match pos.adjacent_to_face(ft) {
                     // in range
                     Ok(adj) => {
-                        if !adj_is_air(self.center, adj) {
+                        if !shows_face(&self.center.data, adj, block) {
                             continue;
                         }
+
+                        self.center.light.get(adj).level()
                     }
-                    Err(adj) => {
-                        if !self.sides[ft as usize].is_none_or(|blocks| adj_is_air(blocks, adj)) {
-                            continue;
-                        }
+                    Err(adj) => match self.sides[ft as usize] {
+                        Some(side) if !shows_face(&side.data, adj, block) => continue,
+                        Some(side) => side.light.get(adj).level(),
+                        None => MAX_LIGHT,
                     }
-                }
+                };
 
-                faces.push(FaceData::new(pos, ft, block.texture_id(ft).expect("not air")));
+                faces.push(FaceData::new(pos, ft, block.texture_id(ft).expect("has textures"), light));
             }
         }
 
@@ -4,14 +4,15 @@ pub use game::texture_ids::TextureId;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-// layout PPPPPPPPPPPPPPPPFFFTTTTTTTT_____
+// layout PPPPPPPPPPPPPPPPFFFTTTTTTTTLLLL_
 pub struct FaceData(u32);
 
 impl FaceData {
-    pub fn new(pos: ChunkPos, face: FaceType, texture_id: TextureId) -> Self {
+    pub fn new(pos: ChunkPos, face: FaceType, texture_id: TextureId, light: u8) -> Self {
         let mut data = pos.0 as _;
         data |= (face as u8 as u32 & 0x7) << 16;
         data |= (texture_id as u32 & 0xFF) << (16 + 3);
+        data |= (light as u32 & 0xF) << (16 + 3 + 8);
 
         Self(data)
     }
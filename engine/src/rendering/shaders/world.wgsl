@@ -38,6 +38,7 @@ const FACE_LEFT: u32 = 4;
 const FACE_RIGHT: u32 = 5;
 
 const CHUNK_SIZE: vec3<u32> = vec3(32, 64, 32);
+const MAX_LIGHT: u32 = 15;
 
 @vertex
 fn vs_main(
@@ -91,6 +92,7 @@ var s_diffuse: sampler;
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> { // store result in first color target
     let face_type = in.face_data >> 16 & 0x7;
     let texture_id = in.face_data >> (16 + 3) & 0xFF;
+    let light = in.face_data >> (16 + 3 + 8) & 0xF;
 
     // TODO: refactor this?
     var rotated_coords: vec2<f32>;
@@ -115,7 +117,10 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> { // store result in firs
         default: { shadow_factor = 0.875; }
     }
 
+    // each level of light is a little dimmer than the one above it, with a bit of light even in the dark
+    let brightness = max(pow(0.8, f32(MAX_LIGHT - light)), 0.05);
+
     let color = textureSample(t_diffuse, s_diffuse, rotated_coords, texture_id);
 
-    return vec4(color.rgb * shadow_factor, color.a);
+    return vec4(color.rgb * shadow_factor * brightness, color.a);
 }
\ No newline at end of file
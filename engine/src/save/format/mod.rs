@@ -0,0 +1,473 @@
+use std::io::{self, Read};
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use game::chunk::BLOCKS_PER_CHUNK;
+use game::chunk::data::ChunkData;
+use crate::save::format::v0::ChunkV0;
+use crate::save::format::v1::ChunkV1;
+use crate::save::format::v2::ChunkV2;
+use crate::save::format::v4::ChunkV4;
+use crate::save::format::v5::ChunkV5;
+use crate::save::format::v6::ChunkV6;
+use crate::save::format::v7::ChunkV7;
+use crate::save::format::v8::ChunkV8;
+
+pub mod packed;
+pub mod v0;
+pub mod v1;
+pub mod v2;
+pub mod v4;
+pub mod v5;
+pub mod v6;
+pub mod v7;
+pub mod v8;
+
+/// Bumped whenever the layout of a saved chunk changes, alongside a migration from the previous version.
+/// Version 3 has the same body as version 2, with a checksum added to the header,
+/// version 4 replaces its crate inventories with block entities, version 5 saves blocks by name,
+/// version 6 saves the items in block entities by name, version 7 saves them with their components,
+/// and version 8 saves scheduled block ticks.
+pub const CHUNK_FORMAT_VERSION: u16 = 8;
+
+/// Every chunk saved with a header starts with these bytes.
+/// Headerless (version 0) chunks can never start with them, since the last byte isn't a valid block variant.
+const MAGIC: [u8; 4] = *b"PVCF";
+const HEADER_LEN: usize = MAGIC.len() + size_of::<u16>();
+const CHECKSUM_LEN: usize = size_of::<u32>();
+/// The first version with a CRC32 of the rest of the chunk after its header.
+const CHECKSUM_VERSION: u16 = 3;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChunkFormatError {
+    #[error("failed to (de)serialize chunk: {0}")]
+    Serialization(#[from] postcard::Error),
+    #[error("failed to (de)compress chunk: {0}")]
+    Compression(#[from] io::Error),
+    #[error("chunk format version {0} is newer than the supported version {CHUNK_FORMAT_VERSION}")]
+    UnsupportedVersion(u16),
+    #[error("unknown compression {0}")]
+    UnknownCompression(u8),
+    #[error("unknown block id {0}")]
+    UnknownBlockId(u16),
+    #[error("block {0} isn't defined")]
+    UnknownBlock(String),
+    #[error("saved state doesn't match block {0}")]
+    InvalidBlockState(String),
+    #[error("chunk had {0} blocks instead of {BLOCKS_PER_CHUNK}")]
+    WrongBlockCount(usize),
+    #[error("palette indices didn't match the palette or the size of the chunk")]
+    MalformedIndices,
+    #[error("unknown item id {0}")]
+    UnknownItemId(u32),
+    #[error("item {0} isn't defined")]
+    UnknownItem(String),
+    #[error("unknown block entity kind {0}")]
+    UnknownBlockEntity(u16),
+    #[error("saved block entity doesn't belong to block {0}")]
+    InvalidBlockEntity(String),
+    #[error("checksum mismatch, expected {expected:#010x} but was {actual:#010x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+/// How the body of a chunk is compressed, stored at the start of the body since version 2.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ChunkCompression {
+    None = 0,
+    /// Deflate, with zlib's header and checksum.
+    #[default]
+    Zlib = 1,
+}
+
+impl ChunkCompression {
+    fn from_repr(repr: u8) -> Result<Self, ChunkFormatError> {
+        match repr {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Zlib),
+            _ => Err(ChunkFormatError::UnknownCompression(repr)),
+        }
+    }
+}
+
+/// A chunk as it was saved, before being migrated to the latest version.
+enum VersionedChunk {
+    V0(ChunkV0),
+    V1(ChunkV1),
+    V2(ChunkV2),
+    V4(ChunkV4),
+    V5(ChunkV5),
+    V6(ChunkV6),
+    V7(ChunkV7),
+    V8(ChunkV8),
+}
+
+impl VersionedChunk {
+    fn parse(version: u16, body: &[u8]) -> Result<Self, ChunkFormatError> {
+        let chunk = match version {
+            0 => Self::V0(postcard::from_bytes(body)?),
+            1 => Self::V1(postcard::from_bytes(body)?),
+            2 | 3 => Self::V2(postcard::from_bytes(&decompress(body)?)?),
+            4 => Self::V4(postcard::from_bytes(&decompress(body)?)?),
+            5 => Self::V5(postcard::from_bytes(&decompress(body)?)?),
+            6 => Self::V6(postcard::from_bytes(&decompress(body)?)?),
+            7 => Self::V7(postcard::from_bytes(&decompress(body)?)?),
+            8 => Self::V8(postcard::from_bytes(&decompress(body)?)?),
+            _ => return Err(ChunkFormatError::UnsupportedVersion(version)),
+        };
+
+        Ok(chunk)
+    }
+
+    /// Upgrades the chunk by a single version.
+    fn migrate(self) -> Result<Self, ChunkFormatError> {
+        let chunk = match self {
+            Self::V0(chunk) => Self::V1(chunk.into()),
+            Self::V1(chunk) => Self::V2(chunk.try_into()?),
+            Self::V2(chunk) => Self::V4(chunk.try_into()?),
+            Self::V4(chunk) => Self::V5(chunk.try_into()?),
+            Self::V5(chunk) => Self::V6(chunk.try_into()?),
+            Self::V6(chunk) => Self::V7(chunk.try_into()?),
+            Self::V7(chunk) => Self::V8(chunk.into()),
+            Self::V8(_) => unreachable!("already the latest version"),
+        };
+
+        Ok(chunk)
+    }
+}
+
+fn decompress(body: &[u8]) -> Result<Vec<u8>, ChunkFormatError> {
+    let (&compression, body) = body.split_first().ok_or(postcard::Error::DeserializeUnexpectedEnd)?;
+
+    match ChunkCompression::from_repr(compression)? {
+        ChunkCompression::None => Ok(body.to_vec()),
+        ChunkCompression::Zlib => {
+            let mut bytes = Vec::new();
+
+            ZlibDecoder::new(body).read_to_end(&mut bytes)?;
+
+            Ok(bytes)
+        }
+    }
+}
+
+pub fn encode_chunk(data: &ChunkData, compression: ChunkCompression) -> Result<Vec<u8>, ChunkFormatError> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + CHECKSUM_LEN + 1 + BLOCKS_PER_CHUNK / 8);
+
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&CHUNK_FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&[0; CHECKSUM_LEN]); // filled in once the body is written
+    bytes.push(compression as u8);
+
+    let chunk = ChunkV8::from_chunk(data)?;
+
+    let mut bytes = match compression {
+        ChunkCompression::None => postcard::to_extend(&chunk, bytes)?,
+        ChunkCompression::Zlib => {
+            let mut encoder = ZlibEncoder::new(bytes, Compression::default());
+
+            postcard::to_io(&chunk, &mut encoder)?;
+
+            encoder.finish()?
+        }
+    };
+
+    let (header, body) = bytes.split_at_mut(HEADER_LEN + CHECKSUM_LEN);
+
+    header[HEADER_LEN..].copy_from_slice(&crc32fast::hash(body).to_le_bytes());
+
+    Ok(bytes)
+}
+
+/// Decodes a chunk saved with any version of the format, migrating it to the latest one.
+pub fn decode_chunk(bytes: &[u8]) -> Result<ChunkData, ChunkFormatError> {
+    let (version, body) = match bytes.split_first_chunk::<HEADER_LEN>() {
+        Some((header, body)) if header.starts_with(&MAGIC) => {
+            let version = u16::from_le_bytes([header[MAGIC.len()], header[MAGIC.len() + 1]]);
+
+            (version, body)
+        }
+        _ => (0, bytes),
+    };
+
+    // checked first, since newer versions might not have a checksum in the same place
+    if version > CHUNK_FORMAT_VERSION {
+        return Err(ChunkFormatError::UnsupportedVersion(version));
+    }
+
+    let body = if version >= CHECKSUM_VERSION {
+        let (checksum, body) = body
+            .split_first_chunk::<CHECKSUM_LEN>()
+            .ok_or(postcard::Error::DeserializeUnexpectedEnd)?;
+
+        let expected = u32::from_le_bytes(*checksum);
+        let actual = crc32fast::hash(body);
+
+        if expected != actual {
+            return Err(ChunkFormatError::ChecksumMismatch { expected, actual });
+        }
+
+        body
+    } else {
+        body
+    };
+
+    let mut chunk = VersionedChunk::parse(version, body)?;
+
+    loop {
+        match chunk {
+            VersionedChunk::V8(chunk) => return chunk.into_chunk(),
+            older => chunk = older.migrate()?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use glm::IVec3;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use game::block::registry::{BlockId, BlockRegistry};
+    use game::block::{Block, BlockInventory};
+    use game::block_entity::{BlockEntityKind, CrateEntity};
+    use game::block::face_type::Axis;
+    use game::chunk::location::ChunkLocation;
+    use game::chunk::pos::ChunkPos;
+    use game::inventory::Inventory;
+    use game::item::ItemType;
+    use game::item::registry::{ItemId, ItemRegistry};
+    use game::machine::SmelterEntity;
+    use crate::save::format::packed::PackedIndices;
+    use crate::save::format::v4::BlockEntityV4;
+    use crate::save::format::v5::PaletteEntryV5;
+    use crate::save::format::v6::{InventoryV6, ItemStackV6, SmelterEntityV6};
+    use crate::save::with_large_stack;
+    use super::*;
+
+    const FIXTURE_V0: &[u8] = include_bytes!("fixtures/chunk_v0.bin");
+    const FIXTURE_V1: &[u8] = include_bytes!("fixtures/chunk_v1.bin");
+    const FIXTURE_V2: &[u8] = include_bytes!("fixtures/chunk_v2.bin");
+    const FIXTURE_V3: &[u8] = include_bytes!("fixtures/chunk_v3.bin");
+    const FIXTURE_V4: &[u8] = include_bytes!("fixtures/chunk_v4.bin");
+    const FIXTURE_V5: &[u8] = include_bytes!("fixtures/chunk_v5.bin");
+    const FIXTURE_V6: &[u8] = include_bytes!("fixtures/chunk_v6.bin");
+    const FIXTURE_V7: &[u8] = include_bytes!("fixtures/chunk_v7.bin");
+    const FIXTURE_V8: &[u8] = include_bytes!("fixtures/chunk_v8.bin");
+
+    /// The chunk every fixture was saved from.
+    fn fixture_chunk() -> ChunkData {
+        let mut data = ChunkData::empty(ChunkLocation(IVec3::new(1, -2, 3)));
+
+        let mut inventory = BlockInventory::default();
+        inventory.try_insert(ItemType::PLANKS.default_item().with_count(5.try_into().expect("5 is nonzero")));
+
+        data.set(ChunkPos(0), Block::GRASS);
+        data.set(ChunkPos(1), Block::DIRT);
+        data.set(ChunkPos(2), Block::STONE);
+        data.set(ChunkPos(3), Block::LOG.rotated(Axis::Z));
+        data.set(ChunkPos(4), Block::CRATE);
+        data.set_block_entity(ChunkPos(4), Box::new(CrateEntity { inventory })).expect("crates have a crate entity");
+        data.set(ChunkPos(5), Block::HEMATITE_DEPOSIT);
+        data.set(ChunkPos(6), Block::WATER);
+        data.set(ChunkPos((BLOCKS_PER_CHUNK - 1) as _), Block::COBBLESTONE);
+
+        data
+    }
+
+    fn random_block(rng: &mut StdRng) -> Block {
+        let id = BlockId(rng.gen_range(0..BlockRegistry::global().len()) as _);
+
+        Block::new(id).rotated(Axis::from_repr(rng.gen_range(0..3)).expect("in range"))
+    }
+
+    /// A chunk made of `distinct` random blocks, so that palettes of many different widths get tested.
+    fn random_chunk(rng: &mut StdRng, distinct: usize) -> ChunkData {
+        let location = ChunkLocation(IVec3::new(rng.r#gen(), rng.r#gen(), rng.r#gen()));
+        let choices = (0..distinct).map(|_| random_block(rng)).collect::<Vec<_>>();
+
+        let mut data = ChunkData::empty(location);
+
+        for i in 0..BLOCKS_PER_CHUNK {
+            let pos = ChunkPos(i as _);
+
+            data.replace(pos, choices[rng.gen_range(0..choices.len())]);
+
+            if let Some(entity) = data.block_entity_mut(pos)
+                && let Some(CrateEntity { inventory }) = entity.downcast_mut()
+            {
+                for _ in 0..rng.gen_range(0..4) {
+                    let ty = ItemType::new(ItemId(rng.gen_range(0..ItemRegistry::global().len()) as _));
+                    let count = rng.gen_range(1..=16u8).try_into().expect("nonzero");
+
+                    inventory.try_insert_at(rng.gen_range(0..36), ty.default_item().with_count(count));
+                }
+            }
+        }
+
+        for _ in 0..rng.gen_range(0..8) {
+            data.schedule_tick(ChunkPos(rng.gen_range(0..BLOCKS_PER_CHUNK) as _), rng.r#gen());
+        }
+
+        data
+    }
+
+    fn assert_same_chunk(a: &ChunkData, b: &ChunkData) {
+        assert_eq!(a.location, b.location);
+        assert!(a.blocks_ref().eq(b.blocks_ref()), "blocks didn't match");
+
+        let encoded = |data: &ChunkData| data.block_entities()
+            .map(|(pos, entity)| (pos.0, entity.kind(), entity.encode().expect("block entity should encode")))
+            .collect::<Vec<_>>();
+
+        assert_eq!(encoded(a), encoded(b), "block entities didn't match");
+        assert!(a.scheduled_ticks().eq(b.scheduled_ticks()), "scheduled ticks didn't match");
+    }
+
+    #[test]
+    fn test_migrate_v0_fixture() {
+        with_large_stack(|| assert_same_chunk(&decode_chunk(FIXTURE_V0).expect("v0 fixture should decode"), &fixture_chunk()));
+    }
+
+    #[test]
+    fn test_migrate_v1_fixture() {
+        with_large_stack(|| assert_same_chunk(&decode_chunk(FIXTURE_V1).expect("v1 fixture should decode"), &fixture_chunk()));
+    }
+
+    #[test]
+    fn test_decode_v2_fixture() {
+        with_large_stack(|| assert_same_chunk(&decode_chunk(FIXTURE_V2).expect("v2 fixture should decode"), &fixture_chunk()));
+    }
+
+    #[test]
+    fn test_decode_v3_fixture() {
+        with_large_stack(|| assert_same_chunk(&decode_chunk(FIXTURE_V3).expect("v3 fixture should decode"), &fixture_chunk()));
+    }
+
+    #[test]
+    fn test_decode_v4_fixture() {
+        with_large_stack(|| assert_same_chunk(&decode_chunk(FIXTURE_V4).expect("v4 fixture should decode"), &fixture_chunk()));
+    }
+
+    #[test]
+    fn test_decode_v5_fixture() {
+        with_large_stack(|| assert_same_chunk(&decode_chunk(FIXTURE_V5).expect("v5 fixture should decode"), &fixture_chunk()));
+    }
+
+    #[test]
+    fn test_decode_v6_fixture() {
+        with_large_stack(|| assert_same_chunk(&decode_chunk(FIXTURE_V6).expect("v6 fixture should decode"), &fixture_chunk()));
+    }
+
+    #[test]
+    fn test_decode_v7_fixture() {
+        with_large_stack(|| assert_same_chunk(&decode_chunk(FIXTURE_V7).expect("v7 fixture should decode"), &fixture_chunk()));
+    }
+
+    #[test]
+    fn test_decode_v8_fixture() {
+        with_large_stack(|| assert_same_chunk(&decode_chunk(FIXTURE_V8).expect("v8 fixture should decode"), &fixture_chunk()));
+    }
+
+    #[test]
+    fn test_migrates_v6_smelter_items() {
+        with_large_stack(|| {
+            let mut chunk = ChunkV8::from_chunk(&fixture_chunk()).expect("chunk should convert");
+            let slot = |ty: &str, count: u8| InventoryV6(Box::new([Some(ItemStackV6 { ty: ty.into(), count: count.try_into().expect("nonzero") })]));
+
+            let smelter = SmelterEntityV6 {
+                input: slot("protovox:hematite_nuggets", 3),
+                fuel: slot("protovox:log", 1),
+                output: InventoryV6(Box::new([None])),
+                progress: Duration::from_secs(2),
+                burn_left: Duration::from_secs(5),
+                burn_time: Duration::from_secs(15),
+            };
+
+            chunk.palette.push(PaletteEntryV5 { name: "protovox:smelter".into(), state: 0 });
+            chunk.indices = PackedIndices::pack(
+                PackedIndices::bits_for(chunk.palette.len()),
+                BLOCKS_PER_CHUNK,
+                (0..BLOCKS_PER_CHUNK).map(|i| if i == 7 { chunk.palette.len() - 1 } else { 0 }),
+            );
+            chunk.block_entities = vec![BlockEntityV4 {
+                pos: 7,
+                kind: BlockEntityKind::Smelter.stable_id(),
+                data: postcard::to_allocvec(&smelter).expect("should serialize"),
+            }];
+
+            let v6 = ChunkV6 { location: chunk.location, palette: chunk.palette, indices: chunk.indices, block_entities: chunk.block_entities };
+            let data = ChunkV7::try_from(v6)
+                .map(ChunkV8::from)
+                .and_then(ChunkV8::into_chunk)
+                .expect("v6 smelter should migrate");
+
+            let smelter = data.block_entity_ref(ChunkPos(7))
+                .and_then(|entity| entity.downcast_ref::<SmelterEntity>())
+                .expect("should be a smelter");
+
+            assert_eq!(smelter.input.as_slice()[0], Some(ItemType::HEMATITE_NUGGETS.default_item().with_count(3.try_into().expect("3 is nonzero"))));
+            assert_eq!(smelter.fuel.as_slice()[0], Some(ItemType::LOG.default_one()));
+            assert_eq!(smelter.burn_left, Duration::from_secs(5));
+        });
+    }
+
+    #[test]
+    fn test_rejects_unknown_block_names() {
+        with_large_stack(|| {
+            let mut chunk = ChunkV8::from_chunk(&fixture_chunk()).expect("chunk should convert");
+            chunk.palette[0].name = "somemod:missing".into();
+
+            assert!(matches!(chunk.into_chunk(), Err(ChunkFormatError::UnknownBlock(name)) if name == "somemod:missing"));
+        });
+    }
+
+    #[test]
+    fn test_detects_corruption() {
+        let mut truncated = FIXTURE_V8.to_vec();
+        truncated.truncate(truncated.len() / 2);
+
+        let mut flipped = FIXTURE_V8.to_vec();
+        *flipped.last_mut().expect("not empty") ^= 1;
+
+        for bytes in [truncated, flipped] {
+            assert!(matches!(decode_chunk(&bytes), Err(ChunkFormatError::ChecksumMismatch { .. })));
+        }
+    }
+
+    #[test]
+    fn test_round_trip_random_chunks() {
+        with_large_stack(|| {
+            let mut rng = StdRng::seed_from_u64(0xc0ffee);
+
+            for distinct in [1, 2, 3, 5, 8, 13, 40] {
+                let data = random_chunk(&mut rng, distinct);
+
+                for compression in [ChunkCompression::None, ChunkCompression::Zlib] {
+                    let bytes = encode_chunk(&data, compression).expect("chunk should encode");
+
+                    assert!(bytes.starts_with(&MAGIC));
+                    assert_same_chunk(&decode_chunk(&bytes).expect("chunk should decode"), &data);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn test_uniform_chunk_is_small() {
+        with_large_stack(|| {
+            let bytes = encode_chunk(&ChunkData::empty(ChunkLocation::default()), ChunkCompression::None).expect("chunk should encode");
+
+            // mostly the header and the name of air
+            assert!(bytes.len() < 48, "an empty chunk took {} bytes", bytes.len());
+        });
+    }
+
+    #[test]
+    fn test_rejects_newer_version() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&(CHUNK_FORMAT_VERSION + 1).to_le_bytes());
+
+        assert!(matches!(decode_chunk(&bytes), Err(ChunkFormatError::UnsupportedVersion(_))));
+    }
+}
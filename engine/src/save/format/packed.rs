@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+/// Fixed-width indices packed into words. Indices never straddle two words,
+/// so some bits at the end of each word go unused when the width doesn't divide 64.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PackedIndices {
+    bits: u8,
+    words: Vec<u64>,
+}
+
+impl PackedIndices {
+    /// The width needed to index into a palette with `palette_len` entries.
+    /// A palette with a single entry doesn't need any bits at all.
+    pub fn bits_for(palette_len: usize) -> u8 {
+        match palette_len {
+            0 | 1 => 0,
+            len => (usize::BITS - (len - 1).leading_zeros()) as _,
+        }
+    }
+
+    pub fn pack(bits: u8, len: usize, indices: impl IntoIterator<Item = usize>) -> Self {
+        assert!(bits <= 32, "indices should be at most 32 bits wide");
+
+        if bits == 0 {
+            return Self { bits, words: Vec::new() };
+        }
+
+        let per_word = Self::per_word(bits);
+        let mut words = vec![0; len.div_ceil(per_word)];
+
+        for (i, index) in indices.into_iter().enumerate().take(len) {
+            debug_assert!(bits == 32 || index < 1 << bits, "index {index} doesn't fit in {bits} bits");
+
+            words[i / per_word] |= (index as u64) << (i % per_word * bits as usize);
+        }
+
+        Self { bits, words }
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// Whether this holds exactly `len` indices, which should be checked before unpacking untrusted data.
+    pub fn is_valid_for(&self, len: usize) -> bool {
+        match self.bits {
+            0 => self.words.is_empty(),
+            1..=32 => self.words.len() == len.div_ceil(Self::per_word(self.bits)),
+            _ => false,
+        }
+    }
+
+    pub fn unpack(&self, len: usize) -> impl Iterator<Item = usize> + '_ {
+        let bits = self.bits as usize;
+        let mask = (1u64 << bits) - 1;
+        let per_word = if bits == 0 { 1 } else { Self::per_word(self.bits) };
+
+        (0..len).map(move |i| match bits {
+            0 => 0,
+            _ => (self.words[i / per_word] >> (i % per_word * bits) & mask) as usize,
+        })
+    }
+
+    fn per_word(bits: u8) -> usize {
+        u64::BITS as usize / bits as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use super::*;
+
+    #[test]
+    fn test_bits_for() {
+        assert_eq!(PackedIndices::bits_for(1), 0);
+        assert_eq!(PackedIndices::bits_for(2), 1);
+        assert_eq!(PackedIndices::bits_for(3), 2);
+        assert_eq!(PackedIndices::bits_for(16), 4);
+        assert_eq!(PackedIndices::bits_for(17), 5);
+    }
+
+    #[test]
+    fn test_pack_unpack() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+
+        for bits in 0..=16 {
+            let len = rng.gen_range(1..5000);
+            let indices = (0..len).map(|_| rng.gen_range(0..1usize << bits)).collect::<Vec<_>>();
+
+            let packed = PackedIndices::pack(bits, len, indices.iter().copied());
+
+            assert!(packed.is_valid_for(len));
+            assert_eq!(packed.unpack(len).collect::<Vec<_>>(), indices, "mismatch with {bits} bits");
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::num::NonZeroU8;
+use serde::{Deserialize, Serialize};
+use game::block::face_type::Axis;
+use game::chunk::BLOCKS_PER_CHUNK;
+use game::chunk::location::ChunkLocation;
+use game::item::{ItemStack, ItemType};
+use crate::save::format::ChunkFormatError;
+use crate::save::format::v1::{BlockStateV1, BlockV1, ChunkV1};
+
+/// The names of the items saved by id, from before items were loaded from definition files, indexed by their id.
+pub const LEGACY_ITEM_NAMES: [&str; 11] = [
+    "protovox:grass",
+    "protovox:dirt",
+    "protovox:cobblestone",
+    "protovox:stone",
+    "protovox:log",
+    "protovox:leaf_pile",
+    "protovox:crate",
+    "protovox:planks",
+    "protovox:stone_bricks",
+    "protovox:hematite_nuggets",
+    "protovox:carbon_steel",
+];
+
+/// A frozen copy of `ItemStack` from before items were loaded from definition files,
+/// when every item kept its own title and description.
+/// Inventories were saved this way in every chunk before version 6, and in player data.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemStackV0 {
+    /// See [`LEGACY_ITEM_NAMES`].
+    pub ty: u32,
+    pub title: String,
+    pub desc: String,
+    pub count: NonZeroU8,
+}
+
+/// A frozen copy of a crate's `BlockInventory<36>`, holding [`ItemStackV0`]s.
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InventoryV0(
+    #[serde_as(as = "Box<[_; 36]>")]
+    pub Box<[Option<ItemStackV0>; 36]>,
+);
+
+/// Chunks saved before the format had a header, which were `ChunkData` serialized directly.
+/// This is a frozen copy of `Block` at that time, since its variants were encoded by position.
+#[derive(Deserialize)]
+pub enum BlockV0 {
+    Air,
+    Grass,
+    Dirt,
+    Cobblestone,
+    Stone,
+    Log { rotation: Axis },
+    Leaf,
+    Debug,
+    Crate { inventory: InventoryV0 },
+    StoneBrick,
+    Planks,
+    Water,
+    HematiteDeposit,
+}
+
+#[serde_with::serde_as]
+#[derive(Deserialize)]
+pub struct ChunkV0 {
+    pub location: ChunkLocation,
+    #[serde_as(as = "Box<[_; BLOCKS_PER_CHUNK]>")]
+    pub blocks: Box<[BlockV0; BLOCKS_PER_CHUNK]>,
+}
+
+impl From<BlockV0> for BlockV1 {
+    fn from(block: BlockV0) -> Self {
+        use BlockV0 as B;
+
+        let (id, state) = match block {
+            B::Air => (0, BlockStateV1::None),
+            B::Grass => (1, BlockStateV1::None),
+            B::Dirt => (2, BlockStateV1::None),
+            B::Cobblestone => (3, BlockStateV1::None),
+            B::Stone => (4, BlockStateV1::None),
+            B::Log { rotation } => (5, BlockStateV1::Axis(rotation as u8)),
+            B::Leaf => (6, BlockStateV1::None),
+            B::Debug => (7, BlockStateV1::None),
+            B::Crate { inventory } => (8, BlockStateV1::Inventory(inventory)),
+            B::StoneBrick => (9, BlockStateV1::None),
+            B::Planks => (10, BlockStateV1::None),
+            B::Water => (11, BlockStateV1::None),
+            B::HematiteDeposit => (12, BlockStateV1::None),
+        };
+
+        Self { id, state }
+    }
+}
+
+impl From<ChunkV0> for ChunkV1 {
+    fn from(chunk: ChunkV0) -> Self {
+        Self {
+            location: chunk.location,
+            blocks: chunk.blocks
+                .into_iter()
+                .map(BlockV1::from)
+                .collect(),
+        }
+    }
+}
+
+impl ItemStackV0 {
+    /// Counts above the item's stack limit are kept, so nothing is lost.
+    pub fn into_stack(self) -> Result<ItemStack, ChunkFormatError> {
+        let name = LEGACY_ITEM_NAMES
+            .get(self.ty as usize)
+            .ok_or(ChunkFormatError::UnknownItemId(self.ty))?;
+
+        let ty = ItemType::by_name(name).ok_or_else(|| ChunkFormatError::UnknownItem(name.to_string()))?;
+
+        Ok(ty.default_item().with_count(self.count))
+    }
+}
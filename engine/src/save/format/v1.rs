@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use game::chunk::BLOCKS_PER_CHUNK;
+use game::chunk::location::ChunkLocation;
+use crate::save::format::ChunkFormatError;
+use crate::save::format::v0::InventoryV0;
+use crate::save::format::v2::{BlockStateV2, ChunkV2, PaletteEntryV2};
+
+/// The state a block needs besides its id, independent of the layout of `Block`.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum BlockStateV1 {
+    None,
+    Axis(u8),
+    Inventory(InventoryV0),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlockV1 {
+    /// See [`LEGACY_BLOCK_NAMES`](crate::save::format::v2::LEGACY_BLOCK_NAMES).
+    pub id: u16,
+    pub state: BlockStateV1,
+}
+
+/// Every block stored one after another, with no palette or compression.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkV1 {
+    pub location: ChunkLocation,
+    pub blocks: Vec<BlockV1>,
+}
+
+impl TryFrom<ChunkV1> for ChunkV2 {
+    type Error = ChunkFormatError;
+
+    fn try_from(chunk: ChunkV1) -> Result<Self, Self::Error> {
+        if chunk.blocks.len() != BLOCKS_PER_CHUNK {
+            return Err(ChunkFormatError::WrongBlockCount(chunk.blocks.len()));
+        }
+
+        let blocks = chunk.blocks
+            .into_iter()
+            .map(|BlockV1 { id, state }| match state {
+                BlockStateV1::None => (PaletteEntryV2 { id, state: BlockStateV2::None }, None),
+                BlockStateV1::Axis(axis) => (PaletteEntryV2 { id, state: BlockStateV2::Axis(axis) }, None),
+                BlockStateV1::Inventory(inventory) => (PaletteEntryV2 { id, state: BlockStateV2::None }, Some(inventory)),
+            });
+
+        Ok(Self::from_blocks(chunk.location, blocks))
+    }
+}
@@ -0,0 +1,78 @@
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use game::chunk::BLOCKS_PER_CHUNK;
+use game::chunk::location::ChunkLocation;
+use crate::save::format::packed::PackedIndices;
+use crate::save::format::v0::InventoryV0;
+
+/// The state a block needs besides its id. Inventories aren't part of it, since every one is different
+/// and would give each block holding one its own palette entry, so they're stored next to the palette instead.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum BlockStateV2 {
+    None,
+    Axis(u8),
+}
+
+/// The names of the blocks saved by id, from before blocks were loaded from definition files, indexed by their id.
+pub const LEGACY_BLOCK_NAMES: [&str; 13] = [
+    "protovox:air",
+    "protovox:grass",
+    "protovox:dirt",
+    "protovox:cobblestone",
+    "protovox:stone",
+    "protovox:log",
+    "protovox:leaf",
+    "protovox:debug",
+    "protovox:crate",
+    "protovox:stone_brick",
+    "protovox:planks",
+    "protovox:water",
+    "protovox:hematite_deposit",
+];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct PaletteEntryV2 {
+    /// See [`LEGACY_BLOCK_NAMES`].
+    pub id: u16,
+    pub state: BlockStateV2,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkV2 {
+    pub location: ChunkLocation,
+    pub palette: Vec<PaletteEntryV2>,
+    pub indices: PackedIndices,
+    /// Inventories of blocks such as crates, keyed by their index in the chunk.
+    pub inventories: Vec<(u16, InventoryV0)>,
+}
+
+impl ChunkV2 {
+    /// Builds the palette from every block in the chunk, in order, along with its inventory if it has one.
+    pub fn from_blocks(location: ChunkLocation, blocks: impl IntoIterator<Item = (PaletteEntryV2, Option<InventoryV0>)>) -> Self {
+        let mut palette = Vec::new();
+        let mut palette_lookup = HashMap::new();
+        let mut inventories = Vec::new();
+
+        let indices = blocks
+            .into_iter()
+            .enumerate()
+            .map(|(i, (entry, inventory))| {
+                if let Some(inventory) = inventory {
+                    inventories.push((i as u16, inventory));
+                }
+
+                *palette_lookup.entry(entry).or_insert_with(|| {
+                    palette.push(entry);
+                    palette.len() - 1
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            location,
+            indices: PackedIndices::pack(PackedIndices::bits_for(palette.len()), BLOCKS_PER_CHUNK, indices),
+            palette,
+            inventories,
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+use game::block_entity::{BlockEntity, BlockEntityKind};
+use game::chunk::location::ChunkLocation;
+use game::chunk::pos::ChunkPos;
+use crate::save::format::ChunkFormatError;
+use crate::save::format::packed::PackedIndices;
+use crate::save::format::v2::{ChunkV2, PaletteEntryV2};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlockEntityV4 {
+    /// The index of its block in the chunk.
+    pub pos: u16,
+    /// See [`BlockEntityKind::stable_id`](game::block_entity::BlockEntityKind::stable_id).
+    pub kind: u16,
+    /// Written by [`BlockEntity::encode`], so each kind can change its own layout.
+    pub data: Vec<u8>,
+}
+
+/// The same palette as version 2, with block entities of any kind in place of crate inventories.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkV4 {
+    pub location: ChunkLocation,
+    pub palette: Vec<PaletteEntryV2>,
+    pub indices: PackedIndices,
+    pub block_entities: Vec<BlockEntityV4>,
+}
+
+impl BlockEntityV4 {
+    pub fn from_entity(pos: ChunkPos, entity: &dyn BlockEntity) -> Result<Self, ChunkFormatError> {
+        Ok(Self {
+            pos: pos.0,
+            kind: entity.kind().stable_id(),
+            data: entity.encode()?,
+        })
+    }
+}
+
+impl TryFrom<ChunkV2> for ChunkV4 {
+    type Error = ChunkFormatError;
+
+    fn try_from(chunk: ChunkV2) -> Result<Self, Self::Error> {
+        let block_entities = chunk.inventories
+            .into_iter()
+            // a crate entity was only its inventory, so it's written the same way
+            .map(|(pos, inventory)| Ok(BlockEntityV4 {
+                pos,
+                kind: BlockEntityKind::Crate.stable_id(),
+                data: postcard::to_allocvec(&inventory)?,
+            }))
+            .collect::<Result<Vec<_>, ChunkFormatError>>()?;
+
+        Ok(Self {
+            location: chunk.location,
+            palette: chunk.palette,
+            indices: chunk.indices,
+            block_entities,
+        })
+    }
+}
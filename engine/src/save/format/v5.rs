@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use game::block::Block;
+use game::block::registry::BlockRegistry;
+use game::chunk::location::ChunkLocation;
+use crate::save::format::ChunkFormatError;
+use crate::save::format::packed::PackedIndices;
+use crate::save::format::v2::{BlockStateV2, LEGACY_BLOCK_NAMES, PaletteEntryV2};
+use crate::save::format::v4::{BlockEntityV4, ChunkV4};
+
+/// Blocks are saved by name, since their ids depend on which block definitions are loaded.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PaletteEntryV5 {
+    pub name: String,
+    pub state: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkV5 {
+    pub location: ChunkLocation,
+    pub palette: Vec<PaletteEntryV5>,
+    pub indices: PackedIndices,
+    pub block_entities: Vec<BlockEntityV4>,
+}
+
+impl TryFrom<PaletteEntryV2> for PaletteEntryV5 {
+    type Error = ChunkFormatError;
+
+    fn try_from(entry: PaletteEntryV2) -> Result<Self, Self::Error> {
+        let name = LEGACY_BLOCK_NAMES
+            .get(entry.id as usize)
+            .ok_or(ChunkFormatError::UnknownBlockId(entry.id))?;
+
+        let state = match entry.state {
+            BlockStateV2::None => 0,
+            BlockStateV2::Axis(axis) => axis,
+        };
+
+        Ok(Self { name: name.to_string(), state })
+    }
+}
+
+impl PaletteEntryV5 {
+    pub fn to_block(&self, registry: &BlockRegistry) -> Result<Block, ChunkFormatError> {
+        let id = registry
+            .by_name(&self.name)
+            .ok_or_else(|| ChunkFormatError::UnknownBlock(self.name.clone()))?;
+
+        Block::with_state(id, self.state).ok_or_else(|| ChunkFormatError::InvalidBlockState(self.name.clone()))
+    }
+}
+
+impl TryFrom<ChunkV4> for ChunkV5 {
+    type Error = ChunkFormatError;
+
+    fn try_from(chunk: ChunkV4) -> Result<Self, Self::Error> {
+        Ok(Self {
+            location: chunk.location,
+            palette: chunk.palette
+                .into_iter()
+                .map(PaletteEntryV5::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+            indices: chunk.indices,
+            block_entities: chunk.block_entities,
+        })
+    }
+}
@@ -0,0 +1,123 @@
+use std::num::NonZeroU8;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use game::block::BlockInventory;
+use game::block_entity::BlockEntityKind;
+use game::chunk::location::ChunkLocation;
+use game::inventory::Inventory;
+use game::item::{ItemStack, ItemType};
+use crate::save::format::ChunkFormatError;
+use crate::save::format::packed::PackedIndices;
+use crate::save::format::v0::{InventoryV0, ItemStackV0, LEGACY_ITEM_NAMES};
+use crate::save::format::v4::BlockEntityV4;
+use crate::save::format::v5::{ChunkV5, PaletteEntryV5};
+
+/// A frozen copy of `ItemStack` from before items had components, when an item was only its type.
+/// Inventories were saved this way in chunks of version 6, and in player data.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemStackV6 {
+    /// The name of its item type.
+    pub ty: String,
+    pub count: NonZeroU8,
+}
+
+/// A frozen copy of `BlockInventory<N>`, holding [`ItemStackV6`]s.
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InventoryV6<const N: usize>(
+    #[serde_as(as = "Box<[_; N]>")]
+    pub Box<[Option<ItemStackV6>; N]>,
+);
+
+/// A frozen copy of `SmelterEntity`, whose slots were saved as [`InventoryV6`]s.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SmelterEntityV6 {
+    pub input: InventoryV6<1>,
+    pub fuel: InventoryV6<1>,
+    pub output: InventoryV6<1>,
+    pub progress: Duration,
+    pub burn_left: Duration,
+    pub burn_time: Duration,
+}
+
+/// The same layout as version 5, except that the items in block entities refer to the item registry by name,
+/// instead of keeping their own title and description.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkV6 {
+    pub location: ChunkLocation,
+    pub palette: Vec<PaletteEntryV5>,
+    pub indices: PackedIndices,
+    pub block_entities: Vec<BlockEntityV4>,
+}
+
+impl TryFrom<ChunkV5> for ChunkV6 {
+    type Error = ChunkFormatError;
+
+    fn try_from(chunk: ChunkV5) -> Result<Self, Self::Error> {
+        let block_entities = chunk.block_entities
+            .into_iter()
+            .map(|entity| match BlockEntityKind::from_stable_id(entity.kind) {
+                // a crate entity is only its inventory, so it's written the same way
+                Some(BlockEntityKind::Crate) => {
+                    let inventory = postcard::from_bytes::<InventoryV0>(&entity.data)?.into_inventory_v6()?;
+
+                    Ok(BlockEntityV4 { data: postcard::to_allocvec(&inventory)?, ..entity })
+                }
+                // smelters were added after this version, and unknown kinds are reported once the chunk is loaded
+                Some(BlockEntityKind::Smelter) | None => Ok(entity),
+            })
+            .collect::<Result<Vec<_>, ChunkFormatError>>()?;
+
+        Ok(Self {
+            location: chunk.location,
+            palette: chunk.palette,
+            indices: chunk.indices,
+            block_entities,
+        })
+    }
+}
+
+impl TryFrom<ItemStackV0> for ItemStackV6 {
+    type Error = ChunkFormatError;
+
+    fn try_from(stack: ItemStackV0) -> Result<Self, Self::Error> {
+        let name = LEGACY_ITEM_NAMES
+            .get(stack.ty as usize)
+            .ok_or(ChunkFormatError::UnknownItemId(stack.ty))?;
+
+        Ok(Self { ty: name.to_string(), count: stack.count })
+    }
+}
+
+impl ItemStackV6 {
+    /// Counts above the item's stack limit are kept, so nothing is lost.
+    pub fn into_stack(self) -> Result<ItemStack, ChunkFormatError> {
+        let ty = ItemType::by_name(&self.ty).ok_or(ChunkFormatError::UnknownItem(self.ty))?;
+
+        Ok(ty.default_item().with_count(self.count))
+    }
+}
+
+impl<const N: usize> InventoryV6<N> {
+    pub fn into_inventory(self) -> Result<BlockInventory<N>, ChunkFormatError> {
+        let mut inventory = BlockInventory::default();
+
+        for (slot, stack) in inventory.as_mut_slice().iter_mut().zip(*self.0) {
+            *slot = stack.map(ItemStackV6::into_stack).transpose()?;
+        }
+
+        Ok(inventory)
+    }
+}
+
+impl InventoryV0 {
+    pub fn into_inventory_v6(self) -> Result<InventoryV6<36>, ChunkFormatError> {
+        let mut inventory = InventoryV6(Box::new([const { None }; 36]));
+
+        for (slot, stack) in inventory.0.iter_mut().zip(*self.0) {
+            *slot = stack.map(ItemStackV6::try_from).transpose()?;
+        }
+
+        Ok(inventory)
+    }
+}
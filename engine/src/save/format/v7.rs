@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use game::block_entity::{BlockEntityKind, CrateEntity};
+use game::chunk::location::ChunkLocation;
+use game::chunk::pos::ChunkPos;
+use game::machine::SmelterEntity;
+use crate::save::format::ChunkFormatError;
+use crate::save::format::packed::PackedIndices;
+use crate::save::format::v4::BlockEntityV4;
+use crate::save::format::v5::PaletteEntryV5;
+use crate::save::format::v6::{ChunkV6, InventoryV6, SmelterEntityV6};
+
+/// The same layout as version 6, except that the items in block entities are saved with their components.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkV7 {
+    pub location: ChunkLocation,
+    pub palette: Vec<PaletteEntryV5>,
+    pub indices: PackedIndices,
+    pub block_entities: Vec<BlockEntityV4>,
+}
+
+impl TryFrom<ChunkV6> for ChunkV7 {
+    type Error = ChunkFormatError;
+
+    fn try_from(chunk: ChunkV6) -> Result<Self, Self::Error> {
+        let block_entities = chunk.block_entities
+            .into_iter()
+            .map(|entity| {
+                let pos = ChunkPos(entity.pos);
+
+                match BlockEntityKind::from_stable_id(entity.kind) {
+                    Some(BlockEntityKind::Crate) => {
+                        let inventory = postcard::from_bytes::<InventoryV6<36>>(&entity.data)?.into_inventory()?;
+
+                        BlockEntityV4::from_entity(pos, &CrateEntity { inventory })
+                    }
+                    Some(BlockEntityKind::Smelter) => {
+                        let smelter = postcard::from_bytes::<SmelterEntityV6>(&entity.data)?;
+
+                        BlockEntityV4::from_entity(pos, &SmelterEntity {
+                            input: smelter.input.into_inventory()?,
+                            fuel: smelter.fuel.into_inventory()?,
+                            output: smelter.output.into_inventory()?,
+                            progress: smelter.progress,
+                            burn_left: smelter.burn_left,
+                            burn_time: smelter.burn_time,
+                        })
+                    }
+                    // unknown kinds are reported once the chunk is loaded
+                    None => Ok(entity),
+                }
+            })
+            .collect::<Result<Vec<_>, ChunkFormatError>>()?;
+
+        Ok(Self {
+            location: chunk.location,
+            palette: chunk.palette,
+            indices: chunk.indices,
+            block_entities,
+        })
+    }
+}
@@ -0,0 +1,100 @@
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use game::block::registry::BlockRegistry;
+use game::block_entity::BlockEntityKind;
+use game::chunk::BLOCKS_PER_CHUNK;
+use game::chunk::data::ChunkData;
+use game::chunk::location::ChunkLocation;
+use game::chunk::pos::ChunkPos;
+use crate::save::format::ChunkFormatError;
+use crate::save::format::packed::PackedIndices;
+use crate::save::format::v4::BlockEntityV4;
+use crate::save::format::v5::PaletteEntryV5;
+use crate::save::format::v7::ChunkV7;
+
+/// The same layout as version 7, with the block ticks scheduled in the chunk.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkV8 {
+    pub location: ChunkLocation,
+    pub palette: Vec<PaletteEntryV5>,
+    pub indices: PackedIndices,
+    pub block_entities: Vec<BlockEntityV4>,
+    /// Positions in the chunk, along with the tick of the world they're due on.
+    pub scheduled_ticks: Vec<(u16, u64)>,
+}
+
+impl ChunkV8 {
+    pub fn from_chunk(data: &ChunkData) -> Result<Self, ChunkFormatError> {
+        let mut palette = Vec::new();
+        let mut palette_lookup = HashMap::new();
+
+        let indices = data.blocks_ref()
+            .map(|block| {
+                *palette_lookup.entry(*block).or_insert_with(|| {
+                    palette.push(PaletteEntryV5 { name: block.definition().name.clone(), state: block.state() });
+                    palette.len() - 1
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let block_entities = data.block_entities()
+            .map(|(pos, entity)| BlockEntityV4::from_entity(pos, entity))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            location: data.location.clone(),
+            indices: PackedIndices::pack(PackedIndices::bits_for(palette.len()), BLOCKS_PER_CHUNK, indices),
+            palette,
+            block_entities,
+            scheduled_ticks: data.scheduled_ticks().map(|(pos, due)| (pos.0, due)).collect(),
+        })
+    }
+
+    pub fn into_chunk(self) -> Result<ChunkData, ChunkFormatError> {
+        if !self.indices.is_valid_for(BLOCKS_PER_CHUNK) || self.palette.is_empty() {
+            return Err(ChunkFormatError::MalformedIndices);
+        }
+
+        let registry = BlockRegistry::global();
+
+        let palette = self.palette
+            .iter()
+            .map(|entry| entry.to_block(registry))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut data = ChunkData::empty(self.location);
+
+        for (i, index) in self.indices.unpack(BLOCKS_PER_CHUNK).enumerate() {
+            data.set(ChunkPos(i as _), *palette.get(index).ok_or(ChunkFormatError::MalformedIndices)?);
+        }
+
+        for BlockEntityV4 { pos, kind, data: bytes } in self.block_entities {
+            let pos = ChunkPos(pos);
+
+            let entity = BlockEntityKind::from_stable_id(kind)
+                .ok_or(ChunkFormatError::UnknownBlockEntity(kind))?
+                .decode(&bytes)?;
+
+            data.set_block_entity(pos, entity)
+                .map_err(|_| ChunkFormatError::InvalidBlockEntity(data.block_ref(pos).definition().name.clone()))?;
+        }
+
+        for (pos, due) in self.scheduled_ticks {
+            data.schedule_tick(ChunkPos(pos), due);
+        }
+
+        Ok(data)
+    }
+}
+
+impl From<ChunkV7> for ChunkV8 {
+    fn from(chunk: ChunkV7) -> Self {
+        Self {
+            location: chunk.location,
+            palette: chunk.palette,
+            indices: chunk.indices,
+            block_entities: chunk.block_entities,
+            scheduled_ticks: Vec::new(),
+        }
+    }
+}
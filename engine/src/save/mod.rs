@@ -0,0 +1,464 @@
+use std::{fs, io};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use glm::IVec3;
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use shipyard::Unique;
+use game::chunk::data::ChunkData;
+use game::chunk::location::ChunkLocation;
+use crate::save::format::{decode_chunk, encode_chunk, ChunkCompression, ChunkFormatError};
+use crate::save::region::ChunkSaveToRegion;
+use crate::save::worker::SaveWorker;
+
+pub mod format;
+pub mod region;
+pub mod world;
+pub mod player;
+pub mod worker;
+pub mod snapshot;
+
+// chunks are deserialized on the stack before being boxed, which overflows the default test thread
+#[cfg(test)]
+pub(crate) fn with_large_stack(f: impl FnOnce() + Send + 'static) {
+    std::thread::Builder::new()
+        .stack_size(32 * 1024 * 1024)
+        .spawn(f)
+        .expect("thread should spawn")
+        .join()
+        .expect("test thread panicked");
+}
+
+/// Corrupt chunks are moved into this directory inside the chunk store, rather than being regenerated over.
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChunkLoadError {
+    #[error("chunk was never saved")]
+    NotFound,
+    #[error("failed to read chunk: {0}")]
+    Io(#[from] io::Error),
+    /// The saved chunk has already been quarantined, so it's safe to regenerate.
+    #[error("saved chunk was corrupt: {0}")]
+    Corrupt(#[from] ChunkFormatError),
+}
+
+/// Writes to a temporary file first and renames it over the destination,
+/// so a crash part way through can never leave a truncated file behind.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+
+    let mut file = File::create(&tmp_path)?;
+
+    file.write_all(bytes)?;
+    file.sync_all()?;
+
+    fs::rename(tmp_path, path)
+}
+
+/// Keeps a copy of a chunk that failed to load in the quarantine directory, returning where it was put.
+pub fn quarantine(chunks_dir: &Path, loc: &ChunkLocation, bytes: &[u8]) -> io::Result<PathBuf> {
+    let dir = chunks_dir.join(QUARANTINE_DIR);
+
+    fs::create_dir_all(&dir)?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let path = dir.join(format!("{}_{}_{}-{timestamp}.cff", loc.0.x, loc.0.y, loc.0.z));
+
+    write_atomic(&path, bytes)?;
+
+    Ok(path)
+}
+
+#[derive(Unique)]
+pub struct WorldSaver {
+    default_cache_time: Duration,
+    cache: HashMap<ChunkLocation, (Instant, ChunkSaveCache)>,
+    saved: HashSet<ChunkLocation>,
+    saver: Arc<dyn ChunkSaver + Send + Sync + 'static>,
+    worker: SaveWorker,
+}
+
+impl WorldSaver {
+    pub const DEFAULT_CACHE_TIME: Duration = Duration::from_secs(45);
+
+    /// How many chunks can wait to be written before [`WorldSaver::process`] holds off on handing off more.
+    pub const QUEUE_CAPACITY: usize = 256;
+
+    pub fn new(default_cache_time: Duration, saver: impl ChunkSaver + Send + Sync + 'static) -> Self {
+        let mut saved = HashSet::default();
+        
+        let saver = Arc::new(saver);
+        
+        saver.update_saved(&mut saved);
+        
+        Self {
+            default_cache_time,
+            cache: HashMap::default(),
+            saved,
+            worker: SaveWorker::spawn(saver.clone(), Self::QUEUE_CAPACITY),
+            saver,
+        }
+    }
+    
+    pub fn cache_with_duration(&mut self, loc: ChunkLocation, data: ChunkData, duration: Duration) {
+        let expired_at = Instant::now() + duration;
+        
+        let chunk_save_cache = ChunkSaveCache { data };
+        
+        let prev = self.cache.insert(loc, (expired_at, chunk_save_cache));
+        
+        if let Some((expiration, data)) = prev {
+            let loc = data.data.location;
+            
+            let time_rem = Instant::now() - expiration;
+            
+            tracing::warn!("Tried to save Chunk at {loc:?}, which would expire in {time_rem:?}");
+        }
+    }
+    
+    pub fn cache(&mut self, loc: ChunkLocation, data: ChunkData) {
+        self.cache_with_duration(loc, data, self.default_cache_time)
+    }
+    
+    /// Hands expired chunks off to be written in the background.
+    /// If the queue is full, the rest stay cached until the worker catches up.
+    /// Chunks that failed to be written are cached again, so they're tried again later rather than lost.
+    pub fn process(&mut self) {
+        let now = Instant::now();
+
+        for cache in self.worker.take_failed() {
+            // a newer copy cached since replaces it anyway
+            self.cache
+                .entry(cache.data.location.clone())
+                .or_insert_with(|| (now + self.default_cache_time, cache));
+        }
+
+        let expired = self.cache
+            .iter()
+            .filter(|(_, (time, _))| now >= *time)
+            .map(|(loc, _)| loc.clone())
+            .collect::<Vec<_>>();
+
+        for loc in expired {
+            let (time, cache) = self.cache.remove(&loc).expect("was just in the cache");
+
+            match self.worker.try_submit(cache) {
+                Ok(()) => {
+                    self.saved.insert(loc);
+                }
+                Err(cache) => {
+                    self.cache.insert(loc, (time, *cache));
+                    break;
+                }
+            }
+        }
+    }
+    
+    /// Writes every cached chunk, waiting until they're all on disk.
+    pub fn save_all(&mut self) {
+        for (loc, (_, cache)) in self.cache.drain() {
+            self.worker.submit(cache);
+            self.saved.insert(loc);
+        }
+
+        self.worker.flush();
+
+        for cache in self.worker.take_failed() {
+            let loc = cache.data.location.clone();
+
+            tracing::error!("Chunk at {loc:?} couldn't be saved, keeping it cached");
+
+            self.cache.insert(loc, (Instant::now() + self.default_cache_time, cache));
+        }
+    }
+    
+    pub fn try_get(&mut self, loc: &ChunkLocation) -> Result<ChunkSaveCache, ChunkLoadError> {
+        if let Some(cache) = self.cache.remove(loc).map(|v| v.1) {
+            Ok(cache)
+        } else if let Some(cache) = self.worker.take_pending(loc) {
+            Ok(cache)
+        } else {
+            self.get_from_saved(loc)
+        }
+    }
+    
+    fn get_from_saved(&mut self, loc: &ChunkLocation) -> Result<ChunkSaveCache, ChunkLoadError> {
+        if !self.saved.contains(loc) {
+            return Err(ChunkLoadError::NotFound);
+        }
+
+        let res = self.saver.retrieve(loc);
+
+        // io errors might be temporary, so the chunk could still be loaded later
+        if let Err(ChunkLoadError::NotFound | ChunkLoadError::Corrupt(_)) = &res {
+            self.saved.remove(loc);
+        }
+
+        res
+    }
+}
+
+pub struct FakeSaver;
+
+impl ChunkSaver for FakeSaver {
+    fn save(&self, _data: &ChunkSaveCache) -> bool {
+        true
+    }
+
+    fn retrieve(&self, _loc: &ChunkLocation) -> Result<ChunkSaveCache, ChunkLoadError> {
+        Err(ChunkLoadError::NotFound)
+    }
+
+    fn read_bytes(&self, _loc: &ChunkLocation) -> Result<Vec<u8>, ChunkLoadError> {
+        Err(ChunkLoadError::NotFound)
+    }
+
+    fn remove(&self, _loc: &ChunkLocation) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    fn update_saved(&self, _saved: &mut HashSet<ChunkLocation>) {
+        
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ChunkSaveCache {
+    pub data: ChunkData,
+}
+
+impl ChunkSaveCache {
+    pub fn new(data: ChunkData) -> Self {
+        Self { data }
+    }
+}
+
+pub trait ChunkSaver {
+    /// Fails rather than panicking, leaving the chunk with whoever asked to save it so it can be saved again.
+    fn save(&self, data: &ChunkSaveCache) -> bool;
+    
+    fn retrieve(&self, loc: &ChunkLocation) -> Result<ChunkSaveCache, ChunkLoadError>;
+
+    /// Reads a saved chunk without decoding it, so unlike [`ChunkSaver::retrieve`], nothing is ever quarantined.
+    fn read_bytes(&self, loc: &ChunkLocation) -> Result<Vec<u8>, ChunkLoadError>;
+
+    /// Returns whether there was a saved chunk to remove.
+    fn remove(&self, loc: &ChunkLocation) -> io::Result<bool>;
+
+    fn update_saved(&self, saved: &mut HashSet<ChunkLocation>);
+}
+
+impl<T: ChunkSaver + ?Sized> ChunkSaver for Box<T> {
+    fn save(&self, data: &ChunkSaveCache) -> bool {
+        (**self).save(data)
+    }
+
+    fn retrieve(&self, loc: &ChunkLocation) -> Result<ChunkSaveCache, ChunkLoadError> {
+        (**self).retrieve(loc)
+    }
+
+    fn read_bytes(&self, loc: &ChunkLocation) -> Result<Vec<u8>, ChunkLoadError> {
+        (**self).read_bytes(loc)
+    }
+
+    fn remove(&self, loc: &ChunkLocation) -> io::Result<bool> {
+        (**self).remove(loc)
+    }
+
+    fn update_saved(&self, saved: &mut HashSet<ChunkLocation>) {
+        (**self).update_saved(saved)
+    }
+}
+
+/// Which [`ChunkSaver`] a world's chunks are stored with.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum ChunkStorageKind {
+    /// One `.cff` file per chunk, with [`ChunkSaveToFile`].
+    Files,
+    /// Chunks grouped into `.cfr` region files, with [`ChunkSaveToRegion`].
+    #[default]
+    Region,
+}
+
+impl ChunkStorageKind {
+    /// Guesses from the files in the chunk store, or `None` if there aren't any chunks saved yet.
+    pub fn detect(chunks_dir: &Path) -> Option<Self> {
+        fs::read_dir(chunks_dir)
+            .ok()?
+            .filter_map(Result::ok)
+            .find_map(|entry| match entry.path().extension()?.to_str()? {
+                "cff" => Some(Self::Files),
+                "cfr" => Some(Self::Region),
+                _ => None,
+            })
+    }
+
+    pub fn open(self, chunks_dir: impl Into<PathBuf>) -> Option<Box<dyn ChunkSaver + Send + Sync>> {
+        match self {
+            Self::Files => Some(Box::new(ChunkSaveToFile::new(chunks_dir)?)),
+            Self::Region => Some(Box::new(ChunkSaveToRegion::new(chunks_dir)?)),
+        }
+    }
+}
+
+pub struct ChunkSaveToFile {
+    path: PathBuf,
+    compression: ChunkCompression,
+}
+
+impl ChunkSaveToFile {
+    pub fn new(path: impl Into<PathBuf>) -> Option<Self> {
+        let path = path.into();
+        
+        // TODO: check to make sure this usage is right
+        match fs::create_dir_all(&path) {
+            Ok(_) => { Some(Self { path, compression: ChunkCompression::default() }) }
+            Err(err) => {
+                tracing::error!("failed to create dir at {path:?}: {err}");
+                None
+            }
+        }
+    }
+
+    pub fn with_compression(mut self, compression: ChunkCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    fn loc_to_file_name(loc: &ChunkLocation) -> PathBuf {
+        PathBuf::from(format!("{}_{}_{}.cff", loc.0.x, loc.0.y, loc.0.z))
+    }
+
+    fn file_name_to_loc(file: &Path) -> Option<ChunkLocation> {
+        if file.extension().is_none_or(|ext| ext != "cff") {
+            tracing::warn!("extension was incorrect; TODO: errors");
+            return None;
+        }
+        
+        let Some(stem) = file.file_stem() else {
+            tracing::warn!("no file name; TODO: errors");
+            return None;
+        };
+        
+        let stem = stem.to_string_lossy();
+        
+        let mut components = stem.split('_');
+        
+        let (Some((x, y, z)), None) = (components.next_tuple(), components.next()) else {
+            tracing::warn!("invalid name; TODO: errors");
+            return None;
+        };
+        
+        let (Ok(x), Ok(y), Ok(z)) = (x.parse(), y.parse(), z.parse()) else {
+            tracing::warn!("failed to parse coordinates; TODO: errors");
+            return None;
+        };
+        
+        let loc = ChunkLocation(IVec3::new(x, y, z));
+        
+        // performance cost here is negligible since this function is only called on startup
+        assert_eq!(Self::loc_to_file_name(&loc).file_name(), file.file_name(), "not inverse operations");
+        
+        Some(loc)
+    }
+}
+
+impl ChunkSaver for ChunkSaveToFile {
+    fn save(&self, data: &ChunkSaveCache) -> bool {
+        let save_path = self.path.join(Self::loc_to_file_name(&data.data.location));
+
+        let bytes = match encode_chunk(&data.data, self.compression) {
+            Ok(bytes) => bytes, 
+            Err(err) => {
+                tracing::error!("Failed to serialize chunk at {:?}: {err}", &data.data.location);
+                return false;
+            }
+        };
+
+        match write_atomic(&save_path, &bytes) {
+            Ok(_) => true,
+            Err(err) => {
+                tracing::error!("Failed to create and write to file at {save_path:?}: {err}");
+                false
+            }
+        }
+    }
+
+    fn retrieve(&self, loc: &ChunkLocation) -> Result<ChunkSaveCache, ChunkLoadError> {
+        let saved_path = self.path.join(Self::loc_to_file_name(loc));
+        
+        let bytes = self.read_bytes(loc)?;
+
+        match decode_chunk(&bytes) {
+            Ok(data) => Ok(ChunkSaveCache::new(data)),
+            Err(err) => {
+                match quarantine(&self.path, loc, &bytes).and_then(|path| fs::remove_file(&saved_path).map(|_| path)) {
+                    Ok(path) => tracing::error!("chunk {loc:?} at {saved_path:?} was corrupt, moved it to {path:?}: {err}"),
+                    Err(io_err) => {
+                        tracing::error!("chunk {loc:?} at {saved_path:?} was corrupt, and couldn't be quarantined: {io_err}");
+                        return Err(io_err.into());
+                    }
+                }
+
+                Err(err.into())
+            }
+        }
+    }
+
+    fn read_bytes(&self, loc: &ChunkLocation) -> Result<Vec<u8>, ChunkLoadError> {
+        let saved_path = self.path.join(Self::loc_to_file_name(loc));
+
+        match fs::read(&saved_path) {
+            Ok(bytes) => Ok(bytes),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Err(ChunkLoadError::NotFound),
+            Err(err) => {
+                tracing::error!("failed to open & read chunk {loc:?} at {saved_path:?}: {err}");
+                Err(err.into())
+            }
+        }
+    }
+
+    fn remove(&self, loc: &ChunkLocation) -> io::Result<bool> {
+        match fs::remove_file(self.path.join(Self::loc_to_file_name(loc))) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn update_saved(&self, saved: &mut HashSet<ChunkLocation>) {
+        let Ok(read_dir) = fs::read_dir(&self.path) else {
+            tracing::warn!("invalid directory; TODO: errors");
+            return;
+        };
+        
+        for entry in read_dir {
+            let Ok(entry) = entry else {
+                tracing::warn!("unable to get dir entry");
+                continue;
+            };
+            
+            let path = entry.path();
+
+            // the quarantine directory, or a temporary file left behind by a crash
+            if path.is_dir() || path.extension().is_some_and(|ext| ext == "tmp") {
+                continue;
+            }
+            
+            if let Some(location) = Self::file_name_to_loc(&path) {
+                saved.insert(location);
+            } else {
+                tracing::warn!("file \"{path:?}\" wasn't a chunk save file");
+            }
+        }
+    }
+}
@@ -0,0 +1,441 @@
+use std::{fs, io};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use glm::Vec3;
+use serde::{Deserialize, Serialize};
+use shipyard::{Borrow, BorrowInfo, Component, EntitiesView, EntityId, Get, IntoIter, IntoWithId, Remove, Unique, UniqueOrDefaultViewMut, UniqueView, View, ViewMut};
+use game::inventory::Inventory;
+use game::item::ItemType;
+use crate::components::{GravityAffected, Health, Hitbox, Mana, Transform};
+use crate::gamemode::Gamemode;
+use crate::identity::PlayerIdentity;
+use crate::crafting::CraftingGrid;
+use crate::inventory::{return_held, InventoryHand, PlayerInventory};
+use crate::save::format::ChunkFormatError;
+use crate::save::format::v0::ItemStackV0;
+use crate::save::format::v6::ItemStackV6;
+use crate::save::write_atomic;
+
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Everything about a player that should survive them leaving, or the server restarting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerData {
+    pub transform: Transform,
+    pub inventory: PlayerInventory,
+    pub gamemode: Gamemode,
+    pub health: Health,
+    pub mana: Mana,
+}
+
+impl PlayerData {
+    /// What a player starts with the first time they join a world.
+    pub fn starting(spawn: Vec3) -> Self {
+        let mut inventory = PlayerInventory::new(18.try_into().expect("18 is nonzero"));
+
+        inventory.try_insert(ItemType::CRATE.default_item().with_count(5.try_into().expect("should be nonzero")));
+
+        Self {
+            transform: Transform {
+                position: spawn,
+                .. Default::default()
+            },
+            inventory,
+            gamemode: Gamemode::Survival,
+            health: Health { curr: 9.0, max: 10.0 },
+            mana: Mana { curr: 6.0, max: 10.0 },
+        }
+    }
+}
+
+/// Player data saved before items were loaded from definition files, which is upgraded when it's loaded.
+#[derive(Debug, Serialize, Deserialize)]
+struct PlayerDataV0 {
+    transform: Transform,
+    inventory: Vec<Option<ItemStackV0>>,
+    gamemode: Gamemode,
+    health: Health,
+    mana: Mana,
+}
+
+impl TryFrom<PlayerDataV0> for PlayerData {
+    type Error = ChunkFormatError;
+
+    fn try_from(data: PlayerDataV0) -> Result<Self, Self::Error> {
+        let slots = data.inventory
+            .into_iter()
+            .map(|stack| stack.map(ItemStackV0::into_stack).transpose())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            transform: data.transform,
+            inventory: PlayerInventory::from_slots(slots),
+            gamemode: data.gamemode,
+            health: data.health,
+            mana: data.mana,
+        })
+    }
+}
+
+/// Player data saved before items had components, which is upgraded when it's loaded.
+#[derive(Debug, Serialize, Deserialize)]
+struct PlayerDataV1 {
+    transform: Transform,
+    inventory: Vec<Option<ItemStackV6>>,
+    gamemode: Gamemode,
+    health: Health,
+    mana: Mana,
+}
+
+impl TryFrom<PlayerDataV1> for PlayerData {
+    type Error = ChunkFormatError;
+
+    fn try_from(data: PlayerDataV1) -> Result<Self, Self::Error> {
+        let slots = data.inventory
+            .into_iter()
+            .map(|stack| stack.map(ItemStackV6::into_stack).transpose())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            transform: data.transform,
+            inventory: PlayerInventory::from_slots(slots),
+            gamemode: data.gamemode,
+            health: data.health,
+            mana: data.mana,
+        })
+    }
+}
+
+/// Added to a newly connected client's entity, so their saved data is restored once they've spawned.
+#[derive(Copy, Clone, Component, Debug, Default)]
+pub struct RestorePlayerDataRequest;
+
+#[derive(Unique, Debug)]
+pub struct LastPlayerAutosave(pub Instant);
+
+impl Default for LastPlayerAutosave {
+    fn default() -> Self {
+        Self(Instant::now())
+    }
+}
+
+/// Stores one file per player, named after their [`PlayerIdentity`].
+#[derive(Unique, Debug)]
+pub struct PlayerDataStore {
+    path: PathBuf,
+}
+
+impl PlayerDataStore {
+    pub fn new(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+
+        fs::create_dir_all(&path)?;
+
+        Ok(Self { path })
+    }
+
+    pub fn load(&self, identity: &PlayerIdentity) -> Option<PlayerData> {
+        let path = self.path_of(identity);
+
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+            Err(err) => {
+                tracing::error!("failed to read player data for {identity} at {path:?}: {err}");
+                return None;
+            }
+        };
+
+        let err = match from_whole_bytes(&bytes) {
+            Ok(data) => return Some(data),
+            Err(err) => err,
+        };
+
+        if let Ok(Ok(data)) = from_whole_bytes::<PlayerDataV1>(&bytes).map(PlayerData::try_from) {
+            tracing::info!("Upgraded player data for {identity} saved before item components");
+            return Some(data);
+        }
+
+        match from_whole_bytes::<PlayerDataV0>(&bytes).map(PlayerData::try_from) {
+            Ok(Ok(data)) => {
+                tracing::info!("Upgraded player data for {identity} saved before the item registry");
+                Some(data)
+            }
+            _ => {
+                tracing::error!("failed to deserialize player data for {identity} at {path:?}: {err}");
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, identity: &PlayerIdentity, data: &PlayerData) -> bool {
+        let path = self.path_of(identity);
+
+        let bytes = match postcard::to_allocvec(data) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                tracing::error!("Failed to serialize player data for {identity}: {err}");
+                return false;
+            }
+        };
+
+        match write_atomic(&path, &bytes) {
+            Ok(_) => true,
+            Err(err) => {
+                tracing::error!("Failed to write player data for {identity} to {path:?}: {err}");
+                false
+            }
+        }
+    }
+
+    fn path_of(&self, identity: &PlayerIdentity) -> PathBuf {
+        self.path.join(format!("{identity}.dat"))
+    }
+}
+
+/// Since older layouts are tried in turn, data only counts as one of them if it was read to the end.
+fn from_whole_bytes<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> postcard::Result<T> {
+    match postcard::take_from_bytes(bytes)? {
+        (data, []) => Ok(data),
+        _ => Err(postcard::Error::DeserializeBadEncoding),
+    }
+}
+
+#[derive(Borrow, BorrowInfo)]
+pub struct PlayerDataView<'v> {
+    pub transform: View<'v, Transform>,
+    pub inventory: View<'v, PlayerInventory>,
+    pub hand: View<'v, InventoryHand>,
+    pub grid: View<'v, CraftingGrid>,
+    pub gamemode: View<'v, Gamemode>,
+    pub health: View<'v, Health>,
+    pub mana: View<'v, Mana>,
+}
+
+impl PlayerDataView<'_> {
+    /// What's in the player's hand and crafting grid is saved as part of their inventory, since neither is saved on its own.
+    pub fn get(&self, id: EntityId) -> Option<PlayerData> {
+        let (transform, inventory, gamemode, health, mana) = (&self.transform, &self.inventory, &self.gamemode, &self.health, &self.mana)
+            .get(id)
+            .ok()?;
+
+        let mut inventory = inventory.clone();
+
+        if let Ok((hand, grid)) = (&self.hand, &self.grid).get(id) {
+            let lost = return_held(&mut hand.clone(), &mut grid.clone(), &mut inventory);
+
+            if !lost.is_empty() {
+                tracing::warn!("{lost:?} held by {id:?} didn't fit in their saved inventory");
+            }
+        }
+
+        Some(PlayerData {
+            transform: transform.clone(),
+            inventory,
+            gamemode: gamemode.clone(),
+            health: *health,
+            mana: *mana,
+        })
+    }
+}
+
+#[derive(Borrow, BorrowInfo)]
+pub struct PlayerDataViewMut<'v> {
+    pub entities: EntitiesView<'v>,
+    pub transform: ViewMut<'v, Transform>,
+    pub inventory: ViewMut<'v, PlayerInventory>,
+    pub gamemode: ViewMut<'v, Gamemode>,
+    pub health: ViewMut<'v, Health>,
+    pub mana: ViewMut<'v, Mana>,
+    pub hitbox: ViewMut<'v, Hitbox>,
+    pub gravity_affected: ViewMut<'v, GravityAffected>,
+}
+
+impl PlayerDataViewMut<'_> {
+    pub fn apply(&mut self, id: EntityId, data: PlayerData) {
+        let PlayerData { transform, inventory, gamemode, health, mana } = data;
+
+        self.set_gamemode(id, gamemode);
+
+        self.entities.add_component(
+            id,
+            (&mut self.transform, &mut self.inventory, &mut self.health, &mut self.mana),
+            (transform, inventory, health, mana),
+        );
+    }
+
+    /// Also adds or removes the components that make a player collide and fall, which spectators don't.
+    pub fn set_gamemode(&mut self, id: EntityId, gamemode: Gamemode) {
+        match gamemode {
+            Gamemode::Survival => {
+                if !self.hitbox.contains(id) {
+                    self.entities.add_component(id, &mut self.hitbox, Hitbox::default_player());
+                }
+
+                self.entities.add_component(id, &mut self.gravity_affected, GravityAffected);
+            }
+            Gamemode::Spectator => {
+                self.hitbox.remove(id);
+                self.gravity_affected.remove(id);
+            }
+        }
+
+        self.entities.add_component(id, &mut self.gamemode, gamemode);
+    }
+}
+
+pub fn apply_player_data((id, data): (EntityId, PlayerData), mut player_data: PlayerDataViewMut) {
+    player_data.apply(id, data);
+}
+
+pub fn save_player(id: EntityId, store: UniqueView<PlayerDataStore>, v_identity: View<PlayerIdentity>, player_data: PlayerDataView) {
+    let (Ok(identity), Some(data)) = (v_identity.get(id), player_data.get(id)) else {
+        // a client that disconnected before spawning has nothing worth overwriting the saved data with
+        return;
+    };
+
+    store.save(identity, &data);
+}
+
+pub fn save_all_players(store: UniqueView<PlayerDataStore>, v_identity: View<PlayerIdentity>, player_data: PlayerDataView) {
+    for (id, identity) in v_identity.iter().with_id() {
+        if let Some(data) = player_data.get(id) {
+            store.save(identity, &data);
+        }
+    }
+}
+
+pub fn autosave_players(mut last_autosave: UniqueOrDefaultViewMut<LastPlayerAutosave>, store: UniqueView<PlayerDataStore>, v_identity: View<PlayerIdentity>, player_data: PlayerDataView) {
+    if last_autosave.0.elapsed() > AUTOSAVE_INTERVAL {
+        save_all_players(store, v_identity, player_data);
+
+        last_autosave.0 = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::item::component::Durability;
+    use super::*;
+
+    #[test]
+    fn test_store_round_trip() {
+        let root = std::env::temp_dir().join(format!("protovox-players-{}", std::process::id()));
+
+        let _ = fs::remove_dir_all(&root);
+
+        let store = PlayerDataStore::new(&root).expect("store should be created");
+        let identity = PlayerIdentity::random();
+
+        assert!(store.load(&identity).is_none());
+
+        let mut inventory = PlayerInventory::new(18.try_into().expect("18 is nonzero"));
+        inventory.try_insert(ItemType::WOODEN_PICKAXE.default_item().with_component(Durability { remaining: 3, max: 60 }).stack_one());
+
+        let data = PlayerData {
+            transform: Transform { position: Vec3::new(1.0, 2.0, 3.0), yaw: 0.5, pitch: -0.25 },
+            inventory,
+            gamemode: Gamemode::Spectator,
+            health: Health { curr: 3.0, max: 10.0 },
+            mana: Mana { curr: 7.0, max: 10.0 },
+        };
+
+        assert!(store.save(&identity, &data));
+
+        let loaded = store.load(&identity).expect("data should've been saved");
+
+        assert_eq!(loaded.transform.position, data.transform.position);
+        assert_eq!(loaded.gamemode, Gamemode::Spectator);
+        assert_eq!(loaded.health, data.health);
+        assert_eq!(loaded.mana, data.mana);
+        assert_eq!(loaded.inventory.as_slice(), data.inventory.as_slice(), "item components should be saved");
+
+        fs::remove_dir_all(root).expect("should be able to clean up");
+    }
+
+    #[test]
+    fn test_upgrades_items_saved_with_their_title() {
+        let root = std::env::temp_dir().join(format!("protovox-legacy-players-{}", std::process::id()));
+
+        let _ = fs::remove_dir_all(&root);
+
+        let store = PlayerDataStore::new(&root).expect("store should be created");
+        let identity = PlayerIdentity::random();
+
+        let legacy = PlayerDataV0 {
+            transform: Transform { position: Vec3::new(1.0, 2.0, 3.0), yaw: 0.5, pitch: -0.25 },
+            inventory: vec![
+                None,
+                Some(ItemStackV0 { ty: 6, title: "Crate".into(), desc: "Can store items".into(), count: 5.try_into().expect("5 is nonzero") }),
+            ],
+            gamemode: Gamemode::Survival,
+            health: Health { curr: 3.0, max: 10.0 },
+            mana: Mana { curr: 7.0, max: 10.0 },
+        };
+
+        fs::write(store.path_of(&identity), postcard::to_allocvec(&legacy).expect("should serialize")).expect("should write");
+
+        let loaded = store.load(&identity).expect("legacy data should be upgraded");
+
+        assert_eq!(loaded.inventory.as_slice(), &[None, Some(ItemType::CRATE.default_item().with_count(5.try_into().expect("5 is nonzero")))]);
+
+        fs::remove_dir_all(root).expect("should be able to clean up");
+    }
+
+    #[test]
+    fn test_upgrades_items_saved_without_components() {
+        let root = std::env::temp_dir().join(format!("protovox-v1-players-{}", std::process::id()));
+
+        let _ = fs::remove_dir_all(&root);
+
+        let store = PlayerDataStore::new(&root).expect("store should be created");
+        let identity = PlayerIdentity::random();
+
+        let legacy = PlayerDataV1 {
+            transform: Transform { position: Vec3::new(1.0, 2.0, 3.0), yaw: 0.5, pitch: -0.25 },
+            inventory: vec![
+                Some(ItemStackV6 { ty: "protovox:planks".into(), count: 1.try_into().expect("1 is nonzero") }),
+                None,
+                Some(ItemStackV6 { ty: "protovox:crate".into(), count: 5.try_into().expect("5 is nonzero") }),
+            ],
+            gamemode: Gamemode::Survival,
+            health: Health { curr: 3.0, max: 10.0 },
+            mana: Mana { curr: 7.0, max: 10.0 },
+        };
+
+        fs::write(store.path_of(&identity), postcard::to_allocvec(&legacy).expect("should serialize")).expect("should write");
+
+        let loaded = store.load(&identity).expect("data without components should be upgraded");
+
+        assert_eq!(loaded.inventory.as_slice(), &[
+            Some(ItemType::PLANKS.default_one()),
+            None,
+            Some(ItemType::CRATE.default_item().with_count(5.try_into().expect("5 is nonzero"))),
+        ]);
+
+        fs::remove_dir_all(root).expect("should be able to clean up");
+    }
+
+    #[test]
+    fn test_saves_held_items_in_the_inventory() {
+        let mut world = shipyard::World::new();
+
+        let mut grid = CraftingGrid::default();
+        grid.0.try_insert(ItemType::LOG.default_one());
+
+        let id = world.add_entity((
+            Transform::default(),
+            PlayerInventory::new(2.try_into().expect("2 is nonzero")),
+            InventoryHand(Some(ItemType::DIRT.default_one())),
+            grid,
+            Gamemode::Survival,
+            Health { curr: 3.0, max: 10.0 },
+            Mana { curr: 7.0, max: 10.0 },
+        ));
+
+        let data = world.run(|player_data: PlayerDataView| player_data.get(id)).expect("player has every component");
+
+        assert_eq!(data.inventory.as_slice(), &[Some(ItemType::DIRT.default_one()), Some(ItemType::LOG.default_one())]);
+        assert!(world.run(|v_hand: View<InventoryHand>| v_hand.get(id).is_ok_and(|hand| hand.0.is_some())), "saving shouldn't take what they're holding");
+    }
+}
@@ -0,0 +1,658 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use glm::IVec3;
+use hashbrown::{HashMap, HashSet};
+use hashbrown::hash_map::Entry;
+use itertools::Itertools;
+use game::chunk::location::ChunkLocation;
+use crate::save::{quarantine, ChunkLoadError, ChunkSaveCache, ChunkSaver};
+use crate::save::format::{decode_chunk, encode_chunk, ChunkCompression};
+
+/// How many chunks along each axis are grouped into a single region file.
+pub const REGION_SIZE: IVec3 = IVec3::new(16, 4, 16);
+pub const CHUNKS_PER_REGION: usize = 16 * 4 * 16;
+
+const SECTOR_SIZE: u64 = 4096;
+const ENTRY_SIZE: usize = 2 * size_of::<u32>();
+const HEADER_BYTES: u64 = (CHUNKS_PER_REGION * ENTRY_SIZE) as _;
+const HEADER_SECTORS: usize = HEADER_BYTES.div_ceil(SECTOR_SIZE) as _;
+
+// don't bother compacting a region until at least this many sectors are unused
+const MIN_COMPACT_SECTORS: usize = 64;
+
+#[repr(transparent)]
+#[derive(Default, Eq, PartialEq, Clone, Debug, Hash)]
+pub struct RegionLocation(pub IVec3);
+
+impl From<&ChunkLocation> for RegionLocation {
+    fn from(loc: &ChunkLocation) -> Self {
+        Self(loc.0.zip_map(&REGION_SIZE, i32::div_euclid))
+    }
+}
+
+impl From<ChunkLocation> for RegionLocation {
+    fn from(loc: ChunkLocation) -> Self {
+        (&loc).into()
+    }
+}
+
+impl RegionLocation {
+    /// Index of the chunk in the header of whichever region it belongs to.
+    pub fn index_of(loc: &ChunkLocation) -> usize {
+        let local = loc.0.zip_map(&REGION_SIZE, i32::rem_euclid);
+
+        (local.x + local.z * REGION_SIZE.x + local.y * REGION_SIZE.x * REGION_SIZE.z) as _
+    }
+
+    pub fn chunk_at(&self, index: usize) -> ChunkLocation {
+        debug_assert!(index < CHUNKS_PER_REGION, "index out of range");
+
+        let index = index as i32;
+        let layer = REGION_SIZE.x * REGION_SIZE.z;
+
+        let local = IVec3::new(index % REGION_SIZE.x, index / layer, index % layer / REGION_SIZE.x);
+
+        ChunkLocation(self.0.component_mul(&REGION_SIZE) + local)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+struct HeaderEntry {
+    sector: u32,
+    len: u32,
+}
+
+impl HeaderEntry {
+    const EMPTY: Self = Self { sector: 0, len: 0 };
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn sector_count(&self) -> usize {
+        sectors_for(self.len as _)
+    }
+
+    fn sector_range(&self) -> Range<usize> {
+        let start = self.sector as usize;
+
+        start..start + self.sector_count()
+    }
+
+    fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+
+        bytes[..4].copy_from_slice(&self.sector.to_le_bytes());
+        bytes[4..].copy_from_slice(&self.len.to_le_bytes());
+
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let (sector, len) = bytes.split_at(size_of::<u32>());
+
+        Self {
+            sector: u32::from_le_bytes(sector.try_into().expect("must be 4 bytes")),
+            len: u32::from_le_bytes(len.try_into().expect("must be 4 bytes")),
+        }
+    }
+}
+
+fn sectors_for(len: u64) -> usize {
+    len.div_ceil(SECTOR_SIZE) as _
+}
+
+/// A single region file: a fixed size header of `(sector, length)` entries, one per chunk,
+/// followed by the serialized chunks, each starting on a sector boundary.
+pub struct RegionFile {
+    file: File,
+    header: Box<[HeaderEntry; CHUNKS_PER_REGION]>,
+    used_sectors: Vec<bool>,
+}
+
+impl RegionFile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let len = file.metadata()?.len();
+
+        let mut header = Box::new([HeaderEntry::EMPTY; CHUNKS_PER_REGION]);
+
+        if len < HEADER_BYTES {
+            // new (or truncated) region, zeroed header means no chunks
+            file.set_len(HEADER_BYTES)?;
+        } else {
+            let mut bytes = vec![0; HEADER_BYTES as usize];
+
+            file.seek(SeekFrom::Start(0))?;
+            file.read_exact(&mut bytes)?;
+
+            for (entry, bytes) in header.iter_mut().zip(bytes.chunks_exact(ENTRY_SIZE)) {
+                *entry = HeaderEntry::from_bytes(bytes);
+            }
+        }
+
+        let mut used_sectors = vec![false; sectors_for(len.max(HEADER_BYTES))];
+
+        used_sectors[..HEADER_SECTORS].fill(true);
+
+        for (index, entry) in header.iter_mut().enumerate().filter(|(_, e)| !e.is_empty()) {
+            let range = entry.sector_range();
+
+            if range.start < HEADER_SECTORS || range.end > used_sectors.len() {
+                tracing::warn!("chunk {index} in region {path:?} points outside of the file, ignoring it");
+                *entry = HeaderEntry::EMPTY;
+                continue;
+            }
+
+            used_sectors[range].fill(true);
+        }
+
+        Ok(Self { file, header, used_sectors })
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        !self.header[index].is_empty()
+    }
+
+    pub fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.header
+            .iter()
+            .positions(|entry| !entry.is_empty())
+    }
+
+    pub fn read(&mut self, index: usize) -> io::Result<Option<Vec<u8>>> {
+        let entry = self.header[index];
+
+        if entry.is_empty() {
+            return Ok(None);
+        }
+
+        let mut bytes = vec![0; entry.len as usize];
+
+        self.file.seek(SeekFrom::Start(entry.sector as u64 * SECTOR_SIZE))?;
+        self.file.read_exact(&mut bytes)?;
+
+        Ok(Some(bytes))
+    }
+
+    pub fn write(&mut self, index: usize, bytes: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(bytes.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "chunk too large for region file"))?;
+
+        let old = self.header[index];
+        let needed = sectors_for(len as _);
+
+        // never overwrite the old data, since the header still points to it until the new data is written
+        let start = self.find_free(needed);
+
+        self.file.seek(SeekFrom::Start(start as u64 * SECTOR_SIZE))?;
+        self.file.write_all(bytes)?;
+        self.file.sync_data()?;
+
+        let entry = HeaderEntry { sector: start as _, len };
+
+        self.write_entry(index, entry)?;
+
+        if self.used_sectors.len() < start + needed {
+            self.used_sectors.resize(start + needed, false);
+        }
+
+        self.used_sectors[old.sector_range()].fill(false);
+        self.used_sectors[entry.sector_range()].fill(true);
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, index: usize) -> io::Result<bool> {
+        let old = self.header[index];
+
+        if old.is_empty() {
+            return Ok(false);
+        }
+
+        self.write_entry(index, HeaderEntry::EMPTY)?;
+
+        self.used_sectors[old.sector_range()].fill(false);
+
+        Ok(true)
+    }
+
+    pub fn free_sectors(&self) -> usize {
+        self.used_sectors.iter().filter(|used| !**used).count()
+    }
+
+    pub fn should_compact(&self) -> bool {
+        let free = self.free_sectors();
+
+        free >= MIN_COMPACT_SECTORS && free > self.used_sectors.len() - free
+    }
+
+    /// Rewrites the region into a new file with every chunk packed contiguously, then replaces the old file.
+    pub fn compact(mut self, path: &Path) -> io::Result<Self> {
+        let tmp_path = path.with_extension("tmp");
+
+        let mut out = File::create(&tmp_path)?;
+        let mut header = [HeaderEntry::EMPTY; CHUNKS_PER_REGION];
+        let mut next = HEADER_SECTORS;
+
+        for index in self.indices().collect::<Vec<_>>() {
+            let Some(bytes) = self.read(index)? else {
+                continue;
+            };
+
+            out.seek(SeekFrom::Start(next as u64 * SECTOR_SIZE))?;
+            out.write_all(&bytes)?;
+
+            header[index] = HeaderEntry { sector: next as _, len: bytes.len() as _ };
+            next += header[index].sector_count();
+        }
+
+        let header_bytes = header.iter()
+            .flat_map(|entry| entry.to_bytes())
+            .collect::<Vec<_>>();
+
+        out.seek(SeekFrom::Start(0))?;
+        out.write_all(&header_bytes)?;
+        out.sync_all()?;
+
+        drop(out);
+        drop(self);
+
+        fs::rename(&tmp_path, path)?;
+
+        Self::open(path)
+    }
+
+    fn write_entry(&mut self, index: usize, entry: HeaderEntry) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start((index * ENTRY_SIZE) as _))?;
+        self.file.write_all(&entry.to_bytes())?;
+
+        self.header[index] = entry;
+
+        Ok(())
+    }
+
+    // first fit, falling back to the end of the file
+    fn find_free(&self, needed: usize) -> usize {
+        let mut run_start = HEADER_SECTORS;
+
+        for (sector, &used) in self.used_sectors.iter().enumerate().skip(HEADER_SECTORS) {
+            if used {
+                run_start = sector + 1;
+            } else if sector + 1 - run_start >= needed {
+                return run_start;
+            }
+        }
+
+        run_start
+    }
+}
+
+/// Stores chunks grouped into region files of [`REGION_SIZE`] chunks each, rather than one file per chunk.
+pub struct ChunkSaveToRegion {
+    path: PathBuf,
+    regions: Mutex<HashMap<RegionLocation, RegionFile>>,
+    compression: ChunkCompression,
+}
+
+impl ChunkSaveToRegion {
+    pub fn new(path: impl Into<PathBuf>) -> Option<Self> {
+        let path = path.into();
+
+        match fs::create_dir_all(&path) {
+            Ok(_) => Some(Self { path, regions: Mutex::default(), compression: ChunkCompression::default() }),
+            Err(err) => {
+                tracing::error!("failed to create dir at {path:?}: {err}");
+                None
+            }
+        }
+    }
+
+    pub fn with_compression(mut self, compression: ChunkCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn region_to_file_name(region: &RegionLocation) -> PathBuf {
+        PathBuf::from(format!("r_{}_{}_{}.cfr", region.0.x, region.0.y, region.0.z))
+    }
+
+    pub fn file_name_to_region(file: &Path) -> Option<RegionLocation> {
+        if file.extension().is_none_or(|ext| ext != "cfr") {
+            return None;
+        }
+
+        let stem = file.file_stem()?.to_string_lossy();
+
+        let mut components = stem.split('_');
+
+        let (Some(("r", x, y, z)), None) = (components.next_tuple(), components.next()) else {
+            return None;
+        };
+
+        let (Ok(x), Ok(y), Ok(z)) = (x.parse(), y.parse(), z.parse()) else {
+            return None;
+        };
+
+        Some(RegionLocation(IVec3::new(x, y, z)))
+    }
+
+    pub fn compact_all(&self) {
+        let mut regions = self.regions.lock().expect("region lock poisoned");
+
+        let to_compact = regions.iter()
+            .filter(|(_, file)| file.free_sectors() > 0)
+            .map(|(region, _)| region.clone())
+            .collect::<Vec<_>>();
+
+        for region in to_compact {
+            self.compact_region(&mut regions, &region);
+        }
+    }
+
+    fn region_path(&self, region: &RegionLocation) -> PathBuf {
+        self.path.join(Self::region_to_file_name(region))
+    }
+
+    fn open_region<'a>(&self, regions: &'a mut HashMap<RegionLocation, RegionFile>, region: &RegionLocation) -> io::Result<&'a mut RegionFile> {
+        match regions.entry(region.clone()) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(RegionFile::open(&self.region_path(region))?)),
+        }
+    }
+
+    fn compact_region(&self, regions: &mut HashMap<RegionLocation, RegionFile>, region: &RegionLocation) {
+        let Some(file) = regions.remove(region) else {
+            return;
+        };
+
+        let path = self.region_path(region);
+
+        // if this fails, the region will be reopened from whichever file exists next time it's accessed
+        match file.compact(&path) {
+            Ok(file) => {
+                tracing::debug!("compacted region {region:?}");
+                regions.insert(region.clone(), file);
+            }
+            Err(err) => tracing::error!("failed to compact region at {path:?}: {err}"),
+        }
+    }
+}
+
+impl ChunkSaver for ChunkSaveToRegion {
+    fn save(&self, data: &ChunkSaveCache) -> bool {
+        let loc = &data.data.location;
+        let region = RegionLocation::from(loc);
+
+        let bytes = match encode_chunk(&data.data, self.compression) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                tracing::error!("Failed to serialize chunk at {loc:?}: {err}");
+                return false;
+            }
+        };
+
+        let mut regions = self.regions.lock().expect("region lock poisoned");
+
+        let file = match self.open_region(&mut regions, &region) {
+            Ok(file) => file,
+            Err(err) => {
+                tracing::error!("Failed to open region {region:?} to save {loc:?}: {err}");
+                return false;
+            }
+        };
+
+        if let Err(err) = file.write(RegionLocation::index_of(loc), &bytes) {
+            tracing::error!("Failed to write chunk {loc:?} to region {region:?}: {err}");
+            return false;
+        }
+
+        if file.should_compact() {
+            self.compact_region(&mut regions, &region);
+        }
+
+        true
+    }
+
+    fn retrieve(&self, loc: &ChunkLocation) -> Result<ChunkSaveCache, ChunkLoadError> {
+        let region = RegionLocation::from(loc);
+
+        let bytes = self.read_bytes(loc)?;
+
+        match decode_chunk(&bytes) {
+            Ok(data) => Ok(ChunkSaveCache::new(data)),
+            Err(err) => {
+                match quarantine(&self.path, loc, &bytes).and_then(|path| self.remove(loc).map(|_| path)) {
+                    Ok(path) => tracing::error!("chunk {loc:?} in region {region:?} was corrupt, moved it to {path:?}: {err}"),
+                    Err(io_err) => {
+                        tracing::error!("chunk {loc:?} in region {region:?} was corrupt, and couldn't be quarantined: {io_err}");
+                        return Err(io_err.into());
+                    }
+                }
+
+                Err(err.into())
+            }
+        }
+    }
+
+    fn read_bytes(&self, loc: &ChunkLocation) -> Result<Vec<u8>, ChunkLoadError> {
+        let region = RegionLocation::from(loc);
+
+        let mut regions = self.regions.lock().expect("region lock poisoned");
+
+        let file = self.open_region(&mut regions, &region)?;
+
+        match file.read(RegionLocation::index_of(loc)) {
+            Ok(Some(bytes)) => Ok(bytes),
+            Ok(None) => Err(ChunkLoadError::NotFound),
+            Err(err) => {
+                tracing::error!("failed to read chunk {loc:?} from region {region:?}: {err}");
+                Err(err.into())
+            }
+        }
+    }
+
+    fn remove(&self, loc: &ChunkLocation) -> io::Result<bool> {
+        let mut regions = self.regions.lock().expect("region lock poisoned");
+
+        self.open_region(&mut regions, &RegionLocation::from(loc))?
+            .remove(RegionLocation::index_of(loc))
+    }
+
+    fn update_saved(&self, saved: &mut HashSet<ChunkLocation>) {
+        let Ok(read_dir) = fs::read_dir(&self.path) else {
+            tracing::warn!("invalid directory; TODO: errors");
+            return;
+        };
+
+        let mut regions = self.regions.lock().expect("region lock poisoned");
+
+        for entry in read_dir {
+            let Ok(entry) = entry else {
+                tracing::warn!("unable to get dir entry");
+                continue;
+            };
+
+            let path = entry.path();
+
+            // the quarantine directory, or a temporary file left behind by compaction
+            if path.is_dir() || path.extension().is_some_and(|ext| ext == "tmp") {
+                continue;
+            }
+
+            let Some(region) = Self::file_name_to_region(&path) else {
+                tracing::warn!("file \"{path:?}\" wasn't a region file");
+                continue;
+            };
+
+            match self.open_region(&mut regions, &region) {
+                Ok(file) => saved.extend(file.indices().map(|index| region.chunk_at(index))),
+                Err(err) => tracing::error!("failed to read header of region at {path:?}: {err}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::block::Block;
+    use game::chunk::data::ChunkData;
+    use game::chunk::pos::ChunkPos;
+    use crate::save::{with_large_stack, ChunkStorageKind, QUARANTINE_DIR};
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("protovox-{name}-{}", std::process::id()));
+
+        let _ = fs::remove_dir_all(&path);
+
+        path
+    }
+
+    fn test_chunk(loc: IVec3, block: Block) -> ChunkSaveCache {
+        let mut data = ChunkData::empty(ChunkLocation(loc));
+
+        for y in 0..8 {
+            data.set(ChunkPos::new(3, y, 7).expect("in range"), block);
+        }
+
+        ChunkSaveCache::new(data)
+    }
+
+    #[test]
+    fn test_region_index_round_trip() {
+        for loc in [IVec3::new(0, 0, 0), IVec3::new(-1, -1, -1), IVec3::new(17, -5, 33), IVec3::new(-16, 3, 15)] {
+            let loc = ChunkLocation(loc);
+            let region = RegionLocation::from(&loc);
+
+            assert_eq!(region.chunk_at(RegionLocation::index_of(&loc)), loc);
+        }
+    }
+
+    #[test]
+    fn test_save_retrieve_and_rewrite() {
+        with_large_stack(|| {
+            let dir = test_dir("region-save");
+
+            let saver = ChunkSaveToRegion::new(&dir).expect("dir should be created");
+
+            assert!(saver.save(&test_chunk(IVec3::new(1, 0, -2), Block::STONE)));
+            assert!(saver.save(&test_chunk(IVec3::new(-20, 2, 5), Block::DIRT)));
+            assert!(saver.save(&test_chunk(IVec3::new(1, 0, -2), Block::PLANKS)));
+
+            let mut saved = HashSet::new();
+
+            // a separate saver has to build its saved set from the region headers
+            ChunkSaveToRegion::new(&dir)
+                .expect("dir should exist")
+                .update_saved(&mut saved);
+
+            assert_eq!(saved, HashSet::from([ChunkLocation(IVec3::new(1, 0, -2)), ChunkLocation(IVec3::new(-20, 2, 5))]));
+
+            let cache = saver.retrieve(&ChunkLocation(IVec3::new(1, 0, -2))).expect("chunk was saved");
+
+            assert!(cache.data.blocks_ref().eq(test_chunk(IVec3::new(1, 0, -2), Block::PLANKS).data.blocks_ref()));
+            assert!(matches!(saver.retrieve(&ChunkLocation(IVec3::new(2, 0, -2))), Err(ChunkLoadError::NotFound)));
+
+            fs::remove_dir_all(dir).expect("should be able to clean up");
+        });
+    }
+
+    #[test]
+    fn test_detect_read_and_remove() {
+        with_large_stack(|| {
+            let dir = test_dir("region-remove");
+
+            let saver = ChunkSaveToRegion::new(&dir).expect("dir should be created");
+
+            assert_eq!(ChunkStorageKind::detect(&dir), None);
+
+            let loc = ChunkLocation(IVec3::new(4, -1, 9));
+
+            assert!(saver.save(&test_chunk(loc.0, Block::STONE)));
+
+            assert_eq!(ChunkStorageKind::detect(&dir), Some(ChunkStorageKind::Region));
+
+            let bytes = saver.read_bytes(&loc).expect("chunk was saved");
+
+            assert!(decode_chunk(&bytes).expect("chunk should decode").blocks_ref().eq(test_chunk(loc.0, Block::STONE).data.blocks_ref()));
+
+            assert!(saver.remove(&loc).expect("should remove"));
+            assert!(!saver.remove(&loc).expect("should remove"));
+            assert!(matches!(saver.read_bytes(&loc), Err(ChunkLoadError::NotFound)));
+
+            fs::remove_dir_all(dir).expect("should be able to clean up");
+        });
+    }
+
+    #[test]
+    fn test_corrupt_chunk_is_quarantined() {
+        with_large_stack(|| {
+            let dir = test_dir("region-corrupt");
+            let loc = ChunkLocation(IVec3::new(0, 0, 0));
+
+            let saver = ChunkSaveToRegion::new(&dir).expect("dir should be created");
+
+            assert!(saver.save(&test_chunk(loc.0, Block::STONE)));
+
+            // simulate a torn write by flipping a byte near the end of the chunk's data
+            {
+                let mut regions = saver.regions.lock().expect("not poisoned");
+                let file = saver.open_region(&mut regions, &RegionLocation::from(&loc)).expect("region exists");
+                let entry = file.header[RegionLocation::index_of(&loc)];
+
+                let offset = entry.sector as u64 * SECTOR_SIZE + entry.len as u64 - 1;
+
+                let mut byte = [0];
+                file.file.seek(SeekFrom::Start(offset)).expect("seek should succeed");
+                file.file.read_exact(&mut byte).expect("read should succeed");
+                file.file.seek(SeekFrom::Start(offset)).expect("seek should succeed");
+                file.file.write_all(&[byte[0] ^ 0xff]).expect("write should succeed");
+            }
+
+            assert!(matches!(saver.retrieve(&loc), Err(ChunkLoadError::Corrupt(_))));
+            assert_eq!(fs::read_dir(dir.join(QUARANTINE_DIR)).expect("quarantine should exist").count(), 1);
+            assert!(matches!(saver.retrieve(&loc), Err(ChunkLoadError::NotFound)));
+
+            fs::remove_dir_all(dir).expect("should be able to clean up");
+        });
+    }
+
+    #[test]
+    fn test_compaction() {
+        let dir = test_dir("region-compact");
+        let path = dir.join("r_0_0_0.cfr");
+
+        fs::create_dir_all(&dir).expect("should be able to create dir");
+
+        let mut file = RegionFile::open(&path).expect("should be able to create region");
+
+        for index in 0..8 {
+            file.write(index, &vec![index as u8; 3 * SECTOR_SIZE as usize]).expect("write should succeed");
+        }
+
+        for index in 0..6 {
+            file.remove(index).expect("remove should succeed");
+        }
+
+        assert_eq!(file.free_sectors(), 18);
+
+        let mut file = file.compact(&path).expect("compaction should succeed");
+
+        assert_eq!(file.free_sectors(), 0);
+        assert_eq!(file.indices().collect::<Vec<_>>(), [6, 7]);
+        assert_eq!(file.read(7).expect("read should succeed"), Some(vec![7; 3 * SECTOR_SIZE as usize]));
+        assert_eq!(fs::metadata(&path).expect("file exists").len(), HEADER_BYTES + 6 * SECTOR_SIZE);
+
+        fs::remove_dir_all(dir).expect("should be able to clean up");
+    }
+}
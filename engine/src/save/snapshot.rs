@@ -0,0 +1,435 @@
+use std::{fs, io};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use shipyard::{Unique, UniqueOrDefaultViewMut, UniqueView, UniqueViewMut, View};
+use crate::chunks::chunk_manager::ChunkManager;
+use crate::identity::PlayerIdentity;
+use crate::save::player::{save_all_players, PlayerDataStore, PlayerDataView};
+use crate::save::WorldSaver;
+use crate::save::world::{WorldDirectory, WorldLoadError};
+
+/// Snapshots are kept inside the world they're of, and are never included in other snapshots.
+pub const SNAPSHOTS_DIR: &str = "snapshots";
+
+const SNAPSHOT_EXTENSION: &str = ".tar.gz";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+const RESTORE_STAGING_DIR: &str = ".restoring";
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("failed to access snapshot: {0}")]
+    Io(#[from] io::Error),
+    #[error("no snapshot named {0:?}")]
+    NotFound(String),
+    #[error("snapshot doesn't contain a valid world: {0}")]
+    InvalidWorld(#[from] WorldLoadError),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SnapshotKind {
+    /// Requested by someone, and only ever removed by hand.
+    Manual,
+    /// Taken periodically, and pruned by the [`RetentionPolicy`].
+    Automatic,
+}
+
+impl SnapshotKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            SnapshotKind::Manual => "snapshot-",
+            SnapshotKind::Automatic => "auto-",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub name: String,
+    pub path: PathBuf,
+    pub kind: SnapshotKind,
+    pub created: DateTime<Utc>,
+    pub size: u64,
+}
+
+impl Snapshot {
+    fn from_path(path: PathBuf) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+
+        let name = file_name.strip_suffix(SNAPSHOT_EXTENSION)?.to_owned();
+
+        let (kind, rest) = [SnapshotKind::Manual, SnapshotKind::Automatic]
+            .into_iter()
+            .find_map(|kind| name.strip_prefix(kind.prefix()).map(|rest| (kind, rest)))?;
+
+        // snapshots taken within the same second get a counter after the timestamp
+        let timestamp = rest.get(..15)?;
+
+        let created = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+            .ok()?
+            .and_utc();
+
+        let size = fs::metadata(&path).ok()?.len();
+
+        Some(Self { name, path, kind, created, size })
+    }
+}
+
+/// How many automatic snapshots to keep; manual snapshots are never pruned.
+#[derive(Copy, Clone, Debug)]
+pub struct RetentionPolicy {
+    pub keep_automatic: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self { keep_automatic: 5 }
+    }
+}
+
+/// Archives of a world directory, stored as `.tar.gz` files in its `snapshots` directory.
+///
+/// Snapshots are only consistent if nothing is written to the world while they're taken,
+/// so a running world should be flushed first with [`snapshot_world`].
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    world_root: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(world_root: impl Into<PathBuf>) -> Self {
+        Self { world_root: world_root.into() }
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        self.world_root.join(SNAPSHOTS_DIR)
+    }
+
+    pub fn create(&self, kind: SnapshotKind) -> Result<Snapshot, SnapshotError> {
+        let dir = self.dir();
+
+        fs::create_dir_all(&dir)?;
+
+        let base = format!("{}{}", kind.prefix(), Utc::now().format(TIMESTAMP_FORMAT));
+
+        let path = (0..)
+            .map(|n| match n {
+                0 => dir.join(format!("{base}{SNAPSHOT_EXTENSION}")),
+                n => dir.join(format!("{base}-{n}{SNAPSHOT_EXTENSION}")),
+            })
+            .find(|path| !path.exists())
+            .expect("some counter should be free");
+
+        // written under a temporary name first, so a partial archive never looks like a snapshot
+        let tmp_path = path.with_extension("tmp");
+
+        let encoder = GzEncoder::new(File::create(&tmp_path)?, Compression::default());
+
+        let mut builder = tar::Builder::new(encoder);
+
+        Self::append_dir(&mut builder, &self.world_root, Path::new(""))?;
+
+        let file = builder.into_inner()?.finish()?;
+        file.sync_all()?;
+
+        fs::rename(&tmp_path, &path)?;
+
+        let snapshot = Snapshot::from_path(path).expect("snapshot was just created with a valid name");
+
+        tracing::debug!("Created snapshot {} ({} bytes)", snapshot.name, snapshot.size);
+
+        Ok(snapshot)
+    }
+
+    fn append_dir(builder: &mut tar::Builder<GzEncoder<File>>, root: &Path, relative: &Path) -> io::Result<()> {
+        for entry in fs::read_dir(root.join(relative))? {
+            let entry = entry?;
+
+            let relative = relative.join(entry.file_name());
+
+            if relative == Path::new(SNAPSHOTS_DIR) {
+                continue;
+            }
+
+            if entry.file_type()?.is_dir() {
+                builder.append_dir(&relative, entry.path())?;
+
+                Self::append_dir(builder, root, &relative)?;
+            } else if entry.path().extension().is_none_or(|ext| ext != "tmp") {
+                // leftover temporary files are from interrupted writes, so they're never worth keeping
+                builder.append_path_with_name(entry.path(), &relative)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every snapshot of this world, oldest first.
+    pub fn list(&self) -> Result<Vec<Snapshot>, SnapshotError> {
+        let entries = match fs::read_dir(self.dir()) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut snapshots = Vec::new();
+
+        for entry in entries {
+            if let Some(snapshot) = Snapshot::from_path(entry?.path()) {
+                snapshots.push(snapshot);
+            }
+        }
+
+        snapshots.sort_by(|a, b| a.created.cmp(&b.created).then_with(|| a.name.cmp(&b.name)));
+
+        Ok(snapshots)
+    }
+
+    pub fn find(&self, name: &str) -> Result<Snapshot, SnapshotError> {
+        let name = name.strip_suffix(SNAPSHOT_EXTENSION).unwrap_or(name);
+
+        self.list()?
+            .into_iter()
+            .find(|snapshot| snapshot.name == name)
+            .ok_or_else(|| SnapshotError::NotFound(name.to_owned()))
+    }
+
+    /// Replaces the world with the contents of a snapshot, returning a snapshot of what it replaced.
+    /// The world must not be open while it's restored.
+    pub fn restore(&self, name: &str) -> Result<Snapshot, SnapshotError> {
+        let snapshot = self.find(name)?;
+
+        let staging = self.dir().join(RESTORE_STAGING_DIR);
+
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+
+        tar::Archive::new(GzDecoder::new(File::open(&snapshot.path)?)).unpack(&staging)?;
+
+        // make sure the snapshot is actually usable before touching the world
+        if let Err(err) = WorldDirectory::open(&staging) {
+            fs::remove_dir_all(&staging)?;
+            return Err(err.into());
+        }
+
+        let backup = self.create(SnapshotKind::Manual)?;
+
+        for entry in fs::read_dir(&self.world_root)? {
+            let entry = entry?;
+
+            if entry.file_name() == SNAPSHOTS_DIR {
+                continue;
+            }
+
+            if entry.file_type()?.is_dir() {
+                fs::remove_dir_all(entry.path())?;
+            } else {
+                fs::remove_file(entry.path())?;
+            }
+        }
+
+        for entry in fs::read_dir(&staging)? {
+            let entry = entry?;
+
+            fs::rename(entry.path(), self.world_root.join(entry.file_name()))?;
+        }
+
+        fs::remove_dir(&staging)?;
+
+        tracing::debug!("Restored snapshot {}, the replaced world was saved as {}", snapshot.name, backup.name);
+
+        Ok(backup)
+    }
+
+    /// Removes the oldest automatic snapshots past what the policy keeps, returning the removed ones.
+    pub fn prune(&self, policy: RetentionPolicy) -> Result<Vec<Snapshot>, SnapshotError> {
+        let automatic = self.list()?
+            .into_iter()
+            .filter(|snapshot| snapshot.kind == SnapshotKind::Automatic)
+            .collect::<Vec<_>>();
+
+        let excess = automatic.len().saturating_sub(policy.keep_automatic);
+
+        let removed = automatic.into_iter().take(excess).collect::<Vec<_>>();
+
+        for snapshot in &removed {
+            fs::remove_file(&snapshot.path)?;
+        }
+
+        Ok(removed)
+    }
+}
+
+/// How often a hosted world is snapshotted automatically, and how many of those are kept.
+#[derive(Unique, Copy, Clone, Debug)]
+pub struct AutoSnapshotSettings {
+    /// Automatic snapshots are disabled if this is `None`.
+    pub interval: Option<Duration>,
+    pub retention: RetentionPolicy,
+}
+
+impl Default for AutoSnapshotSettings {
+    fn default() -> Self {
+        Self {
+            interval: Some(Duration::from_secs(30 * 60)),
+            retention: RetentionPolicy::default(),
+        }
+    }
+}
+
+#[derive(Unique, Debug)]
+pub struct LastAutoSnapshot {
+    pub at: Instant,
+    /// The archive being written in the background, so snapshots never overlap.
+    archiving: Option<JoinHandle<()>>,
+}
+
+impl Default for LastAutoSnapshot {
+    fn default() -> Self {
+        Self {
+            at: Instant::now(),
+            archiving: None,
+        }
+    }
+}
+
+/// Writes everything the world has in memory to disk, so the world directory can be archived.
+pub fn flush_world(
+    mut chunk_mgr: UniqueViewMut<ChunkManager>,
+    mut world_saver: UniqueViewMut<WorldSaver>,
+    world_dir: &WorldDirectory,
+    (store, v_identity, player_data): (UniqueView<PlayerDataStore>, View<PlayerIdentity>, PlayerDataView),
+) -> Result<(), SnapshotError> {
+    chunk_mgr.save_modified(&mut world_saver);
+    world_saver.save_all();
+
+    save_all_players(store, v_identity, player_data);
+
+    world_dir.save_level()?;
+
+    Ok(())
+}
+
+/// Flushes the world, then snapshots the world directory.
+pub fn snapshot_world(
+    kind: SnapshotKind,
+    chunk_mgr: UniqueViewMut<ChunkManager>,
+    world_saver: UniqueViewMut<WorldSaver>,
+    world_dir: UniqueView<WorldDirectory>,
+    players: (UniqueView<PlayerDataStore>, View<PlayerIdentity>, PlayerDataView),
+) -> Result<Snapshot, SnapshotError> {
+    flush_world(chunk_mgr, world_saver, &world_dir, players)?;
+
+    SnapshotStore::new(world_dir.root()).create(kind)
+}
+
+/// Only the flush happens on the main thread, since compressing the whole world takes a while.
+pub fn auto_snapshot_world(
+    mut last_snapshot: UniqueOrDefaultViewMut<LastAutoSnapshot>,
+    settings: UniqueView<AutoSnapshotSettings>,
+    chunk_mgr: UniqueViewMut<ChunkManager>,
+    world_saver: UniqueViewMut<WorldSaver>,
+    world_dir: UniqueView<WorldDirectory>,
+    players: (UniqueView<PlayerDataStore>, View<PlayerIdentity>, PlayerDataView),
+) {
+    let Some(interval) = settings.interval else {
+        return;
+    };
+
+    if last_snapshot.at.elapsed() < interval {
+        return;
+    }
+
+    if last_snapshot.archiving.as_ref().is_some_and(|handle| !handle.is_finished()) {
+        return;
+    }
+
+    last_snapshot.at = Instant::now();
+
+    if let Err(err) = flush_world(chunk_mgr, world_saver, &world_dir, players) {
+        tracing::error!("Failed to flush the world for an automatic snapshot: {err}");
+        return;
+    }
+
+    let store = SnapshotStore::new(world_dir.root());
+    let retention = settings.retention;
+
+    let handle = thread::Builder::new()
+        .name("auto-snapshot".into())
+        .spawn(move || archive_automatic(&store, retention));
+
+    match handle {
+        Ok(handle) => last_snapshot.archiving = Some(handle),
+        Err(err) => tracing::error!("Failed to spawn automatic snapshot thread: {err}"),
+    }
+}
+
+fn archive_automatic(store: &SnapshotStore, retention: RetentionPolicy) {
+    if let Err(err) = store.create(SnapshotKind::Automatic) {
+        tracing::error!("Failed to take automatic snapshot: {err}");
+        return;
+    }
+
+    match store.prune(retention) {
+        Ok(removed) => for snapshot in removed {
+            tracing::debug!("Removed old snapshot {}", snapshot.name);
+        },
+        Err(err) => tracing::error!("Failed to prune old snapshots: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::world::LevelData;
+
+    #[test]
+    fn test_snapshot_restore_and_prune() {
+        let root = std::env::temp_dir().join(format!("protovox-snapshots-{}", std::process::id()));
+
+        let _ = fs::remove_dir_all(&root);
+
+        let world_dir = WorldDirectory::create(&root, LevelData::new(42)).expect("world should be created");
+
+        fs::write(world_dir.chunks_path().join("0_0_0.cff"), b"before").expect("should write chunk");
+        fs::write(world_dir.chunks_path().join("1_0_0.tmp"), b"partial").expect("should write chunk");
+
+        let store = SnapshotStore::new(&root);
+
+        let snapshot = store.create(SnapshotKind::Manual).expect("snapshot should be created");
+
+        fs::write(world_dir.chunks_path().join("0_0_0.cff"), b"after").expect("should write chunk");
+        fs::write(world_dir.chunks_path().join("2_0_0.cff"), b"new").expect("should write chunk");
+
+        let backup = store.restore(&snapshot.name).expect("snapshot should be restored");
+
+        assert_eq!(fs::read(world_dir.chunks_path().join("0_0_0.cff")).expect("chunk should be restored"), b"before");
+        assert!(!world_dir.chunks_path().join("1_0_0.tmp").exists());
+        assert!(!world_dir.chunks_path().join("2_0_0.cff").exists());
+        assert_eq!(WorldDirectory::open(&root).expect("world should load").level.seed, 42);
+
+        // the world it replaced was kept, and didn't include the other snapshots
+        assert_eq!(backup.kind, SnapshotKind::Manual);
+        assert_eq!(store.list().expect("should list").len(), 2);
+
+        for _ in 0..3 {
+            store.create(SnapshotKind::Automatic).expect("snapshot should be created");
+        }
+
+        let removed = store.prune(RetentionPolicy { keep_automatic: 1 }).expect("should prune");
+
+        assert_eq!(removed.len(), 2);
+        assert!(removed.iter().all(|snapshot| snapshot.kind == SnapshotKind::Automatic));
+        assert_eq!(store.list().expect("should list").len(), 3);
+
+        assert!(matches!(store.restore("snapshot-missing"), Err(SnapshotError::NotFound(_))));
+
+        fs::remove_dir_all(root).expect("should be able to clean up");
+    }
+}
@@ -0,0 +1,364 @@
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use crossbeam::channel::{Sender, TrySendError};
+use hashbrown::HashMap;
+use game::chunk::location::ChunkLocation;
+use crate::save::{ChunkSaveCache, ChunkSaver};
+
+enum SaveJob {
+    Write(ChunkLocation),
+    /// Answered once every job queued before it is done.
+    Flush(Sender<()>),
+}
+
+#[derive(Default)]
+struct Pending {
+    /// Chunks queued to be written; the queue only holds their locations,
+    /// so saving a chunk again before it's written just replaces it here.
+    chunks: HashMap<ChunkLocation, ChunkSaveCache>,
+    writing: Option<ChunkLocation>,
+    /// Chunks that couldn't be written, waiting for the main thread to take them back.
+    failed: Vec<ChunkSaveCache>,
+}
+
+#[derive(Default)]
+struct Shared {
+    pending: Mutex<Pending>,
+    written: Condvar,
+}
+
+/// Writes chunks on a dedicated thread, so the main thread never waits on disk I/O.
+pub struct SaveWorker {
+    tx: Option<Sender<SaveJob>>,
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl SaveWorker {
+    pub fn spawn(saver: Arc<dyn ChunkSaver + Send + Sync>, capacity: usize) -> Self {
+        let (tx, rx) = crossbeam::channel::bounded(capacity);
+
+        let shared = Arc::new(Shared::default());
+
+        let worker_shared = shared.clone();
+
+        let handle = thread::Builder::new()
+            .name("chunk-saver".into())
+            .spawn(move || {
+                // keeps going until the worker is dropped and every queued job is done
+                for job in rx {
+                    match job {
+                        SaveJob::Write(loc) => Self::write(&worker_shared, saver.as_ref(), loc),
+                        SaveJob::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn chunk saver thread");
+
+        Self {
+            tx: Some(tx),
+            shared,
+            handle: Some(handle),
+        }
+    }
+
+    fn write(shared: &Shared, saver: &dyn ChunkSaver, loc: ChunkLocation) {
+        let data = {
+            let mut pending = shared.pending.lock().expect("pending lock poisoned");
+
+            // it was either already written by an earlier job, or taken back by the main thread
+            let Some(data) = pending.chunks.remove(&loc) else {
+                return;
+            };
+
+            pending.writing = Some(loc.clone());
+
+            data
+        };
+
+        let saved = saver.save(&data);
+
+        let mut pending = shared.pending.lock().expect("pending lock poisoned");
+
+        pending.writing = None;
+
+        if !saved {
+            tracing::error!("Failed to save chunk at {loc:?}");
+
+            // a newer copy queued while this one was being written gets written instead
+            if !pending.chunks.contains_key(&loc) {
+                pending.failed.push(data);
+            }
+        }
+
+        drop(pending);
+        shared.written.notify_all();
+    }
+
+    fn tx(&self) -> &Sender<SaveJob> {
+        self.tx.as_ref().expect("only taken when dropped")
+    }
+
+    /// Queues the chunk to be written, or gives it back if the queue is full.
+    pub fn try_submit(&self, data: ChunkSaveCache) -> Result<(), Box<ChunkSaveCache>> {
+        let loc = data.data.location.clone();
+
+        let mut pending = self.shared.pending.lock().expect("pending lock poisoned");
+
+        if let Some(queued) = pending.chunks.get_mut(&loc) {
+            *queued = data;
+            return Ok(());
+        }
+
+        // sent while holding the lock, so the worker can't look for the chunk before it's inserted
+        match self.tx().try_send(SaveJob::Write(loc.clone())) {
+            Ok(()) => {
+                pending.chunks.insert(loc, data);
+                Ok(())
+            }
+            Err(TrySendError::Full(_)) => Err(Box::new(data)),
+            Err(TrySendError::Disconnected(_)) => panic!("chunk saver thread stopped"),
+        }
+    }
+
+    /// Queues the chunk to be written, waiting for space in the queue if it's full.
+    pub fn submit(&self, data: ChunkSaveCache) {
+        let loc = data.data.location.clone();
+
+        let already_queued = self.shared.pending
+            .lock()
+            .expect("pending lock poisoned")
+            .chunks
+            .insert(loc.clone(), data)
+            .is_some();
+
+        if !already_queued {
+            self.tx()
+                .send(SaveJob::Write(loc))
+                .expect("chunk saver thread stopped");
+        }
+    }
+
+    /// Takes back a chunk that was queued but not written yet, or that failed to be written.
+    /// If it's being written right now, waits for that to finish so it can be read back from the saver.
+    pub fn take_pending(&self, loc: &ChunkLocation) -> Option<ChunkSaveCache> {
+        let mut pending = self.shared.pending.lock().expect("pending lock poisoned");
+
+        if let Some(data) = pending.chunks.remove(loc) {
+            return Some(data);
+        }
+
+        while pending.writing.as_ref() == Some(loc) {
+            pending = self.shared.written.wait(pending).expect("pending lock poisoned");
+        }
+
+        let failed = pending.failed.iter().position(|data| data.data.location == *loc)?;
+
+        Some(pending.failed.swap_remove(failed))
+    }
+
+    /// Takes back every chunk that failed to be written since this was last called, so they can be saved again.
+    pub fn take_failed(&self) -> Vec<ChunkSaveCache> {
+        mem::take(&mut self.shared.pending.lock().expect("pending lock poisoned").failed)
+    }
+
+    /// Blocks until every chunk queued so far has been written.
+    pub fn flush(&self) {
+        let (done_tx, done_rx) = crossbeam::channel::bounded(1);
+
+        self.tx()
+            .send(SaveJob::Flush(done_tx))
+            .expect("chunk saver thread stopped");
+
+        done_rx.recv().expect("chunk saver thread stopped");
+    }
+}
+
+impl Drop for SaveWorker {
+    fn drop(&mut self) {
+        // closing the queue lets the thread finish the remaining jobs and exit
+        drop(self.tx.take());
+
+        if let Some(handle) = self.handle.take()
+            && handle.join().is_err()
+        {
+            tracing::error!("chunk saver thread panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use crossbeam::channel::Receiver;
+    use glm::IVec3;
+    use hashbrown::HashSet;
+    use game::chunk::data::ChunkData;
+    use std::time::Duration;
+    use crate::save::{with_large_stack, ChunkLoadError, WorldSaver};
+    use super::*;
+
+    /// Records what was saved, and only finishes each save once the test allows it.
+    struct GatedSaver {
+        gate: Receiver<()>,
+        saved: Mutex<Vec<ChunkLocation>>,
+    }
+
+    impl ChunkSaver for GatedSaver {
+        fn save(&self, data: &ChunkSaveCache) -> bool {
+            self.gate.recv().expect("gate should stay open");
+            self.saved.lock().expect("not poisoned").push(data.data.location.clone());
+            true
+        }
+
+        fn retrieve(&self, _loc: &ChunkLocation) -> Result<ChunkSaveCache, ChunkLoadError> {
+            Err(ChunkLoadError::NotFound)
+        }
+
+        fn read_bytes(&self, _loc: &ChunkLocation) -> Result<Vec<u8>, ChunkLoadError> {
+            Err(ChunkLoadError::NotFound)
+        }
+
+        fn remove(&self, _loc: &ChunkLocation) -> io::Result<bool> {
+            Ok(false)
+        }
+
+        fn update_saved(&self, _saved: &mut HashSet<ChunkLocation>) {}
+    }
+
+    /// Fails the first few saves, like a disk that's full until some space is freed.
+    struct FailingSaver {
+        failures_left: Mutex<usize>,
+        saved: Arc<Mutex<Vec<ChunkLocation>>>,
+    }
+
+    impl ChunkSaver for FailingSaver {
+        fn save(&self, data: &ChunkSaveCache) -> bool {
+            let mut failures_left = self.failures_left.lock().expect("not poisoned");
+
+            if *failures_left > 0 {
+                *failures_left -= 1;
+                return false;
+            }
+
+            self.saved.lock().expect("not poisoned").push(data.data.location.clone());
+            true
+        }
+
+        fn retrieve(&self, _loc: &ChunkLocation) -> Result<ChunkSaveCache, ChunkLoadError> {
+            Err(ChunkLoadError::NotFound)
+        }
+
+        fn read_bytes(&self, _loc: &ChunkLocation) -> Result<Vec<u8>, ChunkLoadError> {
+            Err(ChunkLoadError::NotFound)
+        }
+
+        fn remove(&self, _loc: &ChunkLocation) -> io::Result<bool> {
+            Ok(false)
+        }
+
+        fn update_saved(&self, _saved: &mut HashSet<ChunkLocation>) {}
+    }
+
+    fn chunk(x: i32) -> ChunkSaveCache {
+        ChunkSaveCache::new(ChunkData::empty(ChunkLocation(IVec3::new(x, 0, 0))))
+    }
+
+    #[test]
+    fn test_queued_chunks_can_be_taken_back() {
+        with_large_stack(|| {
+            let (gate_tx, gate) = crossbeam::channel::unbounded();
+
+            let saver = Arc::new(GatedSaver { gate, saved: Mutex::default() });
+
+            let worker = SaveWorker::spawn(saver.clone(), 4);
+
+            worker.submit(chunk(0));
+            worker.submit(chunk(1));
+            worker.submit(chunk(1)); // coalesced with the previous one
+
+            // the first chunk blocks the worker, so the second one can't have been written yet
+            assert!(worker.take_pending(&ChunkLocation(IVec3::new(1, 0, 0))).is_some());
+
+            worker.submit(chunk(2));
+
+            for _ in 0..2 {
+                gate_tx.send(()).expect("worker should be running");
+            }
+
+            worker.flush();
+
+            assert_eq!(
+                *saver.saved.lock().expect("not poisoned"),
+                [ChunkLocation(IVec3::new(0, 0, 0)), ChunkLocation(IVec3::new(2, 0, 0))],
+            );
+
+            assert!(worker.take_pending(&ChunkLocation(IVec3::new(2, 0, 0))).is_none());
+        });
+    }
+
+    #[test]
+    fn test_full_queue_gives_chunk_back() {
+        with_large_stack(|| {
+            let (gate_tx, gate) = crossbeam::channel::unbounded();
+
+            let worker = SaveWorker::spawn(Arc::new(GatedSaver { gate, saved: Mutex::default() }), 1);
+
+            let mut rejected = None;
+
+            // one chunk is held by the blocked worker and one fills the queue, so eventually one gets rejected
+            for x in 0..3 {
+                if let Err(data) = worker.try_submit(chunk(x)) {
+                    rejected = Some(data);
+                    break;
+                }
+            }
+
+            assert!(rejected.is_some());
+
+            // let the accepted chunks finish, since dropping the worker waits for them
+            for _ in 0..2 {
+                gate_tx.send(()).expect("worker should be running");
+            }
+
+            drop(worker);
+        });
+    }
+
+    #[test]
+    fn test_failed_chunks_are_kept_to_be_saved_again() {
+        with_large_stack(|| {
+            let saved = Arc::<Mutex<Vec<_>>>::default();
+
+            let mut world_saver = WorldSaver::new(Duration::ZERO, FailingSaver { failures_left: Mutex::new(2), saved: saved.clone() });
+
+            let loc = ChunkLocation(IVec3::new(3, 0, 0));
+
+            let mut data = ChunkData::empty(loc.clone());
+            data.mark_modified();
+
+            world_saver.cache(loc.clone(), data);
+            world_saver.process();
+            world_saver.worker.flush();
+
+            let data = world_saver.try_get(&loc).expect("the chunk that failed to save shouldn't be lost").data;
+
+            assert!(data.is_modified());
+
+            world_saver.cache(loc.clone(), data);
+            world_saver.process();
+            world_saver.worker.flush();
+
+            assert!(saved.lock().expect("not poisoned").is_empty());
+
+            // the second failure is cached again, and tried again once it expires
+            world_saver.process();
+            world_saver.worker.flush();
+
+            assert_eq!(*saved.lock().expect("not poisoned"), [loc]);
+        });
+    }
+}
@@ -0,0 +1,204 @@
+use std::{fs, io};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use glm::Vec3;
+use serde::{Deserialize, Serialize};
+use shipyard::Unique;
+use game::location::BlockLocation;
+use crate::block_tick::tick_at;
+use crate::save::write_atomic;
+use crate::world_gen::params::WorldGenParams;
+use crate::world_gen::WorldGenSplines;
+
+/// Bumped whenever the layout of [`LevelData`] changes.
+pub const LEVEL_FORMAT_VERSION: u32 = 1;
+
+const LEVEL_FILE: &str = "level";
+const CHUNKS_DIR: &str = "chunks";
+const PLAYERS_DIR: &str = "players";
+
+/// Everything needed to recreate a world besides its chunks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelData {
+    pub format_version: u32,
+    pub seed: u32,
+    pub gen_params: WorldGenParams,
+    pub gen_splines: WorldGenSplines,
+    pub spawn: Vec3,
+    pub game_time: Duration,
+}
+
+impl LevelData {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            format_version: LEVEL_FORMAT_VERSION,
+            seed,
+            gen_params: WorldGenParams::overworld(),
+            gen_splines: WorldGenSplines::overworld(),
+            spawn: Vec3::new(0.5, 20.0, 0.5),
+            game_time: Duration::ZERO,
+        }
+    }
+
+    /// What loot from a block broken at `location` on the current tick is rolled with. It's only known where
+    /// the world is hosted, so clients can't pick their drops. It's mixed by hand so that it stays the same across Rust releases.
+    pub fn loot_seed(&self, location: &BlockLocation) -> u64 {
+        let [x, y, z] = location.0.into();
+
+        [x as u32 as u64, y as u32 as u64, z as u32 as u64, tick_at(self.game_time)]
+            .into_iter()
+            .fold(splitmix64(self.seed as u64), |acc, n| splitmix64(acc ^ n))
+    }
+}
+
+/// One step of SplitMix64.
+fn splitmix64(n: u64) -> u64 {
+    let mut z = n.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WorldLoadError {
+    #[error("failed to access world directory: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to (de)serialize level data: {0}")]
+    Serialization(#[from] postcard::Error),
+    #[error("level format version {0} is newer than the supported version {LEVEL_FORMAT_VERSION}")]
+    UnsupportedVersion(u32),
+}
+
+/// A world on disk: the `level` metadata file, with the chunk store in the `chunks` directory
+/// and player data in the `players` directory next to it.
+#[derive(Unique, Debug)]
+pub struct WorldDirectory {
+    root: PathBuf,
+    pub level: LevelData,
+}
+
+impl WorldDirectory {
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, WorldLoadError> {
+        let root = root.into();
+
+        let bytes = fs::read(root.join(LEVEL_FILE))?;
+
+        // read the version on its own first, since a newer level might not deserialize at all
+        let (format_version, _) = postcard::take_from_bytes::<u32>(&bytes)?;
+
+        if format_version > LEVEL_FORMAT_VERSION {
+            return Err(WorldLoadError::UnsupportedVersion(format_version));
+        }
+
+        let level = postcard::from_bytes(&bytes)?;
+
+        Ok(Self { root, level })
+    }
+
+    pub fn create(root: impl Into<PathBuf>, level: LevelData) -> Result<Self, WorldLoadError> {
+        let root = root.into();
+
+        fs::create_dir_all(root.join(CHUNKS_DIR))?;
+        fs::create_dir_all(root.join(PLAYERS_DIR))?;
+
+        let this = Self { root, level };
+
+        this.save_level()?;
+
+        Ok(this)
+    }
+
+    pub fn open_or_create(root: impl Into<PathBuf>, seed: impl FnOnce() -> u32) -> Result<Self, WorldLoadError> {
+        let root = root.into();
+
+        if root.join(LEVEL_FILE).exists() {
+            tracing::debug!("Loading world at {root:?}");
+
+            Self::open(root)
+        } else {
+            let level = LevelData::new(seed());
+
+            tracing::debug!("Creating new world at {root:?} with seed {}", level.seed);
+
+            Self::create(root, level)
+        }
+    }
+
+    pub fn save_level(&self) -> Result<(), WorldLoadError> {
+        let bytes = postcard::to_allocvec(&self.level)?;
+
+        write_atomic(&self.level_path(), &bytes)?;
+
+        Ok(())
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn level_path(&self) -> PathBuf {
+        self.root.join(LEVEL_FILE)
+    }
+
+    pub fn chunks_path(&self) -> PathBuf {
+        self.root.join(CHUNKS_DIR)
+    }
+
+    pub fn players_path(&self) -> PathBuf {
+        self.root.join(PLAYERS_DIR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_and_reopen() {
+        let root = std::env::temp_dir().join(format!("protovox-world-{}", std::process::id()));
+
+        let _ = fs::remove_dir_all(&root);
+
+        let mut world_dir = WorldDirectory::open_or_create(&root, || 1234).expect("world should be created");
+
+        assert!(world_dir.chunks_path().is_dir());
+
+        world_dir.level.game_time = Duration::from_secs(90);
+        world_dir.save_level().expect("level should save");
+
+        let reopened = WorldDirectory::open_or_create(&root, || unreachable!("world already exists")).expect("world should load");
+
+        assert_eq!(reopened.level.seed, 1234);
+        assert_eq!(reopened.level.game_time, Duration::from_secs(90));
+        assert_eq!(reopened.level.gen_params, WorldGenParams::overworld());
+
+        world_dir.level.format_version = LEVEL_FORMAT_VERSION + 1;
+        world_dir.save_level().expect("level should save");
+
+        assert!(matches!(WorldDirectory::open(&root), Err(WorldLoadError::UnsupportedVersion(_))));
+
+        fs::remove_dir_all(root).expect("should be able to clean up");
+    }
+
+    #[test]
+    fn test_loot_seed_changes_with_location_and_tick() {
+        let mut level = LevelData::new(1234);
+        let location = BlockLocation(glm::IVec3::new(1, 2, 3));
+
+        let seed = level.loot_seed(&location);
+
+        assert_eq!(level.loot_seed(&location), seed);
+        assert_ne!(level.loot_seed(&BlockLocation(glm::IVec3::new(3, 2, 1))), seed);
+
+        level.game_time += Duration::from_secs(1);
+
+        assert_ne!(level.loot_seed(&location), seed);
+    }
+
+    #[test]
+    fn test_loot_seed_is_stable() {
+        // the first output of the reference implementation seeded with 0
+        assert_eq!(splitmix64(0), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(LevelData::new(1234).loot_seed(&BlockLocation(glm::IVec3::new(1, -2, 3))), 0x1526_1E18_FFFE_688D);
+    }
+}
@@ -4,22 +4,35 @@ use strck::IntoCk;
 use dino_plugins::{path, Identifiable};
 use crate::{args, rendering};
 use crate::application::CaptureState;
-use crate::chunks::chunk_manager::chunk_manager_update_and_request;
+use crate::block_tick::tick_blocks;
+use crate::chunks::chunk_manager::{chunk_manager_update_and_request, emit_block_changes};
+use crate::crafting::craft_pending;
+use crate::dropped_item::{despawn_dropped_items, drop_local_player_items, pick_up_dropped_items, queue_dropped_item_updates, spawn_dropped_items, tick_dropped_items};
 use crate::environment::{is_hosted, is_multiplayer_client};
 use crate::gamemode::local_player_is_gamemode_spectator;
 use crate::input::reset_mouse_manager_state;
+use crate::mining::queue_break_stage_updates;
 use crate::interact::focus_interactable_block;
+use crate::inventory::return_all_hands;
 use crate::networking::{client_acknowledge_connection_success, client_handle_kicked_by_server, client_request_chunks_from_server, client_send_block_updates, client_send_settings, client_update_position, server_broadcast_block_updates, server_broadcast_chunks, server_handle_client_chunk_reqs, server_process_client_connection_req, server_process_render_dist_update, server_request_client_settings, server_update_client_transform};
+use crate::networking::block_entity::{client_apply_block_entity_updates, server_clear_block_entity_updates, queue_block_entity_updates, server_broadcast_block_entity_updates, tick_block_entities};
+use crate::networking::dropped_item::{client_apply_dropped_item_updates, client_send_item_drops, server_accept_item_drops, server_broadcast_dropped_items, server_clear_dropped_item_updates};
+use crate::networking::inventory::{clear_pending_inventory_actions, client_apply_inventory_updates, client_send_inventory_actions, server_apply_inventory_actions, server_send_inventory_updates};
+use crate::networking::crafting::{client_send_craft_requests, server_apply_craft_requests};
 use crate::networking::keep_alive::server_send_keep_alive;
+use crate::networking::mining::{client_apply_break_stages, client_send_break_stages, server_accept_break_stages, server_broadcast_break_stages, server_clear_break_stages};
+use crate::networking::player_data::{client_apply_gamemode_updates, client_restore_player_data, client_send_gamemode_requests, server_apply_gamemode_requests, server_restore_player_data};
 use crate::physics::movement::{adjust_spectator_fly_speed, apply_camera_input, process_movement};
 use crate::physics::process_physics;
 use crate::rendering::block_outline::update_block_outline_buffer;
 use crate::rendering::camera_uniform_buffer::update_camera_uniform_buffer;
 use crate::rendering::render;
 use crate::rendering::render::{block_outline, submit_rendered_frame, world};
-use crate::workloads::shutdown::{disconnect_connected_players, save_world};
-use crate::workloads::startup::{initialize_gameplay_systems, initialize_local_player, initialize_networking, register_packets, set_window_title};
-use crate::workloads::update::{client_apply_block_updates, generate_chunks, get_generated_chunks, place_break_blocks, raycast, server_apply_block_updates, spawn_multiplayer_player, toggle_gamemode, update_world_saver};
+use crate::save::player::{autosave_players, save_all_players};
+use crate::save::snapshot::auto_snapshot_world;
+use crate::workloads::shutdown::{disconnect_connected_players, save_level, save_world};
+use crate::workloads::startup::{initialize_gameplay_systems, initialize_local_player, initialize_networking, initialize_world, register_packets, set_window_title};
+use crate::workloads::update::{advance_game_time, client_apply_block_updates, generate_chunks, get_generated_chunks, place_break_blocks, raycast, server_apply_block_updates, server_validate_block_updates, spawn_multiplayer_player, toggle_gamemode, update_world_saver};
 
 mod startup;
 mod update;
@@ -33,6 +46,7 @@ impl DinoEnginePlugin for VoxelEngine {
     fn early_startup(&self) -> Option<Workload> {
         (
             args::parse_env,
+            initialize_world.run_if(is_hosted),
             rendering::initialize.tag(path!({self}::{EnginePhase::EarlyStartup}::rendering::initialize)),
             initialize_local_player,
         )
@@ -70,6 +84,17 @@ impl DinoEnginePlugin for VoxelEngine {
             reset_mouse_manager_state,
             get_generated_chunks.run_if(is_hosted),
             update_world_saver,
+            advance_game_time.run_if(is_hosted),
+            autosave_players.run_if(is_hosted),
+            auto_snapshot_world.run_if(is_hosted),
+            tick_block_entities.run_if(is_hosted),
+            tick_blocks.run_if(is_hosted),
+            queue_block_entity_updates.run_if(is_hosted),
+            queue_break_stage_updates,
+            tick_dropped_items.run_if(is_hosted),
+            pick_up_dropped_items.run_if(is_hosted),
+            despawn_dropped_items.run_if(is_hosted),
+            queue_dropped_item_updates.run_if(is_hosted),
         )
             .into_sequential_workload()
             .into()
@@ -78,6 +103,10 @@ impl DinoEnginePlugin for VoxelEngine {
     fn networking_client_pre_recv(&self) -> Option<Workload> {
         (
             client_send_block_updates,
+            client_send_gamemode_requests,
+            // crafts and drops use whatever the actions before them moved
+            (client_send_inventory_actions, client_send_craft_requests, client_send_item_drops).into_sequential_workload(),
+            client_send_break_stages,
         ).into_workload()
             .into()
     }
@@ -89,6 +118,9 @@ impl DinoEnginePlugin for VoxelEngine {
             client_update_position,
             client_request_chunks_from_server,
             client_send_settings,
+            client_restore_player_data,
+            client_apply_gamemode_updates,
+            client_apply_inventory_updates,
         ).into_workload()
             .into()
     }
@@ -106,9 +138,15 @@ impl DinoEnginePlugin for VoxelEngine {
     fn networking_server_post_recv(&self) -> Option<Workload> {
         (
             server_broadcast_chunks,
-            server_broadcast_block_updates,
+            // rejected changes have to be taken out before the rest are broadcast, and breaks are checked against
+            // when the block started breaking before this frame's stages move it on
+            (server_validate_block_updates, server_broadcast_block_updates, server_accept_break_stages, server_broadcast_break_stages).into_sequential_workload(),
+            server_broadcast_block_entity_updates,
+            server_broadcast_dropped_items,
             server_process_client_connection_req,
             server_update_client_transform,
+            server_apply_gamemode_requests,
+            (server_apply_inventory_actions, server_apply_craft_requests, server_accept_item_drops, server_send_inventory_updates).into_sequential_workload(),
             server_request_client_settings,
             server_process_render_dist_update,
             server_handle_client_chunk_reqs,
@@ -123,7 +161,19 @@ impl DinoEnginePlugin for VoxelEngine {
             generate_chunks.run_if(is_hosted),
             server_apply_block_updates.run_if(is_hosted),
             client_apply_block_updates.run_if(is_multiplayer_client),
-            spawn_multiplayer_player,
+            server_clear_block_entity_updates.run_if(is_hosted),
+            client_apply_block_entity_updates.run_if(is_multiplayer_client),
+            spawn_multiplayer_player.run_if(is_hosted),
+            server_restore_player_data.run_if(is_hosted),
+            clear_pending_inventory_actions.run_if(is_hosted),
+            craft_pending.run_if(is_hosted),
+            server_clear_break_stages.run_if(is_hosted),
+            client_apply_break_stages.run_if(is_multiplayer_client),
+            drop_local_player_items.run_if(is_hosted),
+            spawn_dropped_items.run_if(is_hosted),
+            server_clear_dropped_item_updates.run_if(is_hosted),
+            client_apply_dropped_item_updates.run_if(is_multiplayer_client),
+            emit_block_changes,
             raycast.skip_if(local_player_is_gamemode_spectator),
             focus_interactable_block,
         ).into_sequential_workload()
@@ -173,8 +223,11 @@ impl DinoEnginePlugin for VoxelEngine {
     fn shutdown(&self) -> Option<Workload> {
         (
             // -- SHUTDOWN -- //
+            return_all_hands.run_if(is_hosted),
+            save_all_players.run_if(is_hosted),
             disconnect_connected_players.run_if(is_hosted),
             save_world,
+            save_level.run_if(is_hosted),
         ).into_sequential_workload()
             .into()
     }
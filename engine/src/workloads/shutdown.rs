@@ -1,9 +1,11 @@
 use laminar::Packet;
 use shipyard::{UniqueView, UniqueViewMut};
 use networking::{PacketRegistry, RuntimePacket};
+use crate::chunks::chunk_manager::ChunkManager;
 use crate::events::KickedByServer;
 use crate::networking::server_handler::ServerHandler;
 use crate::save::WorldSaver;
+use crate::save::world::WorldDirectory;
 
 pub fn disconnect_connected_players(server_handler: UniqueViewMut<ServerHandler>, registry: UniqueView<PacketRegistry>) {
     let tx = &server_handler.tx;
@@ -23,6 +25,13 @@ pub fn disconnect_connected_players(server_handler: UniqueViewMut<ServerHandler>
     }
 }
 
-pub fn save_world(mut world_saver: UniqueViewMut<WorldSaver>) {
+pub fn save_world(mut chunk_mgr: UniqueViewMut<ChunkManager>, mut world_saver: UniqueViewMut<WorldSaver>) {
+    chunk_mgr.unload_all(&mut world_saver);
     world_saver.save_all();
-}
\ No newline at end of file
+}
+
+pub fn save_level(world_dir: UniqueView<WorldDirectory>) {
+    if let Err(err) = world_dir.save_level() {
+        tracing::error!("Failed to save level data to {:?}: {err}", world_dir.level_path());
+    }
+}
@@ -1,33 +1,55 @@
 use glm::{U16Vec3, Vec3};
 use na::Perspective3;
 use shipyard::{AllStoragesView, AllStoragesViewMut, UniqueOrDefaultViewMut, UniqueView};
-use game::block::BlockTy;
 use game::inventory::Inventory;
-use game::item::ItemType;
 use networking::PacketRegistry;
 use crate::application::pause::IsPaused;
+use crate::args::WorldOptions;
 use crate::block_bar_focus::BlockBarFocus;
+use crate::block_tick::BlockTicker;
 use crate::camera::Camera;
 use crate::chunks::chunk_manager::ChunkManager;
-use crate::components::{Entity, GravityAffected, Health, HeldBlock, Hitbox, IsOnGround, LocalPlayer, Mana, Player, PlayerSpeed, SpectatorSpeed, Transform, Velocity};
+use crate::components::{Entity, GravityAffected, HeldBlock, Hitbox, IsOnGround, LocalPlayer, Player, PlayerSpeed, SpectatorSpeed, Transform, Velocity};
+use crate::crafting::{CraftingGrid, PendingCraft};
+use crate::dropped_item::PendingItemDrops;
 use crate::environment::{Environment, is_hosted, is_multiplayer_client};
-use crate::gamemode::Gamemode;
+use crate::identity::PlayerIdentity;
 use crate::interact::CurrentlyFocusedBlock;
-use crate::inventory::PlayerInventory;
+use crate::inventory::{InventoryHand, PendingInventoryActions, PendingPlayerDrops, PlayerInventory};
 use crate::looking_at_block::LookingAtBlock;
+use crate::mining::{BreakingBlocks, BreakProgress};
 use crate::networking::server_connection::ServerConnection;
 use crate::networking::server_handler::ServerHandler;
 use crate::render_distance::RenderDistance;
 use crate::rendering::graphics_context::GraphicsContext;
-use crate::save::WorldSaver;
+use crate::save::{ChunkStorageKind, FakeSaver, WorldSaver};
+use crate::save::player::{apply_player_data, PlayerData, PlayerDataStore};
+use crate::save::world::WorldDirectory;
 use crate::world_gen::WorldGenerator;
 
+pub fn initialize_world(options: UniqueView<WorldOptions>, storages: AllStoragesView) {
+    let world_dir = WorldDirectory::open_or_create(&options.path, || options.seed.unwrap_or_else(rand::random))
+        .unwrap_or_else(|err| panic!("failed to load world at {:?}: {err}", options.path));
+
+    let player_store = PlayerDataStore::new(world_dir.players_path())
+        .expect("failed to open player data store");
+
+    storages.add_unique(world_dir);
+    storages.add_unique(player_store);
+    storages.add_unique(options.auto_snapshots);
+}
+
 pub fn initialize_local_player(mut storages: AllStoragesViewMut) {
     let aspect = storages
         .borrow::<UniqueView<GraphicsContext>>()
         .expect("unable to borrow graphics context")
         .aspect();
 
+    // multiplayer clients don't have a world directory, so they just use the default spawn
+    let spawn = storages
+        .borrow::<UniqueView<WorldDirectory>>()
+        .map_or(Vec3::new(0.5, 20.0, 0.5), |world_dir| world_dir.level.spawn);
+
     let id = storages.add_entity((
         LocalPlayer,
         Player,
@@ -35,7 +57,7 @@ pub fn initialize_local_player(mut storages: AllStoragesViewMut) {
         GravityAffected,
         IsOnGround::default(),
         Transform {
-            position: Vec3::new(0.5, 20.0, 0.5),
+            position: spawn,
             .. Default::default()
         },
         Velocity::default(),
@@ -53,17 +75,25 @@ pub fn initialize_local_player(mut storages: AllStoragesViewMut) {
     ));
     
     storages.add_component(id, LookingAtBlock(None)); // TODO: fix a better way for >10 components
-    storages.add_component(id, Gamemode::Survival);
+    storages.add_component(id, BreakProgress::default());
     storages.add_component(id, SpectatorSpeed::default()); // TODO: should this always be on the player or only added when switching gamemodes?
     storages.add_component(id, RenderDistance(U16Vec3::new(3,1,3)));
-    storages.add_component(id, Health { curr: 9.0, max: 10.0 });
-    storages.add_component(id, Mana { curr: 6.0, max: 10.0 });
+    storages.add_component(id, InventoryHand::default());
+    storages.add_component(id, CraftingGrid::default());
+
+    let identity = *storages
+        .borrow::<UniqueView<PlayerIdentity>>()
+        .expect("identity should've been loaded with the args");
 
-    let mut inv = PlayerInventory::new(18.try_into().expect("18 is nonzero"));
+    storages.add_component(id, identity);
 
-    inv.try_insert(ItemType::Crate.default_item().with_count(5.try_into().expect("should be nonzero")));
+    // only hosts have a player store, clients get their data sent from the server after connecting
+    let saved = storages
+        .borrow::<UniqueView<PlayerDataStore>>()
+        .ok()
+        .and_then(|store| store.load(&identity));
 
-    storages.add_component(id, inv);
+    storages.run_with_data(apply_player_data, (id, saved.unwrap_or_else(|| PlayerData::starting(spawn))));
 }
 
 pub fn initialize_gameplay_systems(storages: AllStoragesView) {
@@ -75,14 +105,39 @@ pub fn initialize_gameplay_systems(storages: AllStoragesView) {
 
     storages.add_unique(IsPaused::new(true));
     storages.add_unique(ChunkManager::new(6, Some(render_dist)));
-    storages.add_unique(WorldGenerator::new(50));
-    storages.add_unique(WorldSaver::default());
+
+    if storages.run(is_hosted) {
+        let world_dir = storages
+            .borrow::<UniqueView<WorldDirectory>>()
+            .expect("world should've been loaded in early startup");
+
+        let level = &world_dir.level;
+
+        // worlds created before region files, or converted with protovox-world, keep their chunks in separate files
+        let chunk_store = ChunkStorageKind::detect(&world_dir.chunks_path())
+            .unwrap_or_default()
+            .open(world_dir.chunks_path())
+            .expect("failed to open chunk store");
+
+        storages.add_unique(WorldGenerator::new(level.seed, level.gen_params.clone(), level.gen_splines.clone()));
+        storages.add_unique(WorldSaver::new(WorldSaver::DEFAULT_CACHE_TIME, chunk_store));
+    } else {
+        // multiplayer clients don't own the world, so there's nothing to save
+        storages.add_unique(WorldSaver::new(WorldSaver::DEFAULT_CACHE_TIME, FakeSaver));
+    }
+
     storages.add_unique(BlockBarFocus::new(inventory.size()));
     storages.add_unique(CurrentlyFocusedBlock(None));
     storages.add_unique(HeldBlock(0));
+    storages.add_unique(PendingCraft::default());
+    storages.add_unique(BreakingBlocks::default());
+    storages.add_unique(PendingItemDrops::default());
+    storages.add_unique(PendingInventoryActions::default());
+    storages.add_unique(PendingPlayerDrops::default());
+    storages.add_unique(BlockTicker::default());
 }
 
-pub fn initialize_networking(env: UniqueView<Environment>, registry: UniqueView<PacketRegistry>, storages: AllStoragesView) {
+pub fn initialize_networking(env: UniqueView<Environment>, registry: UniqueView<PacketRegistry>, identity: UniqueView<PlayerIdentity>, storages: AllStoragesView) {
     if storages.run(is_hosted) {
         storages.add_unique(ServerHandler::new(None));
     } else if storages.run(is_multiplayer_client) {
@@ -94,7 +149,7 @@ pub fn initialize_networking(env: UniqueView<Environment>, registry: UniqueView<
             .identifier_of()
             .expect("should be registered");
 
-        storages.add_unique(ServerConnection::bind(addr, connection_request_ser_id));
+        storages.add_unique(ServerConnection::bind(addr, connection_request_ser_id, *identity));
     }
 }
 
@@ -104,6 +159,8 @@ pub fn register_packets(mut registry: UniqueOrDefaultViewMut<PacketRegistry>) {
     registry.register::<ChunkGenRequestEvent, false, false>();
     registry.register::<ChunkGenEvent, true, false>();
     registry.register::<BlockUpdateEvent, false, true>();
+    registry.register::<HeldSlotUpdate, false, false>();
+    registry.register::<BlockEntityUpdateEvent, false, true>();
     registry.register::<ClientInformationRequestEvent, false, false>();
     registry.register::<ClientInformationUpdateEvent, false, false>();
     registry.register::<ClientSettingsRequestEvent, false, false>();
@@ -111,6 +168,15 @@ pub fn register_packets(mut registry: UniqueOrDefaultViewMut<PacketRegistry>) {
     registry.register::<ConnectionRequest, false, false>();
     registry.register::<ConnectionSuccess, false, false>();
     registry.register::<ClientTransformUpdate, false, false>();
+    registry.register::<RestorePlayerData, false, false>();
+    registry.register::<GamemodeUpdate, false, false>();
+    registry.register::<InventoryActionRequest, false, true>();
+    registry.register::<PlayerInventoryUpdate, false, false>();
+    registry.register::<CraftRequest, false, true>();
+    registry.register::<BlockBreakStage, false, true>();
+    registry.register::<PlayerBreakStage, false, false>();
+    registry.register::<ItemDropRequest, false, true>();
+    registry.register::<DroppedItemUpdate, false, false>();
     registry.register::<ClientChunkRequest, false, true>();
     registry.register::<KeepAlive, false, false>();
     registry.register::<KickedByServer, false, false>();
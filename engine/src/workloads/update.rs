@@ -1,74 +1,160 @@
 use glm::Vec3;
 use crate::chunks::chunk_manager::ChunkManager;
-use shipyard::{UniqueView, UniqueViewMut, ViewMut, IntoIter, View, EntitiesViewMut, EntitiesView, IntoWithId, Remove, UniqueOrDefaultViewMut};
-use strum::EnumCount;
-use game::block::{Block, BlockTy};
+use shipyard::{UniqueView, UniqueViewMut, ViewMut, IntoIter, View, EntitiesViewMut, Get, IntoWithId, Remove, UniqueOrDefaultViewMut};
+use game::block::Block;
+use game::block::loot::{BreakContext, Breaker};
 use game::inventory::Inventory;
-use game::item::{ItemStack, ItemType};
 use game::location::BlockLocation;
+use crate::application::delta_time::LastDeltaTime;
 use crate::camera::Camera;
 use crate::chunks::raycast::{RaycastHit, RaycastResult};
+use crate::crafting::CraftingGrid;
 use crate::components::{Entity, GravityAffected, HeldBlock, Hitbox, IsOnGround, LocalPlayer, Player, PlayerSpeed, SpectatorSpeed, Transform, Velocity};
-use crate::events::{BlockUpdateEvent, ChunkGenEvent, ChunkGenRequestEvent, ClientInformationRequestEvent};
+use crate::dropped_item::{DroppedItem, ItemDrop, PendingItemDrops};
+use crate::environment::{is_multiplayer_client, Environment};
+use crate::events::{BlockChangeCause, BlockUpdateEvent, HeldSlotUpdate, ChunkGenEvent, ChunkGenRequestEvent, ClientInformationRequestEvent, GamemodeUpdate};
 use crate::events::event_bus::EventBus;
-use crate::gamemode::Gamemode;
+use crate::gamemode::{switch_local_gamemode, Gamemode};
+use crate::identity::PlayerIdentity;
 use crate::input::action_map::Action;
 use crate::input::InputManager;
-use crate::inventory::PlayerInventory;
+use crate::inventory::{InventoryHand, PlayerInventory};
 use crate::last_world_interaction::LastWorldInteraction;
 use crate::looking_at_block::LookingAtBlock;
+use crate::mining::{BreakProgress, BreakStarted};
+use crate::networking::MAX_REACH;
 use crate::physics::{collision};
-use crate::save::WorldSaver;
+use crate::save::{ChunkLoadError, WorldSaver};
+use crate::save::player::PlayerDataViewMut;
+use crate::save::world::WorldDirectory;
 use crate::world_gen::WorldGenerator;
 
+/// The host switches straight away, while multiplayer clients ask the server and switch once it answers.
 pub fn toggle_gamemode(
     input: UniqueView<InputManager>,
+    env: UniqueView<Environment>,
     v_local_player: View<LocalPlayer>,
-    mut vm_looking_at_block: ViewMut<LookingAtBlock>,
-    mut vm_gamemode: ViewMut<Gamemode>,
-    mut vm_spec_speed: ViewMut<SpectatorSpeed>,
-    mut vm_velocity: ViewMut<Velocity>,
-    mut vm_hitbox: ViewMut<Hitbox>,
-    mut vm_gravity_affected: ViewMut<GravityAffected>,
-    entities: EntitiesView,
+    mut vm_gamemode_update: ViewMut<GamemodeUpdate>,
+    mut player_data: PlayerDataViewMut,
+    mut reset: (ViewMut<LookingAtBlock>, ViewMut<SpectatorSpeed>, ViewMut<Velocity>),
 ) {
     if !input.just_pressed().get_action(Action::ToggleGamemode) {
         return;
     }
-    
-    let (id, (_, gamemode, velocity, look_at, spec_speed)) = (&v_local_player, &mut vm_gamemode, &mut vm_velocity, &mut vm_looking_at_block, &mut vm_spec_speed).iter().with_id()
+
+    let (id, (_, gamemode)) = (&v_local_player, &player_data.gamemode).iter().with_id()
         .next()
-        .expect("local player should have gamemode and velocity");
-    
-    match gamemode {
-        Gamemode::Survival => {
-            *gamemode = Gamemode::Spectator;
-            spec_speed.curr_speed = SpectatorSpeed::default().curr_speed;
-            look_at.0 = None;
-            
-            vm_gravity_affected.remove(id);
-            vm_hitbox.remove(id);
-        },
-        Gamemode::Spectator => {
-            *gamemode = Gamemode::Survival;
-            
-            entities.add_component(id, &mut vm_hitbox, Hitbox::default_player());
-            entities.add_component(id, &mut vm_gravity_affected, GravityAffected);
-        },
+        .expect("local player should have gamemode");
+
+    let toggled = match gamemode {
+        Gamemode::Survival => Gamemode::Spectator,
+        Gamemode::Spectator => Gamemode::Survival,
     };
 
-    *velocity = Velocity::default();
+    if is_multiplayer_client(env) {
+        player_data.entities.add_component(id, &mut vm_gamemode_update, GamemodeUpdate(toggled));
+    } else {
+        switch_local_gamemode(id, toggled, &mut player_data, &mut reset);
+    }
 }
 
 pub fn update_world_saver(mut world_saver: UniqueViewMut<WorldSaver>) {
     world_saver.process();
 }
 
-pub fn server_apply_block_updates(mut world: UniqueViewMut<ChunkManager>, mut vm_block_update_evt_bus: ViewMut<EventBus<BlockUpdateEvent>>, mut vm_block_update_evt: ViewMut<BlockUpdateEvent>) {
-    for mut bus in vm_block_update_evt_bus.drain() {
+pub fn advance_game_time(delta_time: UniqueView<LastDeltaTime>, mut world_dir: UniqueViewMut<WorldDirectory>) {
+    world_dir.level.game_time += delta_time.0;
+}
+
+/// Clients can only change blocks within reach, and only place blocks they have the item for, which is taken from the server's
+/// copy of their inventory. Breaking has to have started long enough ago for the block's break time with their held tool,
+/// and spectators can't change blocks at all. Rejected changes aren't broadcast, and the block that's really there is sent instead.
+pub fn server_validate_block_updates(
+    world: UniqueView<ChunkManager>,
+    (v_gamemode, v_transform, v_held_slot): (View<Gamemode>, View<Transform>, View<HeldSlotUpdate>),
+    mut vm_inventory: ViewMut<PlayerInventory>,
+    mut vm_break_started: ViewMut<BreakStarted>,
+    mut vm_block_update_evt_bus: ViewMut<EventBus<BlockUpdateEvent>>,
+    mut entities: EntitiesViewMut,
+    mut vm_block_update_evt: ViewMut<BlockUpdateEvent>,
+) {
+    let mut rejected = Vec::new();
+
+    for (id, bus) in (&mut vm_block_update_evt_bus).iter().with_id() {
+        let spectating = v_gamemode.get(id).is_ok_and(|gamemode| *gamemode == Gamemode::Spectator);
+        let position = v_transform.get(id).ok().map(|transform| transform.position);
+        let mut inventory = (&mut vm_inventory).get(id).ok();
+
+        let tool = inventory
+            .as_ref()
+            .zip(v_held_slot.get(id).ok())
+            .and_then(|(inventory, HeldSlotUpdate(held))| inventory.as_slice().get(*held)?.as_ref())
+            .and_then(|stack| stack.item.ty.tool());
+
+        bus.0.retain(|BlockUpdateEvent(loc, new_block)| {
+            let in_reach = position.is_some_and(|position| glm::distance(&loc.0.cast::<f32>(), &position) <= MAX_REACH);
+
+            let allowed = !spectating && in_reach && if *new_block == Block::AIR {
+                let break_time = world.get_block_ref(loc).map_or(Default::default(), |block| block.definition().break_time(tool));
+                let broken = BreakStarted::allows(vm_break_started.get(id).ok(), loc, break_time);
+
+                if broken {
+                    vm_break_started.remove(id);
+                }
+
+                broken
+            } else {
+                inventory.as_mut().is_some_and(|inventory| inventory.take_placing(*new_block).is_some())
+            };
+
+            if !allowed {
+                tracing::debug!("Rejected placing {new_block:?} at {loc:?} from client {id:?}");
+                rejected.push(loc.clone());
+            }
+
+            allowed
+        });
+    }
+
+    for loc in rejected {
+        if let Some(&block) = world.get_block_ref(&loc) {
+            entities.add_entity(&mut vm_block_update_evt, BlockUpdateEvent(loc, block));
+        }
+    }
+}
+
+/// Loot from the blocks clients replace is rolled here, with the tool in their held slot and a seed they don't know.
+pub fn server_apply_block_updates(
+    mut world: UniqueViewMut<ChunkManager>,
+    world_dir: UniqueView<WorldDirectory>,
+    v_identity: View<PlayerIdentity>,
+    v_held_slot: View<HeldSlotUpdate>,
+    v_inventory: View<PlayerInventory>,
+    mut vm_block_update_evt_bus: ViewMut<EventBus<BlockUpdateEvent>>,
+    mut vm_block_update_evt: ViewMut<BlockUpdateEvent>,
+    mut drops: UniqueViewMut<PendingItemDrops>,
+) {
+    for (id, mut bus) in vm_block_update_evt_bus.drain().with_id() {
+        let (cause, breaker) = v_identity
+            .get(id)
+            .map_or((BlockChangeCause::Network, Breaker::World), |identity| (BlockChangeCause::Player(identity.0), Breaker::Player(identity.0)));
+
+        let tool = (&v_held_slot, &v_inventory)
+            .get(id)
+            .ok()
+            .and_then(|(HeldSlotUpdate(held), inventory)| inventory.as_slice().get(*held)?.as_ref())
+            .and_then(|stack| stack.item.ty.tool());
+
         for BlockUpdateEvent(loc, new_block) in bus.0.drain(..) {
-            if world.modify_block(&loc, new_block).is_err() {
+            let Ok(replaced) = world.modify_block(&loc, new_block, cause) else {
                 tracing::error!("Location from block update wasn't loaded");
+                continue;
+            };
+
+            let mut ctx = BreakContext::new(breaker, tool, loc.clone(), world_dir.level.loot_seed(&loc));
+
+            for stack in replaced.on_break(&mut ctx) {
+                drops.push(ItemDrop::from_block(stack, &loc));
             }
         }
     }
@@ -78,7 +164,7 @@ pub fn server_apply_block_updates(mut world: UniqueViewMut<ChunkManager>, mut vm
 
 pub fn client_apply_block_updates(mut world: UniqueViewMut<ChunkManager>, mut vm_block_update_evt_bus: ViewMut<BlockUpdateEvent>) {
     for BlockUpdateEvent(loc, new_block) in vm_block_update_evt_bus.drain() {
-        if world.modify_block(&loc, new_block).is_err() {
+        if world.modify_block(&loc, new_block, BlockChangeCause::Network).is_err() {
             tracing::error!("Location from block update wasn't loaded");
         }
     }
@@ -104,20 +190,20 @@ pub fn raycast(chunk_mgr: UniqueView<ChunkManager>, v_local_player: View<LocalPl
 
 pub fn place_break_blocks(
     mut chunk_mgr: UniqueViewMut<ChunkManager>,
-    v_local_player: View<LocalPlayer>,
-    v_looking_at_block: View<LookingAtBlock>,
-    held: UniqueView<HeldBlock>,
-    input: UniqueView<InputManager>,
+    (v_local_player, v_looking_at_block, v_identity): (View<LocalPlayer>, View<LookingAtBlock>, View<PlayerIdentity>),
+    (held, input, delta_time, world_dir): (UniqueView<HeldBlock>, UniqueView<InputManager>, UniqueView<LastDeltaTime>, Option<UniqueView<WorldDirectory>>),
     mut last_world_interaction: UniqueOrDefaultViewMut<LastWorldInteraction>,
+    mut vm_break_progress: ViewMut<BreakProgress>,
 
     // to ensure we're placing at a valid spot
-    (v_entity, v_transform): (View<Entity>, View<Transform>),
+    (v_entity, v_transform, v_dropped_item): (View<Entity>, View<Transform>, View<DroppedItem>),
     v_hitbox: View<Hitbox>,
     mut vm_inventory: ViewMut<PlayerInventory>,
 
-    (mut entities, mut vm_block_update_evts): (EntitiesViewMut, ViewMut<BlockUpdateEvent>)
+    (mut entities, mut vm_block_update_evts): (EntitiesViewMut, ViewMut<BlockUpdateEvent>),
+    mut drops: UniqueViewMut<PendingItemDrops>,
 ) {
-    let (_, look_at, inventory) = (&v_local_player, &v_looking_at_block, &mut vm_inventory).iter()
+    let (_, look_at, inventory, progress, identity) = (&v_local_player, &v_looking_at_block, &mut vm_inventory, &mut vm_break_progress, &v_identity).iter()
         .next()
         .expect("local player didn't have LookingAtBlock & HeldBlock");
     
@@ -128,51 +214,72 @@ pub fn place_break_blocks(
         },
         ..
     }) = &look_at.0 else {
+        progress.reset();
         return;
     };
 
     let mut should_place = input.just_pressed().get_action(Action::PlaceBlock);
-    let mut should_break = input.just_pressed().get_action(Action::BreakBlock);
 
     if last_world_interaction.cooldown_passed() {
         should_place |= input.pressed().get_action(Action::PlaceBlock);
-        should_break |= input.pressed().get_action(Action::BreakBlock);
     }
 
-    let mut update_block = |world: &mut ChunkManager, pos: BlockLocation, block: Block, inventory: &mut PlayerInventory| {
+    let tool = inventory.as_slice()
+        .get(held.0)
+        .and_then(Option::as_ref)
+        .and_then(|stack| stack.item.ty.tool());
+
+    // after breaking a block, the next one only starts breaking once the cooldown has passed
+    let breaking = input.just_pressed().get_action(Action::BreakBlock)
+        || input.pressed().get_action(Action::BreakBlock) && last_world_interaction.cooldown_passed();
+
+    let should_break = match chunk_mgr.get_block_ref(location) {
+        Some(block) if breaking => progress.advance(location, delta_time.0, block.definition().break_time(tool)),
+        _ => {
+            progress.reset();
+            false
+        }
+    };
+
+    let mut update_block = |world: &mut ChunkManager, pos: BlockLocation, block: Block| {
         last_world_interaction.reset_cooldown();
 
-        entities.add_entity(&mut vm_block_update_evts, BlockUpdateEvent(pos.clone(), block.clone()));
+        entities.add_entity(&mut vm_block_update_evts, BlockUpdateEvent(pos.clone(), block));
         
-        let old = world.modify_block(&pos, block).expect("chunk shouldn't have unloaded so quickly");
+        let replaced = world.modify_block(&pos, block, BlockChangeCause::Player(identity.0)).expect("chunk shouldn't have unloaded so quickly");
 
-        let drops = old.on_break();
+        // only where the world is hosted, since the server rolls the loot of blocks clients break
+        if let Some(world_dir) = &world_dir {
+            let mut ctx = BreakContext::new(Breaker::Player(identity.0), tool, pos.clone(), world_dir.level.loot_seed(&pos));
 
-        // TODO: deal with overflow
-        let _residual = inventory.try_insert_many(drops);
+            for stack in replaced.on_break(&mut ctx) {
+                drops.push(ItemDrop::from_block(stack, &pos));
+            }
+        }
     };
 
+    if should_break {
+        progress.reset();
+    }
+
     if should_place && should_break {
         if let Some(ft) = face {
-            let block = inventory.try_get_place_at(held.0, location.clone(), *ft).unwrap_or(Block::Air);
+            let block = inventory.try_get_place_at(held.0, location.clone(), *ft).unwrap_or(Block::AIR);
 
-            update_block(&mut chunk_mgr, location.clone(), block, inventory);
+            update_block(&mut chunk_mgr, location.clone(), block);
         }
     } else if should_break {
-        update_block(&mut chunk_mgr, location.clone(), Block::Air, inventory);
-    } else if should_place {
-        if let Some(ft) = face {
-            // TODO: impl Add<IVec3> for BlockLocation
-            let adj = BlockLocation(location.0 + ft.as_vector());
+        update_block(&mut chunk_mgr, location.clone(), Block::AIR);
+    } else if should_place && let Some(ft) = face {
+        // TODO: impl Add<IVec3> for BlockLocation
+        let adj = BlockLocation(location.0 + ft.as_vector());
 
-            if chunk_mgr.get_block_ref(&adj) == Some(&Block::Air) {
-                let (min, max) = adj.get_aabb_bounds();
+        if chunk_mgr.get_block_ref(&adj).is_some_and(Block::is_replaceable) {
+            let (min, max) = adj.get_aabb_bounds();
 
-                if collision::collides_with_any_entity(min, max, v_entity, v_transform, v_hitbox).is_none() {
-                    if let Some(block) = inventory.try_get_place_at(held.0, adj.clone(), *ft) {
-                        update_block(&mut chunk_mgr, adj, block, inventory);
-                    }
-                }
+            if collision::collides_with_any_entity(min, max, v_entity, v_transform, v_hitbox, v_dropped_item).is_none()
+                && let Some(block) = inventory.try_get_place_at(held.0, adj.clone(), *ft) {
+                update_block(&mut chunk_mgr, adj, block);
             }
         }
     }
@@ -191,9 +298,11 @@ pub fn spawn_multiplayer_player(
     mut vm_transform: ViewMut<Transform>,
     mut vm_velocity: ViewMut<Velocity>,
     mut vm_player_speed: ViewMut<PlayerSpeed>,
-    mut vm_hitbox: ViewMut<Hitbox>,
+    (mut vm_hitbox, mut vm_hand, mut vm_grid, world_dir): (ViewMut<Hitbox>, ViewMut<InventoryHand>, ViewMut<CraftingGrid>, UniqueView<WorldDirectory>),
 ) {
     for (id, _) in vm_info_req_evt.drain().with_id() {
+        entities.add_component(id, (&mut vm_hand, &mut vm_grid), (InventoryHand::default(), CraftingGrid::default()));
+
         entities.add_component(id,
             (
                 &mut vm_player,
@@ -211,7 +320,7 @@ pub fn spawn_multiplayer_player(
                 GravityAffected,
                 IsOnGround::default(),
                 Transform {
-                    position: Vec3::new(0.5, 60.0, 0.5),
+                    position: world_dir.level.spawn,
                     .. Default::default()
                 },
                 Velocity::default(),
@@ -241,10 +350,17 @@ pub fn get_generated_chunks(world_gen: UniqueView<WorldGenerator>, mut vm_entiti
 
 pub fn generate_chunks(mut reqs: ViewMut<ChunkGenRequestEvent>, world_generator: UniqueView<WorldGenerator>, mut world_saver: UniqueViewMut<WorldSaver>) {
     for req in reqs.drain() {
-        if let Some(cache) = world_saver.try_get(&req.0) {
-            world_generator.send(cache.data);
-        } else {
-            world_generator.spawn_generate_task(req.0, world_generator.splines.clone(), world_generator.params.clone());
+        match world_saver.try_get(&req.0) {
+            Ok(cache) => world_generator.send(cache.data),
+            // it'll be requested again, and generating it now could overwrite the saved chunk
+            Err(ChunkLoadError::Io(err)) => tracing::error!("Failed to load chunk at {:?}: {err}", req.0),
+            Err(err) => {
+                if let ChunkLoadError::Corrupt(_) = err {
+                    tracing::warn!("Regenerating corrupt chunk at {:?}", req.0);
+                }
+
+                world_generator.spawn_generate_task(req.0, world_generator.splines.clone(), world_generator.params.clone());
+            }
         }
     }
 }
\ No newline at end of file
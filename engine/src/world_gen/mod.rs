@@ -9,7 +9,9 @@ use glm::TVec3;
 use game::{block::Block, chunk::{data::ChunkData, location::ChunkLocation, pos::ChunkPos, CHUNK_SIZE}};
 use noise::{NoiseFn, Perlin};
 use rayon::{ThreadPool, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
 use shipyard::Unique;
+use game::chunk::heightmap::Heightmap;
 use game::location::BlockLocation;
 use splines::easings::InOutSine;
 use splines::Spline;
@@ -28,13 +30,23 @@ pub struct WorldGenerator {
     chunk_output: (Sender<ChunkGenEvent>, Receiver<ChunkGenEvent>),
 }
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct WorldGenSplines {
     pub continentalness: SineSpline,
     pub erosion: SineSpline,
     pub peaks_valleys: SineSpline,
 }
 
+impl WorldGenSplines {
+    pub fn overworld() -> Self {
+        Self {
+            continentalness: Spline::new([[-1.0, -1.0], [-0.9279977, -0.90286434], [-0.26820922, -0.8263215], [-0.044113815, -0.14479148], [0.763953, -0.08767879], [0.95565224, 0.9540222], [1.0, 1.0]]),
+            erosion: Spline::new([[-1.0, 1.0], [-0.83050734, 0.4721343], [-0.5038637, 0.26844186], [-0.3988908, 0.43217272], [-0.2064119, -0.816993], [0.5861441, -0.90852606], [0.636498, -0.43075633], [0.7577101, -0.44334638], [0.798712, -0.89013314], [1.0, -1.0]]),
+            peaks_valleys: Spline::new([[-1.0, -1.0], [-0.9223045, -0.8987539], [-0.5608352, -0.8535681], [-0.3662839, -0.24826753], [0.23613429, -0.102552295], [0.767043, 0.8733756], [1.0, 1.0]]),
+        }
+    }
+}
+
 pub struct VeinSpawner {
     offset: TVec3<f64>,
     scale: f64,
@@ -72,7 +84,7 @@ impl VeinThreshold {
 }
 
 impl WorldGenerator {
-    pub fn new(seed: u32) -> Self {
+    pub fn new(seed: u32, params: WorldGenParams, splines: WorldGenSplines) -> Self {
         let thread_pool = ThreadPoolBuilder::new()
             .num_threads(8)
             .build()
@@ -81,26 +93,8 @@ impl WorldGenerator {
         let chunk_output = crossbeam::channel::unbounded::<ChunkGenEvent>();
         let perlin_noise = Arc::new(Perlin::new(seed));
 
-        let params = WorldGenParams {
-            continentalness_scale: 0.00125,
-            erosion_scale: 0.002,
-            peaks_valleys_scale: 0.0125,
-            c_start: -10.0,
-            c_end: 175.0,
-            e_start: -0.5,
-            e_end: 1.0,
-            pv_start: 0.0,
-            pv_end: 35.0,
-        };
-
-        let splines = WorldGenSplines {
-            continentalness: Spline::new([[-1.0, -1.0], [-0.9279977, -0.90286434], [-0.26820922, -0.8263215], [-0.044113815, -0.14479148], [0.763953, -0.08767879], [0.95565224, 0.9540222], [1.0, 1.0]]),
-            erosion: Spline::new([[-1.0, 1.0], [-0.83050734, 0.4721343], [-0.5038637, 0.26844186], [-0.3988908, 0.43217272], [-0.2064119, -0.816993], [0.5861441, -0.90852606], [0.636498, -0.43075633], [0.7577101, -0.44334638], [0.798712, -0.89013314], [1.0, -1.0]]),
-            peaks_valleys: Spline::new([[-1.0, -1.0], [-0.9223045, -0.8987539], [-0.5608352, -0.8535681], [-0.3662839, -0.24826753], [0.23613429, -0.102552295], [0.767043, 0.8733756], [1.0, 1.0]]),
-        };
-
         let spawners = vec![
-            VeinSpawner::new(0.15, 0.0, VeinThreshold::Single(-0.5), Block::Cobblestone)
+            VeinSpawner::new(0.15, 0.0, VeinThreshold::Single(-0.5), Block::COBBLESTONE)
         ];
 
         Self {
@@ -133,13 +127,42 @@ impl WorldGenerator {
         );
     }
     
-    pub fn send(&self, chunk_data: ChunkData) {
+    /// Sends a chunk that was loaded rather than generated, along with where world gen would've put its terrain.
+    pub fn send(&self, mut chunk_data: ChunkData) {
         let sender = self.chunk_output.0.clone();
-        
-        self.thread_pool.spawn(move ||
+        let perlin = self.perlin_noise.clone();
+        let splines = self.splines.clone();
+        let params = self.params.clone();
+
+        self.thread_pool.spawn(move || {
+            chunk_data.set_surface(Self::surface(&perlin, &splines, &params, &chunk_data.location));
+
             sender.send(ChunkGenEvent(chunk_data))
                 .expect("channel should not have disconnected")
-        );
+        });
+    }
+
+    fn column_height(perlin: &Perlin, splines: &WorldGenSplines, params: &WorldGenParams, xf: f64, zf: f64) -> i32 {
+        let noise_range = -1.0..=1.0;
+
+        // Sample the Perlin noise at world coordinates
+        let continentalness_noise = perlin.get([xf * params.continentalness_scale, zf * params.continentalness_scale]) as f32;
+        let erosion_noise = perlin.get([xf * params.erosion_scale, zf * params.erosion_scale]) as f32;
+        let peaks_and_valleys_noise = perlin.get([xf * params.peaks_valleys_scale, zf * params.peaks_valleys_scale]) as f32;
+
+        let continentalness = splines.continentalness.sample(continentalness_noise);
+        let erosion = splines.erosion.sample(erosion_noise);
+        let peaks_and_valleys = splines.peaks_valleys.sample(peaks_and_valleys_noise);
+
+        let height = remap(noise_range.clone(), params.c_start..=params.c_end, continentalness) + remap(noise_range.clone(), params.e_start..=params.e_end, erosion) * remap(noise_range, params.pv_start..=params.pv_end, peaks_and_valleys);
+
+        height as i32
+    }
+
+    fn surface(perlin: &Perlin, splines: &WorldGenSplines, params: &WorldGenParams, chunk: &ChunkLocation) -> Heightmap {
+        let chunk_start = BlockLocation::from(chunk);
+
+        Heightmap::from_fn(|x, z| Self::column_height(perlin, splines, params, (x as i32 + chunk_start.0.x) as f64, (z as i32 + chunk_start.0.z) as f64))
     }
 
     fn generate_chunk(perlin: Arc<Perlin>, splines: Arc<WorldGenSplines>, chunk: ChunkLocation, params: Arc<WorldGenParams>, veins: Arc<[VeinSpawner]>) -> ChunkGenEvent {
@@ -151,44 +174,39 @@ impl WorldGenerator {
 
         let water_level = remap(noise_range.clone(), params.c_start..=params.c_end, -0.175) as i32;
 
+        let surface = Self::surface(&perlin, &splines, &params, &chunk);
+
         for x in 0..CHUNK_SIZE.x {
             for z in 0..CHUNK_SIZE.z {
                 let xf = (x as i32 + chunk_start.0.x) as f64;
                 let zf = (z as i32 + chunk_start.0.z) as f64;
 
-                // Sample the Perlin noise at world coordinates
-                let continentalness_noise = perlin.get([xf * params.continentalness_scale, zf * params.continentalness_scale]) as f32;
-                let erosion_noise = perlin.get([xf * params.erosion_scale, zf * params.erosion_scale]) as f32;
-                let peaks_and_valleys_noise = perlin.get([xf * params.peaks_valleys_scale, zf * params.peaks_valleys_scale]) as f32;
-
-                let continentalness = splines.continentalness.sample(continentalness_noise);
-                let erosion = splines.erosion.sample(erosion_noise);
-                let peaks_and_valleys = splines.peaks_valleys.sample(peaks_and_valleys_noise);
-
-                let height = remap(noise_range.clone(), params.c_start..=params.c_end, continentalness) + remap(noise_range.clone(), params.e_start..=params.e_end, erosion) * remap(noise_range.clone(), params.pv_start..=params.pv_end, peaks_and_valleys);
+                let height = surface.get(x, z);
 
                 for y in 0..CHUNK_SIZE.y {
                     let pos = ChunkPos::new(x, y, z).expect("valid");
 
                     let block_y = chunk_start.0.y + y as i32; // could use BlockLocation::from_chunk_parts, but this is faster
 
-                    match height as i32 - block_y {
+                    match height - block_y {
                         0 => match block_y.cmp(&water_level) {
-                            Ordering::Greater | Ordering::Equal => *out.block_mut(pos) = Block::Grass,
-                            Ordering::Less => *out.block_mut(pos) = Block::Dirt,
+                            Ordering::Greater | Ordering::Equal => *out.block_mut(pos) = Block::GRASS,
+                            Ordering::Less => *out.block_mut(pos) = Block::DIRT,
                         }
-                        1..4 => *out.block_mut(pos) = Block::Dirt,
+                        1..4 => *out.block_mut(pos) = Block::DIRT,
                         y @ 4.. => *out.block_mut(pos) = veins
                             .iter()
                             .find(|vs| vs.sample(&perlin, TVec3::new(xf, y as _, zf)))
-                            .map_or(Block::Stone, |vs| vs.block.clone()),
-                        _ if block_y <= water_level => *out.block_mut(pos) = Block::Water,
+                            .map_or(Block::STONE, |vs| vs.block),
+                        _ if block_y <= water_level => *out.block_mut(pos) = Block::WATER,
                         _ => {}, // AIR
                     }
                 }
             }
         }
 
+        out.set_surface(surface);
+
         ChunkGenEvent(out)
     }
 }
@@ -1,6 +1,7 @@
 use egui::{Response, Ui};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WorldGenParams {
     pub continentalness_scale: f64,
     pub erosion_scale: f64,
@@ -32,6 +33,22 @@ impl Default for WorldGenParams {
     }
 }
 
+impl WorldGenParams {
+    pub fn overworld() -> Self {
+        Self {
+            continentalness_scale: 0.00125,
+            erosion_scale: 0.002,
+            peaks_valleys_scale: 0.0125,
+            c_start: -10.0,
+            c_end: 175.0,
+            e_start: -0.5,
+            e_end: 1.0,
+            pv_start: 0.0,
+            pv_end: 35.0,
+        }
+    }
+}
+
 impl egui::Widget for &mut WorldGenParams {
     fn ui(self, ui: &mut Ui) -> Response {
         ui.vertical(|ui| {
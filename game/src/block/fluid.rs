@@ -0,0 +1,168 @@
+use rand::rngs::StdRng;
+use crate::block::Block;
+use crate::block::face_type::FaceType;
+use crate::block::tick::TickWorld;
+use crate::location::BlockLocation;
+
+/// How many blocks fluid flows sideways from its source before it stops.
+pub const MAX_FLOW_DISTANCE: u8 = 7;
+/// How many ticks fluid takes to flow on to the blocks next to it.
+pub const FLOW_DELAY: u32 = 5;
+
+const FALLING: u8 = MAX_FLOW_DISTANCE + 1;
+const SIDEWAYS: [FaceType; 4] = [FaceType::Left, FaceType::Right, FaceType::Front, FaceType::Back];
+
+/// What a fluid block is doing, kept in its state. Sources stay put, while the rest of the fluid
+/// only lasts as long as there's a source feeding it.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FluidState {
+    Source,
+    /// How many blocks sideways from its source it is, from 1 up to [`MAX_FLOW_DISTANCE`].
+    Flowing(u8),
+    /// Fed by the fluid above it.
+    Falling,
+}
+
+impl FluidState {
+    pub fn from_state(state: u8) -> Option<Self> {
+        match state {
+            0 => Some(Self::Source),
+            FALLING => Some(Self::Falling),
+            distance @ 1..=MAX_FLOW_DISTANCE => Some(Self::Flowing(distance)),
+            _ => None,
+        }
+    }
+
+    pub fn to_state(self) -> u8 {
+        match self {
+            Self::Source => 0,
+            Self::Flowing(distance) => distance,
+            Self::Falling => FALLING,
+        }
+    }
+
+    /// How far it is from a source as far as flowing sideways goes. Falling fluid spreads out
+    /// where it lands as if it were a source.
+    pub fn distance(self) -> u8 {
+        match self {
+            Self::Source | Self::Falling => 0,
+            Self::Flowing(distance) => distance,
+        }
+    }
+}
+
+fn offset(location: &BlockLocation, face: FaceType) -> BlockLocation {
+    BlockLocation(location.0 + face.as_vector())
+}
+
+fn is_same_fluid(block: Block, fluid: Block) -> bool {
+    block.id() == fluid.id()
+}
+
+/// Fluid falls into air, and through the same fluid unless it's a source.
+fn can_fall_into(below: Option<Block>, fluid: Block) -> bool {
+    below.is_some_and(|below| below == Block::AIR || is_same_fluid(below, fluid) && below.fluid() != Some(FluidState::Source))
+}
+
+/// The state the fluid around `location` would give it, or `None` if nothing feeds it.
+/// Fluid that can fall doesn't feed anything sideways.
+fn fed_state(world: &dyn TickWorld, location: &BlockLocation, fluid: Block) -> Option<FluidState> {
+    if world.block(&offset(location, FaceType::Top)).is_some_and(|above| is_same_fluid(above, fluid)) {
+        return Some(FluidState::Falling);
+    }
+
+    SIDEWAYS
+        .into_iter()
+        .filter_map(|face| {
+            let neighbor = offset(location, face);
+            let block = world.block(&neighbor).filter(|block| is_same_fluid(*block, fluid))?;
+
+            if can_fall_into(world.block(&offset(&neighbor, FaceType::Bottom)), fluid) {
+                return None;
+            }
+
+            block.fluid()
+        })
+        .map(|state| state.distance() + 1)
+        .min()
+        .filter(|distance| *distance <= MAX_FLOW_DISTANCE)
+        .map(FluidState::Flowing)
+}
+
+/// Fluid flows into the space a block leaves, and around a block put in its way.
+pub fn wake_fluid(world: &mut dyn TickWorld, location: &BlockLocation, _rng: &mut StdRng) {
+    world.schedule_tick(location, FLOW_DELAY);
+}
+
+/// Fluid that isn't a source settles on what the fluid around it feeds it, drying up if nothing does,
+/// then falls if it can, or otherwise spreads sideways. It's woken by blocks changing next to it,
+/// so it only runs while the fluid is still moving.
+pub fn flow_fluid(world: &mut dyn TickWorld, location: &BlockLocation, _rng: &mut StdRng) {
+    let Some(fluid) = world.block(location) else {
+        return;
+    };
+
+    let Some(mut state) = fluid.fluid() else {
+        return;
+    };
+
+    if state != FluidState::Source {
+        match fed_state(world, location, fluid) {
+            None => {
+                world.set_block(location, Block::AIR);
+                return;
+            }
+            Some(fed) if fed != state => {
+                world.set_block(location, fluid.with_fluid(fed));
+                state = fed;
+            }
+            Some(_) => {}
+        }
+    }
+
+    let below = offset(location, FaceType::Bottom);
+
+    if can_fall_into(world.block(&below), fluid) {
+        world.set_block(&below, fluid.with_fluid(FluidState::Falling));
+        return;
+    }
+
+    let distance = state.distance() + 1;
+
+    if distance > MAX_FLOW_DISTANCE {
+        return;
+    }
+
+    for face in SIDEWAYS {
+        let neighbor = offset(location, face);
+
+        let flows = match world.block(&neighbor) {
+            Some(Block::AIR) => true,
+            Some(block) if is_same_fluid(block, fluid) => matches!(block.fluid(), Some(FluidState::Flowing(other)) if other > distance),
+            _ => false,
+        };
+
+        if flows {
+            world.set_block(&neighbor, fluid.with_fluid(FluidState::Flowing(distance)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_states_round_trip() {
+        for state in 0..=u8::MAX {
+            match FluidState::from_state(state) {
+                Some(fluid) => assert_eq!(fluid.to_state(), state),
+                None => assert!(state > FALLING),
+            }
+        }
+
+        assert_eq!(Block::WATER.fluid(), Some(FluidState::Source), "generated water should be sources");
+        assert_eq!(Block::WATER.with_fluid(FluidState::Falling).fluid(), Some(FluidState::Falling));
+        assert_eq!(Block::STONE.with_fluid(FluidState::Falling), Block::STONE);
+    }
+}
@@ -0,0 +1,289 @@
+use std::num::NonZeroU8;
+use std::ops::RangeInclusive;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use crate::item::tool::{Tool, ToolKind};
+use crate::item::{ItemStack, ItemType};
+use crate::location::BlockLocation;
+
+/// Who or what broke a block.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Breaker {
+    /// A player, by their stable identity.
+    Player(u128),
+    /// Anything that isn't a player.
+    World,
+}
+
+/// Everything about how a block was broken that decides what it drops.
+/// Drops are rolled with its own RNG, so the same seed always gives the same drops.
+#[derive(Debug)]
+pub struct BreakContext<'a> {
+    pub breaker: Breaker,
+    pub tool: Option<&'a Tool>,
+    pub location: BlockLocation,
+    pub rng: StdRng,
+}
+
+impl<'a> BreakContext<'a> {
+    pub fn new(breaker: Breaker, tool: Option<&'a Tool>, location: BlockLocation, seed: u64) -> Self {
+        Self {
+            breaker,
+            tool,
+            location,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn fortune(&self) -> u8 {
+        self.tool.map_or(0, |tool| tool.fortune)
+    }
+
+    pub fn silk_touch(&self) -> bool {
+        self.tool.is_some_and(|tool| tool.silk_touch)
+    }
+}
+
+/// Only lets a [`LootEntry`] be picked when it holds.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LootCondition {
+    Tool(ToolKind),
+    SilkTouch(bool),
+    /// At least this level of fortune.
+    Fortune(u8),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LootEntry {
+    /// `None` for an entry that drops nothing, which makes the other entries of its pool less likely.
+    pub item: Option<ItemType>,
+    /// How likely it is to be picked, relative to the other entries of its pool.
+    pub weight: u32,
+    pub count: RangeInclusive<u8>,
+    /// Added to the most it can drop for each level of fortune.
+    pub fortune_bonus: u8,
+    pub conditions: Vec<LootCondition>,
+}
+
+/// Rolled a number of times, each time picking one of the entries whose conditions hold by their weight.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LootPool {
+    pub rolls: RangeInclusive<u8>,
+    pub entries: Vec<LootEntry>,
+}
+
+/// What a block drops when it's broken, made of pools that are each rolled separately.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LootTable {
+    pub pools: Vec<LootPool>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum CountFile {
+    Exact(u8),
+    /// Picked at random, inclusive of both ends.
+    Range([u8; 2]),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LootEntryFile {
+    item: Option<ItemType>,
+    #[serde(default = "LootEntryFile::default_weight")]
+    weight: u32,
+    #[serde(default = "CountFile::one")]
+    count: CountFile,
+    #[serde(default)]
+    fortune_bonus: u8,
+    tool: Option<ToolKind>,
+    silk_touch: Option<bool>,
+    min_fortune: Option<u8>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LootPoolFile {
+    #[serde(default = "CountFile::one")]
+    rolls: CountFile,
+    entries: Vec<LootEntryFile>,
+}
+
+impl CountFile {
+    fn one() -> Self {
+        Self::Exact(1)
+    }
+
+    fn resolve(self) -> Option<RangeInclusive<u8>> {
+        let range = match self {
+            Self::Exact(count) => count..=count,
+            Self::Range([min, max]) => min..=max,
+        };
+
+        (!range.is_empty()).then_some(range)
+    }
+}
+
+impl LootEntryFile {
+    fn default_weight() -> u32 {
+        1
+    }
+
+    /// Fails if it could be picked without dropping anything, other than when that's what it's for.
+    pub(crate) fn resolve(self) -> Option<LootEntry> {
+        let count = self.count.resolve()?;
+
+        if self.weight == 0 || self.item.is_some() && *count.start() == 0 {
+            return None;
+        }
+
+        let conditions = [
+            self.tool.map(LootCondition::Tool),
+            self.silk_touch.map(LootCondition::SilkTouch),
+            self.min_fortune.map(LootCondition::Fortune),
+        ];
+
+        Some(LootEntry {
+            item: self.item,
+            weight: self.weight,
+            count,
+            fortune_bonus: self.fortune_bonus,
+            conditions: conditions.into_iter().flatten().collect(),
+        })
+    }
+}
+
+impl LootPoolFile {
+    pub(crate) fn resolve(self) -> Option<LootPool> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        Some(LootPool {
+            rolls: self.rolls.resolve()?,
+            entries: self.entries.into_iter().map(LootEntryFile::resolve).collect::<Option<_>>()?,
+        })
+    }
+
+    /// A pool that always drops the entry, for drops given without a pool.
+    pub(crate) fn always(entry: LootEntryFile) -> Self {
+        Self {
+            rolls: CountFile::one(),
+            entries: vec![entry],
+        }
+    }
+}
+
+impl LootCondition {
+    pub fn holds(&self, ctx: &BreakContext) -> bool {
+        match *self {
+            Self::Tool(kind) => ctx.tool.is_some_and(|tool| tool.kind == kind),
+            Self::SilkTouch(silk_touch) => ctx.silk_touch() == silk_touch,
+            Self::Fortune(level) => ctx.fortune() >= level,
+        }
+    }
+}
+
+impl LootEntry {
+    fn roll(&self, ctx: &mut BreakContext, drops: &mut Vec<ItemStack>) {
+        let Some(item) = self.item else {
+            return;
+        };
+
+        let max = self.count.end().saturating_add(self.fortune_bonus.saturating_mul(ctx.fortune()));
+        let mut count = ctx.rng.gen_range(*self.count.start()..=max);
+
+        while let Some(stack_count) = NonZeroU8::new(count.min(item.max_stack().get())) {
+            drops.push(item.default_item().with_count(stack_count));
+            count -= stack_count.get();
+        }
+    }
+}
+
+impl LootPool {
+    fn roll(&self, ctx: &mut BreakContext, drops: &mut Vec<ItemStack>) {
+        for _ in 0..ctx.rng.gen_range(self.rolls.clone()) {
+            let eligible = self.entries
+                .iter()
+                .filter(|entry| entry.conditions.iter().all(|condition| condition.holds(ctx)))
+                .collect::<Vec<_>>();
+
+            let total = eligible.iter().map(|entry| entry.weight).sum::<u32>();
+
+            if total == 0 {
+                return;
+            }
+
+            let mut pick = ctx.rng.gen_range(0..total);
+
+            for entry in eligible {
+                if pick < entry.weight {
+                    entry.roll(ctx, drops);
+                    break;
+                }
+
+                pick -= entry.weight;
+            }
+        }
+    }
+}
+
+impl LootTable {
+    pub fn roll(&self, ctx: &mut BreakContext) -> Vec<ItemStack> {
+        let mut drops = Vec::new();
+
+        for pool in &self.pools {
+            pool.roll(ctx, &mut drops);
+        }
+
+        drops
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glm::IVec3;
+    use crate::block::Block;
+    use super::*;
+
+    fn roll(block: Block, tool: Option<&Tool>, seed: u64) -> Vec<ItemStack> {
+        let mut ctx = BreakContext::new(Breaker::World, tool, BlockLocation(IVec3::zeros()), seed);
+
+        block.on_break(&mut ctx)
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_drops() {
+        let pickaxe = ItemType::WOODEN_PICKAXE.tool();
+
+        for seed in 0..16 {
+            assert_eq!(roll(Block::HEMATITE_DEPOSIT, pickaxe, seed), roll(Block::HEMATITE_DEPOSIT, pickaxe, seed));
+        }
+
+        let counts = (0..16)
+            .map(|seed| roll(Block::HEMATITE_DEPOSIT, pickaxe, seed).iter().map(|stack| stack.count.get()).sum::<u8>())
+            .collect::<Vec<_>>();
+
+        assert!(counts.iter().all(|count| (8..=29).contains(count)));
+        assert!(counts.iter().any(|count| *count != counts[0]), "different seeds should give different drops");
+    }
+
+    #[test]
+    fn test_conditions_pick_entries() {
+        let pickaxe = *ItemType::WOODEN_PICKAXE.tool().expect("should be a tool");
+        let silk_pickaxe = Tool { silk_touch: true, ..pickaxe };
+        let lucky_pickaxe = Tool { fortune: 3, ..pickaxe };
+
+        assert_eq!(roll(Block::STONE, Some(&pickaxe), 0), vec![ItemType::COBBLESTONE.default_one()]);
+        assert_eq!(roll(Block::STONE, Some(&silk_pickaxe), 0), vec![ItemType::STONE.default_one()]);
+        assert_eq!(roll(Block::STONE, None, 0), vec![], "stone can't be harvested by hand");
+
+        let most = |tool: &Tool| (0..64)
+            .map(|seed| roll(Block::HEMATITE_DEPOSIT, Some(tool), seed).iter().map(|stack| stack.count.get() as u32).sum::<u32>())
+            .max()
+            .expect("rolled at least once");
+
+        assert!(most(&lucky_pickaxe) > 29, "fortune should raise the most that drops");
+    }
+}
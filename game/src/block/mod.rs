@@ -1,33 +1,27 @@
-use std::num::NonZeroU8;
-use rand::{thread_rng, Rng};
-use serde::{Deserialize, Serialize};
-use strum::{EnumCount, EnumDiscriminants};
-use crate::block::face_type::{Axis, FaceType};
+use std::fmt;
+use serde::{Deserialize, Deserializer, Serialize};
 use static_assertions::const_assert;
+use crate::block::face_type::{Axis, FaceType};
+use crate::block::fluid::FluidState;
+use crate::block::loot::BreakContext;
+use crate::block::registry::{BlockDefinition, BlockId, BlockRegistry, StateKind};
+use crate::block_entity::BlockEntityKind;
 use crate::inventory::Inventory;
 use crate::item::{ItemStack, ItemType};
 use crate::texture_ids::TextureId;
 
 pub mod face_type;
-
-#[repr(u16)] // TODO: eventually replace strum::EnumCount with std::mem::variant_count
-#[derive(Clone, Eq, PartialEq, Default, Debug, Deserialize, Serialize, EnumCount, EnumDiscriminants)]
-#[strum_discriminants(name(BlockTy), derive(strum::FromRepr, EnumCount))]
-pub enum Block {
-    #[default]
-    Air,
-    Grass,
-    Dirt,
-    Cobblestone,
-    Stone,
-    Log { rotation: Axis },
-    Leaf,
-    Debug,
-    Crate { inventory: BlockInventory<36> },
-    StoneBrick,
-    Planks,
-    Water,
-    HematiteDeposit,
+pub mod fluid;
+pub mod loot;
+pub mod registry;
+pub mod tick;
+
+/// A block in the world: which block it is in the [`BlockRegistry`], along with its state,
+/// such as which way it's rotated. Anything else a block needs to keep goes in a block entity.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Default, Serialize)]
+pub struct Block {
+    id: BlockId,
+    state: u8,
 }
 
 #[serde_with::serde_as]
@@ -53,14 +47,6 @@ impl<const N: usize> Inventory for BlockInventory<N> {
     }
 }
 
-impl Default for BlockTy {
-    fn default() -> Self {
-        Self::Air
-    }
-}
-
-const_assert!(size_of::<Block>() <= 16);
-
 #[repr(u8)]
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub enum TextureType {
@@ -70,78 +56,148 @@ pub enum TextureType {
     UniqueTops,
 }
 
+const_assert!(size_of::<Block>() <= 4);
+
 impl Block {
+    pub const AIR: Self = Self::new(BlockId(0));
+    pub const GRASS: Self = Self::new(BlockId(1));
+    pub const DIRT: Self = Self::new(BlockId(2));
+    pub const COBBLESTONE: Self = Self::new(BlockId(3));
+    pub const STONE: Self = Self::new(BlockId(4));
+    pub const LOG: Self = Self::new(BlockId(5));
+    pub const LEAF: Self = Self::new(BlockId(6));
+    pub const DEBUG: Self = Self::new(BlockId(7));
+    pub const CRATE: Self = Self::new(BlockId(8));
+    pub const STONE_BRICK: Self = Self::new(BlockId(9));
+    pub const PLANKS: Self = Self::new(BlockId(10));
+    pub const WATER: Self = Self::new(BlockId(11));
+    pub const HEMATITE_DEPOSIT: Self = Self::new(BlockId(12));
+    pub const SMELTER: Self = Self::new(BlockId(13));
+
+    /// The block in its default state.
+    pub const fn new(id: BlockId) -> Self {
+        Self { id, state: 0 }
+    }
+
+    /// Fails if the block isn't in the global registry, or the state isn't valid for it.
+    pub fn with_state(id: BlockId, state: u8) -> Option<Self> {
+        BlockRegistry::global()
+            .get(id)
+            .is_some_and(|definition| definition.state.is_valid(state))
+            .then_some(Self { id, state })
+    }
+
+    pub fn id(self) -> BlockId {
+        self.id
+    }
+
+    pub fn state(self) -> u8 {
+        self.state
+    }
+
+    pub fn definition(&self) -> &'static BlockDefinition {
+        BlockRegistry::global()
+            .get(self.id)
+            .expect("blocks are only made with ids from the global registry")
+    }
+
+    /// For blocks with an axis, turns it so its top and bottom face along `axis`.
+    pub fn rotated(self, axis: Axis) -> Self {
+        match self.definition().state {
+            StateKind::Axis => Self { state: axis as u8, ..self },
+            StateKind::None | StateKind::Fluid => self,
+        }
+    }
+
+    pub fn axis(self) -> Option<Axis> {
+        match self.definition().state {
+            StateKind::Axis => Axis::from_repr(self.state),
+            StateKind::None | StateKind::Fluid => None,
+        }
+    }
+
+    /// For fluids, the same fluid in `fluid`'s state.
+    pub fn with_fluid(self, fluid: FluidState) -> Self {
+        match self.definition().state {
+            StateKind::Fluid => Self { state: fluid.to_state(), ..self },
+            StateKind::None | StateKind::Axis => self,
+        }
+    }
+
+    pub fn fluid(self) -> Option<FluidState> {
+        match self.definition().state {
+            StateKind::Fluid => FluidState::from_state(self.state),
+            StateKind::None | StateKind::Axis => None,
+        }
+    }
+
     pub fn texture_id(&self, face_type: FaceType) -> Option<TextureId> {
-        use TextureId as Id;
-
-        let id = match self {
-            Block::Air => return None,
-            Block::Grass => match face_type {
-                FaceType::Top => Id::Grass,
-                FaceType::Bottom => Id::Dirt,
-                _ => Id::GrassSide,
-            }
-            Block::Dirt => Id::Dirt,
-            Block::Cobblestone => Id::Cobblestone,
-            Block::Debug => match face_type.axis() {
-                Axis::X => Id::DebugRed,
-                Axis::Y => Id::DebugBlue,
-                Axis::Z => Id::DebugGreen,
-            }
-            Block::Log { rotation } => if face_type.axis() == *rotation {
-                Id::LogTop
-            } else {
-                Id::LogSide // TODO: rotate texture
-            }
-            Block::Leaf => Id::DebugGreen,
-            Block::Stone => Id::Stone,
-            Block::Crate { .. } => match face_type {
-                FaceType::Top => Id::CrateTop,
-                FaceType::Bottom => Id::CrateBottom,
-                _ => Id::CrateSide,
-            },
-            Block::Water => Id::Water,
-            Block::Planks => Id::Planks,
-            Block::StoneBrick | Block::HematiteDeposit => Id::Missing,
+        // rotating swaps the axis the block faces along with the y axis
+        let face = match self.axis() {
+            Some(axis) if face_type.axis() == axis => FaceType::from_axis_and_sign(Axis::Y, face_type.sign() > 0),
+            Some(axis) if face_type.axis() == Axis::Y => FaceType::from_axis_and_sign(axis, face_type.sign() > 0),
+            _ => face_type,
         };
 
-        Some(id)
-    }
-
-    // TODO: this should return a vec?
-    pub fn on_break(self, /* break_context: BreakContext TODO: break context for fortune*/) -> Vec<ItemStack> {
-        use Block as B;
-        use ItemType as I;
-
-        const NONE: Vec<ItemStack> = Vec::new();
-
-        match self {
-            B::Air | B::Debug | B::Water => NONE,
-            B::Grass | B::Dirt  => vec![I::Dirt.default_one()],
-            B::Cobblestone | B::Stone => vec![I::Cobblestone.default_one()],
-            B::Log { .. } => vec![I::Log.default_one()],
-            B::Leaf => {
-                let count = thread_rng().gen_range(5..15);
-
-                vec![I::LeafPile.default_item().with_count(NonZeroU8::new(count).expect("0 is not in range"))]
-            }
-            B::Crate { inventory: mut inv } => {
-                inv.try_insert(I::Crate.default_one())
-                    .into_iter()
-                    .chain(inv.0.into_iter().flatten())
-                    .collect()
-            },
-            B::Planks => vec![I::Planks.default_one()],
-            B::StoneBrick => vec![I::StoneBricks.default_one()],
-            B::HematiteDeposit => {
-                let count = thread_rng().gen_range(8..30);
-
-                vec![I::HematiteNuggets.default_item().with_count(NonZeroU8::new(count).expect("0 is not in range"))]
-            }
+        self.definition().texture(face)
+    }
+
+    /// Rolls what the block drops, which is nothing unless it can be harvested with the tool it was broken with.
+    pub fn on_break(self, ctx: &mut BreakContext) -> Vec<ItemStack> {
+        let definition = self.definition();
+
+        if !definition.harvestable_with(ctx.tool) {
+            return Vec::new();
         }
+
+        definition.loot.roll(ctx)
+    }
+
+    /// The kind of block entity placed alongside the block, for blocks with state of their own.
+    pub fn block_entity_kind(&self) -> Option<BlockEntityKind> {
+        self.definition().block_entity
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.definition().solid
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.definition().transparent
     }
 
-    pub fn ty(&self) -> BlockTy {
-        self.into()
+    /// Whether blocks can be placed in its place, like air and fluids.
+    pub fn is_replaceable(&self) -> bool {
+        *self == Self::AIR || self.fluid().is_some()
     }
-}
\ No newline at end of file
+
+    /// The block an item places, facing `face` if it can be rotated.
+    pub fn placed_by(item: ItemType, face: FaceType) -> Option<Self> {
+        Some(Self::new(item.block()?).rotated(face.axis()))
+    }
+}
+
+impl fmt::Debug for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match BlockRegistry::global().get(self.id) {
+            Some(definition) if self.state == 0 => write!(f, "Block({})", definition.name),
+            Some(definition) => write!(f, "Block({}, state {})", definition.name, self.state),
+            None => write!(f, "Block(unknown {:?}, state {})", self.id, self.state),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Block {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct RawBlock {
+            id: BlockId,
+            state: u8,
+        }
+
+        let RawBlock { id, state } = RawBlock::deserialize(deserializer)?;
+
+        Self::with_state(id, state)
+            .ok_or_else(|| serde::de::Error::custom(format!("{id:?} with state {state} isn't a known block")))
+    }
+}
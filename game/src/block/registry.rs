@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::{fs, io};
+use serde::{Deserialize, Serialize};
+use crate::block::face_type::{Axis, FaceType};
+use crate::block::fluid::FluidState;
+use crate::block::loot::{LootEntryFile, LootPoolFile, LootTable};
+use crate::block_entity::BlockEntityKind;
+use crate::definitions::{definition_files, fingerprint, is_namespaced};
+use crate::item::tool::ToolKind;
+use crate::texture_ids::TextureId;
+
+/// The blocks every world has, which mods can add to but not change.
+const BASE_DEFINITIONS: &str = include_str!("../../assets/blocks.toml");
+
+pub const MAX_LIGHT: u8 = 15;
+
+static GLOBAL: OnceLock<BlockRegistry> = OnceLock::new();
+
+/// Index of a block's definition in the [`BlockRegistry`]. It depends on which definitions were loaded,
+/// so anything saved refers to blocks by name instead.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Default, Serialize, Deserialize)]
+pub struct BlockId(pub u16);
+
+/// What the state stored alongside a block's id means.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateKind {
+    #[default]
+    None,
+    /// Rotated so that its top and bottom face along an [`Axis`], stored as the axis' discriminant.
+    Axis,
+    /// A fluid, stored as its [`FluidState`].
+    Fluid,
+}
+
+impl StateKind {
+    pub fn is_valid(self, state: u8) -> bool {
+        match self {
+            Self::None => state == 0,
+            Self::Axis => Axis::from_repr(state).is_some(),
+            Self::Fluid => FluidState::from_state(state).is_some(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockDefinition {
+    /// Namespaced, such as `protovox:stone`.
+    pub name: String,
+    /// Indexed by [`FaceType`], or `None` for blocks that aren't drawn at all.
+    pub textures: Option<[TextureId; 6]>,
+    pub loot: LootTable,
+    /// Whether entities collide with it.
+    pub solid: bool,
+    /// Whether the faces of blocks next to it can be seen through it.
+    pub transparent: bool,
+    pub hardness: f32,
+    /// The kind of tool that breaks it faster.
+    pub tool: Option<ToolKind>,
+    /// The lowest tier of `tool` it drops anything when broken with, or 0 if it always does.
+    pub harvest_tier: u8,
+    /// How much light it gives off, up to [`MAX_LIGHT`].
+    pub light: u8,
+    pub state: StateKind,
+    pub block_entity: Option<BlockEntityKind>,
+}
+
+/// Each face takes the most specific texture given for it:
+/// `top` or `bottom`, then the texture for its axis, then `sides` for faces other than the top and bottom, then `all`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TexturesFile {
+    all: Option<TextureId>,
+    sides: Option<TextureId>,
+    top: Option<TextureId>,
+    bottom: Option<TextureId>,
+    x: Option<TextureId>,
+    y: Option<TextureId>,
+    z: Option<TextureId>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockDefinitionFile {
+    name: String,
+    textures: Option<TexturesFile>,
+    /// Each always dropped, as if it were the only entry of its own loot pool.
+    #[serde(default)]
+    drops: Vec<LootEntryFile>,
+    #[serde(default)]
+    loot: Vec<LootPoolFile>,
+    #[serde(default = "BlockDefinitionFile::default_true")]
+    solid: bool,
+    #[serde(default)]
+    transparent: bool,
+    #[serde(default = "BlockDefinitionFile::default_hardness")]
+    hardness: f32,
+    tool: Option<ToolKind>,
+    #[serde(default)]
+    tier: u8,
+    #[serde(default)]
+    light: u8,
+    #[serde(default)]
+    state: StateKind,
+    block_entity: Option<BlockEntityKind>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DefinitionsFile {
+    #[serde(default)]
+    blocks: Vec<BlockDefinitionFile>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BlockRegistryError {
+    #[error("failed to read block definitions from {0:?}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("invalid block definitions in {0}: {1}")]
+    Parse(String, toml::de::Error),
+    #[error("block name {0:?} should be namespaced, like \"namespace:name\"")]
+    InvalidName(String),
+    #[error("block {0} is defined more than once")]
+    Duplicate(String),
+    #[error("block {name} doesn't have a texture for its {face:?} face")]
+    MissingTexture { name: String, face: FaceType },
+    #[error("block {0} has a drop with no items or a weight of 0, a loot pool with no entries, or a range that's backwards")]
+    InvalidDrop(String),
+    #[error("block {0} should have a hardness that's a positive number or 0")]
+    InvalidHardness(String),
+    #[error("block {0} needs a tier of tool to drop anything, but not which kind of tool")]
+    MissingTool(String),
+    #[error("block {0} gives off more than {MAX_LIGHT} light")]
+    InvalidLight(String),
+    #[error("more than {} blocks were defined", u16::MAX)]
+    TooManyBlocks,
+}
+
+impl BlockDefinitionFile {
+    fn default_true() -> bool {
+        true
+    }
+
+    fn default_hardness() -> f32 {
+        1.0
+    }
+}
+
+impl TexturesFile {
+    fn resolve(&self, face: FaceType) -> Option<TextureId> {
+        let own = match face {
+            FaceType::Top => self.top,
+            FaceType::Bottom => self.bottom,
+            _ => None,
+        };
+
+        let axis = match face.axis() {
+            Axis::X => self.x,
+            Axis::Y => self.y,
+            Axis::Z => self.z,
+        };
+
+        let sides = (face.axis() != Axis::Y).then_some(self.sides).flatten();
+
+        own.or(axis).or(sides).or(self.all)
+    }
+}
+
+impl BlockDefinitionFile {
+    fn resolve(self) -> Result<BlockDefinition, BlockRegistryError> {
+        if !is_namespaced(&self.name) {
+            return Err(BlockRegistryError::InvalidName(self.name));
+        }
+
+        let textures = match &self.textures {
+            None => None,
+            Some(textures) => {
+                let mut resolved = [TextureId::Missing; 6];
+
+                for face in FaceType::ALL {
+                    resolved[face as usize] = textures
+                        .resolve(face)
+                        .ok_or_else(|| BlockRegistryError::MissingTexture { name: self.name.clone(), face })?;
+                }
+
+                Some(resolved)
+            }
+        };
+
+        let pools = self.drops
+            .into_iter()
+            .map(LootPoolFile::always)
+            .chain(self.loot)
+            .map(LootPoolFile::resolve)
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| BlockRegistryError::InvalidDrop(self.name.clone()))?;
+
+        if self.light > MAX_LIGHT {
+            return Err(BlockRegistryError::InvalidLight(self.name));
+        }
+
+        if !(self.hardness.is_finite() && self.hardness >= 0.0) {
+            return Err(BlockRegistryError::InvalidHardness(self.name));
+        }
+
+        if self.tier > 0 && self.tool.is_none() {
+            return Err(BlockRegistryError::MissingTool(self.name));
+        }
+
+        Ok(BlockDefinition {
+            name: self.name,
+            textures,
+            loot: LootTable { pools },
+            solid: self.solid,
+            transparent: self.transparent,
+            hardness: self.hardness,
+            tool: self.tool,
+            harvest_tier: self.tier,
+            light: self.light,
+            state: self.state,
+            block_entity: self.block_entity,
+        })
+    }
+}
+
+impl BlockDefinition {
+    pub fn texture(&self, face: FaceType) -> Option<TextureId> {
+        self.textures.map(|textures| textures[face as usize])
+    }
+}
+
+/// Every kind of block, loaded from definition files. Blocks are numbered in the order they were loaded,
+/// starting with the base definitions, so the base blocks always have the same ids.
+#[derive(Debug)]
+pub struct BlockRegistry {
+    definitions: Vec<BlockDefinition>,
+    by_name: HashMap<String, BlockId>,
+}
+
+impl BlockRegistry {
+    /// A registry of only the base blocks.
+    pub fn base() -> Self {
+        let mut registry = Self {
+            definitions: Vec::new(),
+            by_name: HashMap::new(),
+        };
+
+        registry
+            .add_definitions("base definitions", BASE_DEFINITIONS)
+            .expect("base block definitions should be valid");
+
+        registry
+    }
+
+    /// The base blocks, followed by those defined in the directory if it exists.
+    pub fn load(dir: &Path) -> Result<Self, BlockRegistryError> {
+        let mut registry = Self::base();
+
+        if dir.is_dir() {
+            registry.add_definitions_in(dir)?;
+        }
+
+        Ok(registry)
+    }
+
+    /// Adds every block defined in `source`, where `origin` is only used to describe errors.
+    /// Nothing is added if any of the definitions are invalid.
+    pub fn add_definitions(&mut self, origin: &str, source: &str) -> Result<(), BlockRegistryError> {
+        let file = toml::from_str::<DefinitionsFile>(source)
+            .map_err(|err| BlockRegistryError::Parse(origin.to_string(), err))?;
+
+        let definitions = file.blocks
+            .into_iter()
+            .map(BlockDefinitionFile::resolve)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if self.definitions.len() + definitions.len() > u16::MAX as usize {
+            return Err(BlockRegistryError::TooManyBlocks);
+        }
+
+        let mut by_name = self.by_name.clone();
+
+        for (i, definition) in definitions.iter().enumerate() {
+            let id = BlockId((self.definitions.len() + i) as u16);
+
+            if by_name.insert(definition.name.clone(), id).is_some() {
+                return Err(BlockRegistryError::Duplicate(definition.name.clone()));
+            }
+        }
+
+        self.definitions.extend(definitions);
+        self.by_name = by_name;
+
+        Ok(())
+    }
+
+    /// Adds the definitions from every `.toml` file in the directory, in order of their file names.
+    pub fn add_definitions_in(&mut self, dir: &Path) -> Result<(), BlockRegistryError> {
+        let paths = definition_files(dir).map_err(|err| BlockRegistryError::Io(dir.to_path_buf(), err))?;
+
+        for path in paths {
+            let source = fs::read_to_string(&path).map_err(|err| BlockRegistryError::Io(path.clone(), err))?;
+
+            self.add_definitions(&path.display().to_string(), &source)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, id: BlockId) -> Option<&BlockDefinition> {
+        self.definitions.get(id.0 as usize)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<BlockId> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+
+    /// Differs between registries that don't have the same blocks with the same ids.
+    pub fn fingerprint(&self) -> u64 {
+        fingerprint(self.definitions.iter().map(|definition| definition.name.as_str()))
+    }
+
+    /// The registry every [`Block`](crate::block::Block) refers to, which only has the base blocks
+    /// unless another registry was installed before it was first used.
+    pub fn global() -> &'static Self {
+        GLOBAL.get_or_init(Self::base)
+    }
+
+    /// Makes this the global registry. Fails if the global registry was already used,
+    /// since blocks might already refer to it.
+    pub fn install(self) -> Result<(), Self> {
+        GLOBAL.set(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block::Block;
+    use super::*;
+
+    #[test]
+    fn test_base_blocks_match_their_constants() {
+        let registry = BlockRegistry::base();
+
+        let constants = [
+            (Block::AIR, "protovox:air"),
+            (Block::GRASS, "protovox:grass"),
+            (Block::DIRT, "protovox:dirt"),
+            (Block::COBBLESTONE, "protovox:cobblestone"),
+            (Block::STONE, "protovox:stone"),
+            (Block::LOG, "protovox:log"),
+            (Block::LEAF, "protovox:leaf"),
+            (Block::DEBUG, "protovox:debug"),
+            (Block::CRATE, "protovox:crate"),
+            (Block::STONE_BRICK, "protovox:stone_brick"),
+            (Block::PLANKS, "protovox:planks"),
+            (Block::WATER, "protovox:water"),
+            (Block::HEMATITE_DEPOSIT, "protovox:hematite_deposit"),
+            (Block::SMELTER, "protovox:smelter"),
+        ];
+
+        assert_eq!(registry.len(), constants.len());
+
+        for (block, name) in constants {
+            assert_eq!(registry.by_name(name), Some(block.id()), "{name} doesn't match its constant");
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_changes_with_the_blocks() {
+        let base = BlockRegistry::base().fingerprint();
+
+        assert_eq!(BlockRegistry::base().fingerprint(), base);
+
+        let mut registry = BlockRegistry::base();
+
+        registry.add_definitions("test", r#"
+            [[blocks]]
+            name = "test:pillar"
+        "#).expect("definitions should be valid");
+
+        assert_ne!(registry.fingerprint(), base);
+    }
+
+    #[test]
+    fn test_textures_resolve_most_specific_first() {
+        let mut registry = BlockRegistry::base();
+
+        registry.add_definitions("test", r#"
+            [[blocks]]
+            name = "test:pillar"
+            textures = { all = "stone", sides = "planks", top = "log_top", x = "dirt" }
+        "#).expect("definitions should be valid");
+
+        let pillar = registry.get(registry.by_name("test:pillar").expect("was added")).expect("was added");
+
+        assert_eq!(pillar.texture(FaceType::Top), Some(TextureId::LogTop));
+        assert_eq!(pillar.texture(FaceType::Bottom), Some(TextureId::Stone));
+        assert_eq!(pillar.texture(FaceType::Left), Some(TextureId::Dirt));
+        assert_eq!(pillar.texture(FaceType::Front), Some(TextureId::Planks));
+    }
+
+    #[test]
+    fn test_rejects_invalid_definitions() {
+        let invalid = [
+            r#"[[blocks]]
+            name = "unnamespaced""#,
+            r#"[[blocks]]
+            name = "protovox:stone""#,
+            r#"[[blocks]]
+            name = "test:half_textured"
+            textures = { top = "stone" }"#,
+            r#"[[blocks]]
+            name = "test:backwards"
+            drops = [{ item = "protovox:dirt", count = [4, 2] }]"#,
+            r#"[[blocks]]
+            name = "test:drops_nothing"
+            drops = [{ item = "protovox:nothing" }]"#,
+            r#"[[blocks]]
+            name = "test:needs_something"
+            tier = 2"#,
+        ];
+
+        for source in invalid {
+            let mut registry = BlockRegistry::base();
+
+            assert!(registry.add_definitions("test", source).is_err(), "{source} should be rejected");
+            assert_eq!(registry.len(), BlockRegistry::base().len(), "nothing should be added from {source}");
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::ops::Range;
+use glm::IVec3;
+use rand::Rng;
+use rand::rngs::StdRng;
+use crate::block::Block;
+use crate::block::face_type::FaceType;
+use crate::block::fluid::{flow_fluid, wake_fluid};
+use crate::block::registry::BlockId;
+use crate::location::BlockLocation;
+
+/// How many block ticks there are in a second of game time.
+pub const TICKS_PER_SECOND: u64 = 20;
+/// How far leaves can be from a log, counting through other leaves, before they decay.
+pub const LEAF_SUPPORT_DISTANCE: u32 = 4;
+/// How many ticks after a leaf decays the leaves next to it check whether they should too,
+/// so that a whole tree decays bit by bit rather than waiting on random ticks.
+const LEAF_DECAY_DELAY: Range<u32> = 4..20;
+
+/// The part of the world a block tick can see and change.
+pub trait TickWorld {
+    /// `None` where the world isn't loaded.
+    fn block(&self, location: &BlockLocation) -> Option<Block>;
+
+    fn set_block(&mut self, location: &BlockLocation, block: Block);
+
+    /// Ticks the block at `location` again once `delay` ticks have passed, whatever block is there by then.
+    fn schedule_tick(&mut self, location: &BlockLocation, delay: u32);
+}
+
+pub type TickHandler = fn(&mut dyn TickWorld, &BlockLocation, &mut StdRng);
+
+/// What blocks do when they're ticked. Random ticks land on random blocks of every loaded chunk,
+/// while scheduled ticks are asked for through [`TickWorld::schedule_tick`]. Neighbor updates land on
+/// a block that changed and the blocks next to it, so they can react to it.
+#[derive(Default)]
+pub struct BlockTickHandlers {
+    random: HashMap<BlockId, TickHandler>,
+    scheduled: HashMap<BlockId, TickHandler>,
+    neighbor: HashMap<BlockId, TickHandler>,
+}
+
+impl BlockTickHandlers {
+    /// The handlers of the base blocks.
+    pub fn base() -> Self {
+        let mut handlers = Self::default();
+
+        handlers.register_random(Block::GRASS, spread_grass);
+        handlers.register_random(Block::LEAF, decay_leaf);
+        handlers.register_scheduled(Block::LEAF, decay_leaf);
+        handlers.register_scheduled(Block::WATER, flow_fluid);
+        handlers.register_neighbor(Block::LEAF, check_leaf_support);
+        handlers.register_neighbor(Block::WATER, wake_fluid);
+
+        handlers
+    }
+
+    /// Replaces the handler the block already had, whatever its state.
+    pub fn register_random(&mut self, block: Block, handler: TickHandler) {
+        self.random.insert(block.id(), handler);
+    }
+
+    /// Replaces the handler the block already had, whatever its state.
+    pub fn register_scheduled(&mut self, block: Block, handler: TickHandler) {
+        self.scheduled.insert(block.id(), handler);
+    }
+
+    /// Replaces the handler the block already had, whatever its state.
+    pub fn register_neighbor(&mut self, block: Block, handler: TickHandler) {
+        self.neighbor.insert(block.id(), handler);
+    }
+
+    pub fn random(&self, block: Block) -> Option<TickHandler> {
+        self.random.get(&block.id()).copied()
+    }
+
+    pub fn scheduled(&self, block: Block) -> Option<TickHandler> {
+        self.scheduled.get(&block.id()).copied()
+    }
+
+    pub fn neighbor(&self, block: Block) -> Option<TickHandler> {
+        self.neighbor.get(&block.id()).copied()
+    }
+}
+
+impl fmt::Debug for BlockTickHandlers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockTickHandlers")
+            .field("random", &self.random.keys())
+            .field("scheduled", &self.scheduled.keys())
+            .field("neighbor", &self.neighbor.keys())
+            .finish()
+    }
+}
+
+fn offset(location: &BlockLocation, offset: IVec3) -> BlockLocation {
+    BlockLocation(location.0 + offset)
+}
+
+/// Whether the block above lets light through, as far as anything that grows cares.
+fn is_covered(world: &dyn TickWorld, location: &BlockLocation) -> bool {
+    world
+        .block(&offset(location, FaceType::Top.as_vector()))
+        .is_some_and(|block| !block.is_transparent())
+}
+
+/// Grass dies once it's covered, and otherwise spreads to dirt near it that isn't.
+pub fn spread_grass(world: &mut dyn TickWorld, location: &BlockLocation, rng: &mut StdRng) {
+    if is_covered(world, location) {
+        world.set_block(location, Block::DIRT);
+        return;
+    }
+
+    let target = offset(location, IVec3::new(rng.gen_range(-1..=1), rng.gen_range(-3..=1), rng.gen_range(-1..=1)));
+
+    if world.block(&target) == Some(Block::DIRT) && !is_covered(world, &target) {
+        world.set_block(&target, Block::GRASS);
+    }
+}
+
+/// Whether there's a log within [`LEAF_SUPPORT_DISTANCE`] of the leaf, through other leaves.
+/// Leaves next to chunks that aren't loaded count as supported, since there's no telling.
+fn is_supported(world: &dyn TickWorld, location: &BlockLocation) -> bool {
+    let mut visited = HashSet::from([location.0]);
+    let mut frontier = VecDeque::from([(location.clone(), 0)]);
+
+    while let Some((current, distance)) = frontier.pop_front() {
+        for face in FaceType::ALL {
+            let neighbor = offset(&current, face.as_vector());
+
+            if !visited.insert(neighbor.0) {
+                continue;
+            }
+
+            match world.block(&neighbor) {
+                None => return true,
+                Some(block) if block.id() == Block::LOG.id() => return true,
+                Some(Block::LEAF) if distance + 1 < LEAF_SUPPORT_DISTANCE => frontier.push_back((neighbor, distance + 1)),
+                _ => {}
+            }
+        }
+    }
+
+    false
+}
+
+/// Leaves away from any log decay without dropping anything, and get the leaves next to them to check soon after.
+pub fn decay_leaf(world: &mut dyn TickWorld, location: &BlockLocation, rng: &mut StdRng) {
+    if is_supported(world, location) {
+        return;
+    }
+
+    world.set_block(location, Block::AIR);
+
+    for face in FaceType::ALL {
+        let neighbor = offset(location, face.as_vector());
+
+        if world.block(&neighbor) == Some(Block::LEAF) {
+            world.schedule_tick(&neighbor, rng.gen_range(LEAF_DECAY_DELAY));
+        }
+    }
+}
+
+/// Leaves check whether they're still supported soon after something next to them changes, like the log they grew on being cut down.
+pub fn check_leaf_support(world: &mut dyn TickWorld, location: &BlockLocation, rng: &mut StdRng) {
+    world.schedule_tick(location, rng.gen_range(LEAF_DECAY_DELAY));
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use super::*;
+
+    /// Everything within a few blocks of the origin is loaded, and air unless set otherwise.
+    #[derive(Default)]
+    struct TestWorld {
+        blocks: HashMap<IVec3, Block>,
+        scheduled: Vec<BlockLocation>,
+    }
+
+    impl TickWorld for TestWorld {
+        fn block(&self, location: &BlockLocation) -> Option<Block> {
+            (location.0.abs().max() <= 8).then(|| self.blocks.get(&location.0).copied().unwrap_or(Block::AIR))
+        }
+
+        fn set_block(&mut self, location: &BlockLocation, block: Block) {
+            self.blocks.insert(location.0, block);
+        }
+
+        fn schedule_tick(&mut self, location: &BlockLocation, _delay: u32) {
+            self.scheduled.push(location.clone());
+        }
+    }
+
+    fn at(x: i32, y: i32, z: i32) -> BlockLocation {
+        BlockLocation(IVec3::new(x, y, z))
+    }
+
+    #[test]
+    fn test_grass_spreads_to_uncovered_dirt() {
+        let mut world = TestWorld::default();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        world.set_block(&at(0, 0, 0), Block::GRASS);
+        world.set_block(&at(1, 0, 0), Block::DIRT);
+        world.set_block(&at(-1, 0, 0), Block::DIRT);
+        world.set_block(&at(-1, 1, 0), Block::STONE);
+
+        for _ in 0..200 {
+            spread_grass(&mut world, &at(0, 0, 0), &mut rng);
+        }
+
+        assert_eq!(world.block(&at(1, 0, 0)), Some(Block::GRASS));
+        assert_eq!(world.block(&at(-1, 0, 0)), Some(Block::DIRT), "covered dirt shouldn't grow grass");
+
+        world.set_block(&at(0, 1, 0), Block::PLANKS);
+        spread_grass(&mut world, &at(0, 0, 0), &mut rng);
+
+        assert_eq!(world.block(&at(0, 0, 0)), Some(Block::DIRT), "covered grass should die");
+    }
+
+    #[test]
+    fn test_leaves_decay_away_from_logs() {
+        let mut world = TestWorld::default();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        world.set_block(&at(0, 0, 0), Block::LOG);
+
+        for x in 1..=6 {
+            world.set_block(&at(x, 0, 0), Block::LEAF);
+        }
+
+        decay_leaf(&mut world, &at(4, 0, 0), &mut rng);
+        assert_eq!(world.block(&at(4, 0, 0)), Some(Block::LEAF), "leaves close enough to a log should stay");
+
+        decay_leaf(&mut world, &at(5, 0, 0), &mut rng);
+        assert_eq!(world.block(&at(5, 0, 0)), Some(Block::AIR));
+        assert_eq!(world.scheduled, vec![at(4, 0, 0), at(6, 0, 0)], "the leaves next to it should check soon after");
+
+        world.set_block(&at(0, 0, 0), Block::AIR);
+        decay_leaf(&mut world, &at(1, 0, 0), &mut rng);
+
+        assert_eq!(world.block(&at(1, 0, 0)), Some(Block::AIR), "leaves should decay once their log is gone");
+    }
+}
@@ -0,0 +1,166 @@
+use std::any::Any;
+use std::fmt::Debug;
+use std::time::Duration;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::block::BlockInventory;
+use crate::inventory::Inventory;
+use crate::item::ItemStack;
+use crate::machine::SmelterEntity;
+
+/// Which type a block entity is, so it can be decoded again.
+/// The discriminant is what's saved and sent, so once a kind is assigned one it must never change.
+#[repr(u16)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize, strum::FromRepr)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockEntityKind {
+    Crate = 0,
+    Smelter = 1,
+}
+
+impl BlockEntityKind {
+    pub const fn stable_id(self) -> u16 {
+        self as u16
+    }
+
+    pub fn from_stable_id(id: u16) -> Option<Self> {
+        Self::from_repr(id)
+    }
+
+    /// The block entity a newly placed block starts out with.
+    pub fn create(self) -> Box<dyn BlockEntity> {
+        match self {
+            Self::Crate => Box::new(CrateEntity::default()),
+            Self::Smelter => Box::new(SmelterEntity::default()),
+        }
+    }
+
+    /// Reads back a block entity written with [`BlockEntity::encode`].
+    pub fn decode(self, bytes: &[u8]) -> Result<Box<dyn BlockEntity>, postcard::Error> {
+        let entity: Box<dyn BlockEntity> = match self {
+            Self::Crate => Box::new(postcard::from_bytes::<CrateEntity>(bytes)?),
+            Self::Smelter => Box::new(postcard::from_bytes::<SmelterEntity>(bytes)?),
+        };
+
+        Ok(entity)
+    }
+}
+
+/// State belonging to a single block that doesn't fit in the block itself, such as an inventory.
+/// Kept next to the blocks of its chunk, and created and removed along with its block.
+pub trait BlockEntity: Debug + Send + Sync + Any {
+    fn kind(&self) -> BlockEntityKind;
+
+    fn encode(&self) -> Result<Vec<u8>, postcard::Error>;
+
+    fn clone_boxed(&self) -> Box<dyn BlockEntity>;
+
+    /// Called every tick while the chunk is loaded, only where the world is hosted.
+    /// Returns whether anything changed, so that it gets saved and sent to clients.
+    fn tick(&mut self, _delta: Duration) -> bool {
+        false
+    }
+
+    /// Called when the block is removed, returning whatever should be dropped along with the block.
+    fn on_remove(self: Box<Self>) -> Vec<ItemStack> {
+        Vec::new()
+    }
+
+    /// The inventories players can move items in and out of, in the order inventory actions refer to them by.
+    fn inventories_mut(&mut self) -> Vec<&mut [Option<ItemStack>]> {
+        Vec::new()
+    }
+
+    /// Whether players can put `stack` into the inventory at `index` of [`inventories_mut`](Self::inventories_mut).
+    /// Anything can be taken out.
+    fn accepts(&self, _index: usize, _stack: &ItemStack) -> bool {
+        true
+    }
+}
+
+impl dyn BlockEntity {
+    pub fn downcast_ref<T: BlockEntity>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref()
+    }
+
+    pub fn downcast_mut<T: BlockEntity>(&mut self) -> Option<&mut T> {
+        (self as &mut dyn Any).downcast_mut()
+    }
+}
+
+impl Clone for Box<dyn BlockEntity> {
+    fn clone(&self) -> Self {
+        self.clone_boxed()
+    }
+}
+
+impl Serialize for Box<dyn BlockEntity> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = self.encode().map_err(serde::ser::Error::custom)?;
+
+        (self.kind(), bytes).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn BlockEntity> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (kind, bytes) = <(BlockEntityKind, Vec<u8>)>::deserialize(deserializer)?;
+
+        kind.decode(&bytes).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CrateEntity {
+    pub inventory: BlockInventory<36>,
+}
+
+impl BlockEntity for CrateEntity {
+    fn kind(&self) -> BlockEntityKind {
+        BlockEntityKind::Crate
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, postcard::Error> {
+        postcard::to_allocvec(self)
+    }
+
+    fn clone_boxed(&self) -> Box<dyn BlockEntity> {
+        Box::new(self.clone())
+    }
+
+    fn on_remove(mut self: Box<Self>) -> Vec<ItemStack> {
+        self.inventory
+            .as_mut_slice()
+            .iter_mut()
+            .filter_map(Option::take)
+            .collect()
+    }
+
+    fn inventories_mut(&mut self) -> Vec<&mut [Option<ItemStack>]> {
+        vec![self.inventory.as_mut_slice()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::item::ItemType;
+    use super::*;
+
+    #[test]
+    fn test_round_trips_through_serde() {
+        let mut crate_entity = CrateEntity::default();
+        crate_entity.inventory.try_insert_at(7, ItemType::PLANKS.default_item().with_count(3.try_into().expect("3 is nonzero")));
+
+        let boxed: Box<dyn BlockEntity> = Box::new(crate_entity);
+
+        let bytes = postcard::to_allocvec(&boxed).expect("should serialize");
+        let decoded = postcard::from_bytes::<Box<dyn BlockEntity>>(&bytes).expect("should deserialize");
+
+        assert_eq!(decoded.kind(), BlockEntityKind::Crate);
+        assert_eq!(
+            decoded.downcast_ref::<CrateEntity>().expect("should be a crate").inventory,
+            boxed.downcast_ref::<CrateEntity>().expect("should be a crate").inventory,
+        );
+
+        assert_eq!(decoded.on_remove().len(), 1);
+    }
+}
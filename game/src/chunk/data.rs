@@ -1,40 +1,226 @@
+use std::collections::BTreeMap;
+use std::mem;
+use std::ops::{Deref, DerefMut};
 use serde::{Deserialize, Serialize};
 use crate::block::Block;
+use crate::block::loot::BreakContext;
+use crate::block_entity::BlockEntity;
 use crate::chunk::BLOCKS_PER_CHUNK;
+use crate::chunk::heightmap::Heightmap;
 use crate::chunk::location::ChunkLocation;
 use crate::chunk::pos::ChunkPos;
+use crate::chunk::storage::BlockStorage;
+use crate::item::ItemStack;
 
-pub type ChunkBlocks = [Block; BLOCKS_PER_CHUNK];
-
-#[serde_with::serde_as]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChunkData {
     pub location: ChunkLocation,
-    #[serde_as(as = "Box<[_; BLOCKS_PER_CHUNK]>")]
-    blocks: Box<ChunkBlocks>,
+    /// Stored as a palette of the distinct blocks, or just a single block if the whole chunk is the same,
+    /// since a full array of blocks would cost about a megabyte per chunk.
+    blocks: BlockStorage,
+    /// State of the blocks that need more than the block itself, such as the inventory of a crate.
+    block_entities: BTreeMap<ChunkPos, Box<dyn BlockEntity>>,
+    /// The block ticks asked for in the chunk, by the tick of the world they're due on.
+    /// Only the host ticks blocks, so they aren't sent to clients.
+    #[serde(skip)]
+    scheduled_ticks: BTreeMap<ChunkPos, u64>,
+    /// Whether the chunk was changed since it was generated or loaded from a save,
+    /// separate from whether its mesh needs rebaking. Unmodified chunks don't need saving,
+    /// since they can always be generated again.
+    #[serde(skip)]
+    modified: bool,
+    /// Where world gen put the terrain, for chunks that came from world gen. It isn't saved,
+    /// since it's the same every time it's generated, but it's sent to clients to light the chunk with.
+    surface: Option<Heightmap>,
 }
 
 impl ChunkData {
     pub fn empty(location: ChunkLocation) -> Self {
+        Self::filled(location, Block::AIR)
+    }
+
+    pub fn filled(location: ChunkLocation, block: Block) -> Self {
         Self {
             location,
-            blocks: Box::new([const { Block::Air }; BLOCKS_PER_CHUNK]),
+            blocks: BlockStorage::Uniform(block),
+            block_entities: BTreeMap::new(),
+            scheduled_ticks: BTreeMap::new(),
+            modified: false,
+            surface: None,
         }
     }
 
-    pub fn blocks_ref(&self) -> &ChunkBlocks {
-        &self.blocks
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    pub fn mark_modified(&mut self) {
+        self.modified = true;
+    }
+
+    /// Used once a copy of the chunk was handed to the saver, so it isn't saved again until it next changes.
+    pub fn clear_modified(&mut self) {
+        self.modified = false;
+    }
+
+    pub fn surface(&self) -> Option<&Heightmap> {
+        self.surface.as_ref()
     }
 
-    pub fn blocks_mut(&mut self) -> &mut ChunkBlocks {
-        &mut self.blocks
+    pub fn set_surface(&mut self, surface: Heightmap) {
+        self.surface = Some(surface);
     }
 
-    pub fn block_mut(&mut self, pos: ChunkPos) -> &mut Block {
-        &mut self.blocks[pos.0 as usize]
+    /// Every block in the chunk, in the order of their [`ChunkPos`].
+    pub fn blocks_ref(&self) -> impl ExactSizeIterator<Item = &Block> + Clone + '_ {
+        (0..BLOCKS_PER_CHUNK).map(|i| self.blocks.get(i))
+    }
+
+    /// The block making up the whole chunk, if it's all the same block.
+    pub fn uniform(&self) -> Option<&Block> {
+        match &self.blocks {
+            BlockStorage::Uniform(block) => Some(block),
+            BlockStorage::Paletted(_) => None,
+        }
+    }
+
+    /// How many distinct blocks are in the chunk.
+    pub fn palette_len(&self) -> usize {
+        self.blocks.palette_len()
+    }
+
+    /// Writes the block back once the returned guard is dropped, since blocks aren't stored individually.
+    pub fn block_mut(&mut self, pos: ChunkPos) -> BlockMut<'_> {
+        let block = *self.block_ref(pos);
+
+        BlockMut { data: self, pos, block }
     }
 
     pub fn block_ref(&self, pos: ChunkPos) -> &Block {
-        &self.blocks[pos.0 as usize]
+        self.blocks.get(pos.0 as usize)
+    }
+
+    /// Returns the block that was replaced. Block entities are left as they are, see [`ChunkData::replace`].
+    pub fn set(&mut self, pos: ChunkPos, block: Block) -> Block {
+        self.blocks.set(pos.0 as usize, block)
+    }
+
+    /// Places the block, removing the block entity of the block it replaced
+    /// and creating a new one if the block has one.
+    pub fn replace(&mut self, pos: ChunkPos, block: Block) -> ReplacedBlock {
+        let kind = block.block_entity_kind();
+
+        let block = self.set(pos, block);
+        let entity = self.block_entities.remove(&pos);
+
+        if let Some(kind) = kind {
+            self.block_entities.insert(pos, kind.create());
+        }
+
+        ReplacedBlock { block, entity }
+    }
+
+    pub fn block_entity_ref(&self, pos: ChunkPos) -> Option<&dyn BlockEntity> {
+        self.block_entities.get(&pos).map(Box::as_ref)
+    }
+
+    pub fn block_entity_mut(&mut self, pos: ChunkPos) -> Option<&mut dyn BlockEntity> {
+        self.block_entities.get_mut(&pos).map(Box::as_mut)
+    }
+
+    /// Replaces the block entity at `pos`, as long as it's the kind the block there has.
+    pub fn set_block_entity(&mut self, pos: ChunkPos, entity: Box<dyn BlockEntity>) -> Result<(), Box<dyn BlockEntity>> {
+        if self.block_ref(pos).block_entity_kind() != Some(entity.kind()) {
+            return Err(entity);
+        }
+
+        self.block_entities.insert(pos, entity);
+
+        Ok(())
     }
-}
\ No newline at end of file
+
+    /// Every block entity in the chunk, in the order of their [`ChunkPos`].
+    pub fn block_entities(&self) -> impl ExactSizeIterator<Item = (ChunkPos, &dyn BlockEntity)> + '_ {
+        self.block_entities.iter().map(|(pos, entity)| (*pos, entity.as_ref()))
+    }
+
+    pub fn block_entities_mut(&mut self) -> impl ExactSizeIterator<Item = (ChunkPos, &mut dyn BlockEntity)> + '_ {
+        self.block_entities.iter_mut().map(|(pos, entity)| (*pos, entity.as_mut()))
+    }
+
+    /// Ticks the block at `pos` on tick `due` of the world, unless it's already due to be ticked sooner.
+    pub fn schedule_tick(&mut self, pos: ChunkPos, due: u64) {
+        self.scheduled_ticks
+            .entry(pos)
+            .and_modify(|scheduled| *scheduled = (*scheduled).min(due))
+            .or_insert(due);
+    }
+
+    /// Every scheduled tick in the chunk, in the order of their [`ChunkPos`].
+    pub fn scheduled_ticks(&self) -> impl ExactSizeIterator<Item = (ChunkPos, u64)> + '_ {
+        self.scheduled_ticks.iter().map(|(pos, due)| (*pos, *due))
+    }
+
+    /// Removes the ticks that are due by tick `now` of the world, returning where they were.
+    pub fn take_due_ticks(&mut self, now: u64) -> Vec<ChunkPos> {
+        let due = self.scheduled_ticks
+            .iter()
+            .filter(|(_, due)| **due <= now)
+            .map(|(pos, _)| *pos)
+            .collect::<Vec<_>>();
+
+        for pos in &due {
+            self.scheduled_ticks.remove(pos);
+        }
+
+        due
+    }
+}
+
+/// A block that was replaced, along with its block entity if it had one.
+#[derive(Debug)]
+pub struct ReplacedBlock {
+    pub block: Block,
+    pub entity: Option<Box<dyn BlockEntity>>,
+}
+
+impl ReplacedBlock {
+    /// What the block drops when broken, along with anything its block entity was holding,
+    /// which is dropped even when the block itself can't be harvested.
+    pub fn on_break(self, ctx: &mut BreakContext) -> Vec<ItemStack> {
+        let mut drops = self.block.on_break(ctx);
+
+        if let Some(entity) = self.entity {
+            drops.extend(entity.on_remove());
+        }
+
+        drops
+    }
+}
+
+/// A copy of a block in a chunk, which replaces the block in the chunk when it's dropped.
+pub struct BlockMut<'a> {
+    data: &'a mut ChunkData,
+    pos: ChunkPos,
+    block: Block,
+}
+
+impl Deref for BlockMut<'_> {
+    type Target = Block;
+
+    fn deref(&self) -> &Self::Target {
+        &self.block
+    }
+}
+
+impl DerefMut for BlockMut<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.block
+    }
+}
+
+impl Drop for BlockMut<'_> {
+    fn drop(&mut self) {
+        self.data.set(self.pos, mem::take(&mut self.block));
+    }
+}
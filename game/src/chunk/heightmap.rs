@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+use crate::chunk::CHUNK_SIZE;
+
+/// The height of the terrain world gen put in each column of a chunk,
+/// which is where the sky is taken to stop whenever the chunk above isn't loaded.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Heightmap(Box<[i32]>);
+
+impl Heightmap {
+    /// Builds the heightmap from the height of each column, given its x and z in the chunk.
+    pub fn from_fn(mut height: impl FnMut(u8, u8) -> i32) -> Self {
+        let heights = (0..CHUNK_SIZE.z)
+            .flat_map(|z| (0..CHUNK_SIZE.x).map(move |x| (x, z)))
+            .map(|(x, z)| height(x, z))
+            .collect();
+
+        Self(heights)
+    }
+
+    /// Every column at the same height, such as a flat world.
+    pub fn flat(height: i32) -> Self {
+        Self::from_fn(|_, _| height)
+    }
+
+    /// The y of the top block of the terrain in the column.
+    pub fn get(&self, x: u8, z: u8) -> i32 {
+        self.0[z as usize * CHUNK_SIZE.x as usize + x as usize]
+    }
+
+    pub fn max(&self) -> i32 {
+        self.0.iter().copied().max().expect("a chunk has columns")
+    }
+}
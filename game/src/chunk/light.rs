@@ -0,0 +1,136 @@
+use crate::block::registry::MAX_LIGHT;
+use crate::chunk::BLOCKS_PER_CHUNK;
+use crate::chunk::pos::ChunkPos;
+
+/// Sky light comes down from the sky, and block light from blocks that give off light.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LightKind {
+    Sky,
+    Block,
+}
+
+impl LightKind {
+    pub const ALL: [LightKind; 2] = [LightKind::Sky, LightKind::Block];
+}
+
+/// The light at a block, each from 0 up to [`MAX_LIGHT`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Light {
+    pub sky: u8,
+    pub block: u8,
+}
+
+impl Light {
+    pub const SKY: Self = Self { sky: MAX_LIGHT, block: 0 };
+
+    pub fn get(self, kind: LightKind) -> u8 {
+        match kind {
+            LightKind::Sky => self.sky,
+            LightKind::Block => self.block,
+        }
+    }
+
+    /// How lit the block is, which is whichever of its lights is brighter.
+    pub fn level(self) -> u8 {
+        self.sky.max(self.block)
+    }
+
+    fn pack(self) -> u8 {
+        self.sky << 4 | self.block
+    }
+
+    fn unpack(packed: u8) -> Self {
+        Self {
+            sky: packed >> 4,
+            block: packed & 0xF,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Levels {
+    Uniform(u8),
+    Full(Box<[u8]>),
+}
+
+/// The light of every block in a chunk, packed into a byte each. It's worked out from the blocks
+/// whenever a chunk is loaded, so it's never saved. Chunks lit the same throughout, like open sky
+/// or solid rock, don't keep a level for each block.
+#[derive(Clone, Debug)]
+pub struct ChunkLight(Levels);
+
+impl Default for ChunkLight {
+    fn default() -> Self {
+        Self::uniform(Light::default())
+    }
+}
+
+impl ChunkLight {
+    pub fn uniform(light: Light) -> Self {
+        Self(Levels::Uniform(light.pack()))
+    }
+
+    pub fn uniform_light(&self) -> Option<Light> {
+        match &self.0 {
+            Levels::Uniform(packed) => Some(Light::unpack(*packed)),
+            Levels::Full(_) => None,
+        }
+    }
+
+    pub fn get(&self, pos: ChunkPos) -> Light {
+        match &self.0 {
+            Levels::Uniform(packed) => Light::unpack(*packed),
+            Levels::Full(levels) => Light::unpack(levels[pos.0 as usize]),
+        }
+    }
+
+    /// Returns whether the light changed.
+    pub fn set(&mut self, pos: ChunkPos, kind: LightKind, level: u8) -> bool {
+        debug_assert!(level <= MAX_LIGHT, "light can't be brighter than {MAX_LIGHT}");
+
+        let mut light = self.get(pos);
+
+        if light.get(kind) == level {
+            return false;
+        }
+
+        match kind {
+            LightKind::Sky => light.sky = level,
+            LightKind::Block => light.block = level,
+        }
+
+        if let Levels::Uniform(packed) = self.0 {
+            self.0 = Levels::Full(vec![packed; BLOCKS_PER_CHUNK].into_boxed_slice());
+        }
+
+        let Levels::Full(levels) = &mut self.0 else {
+            unreachable!("was just filled in");
+        };
+
+        levels[pos.0 as usize] = light.pack();
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sets_each_kind_of_light() {
+        let mut light = ChunkLight::uniform(Light::SKY);
+        let pos = ChunkPos::new_unchecked(3, 40, 17);
+
+        assert!(!light.set(pos, LightKind::Sky, MAX_LIGHT), "setting the same level shouldn't change anything");
+        assert_eq!(light.uniform_light(), Some(Light::SKY));
+
+        assert!(light.set(pos, LightKind::Block, 9));
+        assert!(light.set(pos, LightKind::Sky, 2));
+
+        assert_eq!(light.get(pos), Light { sky: 2, block: 9 });
+        assert_eq!(light.get(pos).level(), 9);
+        assert_eq!(light.get(ChunkPos::new_unchecked(3, 41, 17)), Light::SKY);
+        assert_eq!(light.uniform_light(), None);
+    }
+}
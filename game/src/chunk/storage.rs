@@ -0,0 +1,352 @@
+use std::mem;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::block::Block;
+use crate::chunk::BLOCKS_PER_CHUNK;
+
+const WORD_BITS: u32 = u64::BITS;
+
+/// Indices packed into words at a power of two width, so an index never straddles two words.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct PackedIndices {
+    bits: u32,
+    words: Box<[u64]>,
+}
+
+impl PackedIndices {
+    const MIN_BITS: u32 = 1;
+    const MAX_BITS: u32 = 16;
+
+    fn zeroed(bits: u32) -> Self {
+        Self {
+            bits,
+            words: vec![0; Self::words_for(bits)].into_boxed_slice(),
+        }
+    }
+
+    fn words_for(bits: u32) -> usize {
+        BLOCKS_PER_CHUNK * bits as usize / WORD_BITS as usize
+    }
+
+    fn is_valid_width(bits: u32) -> bool {
+        bits.is_power_of_two() && (Self::MIN_BITS..=Self::MAX_BITS).contains(&bits)
+    }
+
+    fn capacity(&self) -> usize {
+        1 << self.bits
+    }
+
+    fn get(&self, i: usize) -> usize {
+        let per_word = (WORD_BITS / self.bits) as usize;
+        let shift = (i % per_word) as u32 * self.bits;
+
+        ((self.words[i / per_word] >> shift) & ((1 << self.bits) - 1)) as usize
+    }
+
+    fn set(&mut self, i: usize, index: usize) {
+        debug_assert!(index < self.capacity(), "index doesn't fit in the current width");
+
+        let per_word = (WORD_BITS / self.bits) as usize;
+        let shift = (i % per_word) as u32 * self.bits;
+        let mask = ((1 << self.bits) - 1) << shift;
+
+        let word = &mut self.words[i / per_word];
+
+        *word = (*word & !mask) | ((index as u64) << shift);
+    }
+
+    fn widened(&self) -> Self {
+        let mut wider = Self::zeroed(self.bits * 2);
+
+        for i in 0..BLOCKS_PER_CHUNK {
+            wider.set(i, self.get(i));
+        }
+
+        wider
+    }
+}
+
+/// Blocks stored as indices into a palette of the distinct blocks in the chunk.
+#[derive(Debug, Clone)]
+pub(crate) struct PalettedBlocks {
+    palette: Vec<Block>,
+    /// How many blocks use each palette entry. Unused entries are reused before the palette grows.
+    counts: Vec<u32>,
+    indices: PackedIndices,
+}
+
+impl PalettedBlocks {
+    fn filled(block: Block) -> Self {
+        Self {
+            palette: vec![block],
+            counts: vec![BLOCKS_PER_CHUNK as _],
+            indices: PackedIndices::zeroed(PackedIndices::MIN_BITS),
+        }
+    }
+
+    fn get(&self, i: usize) -> &Block {
+        &self.palette[self.indices.get(i)]
+    }
+
+    /// Returns the previous block, along with whether the chunk is now entirely the new block.
+    fn set(&mut self, i: usize, block: Block) -> (Block, bool) {
+        let old = self.indices.get(i);
+
+        if self.palette[old] == block {
+            return (block, false);
+        }
+
+        self.counts[old] -= 1;
+
+        let prev = self.palette[old];
+
+        let new = self.index_of(block);
+
+        self.counts[new] += 1;
+        self.indices.set(i, new);
+
+        (prev, self.counts[new] == BLOCKS_PER_CHUNK as u32)
+    }
+
+    /// Finds the palette entry for the block, adding it if there isn't one.
+    fn index_of(&mut self, block: Block) -> usize {
+        if let Some(index) = (0..self.palette.len()).find(|&j| self.counts[j] > 0 && self.palette[j] == block) {
+            return index;
+        }
+
+        if let Some(index) = self.counts.iter().position(|count| *count == 0) {
+            self.palette[index] = block;
+            return index;
+        }
+
+        if self.palette.len() == self.indices.capacity() {
+            self.indices = self.indices.widened();
+        }
+
+        self.palette.push(block);
+        self.counts.push(0);
+
+        self.palette.len() - 1
+    }
+}
+
+/// Blocks of a chunk, which only need a palette and indices once the chunk isn't a single block.
+#[derive(Debug, Clone)]
+pub(crate) enum BlockStorage {
+    Uniform(Block),
+    Paletted(PalettedBlocks),
+}
+
+impl BlockStorage {
+    pub fn get(&self, i: usize) -> &Block {
+        match self {
+            Self::Uniform(block) => block,
+            Self::Paletted(paletted) => paletted.get(i),
+        }
+    }
+
+    pub fn set(&mut self, i: usize, block: Block) -> Block {
+        match self {
+            Self::Uniform(current) if *current == block => block,
+            Self::Uniform(current) => {
+                let mut paletted = PalettedBlocks::filled(mem::take(current));
+
+                let (prev, _) = paletted.set(i, block);
+
+                *self = Self::Paletted(paletted);
+
+                prev
+            }
+            Self::Paletted(paletted) => {
+                let (prev, uniform) = paletted.set(i, block);
+
+                if uniform {
+                    let block = *paletted.get(i);
+
+                    *self = Self::Uniform(block);
+                }
+
+                prev
+            }
+        }
+    }
+
+    pub fn palette_len(&self) -> usize {
+        match self {
+            Self::Uniform(_) => 1,
+            Self::Paletted(paletted) => paletted.counts.iter().filter(|count| **count > 0).count(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum InvalidBlockStorage {
+    #[error("index width of {0} bits isn't supported")]
+    Width(u32),
+    #[error("expected {expected} words of indices, got {actual}")]
+    WordCount { expected: usize, actual: usize },
+    #[error("palette of {0} blocks doesn't fit in the index width")]
+    PaletteSize(usize),
+    #[error("index {0} is outside the palette")]
+    Index(usize),
+}
+
+#[derive(Serialize)]
+enum BlockStorageRef<'a> {
+    Uniform(&'a Block),
+    Paletted { palette: &'a [Block], bits: u32, words: &'a [u64] },
+}
+
+#[derive(Deserialize)]
+enum BlockStorageOwned {
+    Uniform(Block),
+    Paletted { palette: Vec<Block>, bits: u32, words: Vec<u64> },
+}
+
+impl TryFrom<BlockStorageOwned> for BlockStorage {
+    type Error = InvalidBlockStorage;
+
+    fn try_from(storage: BlockStorageOwned) -> Result<Self, Self::Error> {
+        let (palette, bits, words) = match storage {
+            BlockStorageOwned::Uniform(block) => return Ok(Self::Uniform(block)),
+            BlockStorageOwned::Paletted { palette, bits, words } => (palette, bits, words),
+        };
+
+        if !PackedIndices::is_valid_width(bits) {
+            return Err(InvalidBlockStorage::Width(bits));
+        }
+
+        if words.len() != PackedIndices::words_for(bits) {
+            return Err(InvalidBlockStorage::WordCount { expected: PackedIndices::words_for(bits), actual: words.len() });
+        }
+
+        let indices = PackedIndices { bits, words: words.into_boxed_slice() };
+
+        if palette.is_empty() || palette.len() > indices.capacity() {
+            return Err(InvalidBlockStorage::PaletteSize(palette.len()));
+        }
+
+        // counts aren't sent, since they can always be recounted from the indices
+        let mut counts = vec![0u32; palette.len()];
+
+        for i in 0..BLOCKS_PER_CHUNK {
+            let index = indices.get(i);
+
+            *counts.get_mut(index).ok_or(InvalidBlockStorage::Index(index))? += 1;
+        }
+
+        Ok(Self::Paletted(PalettedBlocks { palette, counts, indices }))
+    }
+}
+
+impl Serialize for BlockStorage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Uniform(block) => BlockStorageRef::Uniform(block),
+            Self::Paletted(PalettedBlocks { palette, indices, .. }) => BlockStorageRef::Paletted {
+                palette,
+                bits: indices.bits,
+                words: &indices.words,
+            },
+        }
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockStorage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        BlockStorageOwned::deserialize(deserializer)?
+            .try_into()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block::face_type::Axis;
+    use super::*;
+
+    #[test]
+    fn test_palette_grows_and_collapses() {
+        let mut storage = BlockStorage::Uniform(Block::AIR);
+
+        assert_eq!(storage.set(5, Block::AIR), Block::AIR);
+        assert!(matches!(storage, BlockStorage::Uniform(_)));
+
+        let blocks = [Block::STONE, Block::DIRT, Block::GRASS, Block::LOG.rotated(Axis::X), Block::LOG.rotated(Axis::Y)];
+
+        for (i, block) in blocks.iter().enumerate() {
+            assert_eq!(storage.set(i * 1000, *block), Block::AIR);
+        }
+
+        assert_eq!(storage.palette_len(), blocks.len() + 1);
+
+        for (i, block) in blocks.iter().enumerate() {
+            assert_eq!(storage.get(i * 1000), block);
+            assert_eq!(storage.get(i * 1000 + 1), &Block::AIR);
+        }
+
+        // freed entries get reused rather than growing the palette
+        assert_eq!(storage.set(0, Block::AIR), Block::STONE);
+        assert_eq!(storage.set(0, Block::WATER), Block::AIR);
+        assert_eq!(storage.palette_len(), blocks.len() + 1);
+
+        for i in 0..BLOCKS_PER_CHUNK {
+            storage.set(i, Block::COBBLESTONE);
+        }
+
+        assert!(matches!(storage, BlockStorage::Uniform(Block::COBBLESTONE)));
+    }
+
+    #[test]
+    fn test_every_width_round_trips() {
+        for bits in [1, 2, 4, 8, 16] {
+            let mut indices = PackedIndices::zeroed(bits);
+            let capacity = indices.capacity();
+            let expected = |i: usize| i * 7 % capacity;
+
+            for i in 0..BLOCKS_PER_CHUNK {
+                indices.set(i, expected(i));
+            }
+
+            let wider = (bits < PackedIndices::MAX_BITS).then(|| indices.widened());
+
+            for i in 0..BLOCKS_PER_CHUNK {
+                assert_eq!(indices.get(i), expected(i));
+                assert!(wider.as_ref().is_none_or(|wider| wider.get(i) == expected(i)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_storage_round_trips() {
+        let blocks = [
+            Block::AIR, Block::GRASS, Block::DIRT, Block::COBBLESTONE, Block::STONE, Block::LEAF, Block::DEBUG, Block::CRATE,
+            Block::STONE_BRICK, Block::PLANKS, Block::WATER, Block::HEMATITE_DEPOSIT,
+            Block::LOG.rotated(Axis::X), Block::LOG.rotated(Axis::Y), Block::LOG.rotated(Axis::Z),
+        ];
+
+        for distinct in [2, 3, 5, blocks.len()] {
+            let mut storage = BlockStorage::Uniform(Block::AIR);
+
+            for i in 0..BLOCKS_PER_CHUNK {
+                storage.set(i, blocks[i % distinct]);
+            }
+
+            assert_eq!(storage.palette_len(), distinct);
+
+            let bytes = postcard::to_allocvec(&storage).expect("should serialize");
+            let decoded = postcard::from_bytes::<BlockStorage>(&bytes).expect("should deserialize");
+
+            for i in 0..BLOCKS_PER_CHUNK {
+                assert_eq!(storage.get(i), decoded.get(i));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejects_indices_outside_palette() {
+        let storage = BlockStorageOwned::Paletted { palette: vec![Block::AIR], bits: 1, words: vec![u64::MAX; PackedIndices::words_for(1)] };
+
+        assert!(matches!(BlockStorage::try_from(storage), Err(InvalidBlockStorage::Index(1))));
+    }
+}
@@ -0,0 +1,608 @@
+use std::collections::HashMap;
+use std::num::NonZeroU8;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::{fs, io};
+use serde::Deserialize;
+use crate::definitions::{definition_files, is_namespaced};
+use crate::inventory::Inventory;
+use crate::item::{ItemStack, ItemType};
+
+/// The recipes every world has, which mods can add to but not change.
+const BASE_DEFINITIONS: &str = include_str!("../assets/recipes.toml");
+
+/// Recipes are laid out in a square grid this wide.
+pub const GRID_WIDTH: usize = 3;
+pub const GRID_SIZE: usize = GRID_WIDTH * GRID_WIDTH;
+
+static GLOBAL: OnceLock<RecipeBook> = OnceLock::new();
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Ingredient {
+    Item(ItemType),
+    /// Any item with the tag.
+    Tag(String),
+}
+
+#[derive(Clone, Debug)]
+pub enum RecipeShape {
+    /// Rows of `width` cells, trimmed to the smallest rectangle holding every ingredient.
+    /// The grid has to have the same layout, but it can be anywhere in the grid.
+    Shaped { width: usize, cells: Vec<Option<Ingredient>> },
+    /// Each ingredient takes up one slot, in any arrangement, with no other slots used.
+    Shapeless(Vec<Ingredient>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Recipe {
+    /// Namespaced, such as `protovox:planks`.
+    pub name: String,
+    pub shape: RecipeShape,
+    pub output: ItemType,
+    pub count: NonZeroU8,
+}
+
+/// Processed by machines one item at a time, rather than crafted in the grid.
+#[derive(Clone, Debug)]
+pub struct SmeltingRecipe {
+    /// Namespaced, and unique among every kind of recipe.
+    pub name: String,
+    pub input: Ingredient,
+    pub output: ItemType,
+    pub count: NonZeroU8,
+    /// How long it takes to process one input.
+    pub time: Duration,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OutputFile {
+    item: ItemType,
+    #[serde(default = "OutputFile::default_count")]
+    count: u8,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecipeFile {
+    name: String,
+    shaped: Option<Vec<String>>,
+    #[serde(default)]
+    keys: HashMap<String, String>,
+    shapeless: Option<Vec<String>>,
+    output: OutputFile,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SmeltingRecipeFile {
+    name: String,
+    input: String,
+    output: OutputFile,
+    /// In seconds.
+    time: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DefinitionsFile {
+    #[serde(default)]
+    recipes: Vec<RecipeFile>,
+    #[serde(default)]
+    smelting: Vec<SmeltingRecipeFile>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RecipeBookError {
+    #[error("failed to read recipes from {0:?}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("invalid recipes in {0}: {1}")]
+    Parse(String, toml::de::Error),
+    #[error("recipe name {0:?} should be namespaced, like \"namespace:name\"")]
+    InvalidName(String),
+    #[error("recipe {0} is defined more than once")]
+    Duplicate(String),
+    #[error("recipe {0} should either have rows of the same length that aren't all empty, or a list of ingredients, but not both")]
+    InvalidShape(String),
+    #[error("recipe {0} doesn't fit in the {GRID_WIDTH}x{GRID_WIDTH} crafting grid")]
+    TooLarge(String),
+    #[error("recipe {recipe} uses key {key:?}, which should be a single character in its keys")]
+    UnknownKey { recipe: String, key: String },
+    #[error("recipe {recipe} uses item {item}, which isn't defined")]
+    UnknownItem { recipe: String, item: String },
+    #[error("recipe {recipe} uses tag {tag:?}, which should be namespaced, like \"#namespace:name\"")]
+    InvalidTag { recipe: String, tag: String },
+    #[error("recipe {0} makes no items")]
+    InvalidCount(String),
+    #[error("recipe {0} should take a positive number of seconds")]
+    InvalidTime(String),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, thiserror::Error)]
+pub enum CraftError {
+    #[error("the grid doesn't match the recipe")]
+    NoMatch,
+    #[error("there isn't room for what the recipe makes")]
+    NoRoom,
+}
+
+impl OutputFile {
+    fn default_count() -> u8 {
+        1
+    }
+}
+
+impl Ingredient {
+    pub fn accepts(&self, ty: ItemType) -> bool {
+        match self {
+            Self::Item(item) => *item == ty,
+            Self::Tag(tag) => ty.has_tag(tag),
+        }
+    }
+}
+
+impl Ingredient {
+    /// Parses an item name, or a tag prefixed with `#`, used by `recipe`.
+    fn parse(recipe: &str, source: &str) -> Result<Self, RecipeBookError> {
+        match source.strip_prefix('#') {
+            Some(tag) if is_namespaced(tag) => Ok(Self::Tag(tag.to_string())),
+            Some(tag) => Err(RecipeBookError::InvalidTag { recipe: recipe.to_string(), tag: tag.to_string() }),
+            None => ItemType::by_name(source)
+                .map(Self::Item)
+                .ok_or_else(|| RecipeBookError::UnknownItem { recipe: recipe.to_string(), item: source.to_string() }),
+        }
+    }
+}
+
+impl RecipeFile {
+    fn ingredient(&self, source: &str) -> Result<Ingredient, RecipeBookError> {
+        Ingredient::parse(&self.name, source)
+    }
+
+    fn shaped(&self, rows: &[String]) -> Result<RecipeShape, RecipeBookError> {
+        let invalid = || RecipeBookError::InvalidShape(self.name.clone());
+
+        let rows = rows.iter().map(|row| row.chars().collect::<Vec<_>>()).collect::<Vec<_>>();
+        let row_len = rows.first().map_or(0, Vec::len);
+
+        if rows.iter().any(|row| row.len() != row_len) {
+            return Err(invalid());
+        }
+
+        let used = |c: &char| *c != ' ';
+
+        let used_rows = rows.iter().position(|row| row.iter().any(used)).ok_or_else(invalid)?
+            ..=rows.iter().rposition(|row| row.iter().any(used)).ok_or_else(invalid)?;
+
+        let used_columns = (0..row_len).position(|i| rows.iter().any(|row| used(&row[i]))).ok_or_else(invalid)?
+            ..=(0..row_len).rposition(|i| rows.iter().any(|row| used(&row[i]))).ok_or_else(invalid)?;
+
+        if used_rows.clone().count() > GRID_WIDTH || used_columns.clone().count() > GRID_WIDTH {
+            return Err(RecipeBookError::TooLarge(self.name.clone()));
+        }
+
+        let cells = rows[used_rows]
+            .iter()
+            .flat_map(|row| &row[used_columns.clone()])
+            .map(|&key| {
+                if key == ' ' {
+                    return Ok(None);
+                }
+
+                let source = self.keys
+                    .get(key.encode_utf8(&mut [0; 4]) as &str)
+                    .ok_or_else(|| RecipeBookError::UnknownKey { recipe: self.name.clone(), key: key.to_string() })?;
+
+                self.ingredient(source).map(Some)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(key) = self.keys.keys().find(|key| key.chars().count() != 1) {
+            return Err(RecipeBookError::UnknownKey { recipe: self.name.clone(), key: key.clone() });
+        }
+
+        Ok(RecipeShape::Shaped { width: used_columns.count(), cells })
+    }
+
+    fn resolve(self) -> Result<Recipe, RecipeBookError> {
+        if !is_namespaced(&self.name) {
+            return Err(RecipeBookError::InvalidName(self.name));
+        }
+
+        let shape = match (&self.shaped, &self.shapeless) {
+            (Some(rows), None) => self.shaped(rows)?,
+            (None, Some(ingredients)) if !ingredients.is_empty() => {
+                if ingredients.len() > GRID_SIZE {
+                    return Err(RecipeBookError::TooLarge(self.name));
+                }
+
+                RecipeShape::Shapeless(ingredients
+                    .iter()
+                    .map(|source| self.ingredient(source))
+                    .collect::<Result<Vec<_>, _>>()?)
+            }
+            _ => return Err(RecipeBookError::InvalidShape(self.name)),
+        };
+
+        let count = NonZeroU8::new(self.output.count).ok_or_else(|| RecipeBookError::InvalidCount(self.name.clone()))?;
+
+        Ok(Recipe {
+            name: self.name,
+            shape,
+            output: self.output.item,
+            count,
+        })
+    }
+}
+
+impl SmeltingRecipeFile {
+    fn resolve(self) -> Result<SmeltingRecipe, RecipeBookError> {
+        if !is_namespaced(&self.name) {
+            return Err(RecipeBookError::InvalidName(self.name));
+        }
+
+        let input = Ingredient::parse(&self.name, &self.input)?;
+
+        let count = NonZeroU8::new(self.output.count).ok_or_else(|| RecipeBookError::InvalidCount(self.name.clone()))?;
+
+        let time = Duration::try_from_secs_f32(self.time)
+            .ok()
+            .filter(|time| !time.is_zero())
+            .ok_or_else(|| RecipeBookError::InvalidTime(self.name.clone()))?;
+
+        Ok(SmeltingRecipe {
+            name: self.name,
+            input,
+            output: self.output.item,
+            count,
+            time,
+        })
+    }
+}
+
+impl SmeltingRecipe {
+    /// What processing one input makes.
+    pub fn output(&self) -> ItemStack {
+        self.output.default_item().with_count(self.count)
+    }
+}
+
+impl Recipe {
+    /// One stack of what the recipe makes.
+    pub fn output(&self) -> ItemStack {
+        self.output.default_item().with_count(self.count)
+    }
+
+    /// Whether the grid, `width` slots wide, holds the ingredients of the recipe.
+    pub fn matches(&self, grid: &impl Inventory, width: usize) -> bool {
+        self.slots_used(grid, width).is_some()
+    }
+
+    /// Takes one item from each slot of the grid the recipe uses, and puts what it makes in `output`.
+    /// If it doesn't all fit, both are left as they were.
+    pub fn craft(&self, grid: &mut impl Inventory, width: usize, output: &mut impl Inventory) -> Result<(), CraftError> {
+        let slots = self.slots_used(grid, width).ok_or(CraftError::NoMatch)?;
+
+        let grid_before = grid.as_slice().to_vec();
+        let output_before = output.as_slice().to_vec();
+
+        for i in slots {
+            let slot = &mut grid.as_mut_slice()[i];
+
+            *slot = slot
+                .take()
+                .and_then(|stack| stack.split_at_most(NonZeroU8::MIN).1);
+        }
+
+        if output.try_insert(self.output()).is_some() {
+            grid.as_mut_slice().clone_from_slice(&grid_before);
+            output.as_mut_slice().clone_from_slice(&output_before);
+
+            return Err(CraftError::NoRoom);
+        }
+
+        Ok(())
+    }
+
+    /// Which slots an item is taken from to craft the recipe once, if the grid matches it.
+    fn slots_used(&self, grid: &impl Inventory, width: usize) -> Option<Vec<usize>> {
+        let slots = grid.as_slice();
+
+        let occupied = slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| slot.as_ref().map(|stack| (i, stack.item.ty)))
+            .collect::<Vec<_>>();
+
+        match &self.shape {
+            RecipeShape::Shaped { width: recipe_width, cells } => {
+                let left = occupied.iter().map(|(i, _)| i % width).min()?;
+                let right = occupied.iter().map(|(i, _)| i % width).max()?;
+                let top = occupied.iter().map(|(i, _)| i / width).min()?;
+                let bottom = occupied.iter().map(|(i, _)| i / width).max()?;
+
+                if right - left + 1 != *recipe_width || (bottom - top + 1) * recipe_width != cells.len() {
+                    return None;
+                }
+
+                // everything outside of the rectangle is empty, so only the cells inside it need checking
+                let matches = cells
+                    .iter()
+                    .enumerate()
+                    .all(|(i, cell)| {
+                        let slot = slots.get((top + i / recipe_width) * width + left + i % recipe_width).and_then(Option::as_ref);
+
+                        match (cell, slot) {
+                            (None, None) => true,
+                            (Some(ingredient), Some(stack)) => ingredient.accepts(stack.item.ty),
+                            _ => false,
+                        }
+                    });
+
+                matches.then(|| occupied.iter().map(|(i, _)| *i).collect())
+            }
+            RecipeShape::Shapeless(ingredients) => {
+                fn assign(ingredients: &[Ingredient], occupied: &[(usize, ItemType)], used: &mut [bool]) -> bool {
+                    let Some((ingredient, rest)) = ingredients.split_first() else {
+                        return true;
+                    };
+
+                    for (i, (_, ty)) in occupied.iter().enumerate() {
+                        if !used[i] && ingredient.accepts(*ty) {
+                            used[i] = true;
+
+                            if assign(rest, occupied, used) {
+                                return true;
+                            }
+
+                            used[i] = false;
+                        }
+                    }
+
+                    false
+                }
+
+                // tags can overlap with each other, so each ingredient might need to try every slot
+                (occupied.len() == ingredients.len() && assign(ingredients, &occupied, &mut vec![false; occupied.len()]))
+                    .then(|| occupied.iter().map(|(i, _)| *i).collect())
+            }
+        }
+    }
+}
+
+/// Every recipe, loaded from definition files. When more than one recipe matches a grid or an input,
+/// the one loaded first is used.
+#[derive(Debug)]
+pub struct RecipeBook {
+    recipes: Vec<Recipe>,
+    by_name: HashMap<String, usize>,
+    smelting: Vec<SmeltingRecipe>,
+}
+
+impl RecipeBook {
+    /// A recipe book of only the base recipes.
+    pub fn base() -> Self {
+        let mut book = Self {
+            recipes: Vec::new(),
+            by_name: HashMap::new(),
+            smelting: Vec::new(),
+        };
+
+        book
+            .add_definitions("base definitions", BASE_DEFINITIONS)
+            .expect("base recipes should be valid");
+
+        book
+    }
+
+    /// The base recipes, followed by those defined in the directory if it exists.
+    pub fn load(dir: &Path) -> Result<Self, RecipeBookError> {
+        let mut book = Self::base();
+
+        if dir.is_dir() {
+            book.add_definitions_in(dir)?;
+        }
+
+        Ok(book)
+    }
+
+    /// Adds every recipe defined in `source`, where `origin` is only used to describe errors.
+    /// Nothing is added if any of the definitions are invalid.
+    pub fn add_definitions(&mut self, origin: &str, source: &str) -> Result<(), RecipeBookError> {
+        let file = toml::from_str::<DefinitionsFile>(source)
+            .map_err(|err| RecipeBookError::Parse(origin.to_string(), err))?;
+
+        let recipes = file.recipes
+            .into_iter()
+            .map(RecipeFile::resolve)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let smelting = file.smelting
+            .into_iter()
+            .map(SmeltingRecipeFile::resolve)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut by_name = self.by_name.clone();
+
+        for (i, recipe) in recipes.iter().enumerate() {
+            if by_name.insert(recipe.name.clone(), self.recipes.len() + i).is_some() {
+                return Err(RecipeBookError::Duplicate(recipe.name.clone()));
+            }
+        }
+
+        for (i, recipe) in smelting.iter().enumerate() {
+            let mut earlier = self.smelting.iter().chain(&smelting[..i]);
+
+            if by_name.contains_key(&recipe.name) || earlier.any(|other| other.name == recipe.name) {
+                return Err(RecipeBookError::Duplicate(recipe.name.clone()));
+            }
+        }
+
+        self.recipes.extend(recipes);
+        self.by_name = by_name;
+        self.smelting.extend(smelting);
+
+        Ok(())
+    }
+
+    /// Adds the definitions from every `.toml` file in the directory, in order of their file names.
+    pub fn add_definitions_in(&mut self, dir: &Path) -> Result<(), RecipeBookError> {
+        let paths = definition_files(dir).map_err(|err| RecipeBookError::Io(dir.to_path_buf(), err))?;
+
+        for path in paths {
+            let source = fs::read_to_string(&path).map_err(|err| RecipeBookError::Io(path.clone(), err))?;
+
+            self.add_definitions(&path.display().to_string(), &source)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Recipe> {
+        self.by_name.get(name).map(|&i| &self.recipes[i])
+    }
+
+    /// The recipe the grid, `width` slots wide, holds the ingredients for.
+    pub fn find(&self, grid: &impl Inventory, width: usize) -> Option<&Recipe> {
+        self.recipes.iter().find(|recipe| recipe.matches(grid, width))
+    }
+
+    /// The smelting recipe that processes the item.
+    pub fn smelting_for(&self, ty: ItemType) -> Option<&SmeltingRecipe> {
+        self.smelting.iter().find(|recipe| recipe.input.accepts(ty))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Recipe> {
+        self.recipes.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.recipes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recipes.is_empty()
+    }
+
+    /// The recipes used for crafting, which are only the base recipes
+    /// unless another recipe book was installed before it was first used.
+    pub fn global() -> &'static Self {
+        GLOBAL.get_or_init(Self::base)
+    }
+
+    /// Makes this the global recipe book. Fails if the global recipe book was already used.
+    pub fn install(self) -> Result<(), Self> {
+        GLOBAL.set(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block::BlockInventory;
+    use super::*;
+
+    fn grid(slots: [Option<ItemType>; GRID_SIZE]) -> BlockInventory<GRID_SIZE> {
+        let mut grid = BlockInventory::default();
+
+        for (i, ty) in slots.into_iter().enumerate() {
+            if let Some(ty) = ty {
+                grid.try_insert_at(i, ty.default_one());
+            }
+        }
+
+        grid
+    }
+
+    #[test]
+    fn test_shaped_recipes_match_anywhere_in_the_grid() {
+        let book = RecipeBook::base();
+        let stone = Some(ItemType::STONE);
+
+        let bottom_right = grid([None, None, None, None, stone, stone, None, stone, stone]);
+        let diagonal = grid([stone, None, None, None, stone, stone, None, stone, stone]);
+
+        assert_eq!(book.find(&bottom_right, GRID_WIDTH).map(|recipe| recipe.name.as_str()), Some("protovox:stone_bricks"));
+        assert!(book.find(&diagonal, GRID_WIDTH).is_none());
+    }
+
+    #[test]
+    fn test_tags_and_shapeless_recipes_match() {
+        let book = RecipeBook::base();
+        let (log, planks) = (Some(ItemType::LOG), Some(ItemType::PLANKS));
+
+        let mixed_wood = grid([planks, log, planks, planks, None, planks, planks, log, planks]);
+        let one_log = grid([None, None, None, None, None, None, None, log, None]);
+
+        assert_eq!(book.find(&mixed_wood, GRID_WIDTH).map(|recipe| recipe.name.as_str()), Some("protovox:crate"));
+        assert_eq!(book.find(&one_log, GRID_WIDTH).map(|recipe| recipe.name.as_str()), Some("protovox:planks"));
+    }
+
+    #[test]
+    fn test_crafting_rolls_back_when_the_output_is_full() {
+        let recipe = RecipeBook::base().get("protovox:planks").expect("base recipe").clone();
+
+        let mut logs = grid([Some(ItemType::LOG), None, None, None, None, None, None, None, None]);
+        let mut output = BlockInventory::<1>::default();
+
+        output.try_insert_at(0, ItemType::DIRT.default_one());
+
+        assert_eq!(recipe.craft(&mut logs, GRID_WIDTH, &mut output), Err(CraftError::NoRoom));
+        assert_eq!(logs.as_slice()[0], Some(ItemType::LOG.default_one()));
+        assert_eq!(output.as_slice(), &[Some(ItemType::DIRT.default_one())]);
+
+        output.as_mut_slice()[0] = None;
+
+        assert_eq!(recipe.craft(&mut logs, GRID_WIDTH, &mut output), Ok(()));
+        assert!(logs.items().next().is_none());
+        assert_eq!(output.as_slice(), &[Some(recipe.output())]);
+    }
+
+    #[test]
+    fn test_rejects_invalid_definitions() {
+        let invalid = [
+            r#"[[recipes]]
+            name = "test:both"
+            shaped = ["L"]
+            keys = { L = "protovox:log" }
+            shapeless = ["protovox:log"]
+            output = { item = "protovox:planks" }"#,
+            r#"[[recipes]]
+            name = "test:too_wide"
+            shaped = ["LLLL"]
+            keys = { L = "protovox:log" }
+            output = { item = "protovox:planks" }"#,
+            r#"[[recipes]]
+            name = "test:missing_key"
+            shaped = ["LX"]
+            keys = { L = "protovox:log" }
+            output = { item = "protovox:planks" }"#,
+            r#"[[recipes]]
+            name = "test:unknown_item"
+            shapeless = ["protovox:nothing"]
+            output = { item = "protovox:planks" }"#,
+            r#"[[recipes]]
+            name = "protovox:planks"
+            shapeless = ["protovox:dirt"]
+            output = { item = "protovox:planks" }"#,
+            r#"[[smelting]]
+            name = "protovox:planks"
+            input = "protovox:dirt"
+            output = { item = "protovox:planks" }
+            time = 1.0"#,
+            r#"[[smelting]]
+            name = "test:instant"
+            input = "protovox:dirt"
+            output = { item = "protovox:planks" }
+            time = 0.0"#,
+        ];
+
+        for source in invalid {
+            let mut book = RecipeBook::base();
+
+            assert!(book.add_definitions("test", source).is_err(), "{source} should be rejected");
+            assert_eq!(book.len(), RecipeBook::base().len(), "nothing should be added from {source}");
+            assert!(book.smelting_for(ItemType::DIRT).is_none(), "nothing should be added from {source}");
+        }
+    }
+}
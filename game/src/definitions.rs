@@ -0,0 +1,31 @@
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// Whether a name from a definition file is namespaced, like `namespace:name`,
+/// with only lowercase letters, digits and underscores in either part.
+pub(crate) fn is_namespaced(name: &str) -> bool {
+    let is_valid_part = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+
+    name.split_once(':').is_some_and(|(namespace, name)| is_valid_part(namespace) && is_valid_part(name))
+}
+
+/// A hash of the names in id order, for checking that two registries give the same ids to the same names.
+/// It's FNV-1a, so it stays the same across Rust releases and platforms.
+pub(crate) fn fingerprint<'a>(names: impl IntoIterator<Item = &'a str>) -> u64 {
+    names
+        .into_iter()
+        .flat_map(|name| name.bytes().chain([0]))
+        .fold(0xCBF2_9CE4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3))
+}
+
+/// Every `.toml` file in the directory, in order of their file names.
+pub(crate) fn definition_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "toml"));
+    paths.sort();
+
+    Ok(paths)
+}
@@ -4,6 +4,9 @@ use crate::block::face_type::FaceType;
 use crate::item::{Item, ItemStack};
 use crate::location::BlockLocation;
 
+pub mod transaction;
+
+/// Slots out of range are treated like slots that can't hold anything, rather than panicking.
 pub trait Inventory {
     fn as_slice(&self) -> &[Option<ItemStack>];
 
@@ -35,15 +38,21 @@ pub trait Inventory {
         }
 
 
-        match self.as_mut_slice()
-            .iter_mut()
-            .find(|s| s.is_none())
-        {
-            None => return Some(residual),
-            Some(empty) => *empty = Some(residual),
+        // then fill empty slots, a full stack at a time
+        for empty in self.as_mut_slice().iter_mut().filter(|s| s.is_none()) {
+            let max_ct = residual.max_count();
+
+            let (stack, rem) = residual.split_at_most(max_ct);
+
+            *empty = Some(stack);
+
+            match rem {
+                Some(rem) => residual = rem,
+                None => return None,
+            }
         }
 
-        None
+        Some(residual)
     }
 
     fn try_insert_many(&mut self, items: impl IntoIterator<Item = ItemStack>) -> Vec<ItemStack> {
@@ -52,10 +61,8 @@ pub trait Inventory {
             .collect()
     }
 
-
-    // TODO: refactor these helper methods
     fn split_item_at(&mut self, slot: usize) -> Option<Item> {
-        let slot = self.as_mut_slice().get_mut(slot).expect("TODO: better error, slot out of range");
+        let slot = self.as_mut_slice().get_mut(slot)?;
 
         if let Some(it) = slot.take() {
             let (item, rem) = it.split_item();
@@ -69,7 +76,7 @@ pub trait Inventory {
     }
 
     fn split_at_most_at(&mut self, slot: usize, ct: NonZeroU8) -> Option<ItemStack> {
-        let slot = self.as_mut_slice().get_mut(slot).expect("TODO: better error, slot out of range");
+        let slot = self.as_mut_slice().get_mut(slot)?;
 
         if let Some(it) = slot.take() {
             let (it, rem) = it.split_at_most(ct);
@@ -83,7 +90,7 @@ pub trait Inventory {
     }
 
     fn split_exact_at(&mut self, slot: usize, ct: NonZeroU8) -> Option<ItemStack> {
-        let slot = self.as_mut_slice().get_mut(slot).expect("TODO: better error, slot out of range");
+        let slot = self.as_mut_slice().get_mut(slot)?;
 
         if let Some(it) = slot.take() {
             match it.split_exact(ct) {
@@ -104,25 +111,39 @@ pub trait Inventory {
     }
 
     fn try_insert_at(&mut self, slot: usize, it: ItemStack) -> Option<ItemStack> {
-        let slot = self.as_mut_slice().get_mut(slot).expect("TODO: better error, slot out of range");
+        let Some(slot) = self.as_mut_slice().get_mut(slot) else {
+            return Some(it);
+        };
 
         if let Some(slot) = slot {
             slot.try_combine(it)
         } else {
+            let max_ct = it.max_count();
+
+            let (it, rem) = it.split_at_most(max_ct);
+
             *slot = Some(it);
 
-            None
+            rem
         }
     }
 
+    /// Takes one item that places `block` from the first slot with one, however it's rotated.
+    fn take_placing(&mut self, block: Block) -> Option<Item> {
+        let slot = self.as_slice()
+            .iter()
+            .position(|slot| slot.as_ref().is_some_and(|stack| stack.item.ty.block() == Some(block.id())))?;
+
+        self.split_item_at(slot)
+    }
+
     fn try_get_place_at(&mut self, slot: usize, location: BlockLocation, face_type: FaceType) -> Option<Block> {
         if let Some(item) = self.split_item_at(slot) {
             match item.place(location, face_type) {
                 Ok(block) => Some(block),
                 Err(err_item) => {
-                    let rem = self.try_insert_at(slot, err_item.stack_one());
-
-                    assert!(rem.is_none(), "should have at least one free slot");
+                    // the slot was just taken from, so there's room to put it back
+                    self.try_insert_at(slot, err_item.stack_one());
 
                     None
                 }
@@ -131,4 +152,15 @@ pub trait Inventory {
             None
         }
     }
-}
\ No newline at end of file
+}
+
+/// Any run of slots, so that inventories can be worked on without knowing their type.
+impl Inventory for [Option<ItemStack>] {
+    fn as_slice(&self) -> &[Option<ItemStack>] {
+        self
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [Option<ItemStack>] {
+        self
+    }
+}
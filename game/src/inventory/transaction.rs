@@ -0,0 +1,340 @@
+use std::num::NonZeroU8;
+use serde::{Deserialize, Serialize};
+use crate::inventory::Inventory;
+use crate::item::{Item, ItemStack};
+
+/// A slot taking part in a [`Transaction`], either the item held by the cursor,
+/// or a slot in one of its inventories by the order they were added in.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum SlotRef {
+    Hand,
+    Slot { inventory: u8, index: u16 },
+}
+
+impl SlotRef {
+    pub fn at(inventory: u8, index: usize) -> Self {
+        Self::Slot { inventory, index: index.try_into().unwrap_or(u16::MAX) }
+    }
+}
+
+/// One change to the items in a [`Transaction`], small enough to be sent and checked by the server.
+/// Either all of it happens or none of it does.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum InventoryAction {
+    /// Moves exactly `count` items, which must all fit where they're moved to.
+    Move { from: SlotRef, to: SlotRef, count: NonZeroU8 },
+    Swap(SlotRef, SlotRef),
+    /// Moves the larger half of a stack into an empty slot.
+    Split { from: SlotRef, to: SlotRef },
+    /// Moves as much of a stack as fits into another inventory, as with shift-clicking.
+    QuickTransfer { from: SlotRef, to: u8 },
+    /// Fills up the hand with matching items from every inventory.
+    CollectToHand,
+}
+
+#[derive(Debug, Eq, PartialEq, thiserror::Error)]
+pub enum TransactionError {
+    #[error("there's no inventory {0} in the transaction")]
+    UnknownInventory(u8),
+    #[error("slot {index} is out of range for inventory {inventory}")]
+    SlotOutOfRange { inventory: u8, index: u16 },
+    #[error("there's nothing in {0:?}")]
+    EmptySlot(SlotRef),
+    #[error("{0:?} isn't empty")]
+    OccupiedSlot(SlotRef),
+    #[error("can't move {requested} items when there's only {available}")]
+    NotEnoughItems { requested: u8, available: u8 },
+    #[error("the items in {0:?} don't stack with what's being moved there")]
+    Mismatch(SlotRef),
+    #[error("there's no room for the items")]
+    NoRoom,
+    #[error("can't move items from {0:?} onto itself")]
+    SameSlot(SlotRef),
+}
+
+/// The hand and inventories that [`InventoryAction`]s move items between.
+pub struct Transaction<'a> {
+    hand: &'a mut Option<ItemStack>,
+    inventories: Vec<&'a mut [Option<ItemStack>]>,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn new(hand: &'a mut Option<ItemStack>) -> Self {
+        Self {
+            hand,
+            inventories: Vec::new(),
+        }
+    }
+
+    /// Adds an inventory, which is referred to by how many were added before it.
+    pub fn with<I: Inventory + ?Sized>(mut self, inventory: &'a mut I) -> Self {
+        self.inventories.push(inventory.as_mut_slice());
+        self
+    }
+
+    pub fn slot(&self, slot: SlotRef) -> Result<&Option<ItemStack>, TransactionError> {
+        match slot {
+            SlotRef::Hand => Ok(self.hand),
+            SlotRef::Slot { inventory, index } => self.inventories
+                .get(inventory as usize)
+                .ok_or(TransactionError::UnknownInventory(inventory))?
+                .get(index as usize)
+                .ok_or(TransactionError::SlotOutOfRange { inventory, index }),
+        }
+    }
+
+    fn slot_mut(&mut self, slot: SlotRef) -> Result<&mut Option<ItemStack>, TransactionError> {
+        match slot {
+            SlotRef::Hand => Ok(self.hand),
+            SlotRef::Slot { inventory, index } => self.inventories
+                .get_mut(inventory as usize)
+                .ok_or(TransactionError::UnknownInventory(inventory))?
+                .get_mut(index as usize)
+                .ok_or(TransactionError::SlotOutOfRange { inventory, index }),
+        }
+    }
+
+    /// How many of `item` could be added to the slot.
+    pub fn room_for(&self, slot: SlotRef, item: &Item) -> Result<u8, TransactionError> {
+        let room = match self.slot(slot)? {
+            None => item.ty.max_stack().get(),
+            Some(stack) if stack.item.stacks_with(item) => stack.max_count().get().saturating_sub(stack.count.get()),
+            Some(_) => 0,
+        };
+
+        Ok(room)
+    }
+
+    /// Applies the action, leaving everything as it was if it fails.
+    pub fn apply(&mut self, action: &InventoryAction) -> Result<(), TransactionError> {
+        let hand = self.hand.clone();
+        let inventories = self.inventories.iter().map(|inventory| inventory.to_vec()).collect::<Vec<_>>();
+
+        let result = self.apply_unchecked(action);
+
+        if result.is_err() {
+            *self.hand = hand;
+
+            for (inventory, before) in self.inventories.iter_mut().zip(inventories) {
+                inventory.clone_from_slice(&before);
+            }
+        }
+
+        result
+    }
+
+    fn apply_unchecked(&mut self, action: &InventoryAction) -> Result<(), TransactionError> {
+        match *action {
+            InventoryAction::Move { from, to, count } => self.move_items(from, to, count),
+            InventoryAction::Swap(a, b) => self.swap(a, b),
+            InventoryAction::Split { from, to } => self.split(from, to),
+            InventoryAction::QuickTransfer { from, to } => self.quick_transfer(from, to),
+            InventoryAction::CollectToHand => self.collect_to_hand(),
+        }
+    }
+
+    fn take(&mut self, slot: SlotRef) -> Result<ItemStack, TransactionError> {
+        self.slot_mut(slot)?.take().ok_or(TransactionError::EmptySlot(slot))
+    }
+
+    fn move_items(&mut self, from: SlotRef, to: SlotRef, count: NonZeroU8) -> Result<(), TransactionError> {
+        if from == to {
+            return Err(TransactionError::SameSlot(from));
+        }
+
+        // checked before anything is taken, so that a missing slot is reported as such
+        self.slot(to)?;
+
+        let stack = self.take(from)?;
+        let available = stack.count.get();
+
+        let (moved, rest) = stack
+            .split_exact(count)
+            .map_err(|_| TransactionError::NotEnoughItems { requested: count.get(), available })?;
+
+        *self.slot_mut(from)? = rest;
+
+        if self.room_for(to, &moved.item)? < count.get() {
+            return Err(match self.slot(to)? {
+                Some(stack) if !stack.item.stacks_with(&moved.item) => TransactionError::Mismatch(to),
+                _ => TransactionError::NoRoom,
+            });
+        }
+
+        match self.slot_mut(to)? {
+            Some(stack) => {
+                // there was room for all of it
+                stack.try_combine(moved);
+            }
+            empty => *empty = Some(moved),
+        }
+
+        Ok(())
+    }
+
+    fn swap(&mut self, a: SlotRef, b: SlotRef) -> Result<(), TransactionError> {
+        if a == b {
+            return Err(TransactionError::SameSlot(a));
+        }
+
+        let first = self.slot_mut(a)?.take();
+        let second = std::mem::replace(self.slot_mut(b)?, first);
+
+        *self.slot_mut(a)? = second;
+
+        Ok(())
+    }
+
+    fn split(&mut self, from: SlotRef, to: SlotRef) -> Result<(), TransactionError> {
+        if from == to {
+            return Err(TransactionError::SameSlot(from));
+        }
+
+        if self.slot(to)?.is_some() {
+            return Err(TransactionError::OccupiedSlot(to));
+        }
+
+        let (half, rest) = self.take(from)?.split_half();
+
+        *self.slot_mut(from)? = rest;
+        *self.slot_mut(to)? = Some(half);
+
+        Ok(())
+    }
+
+    fn quick_transfer(&mut self, from: SlotRef, to: u8) -> Result<(), TransactionError> {
+        if matches!(from, SlotRef::Slot { inventory, .. } if inventory == to) {
+            return Err(TransactionError::SameSlot(from));
+        }
+
+        let stack = self.take(from)?;
+        let count = stack.count;
+
+        let residual = self.inventories
+            .get_mut(to as usize)
+            .ok_or(TransactionError::UnknownInventory(to))?
+            .try_insert(stack);
+
+        if residual.as_ref().is_some_and(|residual| residual.count == count) {
+            return Err(TransactionError::NoRoom);
+        }
+
+        *self.slot_mut(from)? = residual;
+
+        Ok(())
+    }
+
+    fn collect_to_hand(&mut self) -> Result<(), TransactionError> {
+        let Some(hand) = self.hand.as_mut() else {
+            return Err(TransactionError::EmptySlot(SlotRef::Hand));
+        };
+
+        for slot in self.inventories.iter_mut().flat_map(|inventory| inventory.iter_mut()) {
+            if hand.count >= hand.max_count() {
+                break;
+            }
+
+            let Some(stack) = slot.take_if(|stack| stack.item.stacks_with(&hand.item)) else {
+                continue;
+            };
+
+            *slot = hand.try_combine(stack);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block::BlockInventory;
+    use crate::item::ItemType;
+    use super::*;
+
+    fn stack(ty: ItemType, count: u8) -> ItemStack {
+        ty.default_item().with_count(count.try_into().expect("nonzero"))
+    }
+
+    fn count(n: u8) -> NonZeroU8 {
+        NonZeroU8::new(n).expect("nonzero")
+    }
+
+    #[test]
+    fn test_failed_actions_change_nothing() {
+        let mut hand = Some(stack(ItemType::DIRT, 10));
+        let mut inventory = BlockInventory::<3>::default();
+        inventory.try_insert_at(0, stack(ItemType::STONE, 4));
+        inventory.try_insert_at(1, stack(ItemType::DIRT, 60));
+
+        let before = (hand.clone(), inventory.clone());
+        let mut transaction = Transaction::new(&mut hand).with(&mut inventory);
+
+        let failures = [
+            (InventoryAction::Move { from: SlotRef::Hand, to: SlotRef::at(0, 1), count: count(10) }, TransactionError::NoRoom),
+            (InventoryAction::Move { from: SlotRef::Hand, to: SlotRef::at(0, 0), count: count(1) }, TransactionError::Mismatch(SlotRef::at(0, 0))),
+            (InventoryAction::Move { from: SlotRef::at(0, 0), to: SlotRef::at(0, 2), count: count(5) }, TransactionError::NotEnoughItems { requested: 5, available: 4 }),
+            (InventoryAction::Move { from: SlotRef::Hand, to: SlotRef::at(0, 9), count: count(1) }, TransactionError::SlotOutOfRange { inventory: 0, index: 9 }),
+            (InventoryAction::Split { from: SlotRef::at(0, 0), to: SlotRef::at(0, 1) }, TransactionError::OccupiedSlot(SlotRef::at(0, 1))),
+            (InventoryAction::Swap(SlotRef::Hand, SlotRef::at(1, 0)), TransactionError::UnknownInventory(1)),
+        ];
+
+        for (action, err) in failures {
+            assert_eq!(transaction.apply(&action), Err(err));
+        }
+
+        drop(transaction);
+
+        assert_eq!((hand, inventory), before);
+    }
+
+    #[test]
+    fn test_swapping_a_slot_with_itself_keeps_its_stack() {
+        let mut hand = None;
+        let mut inventory = BlockInventory::<1>::default();
+        inventory.try_insert_at(0, stack(ItemType::STONE, 4));
+
+        let mut transaction = Transaction::new(&mut hand).with(&mut inventory);
+
+        assert_eq!(transaction.apply(&InventoryAction::Swap(SlotRef::at(0, 0), SlotRef::at(0, 0))), Err(TransactionError::SameSlot(SlotRef::at(0, 0))));
+
+        drop(transaction);
+
+        assert_eq!(inventory.as_slice(), &[Some(stack(ItemType::STONE, 4))]);
+    }
+
+    #[test]
+    fn test_moves_between_inventories() {
+        let mut hand = None;
+        let mut player = BlockInventory::<4>::default();
+        let mut chest = BlockInventory::<2>::default();
+
+        player.try_insert_at(0, stack(ItemType::PLANKS, 40));
+        chest.try_insert_at(0, stack(ItemType::PLANKS, 60));
+
+        let mut transaction = Transaction::new(&mut hand).with(&mut player).with(&mut chest);
+
+        transaction.apply(&InventoryAction::Split { from: SlotRef::at(0, 0), to: SlotRef::Hand }).expect("should split");
+        transaction.apply(&InventoryAction::Move { from: SlotRef::Hand, to: SlotRef::at(0, 3), count: count(5) }).expect("should move");
+        transaction.apply(&InventoryAction::QuickTransfer { from: SlotRef::at(0, 0), to: 1 }).expect("should transfer");
+
+        assert_eq!(transaction.slot(SlotRef::Hand), Ok(&Some(stack(ItemType::PLANKS, 15))));
+
+        transaction.apply(&InventoryAction::CollectToHand).expect("should collect");
+        transaction.apply(&InventoryAction::Swap(SlotRef::Hand, SlotRef::at(0, 1))).expect("should swap");
+
+        drop(transaction);
+
+        assert_eq!(hand, None);
+        assert_eq!(player.as_slice(), &[None, Some(stack(ItemType::PLANKS, 64)), None, None]);
+        assert_eq!(chest.as_slice(), &[Some(stack(ItemType::PLANKS, 20)), Some(stack(ItemType::PLANKS, 16))]);
+    }
+
+    #[test]
+    fn test_actions_round_trip_through_postcard() {
+        let action = InventoryAction::Move { from: SlotRef::Hand, to: SlotRef::at(2, 17), count: count(3) };
+
+        let bytes = postcard::to_allocvec(&action).expect("should serialize");
+
+        assert_eq!(postcard::from_bytes::<InventoryAction>(&bytes).expect("should deserialize"), action);
+    }
+}
@@ -0,0 +1,366 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::item::ItemStack;
+
+/// Which kind of data an item component holds, so it can be decoded again.
+/// The discriminant is what's saved and sent, so once a kind is assigned one it must never change.
+#[repr(u16)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, strum::FromRepr)]
+pub enum ItemComponentKind {
+    Durability = 0,
+    Enchantments = 1,
+    CustomName = 2,
+    Contents = 3,
+}
+
+impl ItemComponentKind {
+    pub const fn stable_id(self) -> u16 {
+        self as u16
+    }
+
+    pub fn from_stable_id(id: u16) -> Option<Self> {
+        Self::from_repr(id)
+    }
+
+    /// Reads back a component written with [`ItemComponent::encode`].
+    pub fn decode(self, bytes: &[u8]) -> Result<ItemComponent, postcard::Error> {
+        let component = match self {
+            Self::Durability => ItemComponent::Durability(postcard::from_bytes(bytes)?),
+            Self::Enchantments => ItemComponent::Enchantments(postcard::from_bytes(bytes)?),
+            Self::CustomName => ItemComponent::CustomName(postcard::from_bytes(bytes)?),
+            Self::Contents => ItemComponent::Contents(postcard::from_bytes(bytes)?),
+        };
+
+        Ok(component)
+    }
+}
+
+/// How much longer an item lasts before it breaks.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Durability {
+    pub remaining: u16,
+    pub max: u16,
+}
+
+impl Durability {
+    pub fn new(max: u16) -> Self {
+        Self { remaining: max, max }
+    }
+
+    /// Wears it down by `amount`, returning whether it's broken.
+    pub fn damage(&mut self, amount: u16) -> bool {
+        self.remaining = self.remaining.saturating_sub(amount);
+
+        self.remaining == 0
+    }
+
+    pub fn is_damaged(&self) -> bool {
+        self.remaining < self.max
+    }
+
+    /// How much of it is left, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.max == 0 {
+            return 0.0;
+        }
+
+        self.remaining as f32 / self.max as f32
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+pub struct Enchantment {
+    pub name: String,
+    pub level: u8,
+}
+
+/// Enchantments are compared regardless of the order they were added in.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Enchantments(pub Vec<Enchantment>);
+
+impl Enchantments {
+    /// The level of the enchantment, or 0 if the item doesn't have it.
+    pub fn level(&self, name: &str) -> u8 {
+        self.0
+            .iter()
+            .filter(|enchantment| enchantment.name == name)
+            .map(|enchantment| enchantment.level)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn sorted(&self) -> Vec<&Enchantment> {
+        let mut enchantments = self.0.iter().collect::<Vec<_>>();
+        enchantments.sort();
+
+        enchantments
+    }
+}
+
+impl PartialEq for Enchantments {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.0.len() == rhs.0.len() && self.sorted() == rhs.sorted()
+    }
+}
+
+impl Eq for Enchantments {}
+
+/// A name given to an item by a player, shown instead of its title.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CustomName(pub String);
+
+/// Items stored inside an item, such as a container that was picked up along with what was in it.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Contents(pub Vec<ItemStack>);
+
+/// One piece of data attached to an item, on top of what its [`ItemType`](crate::item::ItemType) says.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ItemComponent {
+    Durability(Durability),
+    Enchantments(Enchantments),
+    CustomName(CustomName),
+    Contents(Contents),
+}
+
+impl ItemComponent {
+    pub fn kind(&self) -> ItemComponentKind {
+        match self {
+            Self::Durability(_) => ItemComponentKind::Durability,
+            Self::Enchantments(_) => ItemComponentKind::Enchantments,
+            Self::CustomName(_) => ItemComponentKind::CustomName,
+            Self::Contents(_) => ItemComponentKind::Contents,
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, postcard::Error> {
+        match self {
+            Self::Durability(durability) => postcard::to_allocvec(durability),
+            Self::Enchantments(enchantments) => postcard::to_allocvec(enchantments),
+            Self::CustomName(name) => postcard::to_allocvec(name),
+            Self::Contents(contents) => postcard::to_allocvec(contents),
+        }
+    }
+
+    /// Whether items with these components can share a stack, which is stricter than them being equal.
+    /// Damaged items and items holding anything never stack, so that each one can be used or emptied on its own.
+    pub fn stacks_with(&self, rhs: &Self) -> bool {
+        match (self, rhs) {
+            (Self::Durability(lhs), Self::Durability(rhs)) => lhs == rhs && !lhs.is_damaged(),
+            (Self::Contents(lhs), Self::Contents(rhs)) => lhs.0.is_empty() && rhs.0.is_empty(),
+            _ => self == rhs,
+        }
+    }
+}
+
+/// Data that can be attached to an item as one of its components.
+pub trait ItemData: Into<ItemComponent> + Sized {
+    const KIND: ItemComponentKind;
+
+    fn from_component(component: &ItemComponent) -> Option<&Self>;
+
+    fn from_component_mut(component: &mut ItemComponent) -> Option<&mut Self>;
+
+    fn from_component_owned(component: ItemComponent) -> Option<Self>;
+}
+
+macro_rules! impl_item_data {
+    ($($kind:ident),*) => {
+        $(
+            impl From<$kind> for ItemComponent {
+                fn from(data: $kind) -> Self {
+                    Self::$kind(data)
+                }
+            }
+
+            impl ItemData for $kind {
+                const KIND: ItemComponentKind = ItemComponentKind::$kind;
+
+                fn from_component(component: &ItemComponent) -> Option<&Self> {
+                    match component {
+                        ItemComponent::$kind(data) => Some(data),
+                        _ => None,
+                    }
+                }
+
+                fn from_component_mut(component: &mut ItemComponent) -> Option<&mut Self> {
+                    match component {
+                        ItemComponent::$kind(data) => Some(data),
+                        _ => None,
+                    }
+                }
+
+                fn from_component_owned(component: ItemComponent) -> Option<Self> {
+                    match component {
+                        ItemComponent::$kind(data) => Some(data),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_item_data!(Durability, Enchantments, CustomName, Contents);
+
+/// The components of an item, with at most one of each kind, kept in the order of their kinds.
+/// Saved and sent as the stable id of each kind alongside its encoded data.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ItemComponents(Vec<ItemComponent>);
+
+impl ItemComponents {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ItemComponent> {
+        self.0.iter()
+    }
+
+    pub fn get<T: ItemData>(&self) -> Option<&T> {
+        let index = self.index_of(T::KIND).ok()?;
+
+        T::from_component(&self.0[index])
+    }
+
+    pub fn get_mut<T: ItemData>(&mut self) -> Option<&mut T> {
+        let index = self.index_of(T::KIND).ok()?;
+
+        T::from_component_mut(&mut self.0[index])
+    }
+
+    /// Replaces the component of the same kind, returning the one it replaced.
+    pub fn insert(&mut self, component: impl Into<ItemComponent>) -> Option<ItemComponent> {
+        let component = component.into();
+
+        match self.index_of(component.kind()) {
+            Ok(index) => Some(std::mem::replace(&mut self.0[index], component)),
+            Err(index) => {
+                self.0.insert(index, component);
+                None
+            }
+        }
+    }
+
+    pub fn remove<T: ItemData>(&mut self) -> Option<T> {
+        let index = self.index_of(T::KIND).ok()?;
+
+        T::from_component_owned(self.0.remove(index))
+    }
+
+    /// Whether items with these components can share a stack, see [`ItemComponent::stacks_with`].
+    pub fn stacks_with(&self, rhs: &Self) -> bool {
+        self.0.len() == rhs.0.len() && self.0.iter().zip(&rhs.0).all(|(lhs, rhs)| lhs.stacks_with(rhs))
+    }
+
+    fn index_of(&self, kind: ItemComponentKind) -> Result<usize, usize> {
+        self.0.binary_search_by_key(&kind, ItemComponent::kind)
+    }
+}
+
+impl Serialize for ItemComponents {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let encoded = self.0
+            .iter()
+            .map(|component| Ok((component.kind().stable_id(), component.encode()?)))
+            .collect::<Result<Vec<_>, postcard::Error>>()
+            .map_err(serde::ser::Error::custom)?;
+
+        encoded.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemComponents {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = Vec::<(u16, Vec<u8>)>::deserialize(deserializer)?;
+
+        let mut components = Self::default();
+
+        for (id, bytes) in encoded {
+            let kind = ItemComponentKind::from_stable_id(id)
+                .ok_or_else(|| serde::de::Error::custom(format!("unknown item component kind {id}")))?;
+
+            let component = kind.decode(&bytes).map_err(serde::de::Error::custom)?;
+
+            if components.insert(component).is_some() {
+                return Err(serde::de::Error::custom(format!("item had more than one {kind:?} component")));
+            }
+        }
+
+        Ok(components)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::item::{Item, ItemType};
+    use super::*;
+
+    fn round_trip(stack: &ItemStack) -> ItemStack {
+        let bytes = postcard::to_allocvec(stack).expect("should serialize");
+
+        postcard::from_bytes(&bytes).expect("should deserialize")
+    }
+
+    fn enchantments(levels: &[(&str, u8)]) -> Enchantments {
+        Enchantments(levels.iter().map(|(name, level)| Enchantment { name: name.to_string(), level: *level }).collect())
+    }
+
+    #[test]
+    fn test_components_round_trip_through_postcard() {
+        let contents = Contents(vec![
+            ItemType::PLANKS.default_item().with_count(5.try_into().expect("5 is nonzero")),
+            ItemType::WOODEN_AXE.default_item().with_component(CustomName("Chopper".into())).stack_one(),
+        ]);
+
+        let items = [
+            ItemType::DIRT.default_item(),
+            ItemType::WOODEN_PICKAXE.default_item()
+                .with_component(Durability { remaining: 12, max: 60 })
+                .with_component(enchantments(&[("protovox:efficiency", 2), ("protovox:fortune", 1)]))
+                .with_component(CustomName("Old Faithful".into())),
+            ItemType::CRATE.default_item().with_component(contents),
+        ];
+
+        for item in items {
+            let stack = item.stack_one();
+
+            assert_eq!(round_trip(&stack), stack);
+        }
+    }
+
+    #[test]
+    fn test_rejects_unknown_and_repeated_components() {
+        let name = CustomName("Rock".into());
+        let encoded = ItemComponent::from(name).encode().expect("should encode");
+
+        let item = |components: Vec<(u16, Vec<u8>)>| postcard::to_allocvec(&(ItemType::STONE, components)).expect("should serialize");
+
+        assert!(postcard::from_bytes::<Item>(&item(vec![(2, encoded.clone())])).is_ok());
+        assert!(postcard::from_bytes::<Item>(&item(vec![(999, encoded.clone())])).is_err());
+        assert!(postcard::from_bytes::<Item>(&item(vec![(2, encoded.clone()), (2, encoded)])).is_err());
+    }
+
+    #[test]
+    fn test_equality_and_stacking_follow_each_component() {
+        let pickaxe = ItemType::WOODEN_PICKAXE.default_item();
+
+        let a = pickaxe.clone().with_component(enchantments(&[("protovox:efficiency", 2), ("protovox:fortune", 1)]));
+        let b = pickaxe.clone().with_component(enchantments(&[("protovox:fortune", 1), ("protovox:efficiency", 2)]));
+
+        assert_eq!(a, b, "enchantments shouldn't depend on their order");
+        assert!(a.stacks_with(&b));
+        assert_ne!(a, pickaxe);
+
+        let fresh = pickaxe.clone().with_component(Durability::new(60));
+        let mut damaged = fresh.clone();
+        damaged.component_mut::<Durability>().expect("should have durability").damage(1);
+
+        assert!(fresh.stacks_with(&fresh.clone()));
+        assert_eq!(damaged, damaged.clone());
+        assert!(!damaged.stacks_with(&damaged.clone()), "damaged items shouldn't stack");
+
+        let full = ItemType::CRATE.default_item().with_component(Contents(vec![ItemType::DIRT.default_one()]));
+        let mut one = full.clone().stack_one();
+
+        assert_eq!(one.try_combine(full.stack_one()).map(|stack| stack.count.get()), Some(1), "items holding anything shouldn't stack");
+    }
+}
@@ -0,0 +1,293 @@
+use std::fmt::{self, Debug};
+use std::num::NonZeroU8;
+use std::time::Duration;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::block::Block;
+use crate::block::face_type::FaceType;
+use crate::block::registry::{BlockId, BlockRegistry};
+use crate::item::component::{CustomName, ItemComponent, ItemComponents, ItemData};
+use crate::item::registry::{ItemDefinition, ItemId, ItemRegistry};
+use crate::item::tool::Tool;
+use crate::location::BlockLocation;
+use crate::texture_ids::TextureId;
+
+pub mod component;
+pub mod registry;
+pub mod tool;
+
+/// A kind of item in the [`ItemRegistry`]. Saved and sent by name, since ids depend on which definitions were loaded.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ItemType(ItemId);
+
+impl ItemType {
+    pub const GRASS: Self = Self::new(ItemId(0));
+    pub const DIRT: Self = Self::new(ItemId(1));
+    pub const COBBLESTONE: Self = Self::new(ItemId(2));
+    pub const STONE: Self = Self::new(ItemId(3));
+    pub const LOG: Self = Self::new(ItemId(4));
+    pub const LEAF_PILE: Self = Self::new(ItemId(5));
+    pub const CRATE: Self = Self::new(ItemId(6));
+    pub const PLANKS: Self = Self::new(ItemId(7));
+    pub const STONE_BRICKS: Self = Self::new(ItemId(8));
+    pub const HEMATITE_NUGGETS: Self = Self::new(ItemId(9));
+    pub const CARBON_STEEL: Self = Self::new(ItemId(10));
+    pub const SMELTER: Self = Self::new(ItemId(11));
+    pub const WOODEN_PICKAXE: Self = Self::new(ItemId(12));
+    pub const WOODEN_AXE: Self = Self::new(ItemId(13));
+    pub const WOODEN_SHOVEL: Self = Self::new(ItemId(14));
+    pub const CARBON_STEEL_PICKAXE: Self = Self::new(ItemId(15));
+    pub const CARBON_STEEL_AXE: Self = Self::new(ItemId(16));
+    pub const CARBON_STEEL_SHOVEL: Self = Self::new(ItemId(17));
+
+    pub const fn new(id: ItemId) -> Self {
+        Self(id)
+    }
+
+    /// Looks the item up in the global registry.
+    pub fn by_name(name: &str) -> Option<Self> {
+        ItemRegistry::global().by_name(name).map(Self)
+    }
+
+    pub fn id(self) -> ItemId {
+        self.0
+    }
+
+    pub fn definition(self) -> &'static ItemDefinition {
+        ItemRegistry::global()
+            .get(self.0)
+            .expect("item types are only made with ids from the global registry")
+    }
+
+    pub fn default_item(self) -> Item {
+        Item::new(self)
+    }
+
+    pub fn default_one(self) -> ItemStack {
+        ItemStack::one(self.default_item())
+    }
+
+    pub fn texture_id(self) -> TextureId {
+        self.definition().icon
+    }
+
+    pub fn max_stack(self) -> NonZeroU8 {
+        self.definition().max_stack
+    }
+
+    pub fn has_tag(self, tag: &str) -> bool {
+        self.definition().has_tag(tag)
+    }
+
+    pub fn burn_time(self) -> Option<Duration> {
+        self.definition().burn_time
+    }
+
+    pub fn tool(self) -> Option<&'static Tool> {
+        self.definition().tool.as_ref()
+    }
+
+    /// The block it places, if it places one.
+    pub fn block(self) -> Option<BlockId> {
+        BlockRegistry::global().by_name(self.definition().block.as_deref()?)
+    }
+}
+
+impl Debug for ItemType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match ItemRegistry::global().get(self.0) {
+            Some(definition) => write!(f, "ItemType({})", definition.name),
+            None => write!(f, "ItemType(unknown {:?})", self.0),
+        }
+    }
+}
+
+impl Serialize for ItemType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.definition().name)
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        Self::by_name(&name).ok_or_else(|| serde::de::Error::custom(format!("unknown item {name}")))
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: Item,
+    pub count: NonZeroU8,
+}
+
+impl ItemStack {
+    pub fn one(item: Item) -> Self {
+        Self {
+            item,
+            count: NonZeroU8::new(1).expect("shouldn't be zero")
+        }
+    }
+
+    /// How many of the item fit in one stack.
+    pub fn max_count(&self) -> NonZeroU8 {
+        self.item.ty.max_stack()
+    }
+
+    pub fn try_combine(&mut self, rhs: Self) -> Option<Self> {
+        if !self.item.stacks_with(&rhs.item) {
+            return Some(rhs);
+        }
+
+        let max_ct = self.max_count();
+        let lhs_ct = self.count.get();
+        let rhs_ct = rhs.count.get();
+
+        if lhs_ct > max_ct.get() {
+            return Some(rhs);
+        }
+
+        match max_ct.get() - lhs_ct {
+            0 => Some(rhs),
+            n if n >= rhs_ct => {
+                self.count = NonZeroU8::new(lhs_ct + rhs_ct)
+                    .expect("should be nonzero");
+
+                None
+            }
+            n => {
+                let rem = rhs_ct - n;
+
+                self.count = max_ct;
+
+                let count = NonZeroU8::new(rem)
+                    .expect("if it was zero, should've been handled in case above");
+
+                Some(rhs.item.with_count(count))
+            }
+        }
+    }
+    
+    pub fn split_exact(self, first_ct: NonZeroU8) -> Result<(Self, Option<Self>), Self> {
+        if first_ct <= self.count {
+            Ok(self.split_at_most(first_ct))
+        } else {
+            Err(self)
+        }
+    }
+    
+    pub fn split_at_most(mut self, first_ct: NonZeroU8) -> (Self, Option<Self>) {
+        if first_ct >= self.count {
+            (self, None)
+        } else {
+            let mut other = self.clone();
+            
+            other.count = NonZeroU8::new(self.count.get() - first_ct.get()).expect("can't be zero, since first_ct must be less than total");
+            
+            self.count = first_ct;
+
+            (self, Some(other))
+        }
+    }
+    
+    pub fn split_half(self) -> (Self, Option<Self>) {
+        let ct = self.count.get().div_ceil(2);
+        
+        self.split_at_most(NonZeroU8::new(ct).expect("shouldn't ever be zero"))
+    }
+    
+    pub fn split_item(self) -> (Item, Option<Self>) {
+        let (item, res) = self.split_at_most(1.try_into().expect("nonzero"));
+
+        (item.item, res)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Item {
+    pub ty: ItemType,
+    pub components: ItemComponents,
+}
+
+impl Item {
+    pub fn new(ty: ItemType) -> Self {
+        Self { ty, components: ItemComponents::default() }
+    }
+
+    /// Its custom name if it has one, or else the title of its type.
+    pub fn title(&self) -> &str {
+        match self.component::<CustomName>() {
+            Some(CustomName(name)) => name,
+            None => &self.ty.definition().title,
+        }
+    }
+
+    pub fn desc(&self) -> &'static str {
+        &self.ty.definition().desc
+    }
+
+    pub fn component<T: ItemData>(&self) -> Option<&T> {
+        self.components.get()
+    }
+
+    pub fn component_mut<T: ItemData>(&mut self) -> Option<&mut T> {
+        self.components.get_mut()
+    }
+
+    pub fn with_component(mut self, component: impl Into<ItemComponent>) -> Self {
+        self.components.insert(component);
+        self
+    }
+
+    /// Whether it can share a stack with `rhs`, which depends on each of their components.
+    pub fn stacks_with(&self, rhs: &Self) -> bool {
+        self.ty == rhs.ty && self.components.stacks_with(&rhs.components)
+    }
+    
+    pub fn with_count(self, count: NonZeroU8) -> ItemStack {
+        ItemStack {
+            item: self,
+            count,
+        }
+    }
+    
+    pub fn stack_one(self) -> ItemStack {
+        ItemStack::one(self)
+    }
+
+    pub fn place(self, _loc: BlockLocation, face: FaceType) -> Result<Block, Self> {
+        Block::placed_by(self.ty, face).ok_or(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block::BlockInventory;
+    use crate::inventory::Inventory;
+    use super::*;
+
+    #[test]
+    fn test_stacks_respect_their_item_limit() {
+        let crates = |count: u8| ItemType::CRATE.default_item().with_count(count.try_into().expect("nonzero"));
+
+        assert_eq!(ItemType::CRATE.max_stack().get(), 16);
+
+        let mut stack = crates(10);
+
+        assert_eq!(stack.try_combine(crates(10)), Some(crates(4)));
+        assert_eq!(stack, crates(16));
+
+        let mut inventory = BlockInventory::<2>::default();
+
+        assert_eq!(inventory.try_insert(crates(40)), Some(crates(8)));
+        assert_eq!(inventory.as_slice(), &[Some(crates(16)), Some(crates(16))]);
+    }
+
+    #[test]
+    fn test_item_types_are_saved_by_name() {
+        let bytes = postcard::to_allocvec(&ItemType::HEMATITE_NUGGETS).expect("should serialize");
+
+        assert_eq!(postcard::from_bytes::<String>(&bytes).expect("should be a string"), "protovox:hematite_nuggets");
+        assert_eq!(postcard::from_bytes::<ItemType>(&bytes).expect("should deserialize"), ItemType::HEMATITE_NUGGETS);
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::num::NonZeroU8;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::{fs, io};
+use serde::{Deserialize, Serialize};
+use crate::block::registry::BlockRegistry;
+use crate::definitions::{definition_files, fingerprint, is_namespaced};
+use crate::item::tool::Tool;
+use crate::texture_ids::TextureId;
+
+/// The items every world has, which mods can add to but not change.
+const BASE_DEFINITIONS: &str = include_str!("../../assets/items.toml");
+
+pub const DEFAULT_MAX_STACK: NonZeroU8 = NonZeroU8::new(64).expect("64 is nonzero");
+
+static GLOBAL: OnceLock<ItemRegistry> = OnceLock::new();
+
+/// Index of an item's definition in the [`ItemRegistry`]. It depends on which definitions were loaded,
+/// so anything saved refers to items by name instead.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Default, Serialize, Deserialize)]
+pub struct ItemId(pub u16);
+
+#[derive(Clone, Debug)]
+pub struct ItemDefinition {
+    /// Namespaced, such as `protovox:planks`.
+    pub name: String,
+    /// Shown to players.
+    pub title: String,
+    pub desc: String,
+    pub max_stack: NonZeroU8,
+    pub icon: TextureId,
+    /// The name of the block it places. Blocks are loaded after items, so this is checked
+    /// with [`ItemRegistry::check_blocks`] once they are.
+    pub block: Option<String>,
+    /// Namespaced groups the item belongs to, such as `protovox:wood`.
+    pub tags: Vec<String>,
+    /// How long one of the item keeps a machine going, if it can be burned as fuel.
+    pub burn_time: Option<Duration>,
+    pub tool: Option<Tool>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ItemDefinitionFile {
+    name: String,
+    title: String,
+    #[serde(default)]
+    desc: String,
+    #[serde(default = "ItemDefinitionFile::default_max_stack")]
+    max_stack: u8,
+    #[serde(default)]
+    icon: TextureId,
+    block: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    /// In seconds.
+    burn_time: Option<f32>,
+    tool: Option<Tool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DefinitionsFile {
+    #[serde(default)]
+    items: Vec<ItemDefinitionFile>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ItemRegistryError {
+    #[error("failed to read item definitions from {0:?}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("invalid item definitions in {0}: {1}")]
+    Parse(String, toml::de::Error),
+    #[error("item name {0:?} should be namespaced, like \"namespace:name\"")]
+    InvalidName(String),
+    #[error("item {item} has tag {tag:?}, which should be namespaced, like \"namespace:name\"")]
+    InvalidTag { item: String, tag: String },
+    #[error("item {0} is defined more than once")]
+    Duplicate(String),
+    #[error("item {0} can't stack to 0")]
+    InvalidMaxStack(String),
+    #[error("item {0} should burn for a positive number of seconds")]
+    InvalidBurnTime(String),
+    #[error("item {0} should be a tool with a tier above 0 and a speed of at least 1")]
+    InvalidTool(String),
+    #[error("item {item} places block {block}, which isn't defined")]
+    UnknownBlock { item: String, block: String },
+    #[error("more than {} items were defined", u16::MAX)]
+    TooManyItems,
+}
+
+impl ItemDefinitionFile {
+    fn default_max_stack() -> u8 {
+        DEFAULT_MAX_STACK.get()
+    }
+
+    fn resolve(self) -> Result<ItemDefinition, ItemRegistryError> {
+        if !is_namespaced(&self.name) {
+            return Err(ItemRegistryError::InvalidName(self.name));
+        }
+
+        if let Some(tag) = self.tags.iter().find(|tag| !is_namespaced(tag)) {
+            return Err(ItemRegistryError::InvalidTag { item: self.name.clone(), tag: tag.clone() });
+        }
+
+        let max_stack = NonZeroU8::new(self.max_stack).ok_or_else(|| ItemRegistryError::InvalidMaxStack(self.name.clone()))?;
+
+        if self.tool.is_some_and(|tool| !tool.is_valid()) {
+            return Err(ItemRegistryError::InvalidTool(self.name));
+        }
+
+        let burn_time = match self.burn_time {
+            None => None,
+            Some(secs) => Some(Duration::try_from_secs_f32(secs)
+                .ok()
+                .filter(|time| !time.is_zero())
+                .ok_or_else(|| ItemRegistryError::InvalidBurnTime(self.name.clone()))?),
+        };
+
+        Ok(ItemDefinition {
+            name: self.name,
+            title: self.title,
+            desc: self.desc,
+            max_stack,
+            icon: self.icon,
+            block: self.block,
+            tags: self.tags,
+            burn_time,
+            tool: self.tool,
+        })
+    }
+}
+
+impl ItemDefinition {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own == tag)
+    }
+}
+
+/// Every kind of item, loaded from definition files. Items are numbered in the order they were loaded,
+/// starting with the base definitions, so the base items always have the same ids.
+#[derive(Debug)]
+pub struct ItemRegistry {
+    definitions: Vec<ItemDefinition>,
+    by_name: HashMap<String, ItemId>,
+}
+
+impl ItemRegistry {
+    /// A registry of only the base items.
+    pub fn base() -> Self {
+        let mut registry = Self {
+            definitions: Vec::new(),
+            by_name: HashMap::new(),
+        };
+
+        registry
+            .add_definitions("base definitions", BASE_DEFINITIONS)
+            .expect("base item definitions should be valid");
+
+        registry
+    }
+
+    /// The base items, followed by those defined in the directory if it exists.
+    pub fn load(dir: &Path) -> Result<Self, ItemRegistryError> {
+        let mut registry = Self::base();
+
+        if dir.is_dir() {
+            registry.add_definitions_in(dir)?;
+        }
+
+        Ok(registry)
+    }
+
+    /// Adds every item defined in `source`, where `origin` is only used to describe errors.
+    /// Nothing is added if any of the definitions are invalid.
+    pub fn add_definitions(&mut self, origin: &str, source: &str) -> Result<(), ItemRegistryError> {
+        let file = toml::from_str::<DefinitionsFile>(source)
+            .map_err(|err| ItemRegistryError::Parse(origin.to_string(), err))?;
+
+        let definitions = file.items
+            .into_iter()
+            .map(ItemDefinitionFile::resolve)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if self.definitions.len() + definitions.len() > u16::MAX as usize {
+            return Err(ItemRegistryError::TooManyItems);
+        }
+
+        let mut by_name = self.by_name.clone();
+
+        for (i, definition) in definitions.iter().enumerate() {
+            let id = ItemId((self.definitions.len() + i) as u16);
+
+            if by_name.insert(definition.name.clone(), id).is_some() {
+                return Err(ItemRegistryError::Duplicate(definition.name.clone()));
+            }
+        }
+
+        self.definitions.extend(definitions);
+        self.by_name = by_name;
+
+        Ok(())
+    }
+
+    /// Adds the definitions from every `.toml` file in the directory, in order of their file names.
+    pub fn add_definitions_in(&mut self, dir: &Path) -> Result<(), ItemRegistryError> {
+        let paths = definition_files(dir).map_err(|err| ItemRegistryError::Io(dir.to_path_buf(), err))?;
+
+        for path in paths {
+            let source = fs::read_to_string(&path).map_err(|err| ItemRegistryError::Io(path.clone(), err))?;
+
+            self.add_definitions(&path.display().to_string(), &source)?;
+        }
+
+        Ok(())
+    }
+
+    /// Makes sure every block an item places is defined.
+    pub fn check_blocks(&self, blocks: &BlockRegistry) -> Result<(), ItemRegistryError> {
+        for definition in &self.definitions {
+            if let Some(block) = &definition.block
+                && blocks.by_name(block).is_none()
+            {
+                return Err(ItemRegistryError::UnknownBlock { item: definition.name.clone(), block: block.clone() });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, id: ItemId) -> Option<&ItemDefinition> {
+        self.definitions.get(id.0 as usize)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<ItemId> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+
+    /// Differs between registries that don't have the same items with the same ids.
+    pub fn fingerprint(&self) -> u64 {
+        fingerprint(self.definitions.iter().map(|definition| definition.name.as_str()))
+    }
+
+    /// The registry every [`ItemType`](crate::item::ItemType) refers to, which only has the base items
+    /// unless another registry was installed before it was first used.
+    pub fn global() -> &'static Self {
+        GLOBAL.get_or_init(Self::base)
+    }
+
+    /// Makes this the global registry. Fails if the global registry was already used,
+    /// since items might already refer to it.
+    pub fn install(self) -> Result<(), Self> {
+        GLOBAL.set(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::item::ItemType;
+    use super::*;
+
+    #[test]
+    fn test_base_items_match_their_constants() {
+        let registry = ItemRegistry::base();
+
+        let constants = [
+            (ItemType::GRASS, "protovox:grass"),
+            (ItemType::DIRT, "protovox:dirt"),
+            (ItemType::COBBLESTONE, "protovox:cobblestone"),
+            (ItemType::STONE, "protovox:stone"),
+            (ItemType::LOG, "protovox:log"),
+            (ItemType::LEAF_PILE, "protovox:leaf_pile"),
+            (ItemType::CRATE, "protovox:crate"),
+            (ItemType::PLANKS, "protovox:planks"),
+            (ItemType::STONE_BRICKS, "protovox:stone_bricks"),
+            (ItemType::HEMATITE_NUGGETS, "protovox:hematite_nuggets"),
+            (ItemType::CARBON_STEEL, "protovox:carbon_steel"),
+            (ItemType::SMELTER, "protovox:smelter"),
+            (ItemType::WOODEN_PICKAXE, "protovox:wooden_pickaxe"),
+            (ItemType::WOODEN_AXE, "protovox:wooden_axe"),
+            (ItemType::WOODEN_SHOVEL, "protovox:wooden_shovel"),
+            (ItemType::CARBON_STEEL_PICKAXE, "protovox:carbon_steel_pickaxe"),
+            (ItemType::CARBON_STEEL_AXE, "protovox:carbon_steel_axe"),
+            (ItemType::CARBON_STEEL_SHOVEL, "protovox:carbon_steel_shovel"),
+        ];
+
+        assert_eq!(registry.len(), constants.len());
+
+        for (ty, name) in constants {
+            assert_eq!(registry.by_name(name), Some(ty.id()), "{name} doesn't match its constant");
+        }
+
+        registry.check_blocks(&BlockRegistry::base()).expect("base items should only place base blocks");
+    }
+
+    #[test]
+    fn test_rejects_invalid_definitions() {
+        let invalid = [
+            r#"[[items]]
+            name = "unnamespaced"
+            title = "Unnamespaced""#,
+            r#"[[items]]
+            name = "protovox:planks"
+            title = "Planks""#,
+            r#"[[items]]
+            name = "test:unstackable"
+            title = "Unstackable"
+            max_stack = 0"#,
+            r#"[[items]]
+            name = "test:unburnable"
+            title = "Unburnable"
+            burn_time = -1.0"#,
+            r#"[[items]]
+            name = "test:slow_pickaxe"
+            title = "Slow Pickaxe"
+            tool = { kind = "pickaxe", tier = 1, speed = 0.5 }"#,
+            r#"[[items]]
+            name = "test:untagged"
+            title = "Untagged"
+            tags = ["wood"]"#,
+        ];
+
+        for source in invalid {
+            let mut registry = ItemRegistry::base();
+
+            assert!(registry.add_definitions("test", source).is_err(), "{source} should be rejected");
+            assert_eq!(registry.len(), ItemRegistry::base().len(), "nothing should be added from {source}");
+        }
+
+        let mut registry = ItemRegistry::base();
+
+        registry.add_definitions("test", r#"
+            [[items]]
+            name = "test:floating"
+            title = "Floating"
+            block = "test:missing"
+        "#).expect("blocks are only checked once they're loaded");
+
+        assert!(matches!(registry.check_blocks(&BlockRegistry::base()), Err(ItemRegistryError::UnknownBlock { .. })));
+    }
+}
@@ -0,0 +1,88 @@
+use std::time::Duration;
+use serde::Deserialize;
+use crate::block::registry::BlockDefinition;
+
+/// Seconds per point of hardness a block takes to break without a tool that speeds it up.
+/// Blocks that won't drop anything take longer, so that it's clear the wrong tool is being used.
+const HARVESTABLE_SECONDS_PER_HARDNESS: f32 = 1.5;
+const UNHARVESTABLE_SECONDS_PER_HARDNESS: f32 = 5.0;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolKind {
+    Pickaxe,
+    Axe,
+    Shovel,
+}
+
+/// What makes an item a tool, which breaks the blocks it's meant for faster,
+/// and lets blocks that need a tool of at least its tier drop anything.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tool {
+    pub kind: ToolKind,
+    /// The tier of the material it's made of, starting at 1 for wood.
+    pub tier: u8,
+    /// How many times faster it breaks the blocks it's meant for.
+    pub speed: f32,
+    /// Makes blocks with loot that depends on fortune drop more.
+    #[serde(default)]
+    pub fortune: u8,
+    /// Makes blocks with loot that depends on it drop themselves, rather than what they usually drop.
+    #[serde(default)]
+    pub silk_touch: bool,
+}
+
+impl Tool {
+    pub(crate) fn is_valid(&self) -> bool {
+        self.tier > 0 && self.speed.is_finite() && self.speed >= 1.0
+    }
+}
+
+impl BlockDefinition {
+    /// Whether breaking the block with `tool`, or by hand, drops anything.
+    pub fn harvestable_with(&self, tool: Option<&Tool>) -> bool {
+        self.harvest_tier == 0 || tool.is_some_and(|tool| Some(tool.kind) == self.tool && tool.tier >= self.harvest_tier)
+    }
+
+    /// How long the block takes to break with `tool`, or by hand.
+    pub fn break_time(&self, tool: Option<&Tool>) -> Duration {
+        let speed = tool
+            .filter(|tool| Some(tool.kind) == self.tool)
+            .map_or(1.0, |tool| tool.speed);
+
+        let seconds_per_hardness = if self.harvestable_with(tool) {
+            HARVESTABLE_SECONDS_PER_HARDNESS
+        } else {
+            UNHARVESTABLE_SECONDS_PER_HARDNESS
+        };
+
+        Duration::from_secs_f32(self.hardness * seconds_per_hardness / speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block::Block;
+    use crate::item::ItemType;
+    use super::*;
+
+    #[test]
+    fn test_tools_speed_up_and_harvest_their_blocks() {
+        let stone = Block::STONE.definition();
+        let wooden = ItemType::WOODEN_PICKAXE.tool().expect("should be a tool");
+        let steel = ItemType::CARBON_STEEL_PICKAXE.tool().expect("should be a tool");
+        let axe = ItemType::CARBON_STEEL_AXE.tool().expect("should be a tool");
+
+        assert!(!stone.harvestable_with(None));
+        assert!(!stone.harvestable_with(Some(axe)));
+        assert!(stone.harvestable_with(Some(wooden)));
+
+        assert!(stone.break_time(Some(steel)) < stone.break_time(Some(wooden)));
+        assert!(stone.break_time(Some(wooden)) < stone.break_time(None));
+        assert_eq!(stone.break_time(Some(axe)), stone.break_time(None), "axes shouldn't help with stone");
+
+        assert!(Block::DIRT.definition().harvestable_with(None), "anything should harvest dirt");
+        assert_eq!(Block::WATER.definition().break_time(None), Duration::ZERO);
+    }
+}
@@ -2,8 +2,12 @@ extern crate nalgebra as na;
 extern crate nalgebra_glm as glm;
 
 pub mod block;
+pub mod block_entity;
 pub mod chunk;
+pub mod crafting;
+mod definitions;
 pub mod location;
 pub mod item;
+pub mod machine;
 pub mod texture_ids;
 pub mod inventory;
\ No newline at end of file
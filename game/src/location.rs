@@ -28,7 +28,7 @@ impl From<BlockLocation> for WorldLocation {
 }
 
 #[repr(transparent)]
-#[derive(Debug, Default, Clone, Component, Serialize, Deserialize, Eq, PartialEq, derive_more::Add, derive_more::Sub, derive_more::From)]
+#[derive(Debug, Default, Clone, Component, Serialize, Deserialize, Eq, PartialEq, Hash, derive_more::Add, derive_more::Sub, derive_more::From)]
 pub struct BlockLocation(pub IVec3);
 
 impl From<&ChunkLocation> for BlockLocation {
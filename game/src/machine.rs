@@ -0,0 +1,193 @@
+use std::num::NonZeroU8;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::block::BlockInventory;
+use crate::block_entity::{BlockEntity, BlockEntityKind};
+use crate::crafting::{RecipeBook, SmeltingRecipe};
+use crate::inventory::Inventory;
+use crate::item::ItemStack;
+
+/// How many steps the progress of a machine is shown in. Clients are only sent an update when it moves
+/// to the next step, rather than every tick.
+const PROGRESS_STEPS: f32 = 20.0;
+
+/// Burns fuel to process its input with a [`SmeltingRecipe`], one item at a time.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SmelterEntity {
+    pub input: BlockInventory<1>,
+    pub fuel: BlockInventory<1>,
+    pub output: BlockInventory<1>,
+    /// How long the current input has been processed for.
+    pub progress: Duration,
+    /// How much longer the last fuel burned keeps it going, out of `burn_time`.
+    pub burn_left: Duration,
+    pub burn_time: Duration,
+}
+
+impl SmelterEntity {
+    /// The recipe for whatever is in the input slot.
+    pub fn recipe(&self) -> Option<&'static SmeltingRecipe> {
+        let input = self.input.items().next()?;
+
+        RecipeBook::global().smelting_for(input.item.ty)
+    }
+
+    /// How far along processing the current input is, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        self.recipe().map_or(0.0, |recipe| (self.progress.as_secs_f32() / recipe.time.as_secs_f32()).min(1.0))
+    }
+
+    /// How much of the last fuel burned is left, from 0 to 1.
+    pub fn burn(&self) -> f32 {
+        if self.burn_time.is_zero() {
+            return 0.0;
+        }
+
+        self.burn_left.as_secs_f32() / self.burn_time.as_secs_f32()
+    }
+
+    /// Whether there's room in the output slot for everything the recipe makes.
+    fn has_room_for(&self, recipe: &SmeltingRecipe) -> bool {
+        self.output.clone().try_insert(recipe.output()).is_none()
+    }
+
+    /// Starts burning one item from the fuel slot, if it's fuel.
+    fn burn_next_fuel(&mut self) {
+        let Some(burn_time) = self.fuel.items().next().and_then(|stack| stack.item.ty.burn_time()) else {
+            return;
+        };
+
+        self.fuel.split_at_most_at(0, NonZeroU8::MIN);
+
+        self.burn_left = burn_time;
+        self.burn_time = burn_time;
+    }
+
+    /// Everything shown to players, to tell whether a tick changed anything worth sending.
+    fn shown(&self) -> (Self, u8, u8) {
+        let steps = |fraction: f32| (fraction * PROGRESS_STEPS) as u8;
+
+        let slots = Self {
+            input: self.input.clone(),
+            fuel: self.fuel.clone(),
+            output: self.output.clone(),
+            ..Self::default()
+        };
+
+        (slots, steps(self.progress()), steps(self.burn()))
+    }
+}
+
+impl BlockEntity for SmelterEntity {
+    fn kind(&self) -> BlockEntityKind {
+        BlockEntityKind::Smelter
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, postcard::Error> {
+        postcard::to_allocvec(self)
+    }
+
+    fn clone_boxed(&self) -> Box<dyn BlockEntity> {
+        Box::new(self.clone())
+    }
+
+    fn tick(&mut self, delta: Duration) -> bool {
+        let before = self.shown();
+
+        let recipe = self.recipe().filter(|recipe| self.has_room_for(recipe));
+
+        if recipe.is_some() && self.burn_left.is_zero() {
+            self.burn_next_fuel();
+        }
+
+        let burning = !self.burn_left.is_zero();
+        self.burn_left = self.burn_left.saturating_sub(delta);
+
+        match recipe {
+            Some(recipe) if burning => {
+                self.progress += delta;
+
+                if self.progress >= recipe.time {
+                    self.progress = Duration::ZERO;
+
+                    self.input.split_at_most_at(0, NonZeroU8::MIN);
+
+                    // there was room for it before the input was taken
+                    self.output.try_insert(recipe.output());
+                }
+            }
+            _ => self.progress = Duration::ZERO,
+        }
+
+        self.shown() != before
+    }
+
+    fn on_remove(mut self: Box<Self>) -> Vec<ItemStack> {
+        [&mut self.input, &mut self.fuel, &mut self.output]
+            .into_iter()
+            .filter_map(|slot| slot.as_mut_slice()[0].take())
+            .collect()
+    }
+
+    fn inventories_mut(&mut self) -> Vec<&mut [Option<ItemStack>]> {
+        vec![self.input.as_mut_slice(), self.fuel.as_mut_slice(), self.output.as_mut_slice()]
+    }
+
+    /// Only fuel goes in the fuel slot, and nothing goes in the output slot.
+    fn accepts(&self, index: usize, stack: &ItemStack) -> bool {
+        match index {
+            1 => stack.item.ty.burn_time().is_some(),
+            2 => false,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::item::ItemType;
+    use super::*;
+
+    fn count(n: u8) -> NonZeroU8 {
+        NonZeroU8::new(n).expect("nonzero")
+    }
+
+    #[test]
+    fn test_smelts_hematite_into_carbon_steel() {
+        let mut smelter = SmelterEntity::default();
+
+        smelter.input.try_insert(ItemType::HEMATITE_NUGGETS.default_item().with_count(count(2)));
+        smelter.fuel.try_insert(ItemType::LOG.default_one());
+
+        let time = smelter.recipe().expect("hematite should smelt").time;
+
+        assert!(smelter.tick(time / 2));
+        assert!(smelter.fuel.items().next().is_none(), "the log should be burning");
+        assert!(smelter.output.items().next().is_none());
+
+        smelter.tick(time / 2);
+
+        assert_eq!(smelter.output.as_slice()[0], Some(ItemType::CARBON_STEEL.default_one()));
+        assert_eq!(smelter.input.as_slice()[0], Some(ItemType::HEMATITE_NUGGETS.default_one()));
+    }
+
+    #[test]
+    fn test_waits_for_fuel_and_room() {
+        let mut smelter = SmelterEntity::default();
+
+        smelter.input.try_insert(ItemType::HEMATITE_NUGGETS.default_one());
+
+        let time = smelter.recipe().expect("hematite should smelt").time;
+
+        assert!(!smelter.tick(time));
+        assert!(smelter.output.items().next().is_none(), "nothing should smelt without fuel");
+
+        smelter.fuel.try_insert(ItemType::PLANKS.default_one());
+        smelter.output.try_insert(ItemType::CARBON_STEEL.default_item().with_count(ItemType::CARBON_STEEL.max_stack()));
+
+        smelter.tick(time);
+
+        assert_eq!(smelter.fuel.as_slice()[0], Some(ItemType::PLANKS.default_one()), "fuel shouldn't burn without room for the output");
+        assert_eq!(smelter.input.as_slice()[0], Some(ItemType::HEMATITE_NUGGETS.default_one()));
+    }
+}
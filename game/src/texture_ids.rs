@@ -1,6 +1,7 @@
 #[repr(u8)]
-#[derive(Copy, Clone, Debug, Default, strum::Display, strum::VariantArray)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, strum::Display, strum::VariantArray, serde::Deserialize)]
 #[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
 pub enum TextureId {
     Grass = 0,
     GrassSide,
@@ -0,0 +1,72 @@
+use egui::{Align2, Area, Button, Color32, Frame, Sense, Stroke, Vec2};
+use shipyard::{IntoIter, UniqueView, UniqueViewMut, View, ViewMut};
+use egui_systems::CurrentEguiFrame;
+use engine::components::LocalPlayer;
+use engine::crafting::{CraftingGrid, PendingCraft};
+use engine::inventory::{InventoryHand, InventoryRef, PendingInventoryActions, PlayerInventory};
+use game::crafting::{RecipeBook, GRID_WIDTH};
+use game::inventory::Inventory;
+use crate::egui_views::EguiTextureAtlasViews;
+use crate::inventory::InventoryOpen;
+use crate::inventory::render::InventoryGui;
+use crate::item_stack::ItemStackRender;
+
+/// Shown to the left of the player's inventory while it's open, with whatever the grid would craft below it.
+pub fn crafting(
+    egui_frame: UniqueView<CurrentEguiFrame>,
+    texture_atlas_views: UniqueView<EguiTextureAtlasViews>,
+    open: UniqueView<InventoryOpen>,
+    mut vm_hand: ViewMut<InventoryHand>,
+    mut vm_grid: ViewMut<CraftingGrid>,
+    mut pending_actions: UniqueViewMut<PendingInventoryActions>,
+    mut pending: UniqueViewMut<PendingCraft>,
+    v_local_player: View<LocalPlayer>,
+    mut vm_inventory: ViewMut<PlayerInventory>,
+) {
+    if !open.0 {
+        return;
+    }
+
+    let (inventory, hand, grid, _) = (&mut vm_inventory, &mut vm_hand, &mut vm_grid, &v_local_player).iter()
+        .next()
+        .expect("LocalPlayer should exist");
+
+    Area::new("crafting".into())
+        .anchor(Align2::RIGHT_CENTER, [-420.0, 0.0])
+        .show(egui_frame.ctx(), |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add(InventoryGui {
+                    inventory: &mut grid.0,
+                    quick_transfer: Some(inventory.as_mut_slice()),
+                    texture_atlas_views: &texture_atlas_views,
+                    block_bar_focus_input: None,
+                    hand,
+                    synced_as: Some(&[InventoryRef::CraftingGrid, InventoryRef::Player]),
+                    pending: &mut pending_actions,
+                    columns: GRID_WIDTH,
+                    id: "crafting_grid",
+                });
+
+                ui.add_space(10.0);
+
+                let recipe = RecipeBook::global().find(&grid.0, GRID_WIDTH);
+
+                Frame::none()
+                    .stroke(Stroke::new(2.0, Color32::GRAY))
+                    .fill(Color32::from_rgba_unmultiplied(128, 128, 128, 175))
+                    .show(ui, |ui| {
+                        let (rect, _) = ui.allocate_exact_size(Vec2::splat(40.0), Sense::hover());
+
+                        if let Some(recipe) = recipe {
+                            ItemStackRender { it: &recipe.output(), atlas: &texture_atlas_views, rect: rect.shrink(2.5) }.ui(ui);
+                        }
+                    });
+
+                if ui.add_enabled(recipe.is_some(), Button::new("Craft")).clicked()
+                    && let Some(recipe) = recipe
+                {
+                    pending.0 = Some(recipe.name.clone());
+                }
+            });
+        });
+}
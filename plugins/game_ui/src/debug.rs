@@ -39,7 +39,7 @@ pub fn debug_ui(
             ui.label(vec3_fmt("Position", &local_transform.position));
             ui.label(vec3_fmt("Velocity", &velocity.0));
             
-            let held_item = inventory.as_slice().get(held.0).expect("in range").as_ref().map(|it| it.item.title.as_str());
+            let held_item = inventory.as_slice().get(held.0).expect("in range").as_ref().map(|it| it.item.title());
             
             ui.label(format!("{held_item:?}")); // TODO: stop displaying held
 
@@ -2,13 +2,15 @@ use crate::egui_views::EguiTextureAtlasViews;
 use crate::item_stack::ItemStackRender;
 use egui::{Area, Order, Rect, Vec2};
 use egui_systems::CurrentEguiFrame;
-use game::item::ItemStack;
-use shipyard::{Unique, UniqueView};
+use engine::components::LocalPlayer;
+use engine::inventory::InventoryHand;
+use shipyard::{IntoIter, UniqueView, View};
 
-#[derive(Unique)]
-pub struct InventoryHand(pub Option<ItemStack>);
+pub fn render_hand(egui_frame: UniqueView<CurrentEguiFrame>, v_local_player: View<LocalPlayer>, v_hand: View<InventoryHand>, texture_atlas_views: UniqueView<EguiTextureAtlasViews>) {
+    let Some((_, hand)) = (&v_local_player, &v_hand).iter().next() else {
+        return;
+    };
 
-pub fn render_hand(egui_frame: UniqueView<CurrentEguiFrame>, hand: UniqueView<InventoryHand>, texture_atlas_views: UniqueView<EguiTextureAtlasViews>) {
     if let (Some(cursor_pos), Some(it)) = (egui_frame.ctx().pointer_latest_pos(), &hand.0) {
         let size = Vec2::splat(35.0);
 
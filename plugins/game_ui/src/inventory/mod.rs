@@ -1,4 +1,4 @@
-mod render;
+pub mod render;
 pub mod hand;
 
 use std::time::{Duration, Instant};
@@ -9,17 +9,20 @@ use engine::application::pause::ToggleGuiPressed;
 use engine::block_bar_focus::BlockBarFocus;
 use engine::chunks::chunk_manager::ChunkManager;
 use engine::components::LocalPlayer;
+use engine::crafting::CraftingGrid;
 use engine::input::action_map::Action;
 use engine::input::InputManager;
 use engine::interact::CurrentlyFocusedBlock;
-use engine::inventory::PlayerInventory;
+use engine::inventory::{InventoryHand, InventoryRef, PendingInventoryActions, PendingPlayerDrops, PlayerInventory};
 use engine::rendering::gui_bundle::GuiBundle;
-use game::block::Block;
+use game::block_entity::CrateEntity;
+use game::machine::SmelterEntity;
 use game::inventory::Inventory;
+use game::inventory::transaction::{InventoryAction, SlotRef, Transaction};
 use crate::block_bar::BlockBarDisplay;
 use crate::egui_views::EguiTextureAtlasViews;
-use crate::inventory::hand::InventoryHand;
 use crate::inventory::render::InventoryGui;
+use crate::machine::SmelterGui;
 
 #[derive(Unique)]
 pub struct InventoryOpenTime(pub Option<Instant>);
@@ -35,7 +38,6 @@ pub struct ReturnHandEvent;
 
 pub fn initialize(storages: AllStoragesView) {
     storages.add_unique(InventoryOpen(false));
-    storages.add_unique(InventoryHand(None));
     storages.add_unique(InventoryOpenTime(None));
     storages.add_unique(PrevBlockBarState(false));
 }
@@ -48,11 +50,11 @@ pub fn inventory(
     texture_atlas_views: UniqueView<EguiTextureAtlasViews>,
     input_manager: UniqueView<InputManager>,
     open: UniqueView<InventoryOpen>,
-    mut hand: UniqueViewMut<InventoryHand>,
+    (mut vm_hand, mut pending): (ViewMut<InventoryHand>, UniqueViewMut<PendingInventoryActions>),
     mut world: UniqueViewMut<ChunkManager>,
     focused_inv: UniqueView<CurrentlyFocusedBlock>,
 ) {
-    let (inventory, ..) = (&mut inventory, &local_player).iter()
+    let (inventory, hand, ..) = (&mut inventory, &mut vm_hand, &local_player).iter()
         .next()
         .expect("LocalPlayer should exist");
 
@@ -60,35 +62,78 @@ pub fn inventory(
         return;
     }
 
+    let location = focused_inv.0.as_ref();
+    let entity = location.and_then(|location| world.get_block_entity_mut(location));
+    let before = entity.as_ref().and_then(|entity| entity.encode().ok());
+
+    let (mut crate_inventory, mut smelter) = (None, None);
+
+    if let Some(entity) = entity {
+        if entity.downcast_ref::<CrateEntity>().is_some() {
+            crate_inventory = entity.downcast_mut::<CrateEntity>().map(|CrateEntity { inventory }| inventory);
+        } else {
+            smelter = entity.downcast_mut::<SmelterEntity>();
+        }
+    }
+
+    let crate_refs = location.map(|location| [InventoryRef::BlockEntity { location: location.clone(), index: 0 }, InventoryRef::Player]);
+    let player_refs = match &crate_refs {
+        Some([crate_ref, _]) if crate_inventory.is_some() => vec![InventoryRef::Player, crate_ref.clone()],
+        _ => vec![InventoryRef::Player],
+    };
+
     Area::new("inventory".into())
         .anchor(Align2::RIGHT_CENTER, [-100.0, 0.0])
         .show(egui_frame.ctx(), |ui| {
             ui.vertical(|ui| {
                 ui.add(InventoryGui {
-                    inventory,
+                    inventory: &mut *inventory,
+                    quick_transfer: crate_inventory.as_deref_mut().map(Inventory::as_mut_slice),
                     texture_atlas_views: &texture_atlas_views,
                     block_bar_focus_input: Some((&mut block_bar_focus, &input_manager)),
-                    hand: &mut hand,
+                    hand: &mut *hand,
+                    synced_as: Some(&player_refs),
+                    pending: &mut pending,
                     columns: 6,
                     id: "player_inventory",
                 });
 
-                if let Some(location) = &focused_inv.as_ref().0 {
-                    if let Some(Block::Crate { inventory }) = world.get_block_mut(location) {
-                        ui.add_space(10.0);
-
-                        ui.add(InventoryGui {
-                            inventory,
-                            texture_atlas_views: &texture_atlas_views,
-                            block_bar_focus_input: None,
-                            hand: &mut hand,
-                            columns: 6,
-                            id: "crate_ui",
-                        });
-                    }
+                if let Some(crate_inventory) = crate_inventory {
+                    ui.add_space(10.0);
+
+                    ui.add(InventoryGui {
+                        inventory: crate_inventory,
+                        quick_transfer: Some(inventory.as_mut_slice()),
+                        texture_atlas_views: &texture_atlas_views,
+                        block_bar_focus_input: None,
+                        hand: &mut *hand,
+                        synced_as: crate_refs.as_ref().map(|refs| refs.as_slice()),
+                        pending: &mut pending,
+                        columns: 6,
+                        id: "crate_ui",
+                    });
+                } else if let Some(smelter) = smelter {
+                    ui.add_space(10.0);
+
+                    ui.add(SmelterGui {
+                        smelter,
+                        location: location.expect("the smelter was found at a location"),
+                        player_inventory: inventory,
+                        texture_atlas_views: &texture_atlas_views,
+                        hand: &mut *hand,
+                        pending: &mut pending,
+                    });
                 }
             });
         });
+
+    // the host applied the clicks to the only copy there is, so it has to send the changes itself
+    if let Some(location) = location
+        && before.is_some()
+        && world.get_block_entity_ref(location).and_then(|entity| entity.encode().ok()) != before
+    {
+        world.mark_block_entity_changed(location);
+    }
 }
 
 pub fn toggle_inv_block_bar(
@@ -165,18 +210,43 @@ pub fn toggle_inv_block_bar(
     }
 }
 
-pub fn return_hand(v_local_player: View<LocalPlayer>, mut vm_inventory: ViewMut<PlayerInventory>, mut hand: UniqueViewMut<InventoryHand>, storages: AllStoragesView) {
+/// Puts what the local player is holding and has in their crafting grid back into their inventory,
+/// which the server is told about like any other click. Whatever's still held is dropped.
+pub fn return_hand(
+    v_local_player: View<LocalPlayer>,
+    mut vm_inventory: ViewMut<PlayerInventory>,
+    mut vm_hand: ViewMut<InventoryHand>,
+    mut vm_grid: ViewMut<CraftingGrid>,
+    mut pending: UniqueViewMut<PendingInventoryActions>,
+    mut drops: UniqueViewMut<PendingPlayerDrops>,
+    storages: AllStoragesView,
+) {
     let Ok(ReturnHandEvent) = storages.remove_unique() else {
         return;
     };
 
-    let (inventory, ..) = (&mut vm_inventory, &v_local_player).iter()
+    let (inventory, hand, grid, _) = (&mut vm_inventory, &mut vm_hand, &mut vm_grid, &v_local_player).iter()
         .next()
         .expect("LocalPlayer should exist");
-    
-    if let Some(it) = hand.0.take() {
-        if let Some(residual) = inventory.try_insert(it) {
-            tracing::warn!("TODO: couldn't insert remaining {residual:?} into player inventory from hand upon closing inventory");
+
+    if hand.0.is_some() {
+        let action = InventoryAction::QuickTransfer { from: SlotRef::Hand, to: 0 };
+
+        if Transaction::new(&mut hand.0).with(&mut *inventory).apply(&action).is_ok() {
+            pending.push(&[InventoryRef::Player], action);
+        }
+    }
+
+    if let Some(residual) = &hand.0 {
+        drops.push(SlotRef::Hand, residual.clone());
+    }
+
+    // whatever doesn't fit is left in the grid
+    for index in 0..grid.0.size() {
+        let action = InventoryAction::QuickTransfer { from: SlotRef::at(0, index), to: 1 };
+
+        if Transaction::new(&mut hand.0).with(&mut grid.0).with(&mut *inventory).apply(&action).is_ok() {
+            pending.push(&[InventoryRef::CraftingGrid, InventoryRef::Player], action);
         }
     }
-}
\ No newline at end of file
+}
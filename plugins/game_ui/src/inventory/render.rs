@@ -1,21 +1,40 @@
-use std::mem;
 use std::num::NonZeroU8;
 use egui::{Align2, Color32, FontId, Frame, Grid, PointerButton, Response, Sense, Stroke, Ui, Vec2, Widget};
 use engine::block_bar_focus::BlockBarFocus;
 use engine::input::action_map::Action;
 use engine::input::InputManager;
-use engine::inventory::PlayerInventory;
+use engine::inventory::{InventoryHand, InventoryRef, PendingInventoryActions};
 use game::inventory::Inventory;
+use game::inventory::transaction::{InventoryAction, SlotRef, Transaction};
 use game::item::ItemStack;
 use crate::egui_views::EguiTextureAtlasViews;
-use crate::inventory::hand::InventoryHand;
 use crate::item_stack::ItemStackRender;
 
+/// The index of the inventory being shown in the transactions its clicks make.
+const SHOWN: u8 = 0;
+/// The index of the inventory shift-clicked items are moved into.
+const QUICK_TRANSFER: u8 = 1;
+
+/// How a slot was clicked, which decides what happens to the items in it.
+#[derive(Copy, Clone, Debug)]
+enum SlotClick {
+    Primary,
+    Secondary,
+    Shift,
+    Double,
+}
+
 pub struct InventoryGui<'a, I: Inventory> {
     pub inventory: &'a mut I,
+    /// Where shift-clicked items are moved to, if anywhere.
+    pub quick_transfer: Option<&'a mut [Option<ItemStack>]>,
     pub texture_atlas_views: &'a EguiTextureAtlasViews,
     pub block_bar_focus_input: Option<(&'a mut BlockBarFocus, &'a InputManager)>,
     pub hand: &'a mut InventoryHand,
+    /// Which of the server's inventories the shown and quick transfer ones are, so that clicks can be sent to it.
+    /// Clicks in inventories the server doesn't keep are only applied locally.
+    pub synced_as: Option<&'a [InventoryRef]>,
+    pub pending: &'a mut PendingInventoryActions,
     pub columns: usize,
     pub id: &'a str,
 }
@@ -24,9 +43,12 @@ impl<I: Inventory> Widget for InventoryGui<'_, I> {
     fn ui(self, ui: &mut Ui) -> Response {
         let Self {
             inventory,
+            quick_transfer,
             texture_atlas_views,
             mut block_bar_focus_input,
             hand,
+            synced_as,
+            pending,
             columns,
             id,
         } = self;
@@ -35,14 +57,16 @@ impl<I: Inventory> Widget for InventoryGui<'_, I> {
             .show(ui, |ui| {
                 ui.spacing_mut().item_spacing = Vec2::ZERO;
 
+                let mut clicked = None;
+
                 let mut block_bar_focus_selected = Vec::with_capacity(
                     block_bar_focus_input.as_ref().map_or(0, |(bbf, _)| bbf.focus.len())
                 );
 
                 Grid::new(id)
                     .show(ui, |ui| {
-                        for (row_idx, row) in inventory.as_mut_slice().chunks_mut(columns).enumerate() {
-                            for (col_idx, slot) in row.iter_mut().enumerate() {
+                        for (row_idx, row) in inventory.as_slice().chunks(columns).enumerate() {
+                            for (col_idx, slot) in row.iter().enumerate() {
                                 let i = row_idx * columns + col_idx;
 
                                 let response = Frame::none()
@@ -63,10 +87,20 @@ impl<I: Inventory> Widget for InventoryGui<'_, I> {
                                                 ItemStackRender { it, atlas: texture_atlas_views, rect }.ui(ui);
                                             }
 
-                                            if response.clicked_by(PointerButton::Primary) {
-                                                Self::interact_hand_inventory_slot(&mut hand.0, slot, PointerButton::Primary);
+                                            let click = if response.double_clicked_by(PointerButton::Primary) {
+                                                Some(SlotClick::Double)
+                                            } else if response.clicked_by(PointerButton::Primary) && ui.input(|input| input.modifiers.shift) {
+                                                Some(SlotClick::Shift)
+                                            } else if response.clicked_by(PointerButton::Primary) {
+                                                Some(SlotClick::Primary)
                                             } else if response.clicked_by(PointerButton::Secondary) {
-                                                Self::interact_hand_inventory_slot(&mut hand.0, slot, PointerButton::Secondary);
+                                                Some(SlotClick::Secondary)
+                                            } else {
+                                                None
+                                            };
+
+                                            if let Some(click) = click {
+                                                clicked = Some((i, click));
                                             }
                                         });
                                     });
@@ -97,7 +131,7 @@ impl<I: Inventory> Widget for InventoryGui<'_, I> {
                                                     .focus
                                                     .iter_mut()
                                                     .enumerate()
-                                                    .filter(|(j, &mut focus)| *j != bar_slot && focus == Some(i))
+                                                    .filter(|(j, focus)| *j != bar_slot && **focus == Some(i))
                                                     .for_each(|(_, slot)| *slot = None);
                                             }
                                         }
@@ -110,6 +144,23 @@ impl<I: Inventory> Widget for InventoryGui<'_, I> {
                         }
                     });
 
+                if let Some((i, click)) = clicked {
+                    let mut transaction = Transaction::new(&mut hand.0).with(&mut *inventory);
+
+                    if let Some(other) = quick_transfer {
+                        transaction = transaction.with(other);
+                    }
+
+                    if let Some(action) = Self::click_action(&transaction, SlotRef::at(SHOWN, i), click) {
+                        match transaction.apply(&action) {
+                            Ok(()) => if let Some(refs) = synced_as {
+                                pending.push(refs, action);
+                            },
+                            Err(err) => tracing::debug!("Couldn't apply {action:?}: {err}"),
+                        }
+                    }
+                }
+
                 let font_id = FontId::proportional(16.0);
 
                 for (i, rect) in block_bar_focus_selected {
@@ -144,52 +195,33 @@ impl<I: Inventory> Widget for InventoryGui<'_, I> {
 }
 
 impl<I: Inventory> InventoryGui<'_, I> {
-    fn interact_hand_inventory_slot(hand: &mut Option<ItemStack>, slot: &mut Option<ItemStack>, button: PointerButton) {
-        match (&hand, &slot, button) {
-            (None, Some(_), PointerButton::Secondary) => {
-                let s = slot.take().expect("should've matched on Some");
-                
-                let (hand_it, slot_it) = s.split_half();
-                
-                *hand = Some(hand_it);
-                *slot = slot_it;
-            }
-            (Some(hand_it), slot_it, PointerButton::Secondary)
-                if slot_it.as_ref().is_none_or(|slot_it| slot_it.item == hand_it.item) =>
-            {
-                let h = hand.take().expect("should've matched on Some");
-                
-                let (slot_it, mut hand_it) = h.split_at_most(NonZeroU8::new(1).expect("not zero"));
-                
-                match slot {
-                    Some(slot) => {
-                        if let Some(residual) = slot.try_combine(slot_it) {
-                            if let Some(hand_it) = &mut hand_it {
-                                let res = hand_it.try_combine(residual);
-                                
-                                assert!(res.is_none(), "should be eq and have space, since res originally came from hand");
-                            } else {
-                                hand_it = Some(residual);
-                            }
-                        }
-                    }
-                    None => {
-                        *slot = Some(slot_it);
-                    }
-                }
-    
-                *hand = hand_it;
-            }
-            (Some(hand_it), Some(slot_it), PointerButton::Primary) if slot_it.item == hand_it.item => {
-                let hand_it = hand.take().expect("should've matched on Some");
-                let slot = slot.as_mut().expect("should've matched on Some");
-                
-                *hand = slot.try_combine(hand_it);
-            }
-            (_, _, PointerButton::Primary) | (_, _, PointerButton::Secondary) => mem::swap(hand, slot),
-            /* do nothing on middle or extra buttons */
-            _ => tracing::warn!("InventoryGui::interact_hand_inventory_slot should only be called with PointerButton::{{Primary, Secondary}}"),
-        }
+    fn click_action(transaction: &Transaction, slot: SlotRef, click: SlotClick) -> Option<InventoryAction> {
+        let hand = transaction.slot(SlotRef::Hand).ok()?.as_ref();
+        let in_slot = transaction.slot(slot).ok()?.as_ref();
+
+        // how many of what's in the hand fit in the slot, if they stack with what's there
+        let room = hand
+            .filter(|hand| in_slot.is_none_or(|in_slot| in_slot.item.stacks_with(&hand.item)))
+            .map(|hand| transaction.room_for(slot, &hand.item).map_or(0, |room| room.min(hand.count.get())));
+
+        let action = match (click, hand, in_slot) {
+            (SlotClick::Double, Some(_), _) => InventoryAction::CollectToHand,
+            (SlotClick::Shift, _, Some(_)) => InventoryAction::QuickTransfer { from: slot, to: QUICK_TRANSFER },
+            (SlotClick::Primary, Some(_), Some(_)) if room.is_some() => InventoryAction::Move {
+                from: SlotRef::Hand,
+                to: slot,
+                count: NonZeroU8::new(room?)?,
+            },
+            (SlotClick::Secondary, None, Some(_)) => InventoryAction::Split { from: slot, to: SlotRef::Hand },
+            (SlotClick::Secondary, Some(_), _) if room.is_some() => InventoryAction::Move {
+                from: SlotRef::Hand,
+                to: slot,
+                count: NonZeroU8::new(room?.min(1))?,
+            },
+            (_, None, None) => return None,
+            _ => InventoryAction::Swap(SlotRef::Hand, slot),
+        };
+
+        Some(action)
     }
 }
-
@@ -1,21 +1,24 @@
-use egui::{Color32, LayerId};
-use shipyard::{AllStoragesView, IntoWorkload, UniqueView, Workload, WorkloadModificator};
+use egui::{Color32, LayerId, Rect, Vec2};
+use shipyard::{AllStoragesView, IntoIter, IntoWorkload, UniqueView, View, Workload, WorkloadModificator};
 use strck::IntoCk;
 use dino_plugins::engine::{DinoEnginePlugin, EnginePhase, EnginePluginMetadata};
 use dino_plugins::path;
 use egui_systems::{CurrentEguiFrame, EguiSystemsPlugin};
 use egui_systems::DuringEgui;
 use block_bar::block_bar;
+use engine::components::LocalPlayer;
 use engine::gamemode::local_player_is_gamemode_spectator;
+use engine::mining::BreakProgress;
 use engine::VoxelEngine;
 use crate::block_bar::{create_block_bar_display, scroll_block_bar};
 use crate::bottom_bar::bottom_bar;
 use crate::egui_views::initialize_texture_atlas_views;
 use shipyard::SystemModificator;
 use engine::application::pause::{is_paused, listen_for_toggle_pause, toggle_pause_menu};
+use crate::crafting::crafting;
 use crate::debug::debug_ui;
 use crate::inventory::{inventory, return_hand, toggle_inv_block_bar, InventoryOpen};
-use crate::inventory::hand::{render_hand, InventoryHand};
+use crate::inventory::hand::render_hand;
 use crate::pause::draw_pause_menu;
 
 extern crate nalgebra_glm as glm;
@@ -24,6 +27,8 @@ mod bottom_bar;
 mod egui_views;
 mod block_bar;
 mod inventory;
+mod crafting;
+mod machine;
 pub(crate) mod item_stack;
 mod debug;
 mod pause;
@@ -74,6 +79,7 @@ impl DinoEnginePlugin for GameUiPlugin {
                 bottom_bar,
                 block_bar,
                 inventory,
+                crafting,
                 debug_ui,
                 render_hand,
             )
@@ -98,13 +104,27 @@ impl DinoEnginePlugin for GameUiPlugin {
     }
 }
 
-fn reticle(egui_frame: UniqueView<CurrentEguiFrame>) {
+/// Also shows how far the local player is through breaking a block, as a bar under it.
+fn reticle(egui_frame: UniqueView<CurrentEguiFrame>, v_local_player: View<LocalPlayer>, v_break_progress: View<BreakProgress>) {
     let ctx = egui_frame.ctx();
+    let painter = ctx.layer_painter(LayerId::background());
+    let center = ctx.screen_rect().center();
 
-    ctx.layer_painter(LayerId::background())
-        .circle_filled(
-            ctx.screen_rect().center(),
-            2.5,
-            Color32::from_rgba_premultiplied(192, 192, 192, 128),
-        );
+    painter.circle_filled(
+        center,
+        2.5,
+        Color32::from_rgba_premultiplied(192, 192, 192, 128),
+    );
+
+    let fraction = (&v_local_player, &v_break_progress)
+        .iter()
+        .next()
+        .map_or(0.0, |(_, progress)| progress.fraction());
+
+    if fraction > 0.0 {
+        let bar = Rect::from_min_size(center + Vec2::new(-20.0, 12.0), Vec2::new(40.0, 3.0));
+
+        painter.rect_filled(bar, 0.0, Color32::from_rgba_premultiplied(64, 64, 64, 128));
+        painter.rect_filled(Rect::from_min_size(bar.min, Vec2::new(bar.width() * fraction, bar.height())), 0.0, Color32::from_rgba_premultiplied(192, 192, 192, 160));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,84 @@
+use egui::{Color32, ProgressBar, Response, Ui, Widget};
+use engine::inventory::{InventoryHand, InventoryRef, PendingInventoryActions, PlayerInventory};
+use game::inventory::Inventory;
+use game::location::BlockLocation;
+use game::machine::SmelterEntity;
+use crate::egui_views::EguiTextureAtlasViews;
+use crate::inventory::render::InventoryGui;
+
+/// The input and fuel slots of a smelter stacked on the left, with its progress leading to the output slot.
+pub struct SmelterGui<'a> {
+    pub smelter: &'a mut SmelterEntity,
+    pub location: &'a BlockLocation,
+    /// Where shift-clicked items are moved to.
+    pub player_inventory: &'a mut PlayerInventory,
+    pub texture_atlas_views: &'a EguiTextureAtlasViews,
+    pub hand: &'a mut InventoryHand,
+    pub pending: &'a mut PendingInventoryActions,
+}
+
+impl Widget for SmelterGui<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let Self {
+            smelter,
+            location,
+            player_inventory,
+            texture_atlas_views,
+            hand,
+            pending,
+        } = self;
+
+        let progress = smelter.progress();
+        let burn = smelter.burn();
+        // in the order of `SmelterEntity::inventories_mut`, each quick transferring into the player's inventory
+        let [input_refs, fuel_refs, output_refs] = [0, 1, 2].map(|index| [InventoryRef::BlockEntity { location: location.clone(), index }, InventoryRef::Player]);
+
+        ui.horizontal(|ui| {
+            ui.vertical(|ui| {
+                ui.add(InventoryGui {
+                    inventory: &mut smelter.input,
+                    quick_transfer: Some(player_inventory.as_mut_slice()),
+                    texture_atlas_views,
+                    block_bar_focus_input: None,
+                    hand,
+                    synced_as: Some(&input_refs),
+                    pending,
+                    columns: 1,
+                    id: "smelter_input",
+                });
+
+                ui.add(ProgressBar::new(burn).desired_width(44.0).fill(Color32::from_rgb(230, 120, 30)));
+
+                ui.add(InventoryGui {
+                    inventory: &mut smelter.fuel,
+                    quick_transfer: Some(player_inventory.as_mut_slice()),
+                    texture_atlas_views,
+                    block_bar_focus_input: None,
+                    hand,
+                    synced_as: Some(&fuel_refs),
+                    pending,
+                    columns: 1,
+                    id: "smelter_fuel",
+                });
+            });
+
+            ui.add_space(10.0);
+
+            ui.add(ProgressBar::new(progress).desired_width(80.0));
+
+            ui.add_space(10.0);
+
+            ui.add(InventoryGui {
+                inventory: &mut smelter.output,
+                quick_transfer: Some(player_inventory.as_mut_slice()),
+                texture_atlas_views,
+                block_bar_focus_input: None,
+                hand,
+                synced_as: Some(&output_refs),
+                pending,
+                columns: 1,
+                id: "smelter_output",
+            });
+        }).response
+    }
+}
@@ -1,14 +1,17 @@
 use std::fmt;
 use std::marker::PhantomData;
 use glm::Vec2;
+use serde::{Deserialize, Serialize};
 
 pub trait Easing {
     fn ease(x: f32) -> f32;
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct Spline<E: Easing> {
     points: Vec<Vec2>,
+    #[serde(skip)]
     _easing: PhantomData<E>,
 }
 
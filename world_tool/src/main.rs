@@ -0,0 +1,304 @@
+extern crate nalgebra_glm as glm;
+
+use std::{fs, io};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use clap::{Parser, Subcommand};
+use glm::IVec3;
+use hashbrown::HashSet;
+use engine::save::{ChunkLoadError, ChunkSaveCache, ChunkSaver, ChunkStorageKind};
+use engine::save::format::decode_chunk;
+use engine::save::snapshot::{SnapshotError, SnapshotKind, SnapshotStore};
+use engine::save::world::{WorldDirectory, WorldLoadError};
+use game::block::registry::{BlockId, BlockRegistry, BlockRegistryError};
+use game::item::registry::{ItemRegistry, ItemRegistryError};
+use game::chunk::location::ChunkLocation;
+
+#[derive(Parser, Debug)]
+#[command(name = "protovox-world", about = "Inspect and convert protovox worlds without launching the client")]
+struct Args {
+    #[arg(long, value_name = "PATH", default_value = "worlds/world", help = "Directory of the world to work on")]
+    world: PathBuf,
+    #[arg(long, value_name = "PATH", default_value = "blocks", help = "Directory of block definition files the world was played with")]
+    blocks: PathBuf,
+    #[arg(long, value_name = "PATH", default_value = "items", help = "Directory of item definition files the world was played with")]
+    items: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the level metadata
+    Level,
+    /// List every saved chunk and its size on disk
+    Chunks,
+    /// Count how many of each block a saved chunk has
+    Histogram {
+        #[arg(allow_negative_numbers = true)]
+        x: i32,
+        #[arg(allow_negative_numbers = true)]
+        y: i32,
+        #[arg(allow_negative_numbers = true)]
+        z: i32,
+    },
+    /// Check that every saved chunk can be loaded, without quarantining the ones that can't
+    Validate,
+    /// Rewrite every chunk into another storage backend, after taking a snapshot of the world
+    Convert {
+        #[arg(value_enum)]
+        to: ChunkStorageKind,
+    },
+    /// Remove saved chunks horizontally further than RADIUS chunks from the center, after taking a snapshot of the world
+    Prune {
+        radius: u32,
+        #[arg(long, num_args = 2, value_names = ["X", "Z"], default_values_t = [0, 0], allow_negative_numbers = true)]
+        center: Vec<i32>,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+enum ToolError {
+    #[error(transparent)]
+    World(#[from] WorldLoadError),
+    #[error(transparent)]
+    Snapshot(#[from] SnapshotError),
+    #[error(transparent)]
+    Blocks(#[from] BlockRegistryError),
+    #[error(transparent)]
+    Items(#[from] ItemRegistryError),
+    #[error("failed to write output: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to open the chunk store at {0:?}")]
+    ChunkStore(PathBuf),
+    #[error("chunk at {0:?} couldn't be loaded: {1}")]
+    Chunk(ChunkLocation, ChunkLoadError),
+    #[error("failed to save chunk at {0:?}, the original chunks were left untouched")]
+    SaveFailed(ChunkLocation),
+    #[error("{0} chunks failed to load")]
+    Invalid(usize),
+    #[error("chunks are already stored as {0:?}")]
+    AlreadyConverted(ChunkStorageKind),
+}
+
+/// The world's chunk store, along with which chunks are saved in it.
+struct Chunks {
+    kind: ChunkStorageKind,
+    saver: Box<dyn ChunkSaver + Send + Sync>,
+    saved: Vec<ChunkLocation>,
+}
+
+impl Chunks {
+    fn open(world_dir: &WorldDirectory) -> Result<Self, ToolError> {
+        let path = world_dir.chunks_path();
+
+        let kind = ChunkStorageKind::detect(&path).unwrap_or_default();
+
+        let saver = kind.open(&path).ok_or(ToolError::ChunkStore(path))?;
+
+        let mut saved = HashSet::new();
+
+        saver.update_saved(&mut saved);
+
+        let mut saved = saved.into_iter().collect::<Vec<_>>();
+
+        saved.sort_by_key(|loc| (loc.0.x, loc.0.y, loc.0.z));
+
+        Ok(Self { kind, saver, saved })
+    }
+
+    fn load(&self, loc: &ChunkLocation) -> Result<ChunkSaveCache, ChunkLoadError> {
+        let bytes = self.saver.read_bytes(loc)?;
+
+        Ok(ChunkSaveCache::new(decode_chunk(&bytes)?))
+    }
+}
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "warn".into()))
+        .with_writer(io::stderr)
+        .init();
+
+    let args = Args::parse();
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            let _ = writeln!(io::stderr(), "error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), ToolError> {
+    // Installing can only fail if a block or item was used already, and none have been yet.
+    // Blocks refer to the items they drop by name, so items are loaded first.
+    let _ = ItemRegistry::load(&args.items)?.install();
+
+    let blocks = BlockRegistry::load(&args.blocks)?;
+
+    ItemRegistry::global().check_blocks(&blocks)?;
+
+    let _ = blocks.install();
+
+    let world_dir = WorldDirectory::open(&args.world)?;
+
+    let mut out = io::stdout().lock();
+
+    match args.command {
+        Command::Level => {
+            let level = &world_dir.level;
+
+            writeln!(out, "format version: {}", level.format_version)?;
+            writeln!(out, "seed: {}", level.seed)?;
+            writeln!(out, "spawn: ({}, {}, {})", level.spawn.x, level.spawn.y, level.spawn.z)?;
+            writeln!(out, "game time: {:?}", level.game_time)?;
+            writeln!(out, "gen params: {:#?}", level.gen_params)?;
+        }
+        Command::Chunks => {
+            let chunks = Chunks::open(&world_dir)?;
+
+            let mut total = 0;
+
+            for loc in &chunks.saved {
+                match chunks.saver.read_bytes(loc) {
+                    Ok(bytes) => {
+                        writeln!(out, "{} {} {}\t{} bytes", loc.0.x, loc.0.y, loc.0.z, bytes.len())?;
+                        total += bytes.len();
+                    }
+                    Err(err) => writeln!(out, "{} {} {}\tunreadable: {err}", loc.0.x, loc.0.y, loc.0.z)?,
+                }
+            }
+
+            writeln!(out, "{} chunks stored as {:?}, {total} bytes in total", chunks.saved.len(), chunks.kind)?;
+        }
+        Command::Histogram { x, y, z } => {
+            let loc = ChunkLocation(IVec3::new(x, y, z));
+
+            let cache = Chunks::open(&world_dir)?
+                .load(&loc)
+                .map_err(|err| ToolError::Chunk(loc, err))?;
+
+            let mut counts = vec![0usize; BlockRegistry::global().len()];
+
+            for block in cache.data.blocks_ref() {
+                counts[block.id().0 as usize] += 1;
+            }
+
+            let mut counts = counts
+                .into_iter()
+                .enumerate()
+                .filter(|(_, count)| *count > 0)
+                .collect::<Vec<_>>();
+
+            counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+            for (id, count) in counts {
+                let name = &BlockRegistry::global().get(BlockId(id as _)).expect("counted blocks are registered").name;
+
+                writeln!(out, "{name}\t{count}")?;
+            }
+        }
+        Command::Validate => {
+            let chunks = Chunks::open(&world_dir)?;
+
+            let mut invalid = 0;
+
+            for loc in &chunks.saved {
+                if let Err(err) = chunks.load(loc) {
+                    writeln!(out, "{} {} {}\t{err}", loc.0.x, loc.0.y, loc.0.z)?;
+                    invalid += 1;
+                }
+            }
+
+            if invalid > 0 {
+                return Err(ToolError::Invalid(invalid));
+            }
+
+            writeln!(out, "all {} chunks are valid", chunks.saved.len())?;
+        }
+        Command::Convert { to } => {
+            let chunks = Chunks::open(&world_dir)?;
+
+            if chunks.kind == to {
+                return Err(ToolError::AlreadyConverted(to));
+            }
+
+            // load everything up front, so a corrupt chunk stops the conversion before anything is changed
+            let loaded = chunks.saved
+                .iter()
+                .map(|loc| chunks.load(loc).map_err(|err| ToolError::Chunk(loc.clone(), err)))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let snapshot = SnapshotStore::new(world_dir.root()).create(SnapshotKind::Manual)?;
+
+            writeln!(out, "Saved the world as snapshot {} before converting", snapshot.name)?;
+
+            let path = world_dir.chunks_path();
+
+            let staging = path.with_extension("converting");
+
+            if staging.exists() {
+                fs::remove_dir_all(&staging)?;
+            }
+
+            let dest = to.open(&staging).ok_or_else(|| ToolError::ChunkStore(staging.clone()))?;
+
+            let count = loaded.len();
+
+            for cache in loaded {
+                let loc = cache.data.location.clone();
+
+                if !dest.save(&cache) {
+                    return Err(ToolError::SaveFailed(loc));
+                }
+            }
+
+            drop(dest);
+
+            // quarantined chunks aren't in either format, so they're kept as they are
+            let quarantine = path.join(engine::save::QUARANTINE_DIR);
+
+            if quarantine.is_dir() {
+                fs::rename(quarantine, staging.join(engine::save::QUARANTINE_DIR))?;
+            }
+
+            drop(chunks);
+
+            fs::remove_dir_all(&path)?;
+            fs::rename(&staging, &path)?;
+
+            writeln!(out, "Converted {count} chunks to {to:?}")?;
+        }
+        Command::Prune { radius, center } => {
+            let chunks = Chunks::open(&world_dir)?;
+
+            let center = IVec3::new(center[0], 0, center[1]);
+            let radius = radius as i32;
+
+            let outside = chunks.saved
+                .iter()
+                .filter(|loc| (loc.0.x - center.x).abs() > radius || (loc.0.z - center.z).abs() > radius)
+                .collect::<Vec<_>>();
+
+            if outside.is_empty() {
+                writeln!(out, "No chunks are outside the radius")?;
+                return Ok(());
+            }
+
+            let snapshot = SnapshotStore::new(world_dir.root()).create(SnapshotKind::Manual)?;
+
+            writeln!(out, "Saved the world as snapshot {} before pruning", snapshot.name)?;
+
+            for loc in &outside {
+                chunks.saver.remove(loc)?;
+            }
+
+            writeln!(out, "Removed {} of {} chunks", outside.len(), chunks.saved.len())?;
+        }
+    }
+
+    Ok(())
+}